@@ -0,0 +1,23 @@
+use std::error::Error;
+
+use vergen_gitcl::{BuildBuilder, Emitter, GitclBuilder, RustcBuilder};
+
+// Capture build details for `GET /api/v1/version` and generate the gRPC service
+fn main() -> Result<(), Box<dyn Error>> {
+    let build = BuildBuilder::default().build_timestamp(true).build()?;
+    let git = GitclBuilder::default().sha(true).build()?;
+    let rustc = RustcBuilder::default().semver(true).build()?;
+
+    Emitter::default()
+        .add_instructions(&build)?
+        .add_instructions(&git)?
+        .add_instructions(&rustc)?
+        .emit()?;
+
+    // protoc ships with the build instead of being expected on the system
+    std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?);
+    tonic_prost_build::configure()
+        .build_client(false)
+        .compile_protos(&["proto/tlong.proto"], &["proto"])?;
+    Ok(())
+}
@@ -0,0 +1,8 @@
+CREATE TABLE
+    link_previews (
+        short_code VARCHAR(8) PRIMARY KEY REFERENCES urls (short_code) ON DELETE CASCADE,
+        title TEXT,
+        description TEXT,
+        image_url TEXT,
+        fetched_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP NOT NULL
+    );
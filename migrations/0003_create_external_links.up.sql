@@ -0,0 +1,6 @@
+CREATE TABLE
+    external_links (
+        external_id VARCHAR(128) PRIMARY KEY,
+        long_url TEXT NOT NULL,
+        created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP NOT NULL
+    );
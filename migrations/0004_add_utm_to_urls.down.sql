@@ -0,0 +1,6 @@
+ALTER TABLE urls
+DROP COLUMN IF EXISTS utm_source,
+DROP COLUMN IF EXISTS utm_medium,
+DROP COLUMN IF EXISTS utm_campaign,
+DROP COLUMN IF EXISTS utm_term,
+DROP COLUMN IF EXISTS utm_content;
@@ -0,0 +1,6 @@
+ALTER TABLE urls
+ADD COLUMN utm_source TEXT,
+ADD COLUMN utm_medium TEXT,
+ADD COLUMN utm_campaign TEXT,
+ADD COLUMN utm_term TEXT,
+ADD COLUMN utm_content TEXT;
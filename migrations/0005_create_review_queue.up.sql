@@ -0,0 +1,9 @@
+CREATE TABLE
+    review_queue (
+        id SERIAL PRIMARY KEY,
+        long_url TEXT NOT NULL,
+        client_ip TEXT,
+        score INTEGER NOT NULL,
+        reasons TEXT[] NOT NULL,
+        created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP NOT NULL
+    );
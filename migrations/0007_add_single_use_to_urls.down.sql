@@ -0,0 +1,3 @@
+ALTER TABLE urls
+DROP COLUMN IF EXISTS single_use,
+DROP COLUMN IF EXISTS disabled_at;
@@ -0,0 +1,3 @@
+ALTER TABLE urls
+ADD COLUMN single_use BOOLEAN DEFAULT FALSE NOT NULL,
+ADD COLUMN disabled_at TIMESTAMPTZ;
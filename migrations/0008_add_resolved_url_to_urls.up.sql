@@ -0,0 +1,2 @@
+ALTER TABLE urls
+ADD COLUMN resolved_url TEXT;
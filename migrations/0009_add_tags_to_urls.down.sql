@@ -0,0 +1,4 @@
+DROP INDEX IF EXISTS idx_tags;
+
+ALTER TABLE urls
+DROP COLUMN IF EXISTS tags;
@@ -0,0 +1,4 @@
+ALTER TABLE urls
+ADD COLUMN tags TEXT[] DEFAULT '{}' NOT NULL;
+
+CREATE INDEX idx_tags ON urls USING GIN (tags);
@@ -0,0 +1,2 @@
+ALTER TABLE urls
+ADD COLUMN clicks BIGINT DEFAULT 0 NOT NULL;
@@ -0,0 +1,6 @@
+DROP INDEX IF EXISTS idx_campaign_id;
+
+ALTER TABLE urls
+DROP COLUMN IF EXISTS campaign_id;
+
+DROP TABLE IF EXISTS campaigns;
@@ -0,0 +1,12 @@
+CREATE TABLE
+    campaigns (
+        id SERIAL PRIMARY KEY,
+        name TEXT UNIQUE NOT NULL,
+        description TEXT,
+        created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP NOT NULL
+    );
+
+ALTER TABLE urls
+ADD COLUMN campaign_id INTEGER REFERENCES campaigns (id) ON DELETE SET NULL;
+
+CREATE INDEX idx_campaign_id ON urls (campaign_id);
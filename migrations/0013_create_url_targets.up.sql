@@ -0,0 +1,10 @@
+CREATE TABLE
+    url_targets (
+        id SERIAL PRIMARY KEY,
+        short_code VARCHAR(8) NOT NULL REFERENCES urls (short_code) ON DELETE CASCADE,
+        long_url TEXT NOT NULL,
+        weight INTEGER NOT NULL CHECK (weight > 0),
+        clicks BIGINT DEFAULT 0 NOT NULL
+    );
+
+CREATE INDEX idx_url_targets_short_code ON url_targets (short_code);
@@ -0,0 +1,7 @@
+CREATE TABLE
+    url_device_targets (
+        short_code VARCHAR(8) NOT NULL REFERENCES urls (short_code) ON DELETE CASCADE,
+        device VARCHAR(16) NOT NULL,
+        long_url TEXT NOT NULL,
+        PRIMARY KEY (short_code, device)
+    );
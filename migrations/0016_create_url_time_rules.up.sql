@@ -0,0 +1,11 @@
+CREATE TABLE
+    url_time_rules (
+        id SERIAL PRIMARY KEY,
+        short_code VARCHAR(8) NOT NULL REFERENCES urls (short_code) ON DELETE CASCADE,
+        priority INTEGER NOT NULL,
+        starts_at TIME NOT NULL,
+        ends_at TIME NOT NULL,
+        long_url TEXT NOT NULL
+    );
+
+CREATE INDEX idx_url_time_rules_short_code ON url_time_rules (short_code);
@@ -0,0 +1,7 @@
+CREATE TABLE
+    url_deep_links (
+        short_code VARCHAR(8) PRIMARY KEY REFERENCES urls (short_code) ON DELETE CASCADE,
+        uri TEXT NOT NULL,
+        ios_store_url TEXT,
+        android_store_url TEXT
+    );
@@ -0,0 +1,3 @@
+ALTER TABLE urls
+DROP COLUMN IF EXISTS threat_checked_at,
+DROP COLUMN IF EXISTS threat_type;
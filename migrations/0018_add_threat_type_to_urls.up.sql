@@ -0,0 +1,3 @@
+ALTER TABLE urls
+ADD COLUMN threat_type TEXT,
+ADD COLUMN threat_checked_at TIMESTAMPTZ;
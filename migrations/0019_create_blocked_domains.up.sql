@@ -0,0 +1,6 @@
+CREATE TABLE
+    blocked_domains (
+        domain TEXT PRIMARY KEY,
+        reason TEXT,
+        created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP NOT NULL
+    );
@@ -0,0 +1,5 @@
+ALTER TABLE external_links
+DROP CONSTRAINT IF EXISTS long_url_length;
+
+ALTER TABLE urls
+DROP CONSTRAINT IF EXISTS long_url_length;
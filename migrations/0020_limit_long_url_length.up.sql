@@ -0,0 +1,6 @@
+-- Upper bound for MAX_URL_LENGTH; existing rows are not validated
+ALTER TABLE urls
+ADD CONSTRAINT long_url_length CHECK (char_length(long_url) <= 8192) NOT VALID;
+
+ALTER TABLE external_links
+ADD CONSTRAINT long_url_length CHECK (char_length(long_url) <= 8192) NOT VALID;
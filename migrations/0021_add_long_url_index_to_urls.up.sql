@@ -0,0 +1,2 @@
+-- Hash index, since destinations can be longer than a btree entry allows
+CREATE INDEX idx_long_url ON urls USING HASH (long_url);
@@ -0,0 +1 @@
+CREATE SEQUENCE short_code_seq;
@@ -0,0 +1,2 @@
+-- Partial index for purging disabled links, which are few compared to active ones
+CREATE INDEX idx_disabled_at ON urls (disabled_at) WHERE disabled_at IS NOT NULL;
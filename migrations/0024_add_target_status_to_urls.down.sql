@@ -0,0 +1,4 @@
+ALTER TABLE urls
+DROP COLUMN IF EXISTS target_error,
+DROP COLUMN IF EXISTS target_status,
+DROP COLUMN IF EXISTS last_checked_at;
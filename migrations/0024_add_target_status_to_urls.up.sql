@@ -0,0 +1,6 @@
+-- Result of the latest dead link check: the destination's HTTP status, or why it
+-- could not be reached
+ALTER TABLE urls
+ADD COLUMN last_checked_at TIMESTAMPTZ,
+ADD COLUMN target_status SMALLINT,
+ADD COLUMN target_error TEXT;
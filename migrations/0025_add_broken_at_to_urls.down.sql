@@ -0,0 +1,3 @@
+ALTER TABLE urls
+DROP COLUMN IF EXISTS broken_at,
+DROP COLUMN IF EXISTS target_failures;
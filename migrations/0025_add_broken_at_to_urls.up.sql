@@ -0,0 +1,5 @@
+-- Consecutive dead link checks that found the destination gone (404/410 or an unknown
+-- host), and when the link stopped redirecting because of them
+ALTER TABLE urls
+ADD COLUMN target_failures INTEGER NOT NULL DEFAULT 0,
+ADD COLUMN broken_at TIMESTAMPTZ;
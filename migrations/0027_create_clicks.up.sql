@@ -0,0 +1,27 @@
+-- One row per redirect, split into a partition per month of `clicked_at` so old months
+-- are dropped whole instead of deleted row by row. The partitions for the coming months
+-- are created by the click partition job; the current and next month are created here so
+-- clicks can be stored right after migrating.
+CREATE TABLE clicks (
+    short_code VARCHAR(8) NOT NULL,
+    clicked_at TIMESTAMPTZ NOT NULL,
+    country VARCHAR(2)
+) PARTITION BY RANGE (clicked_at);
+
+CREATE INDEX idx_clicks_short_code ON clicks (short_code, clicked_at);
+
+DO $$
+DECLARE
+    month DATE := date_trunc('month', CURRENT_TIMESTAMP AT TIME ZONE 'UTC');
+BEGIN
+    FOR i IN 0..1 LOOP
+        EXECUTE format(
+            'CREATE TABLE %I PARTITION OF clicks FOR VALUES FROM (%L) TO (%L)',
+            to_char(month, '"clicks_y"YYYY"m"MM'),
+            month::TEXT || ' 00:00:00+00',
+            (month + INTERVAL '1 month')::DATE::TEXT || ' 00:00:00+00'
+        );
+        month := month + INTERVAL '1 month';
+    END LOOP;
+END
+$$;
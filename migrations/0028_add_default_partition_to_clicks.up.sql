@@ -0,0 +1,4 @@
+-- Clicks outside every monthly partition, e.g. when the click partition job fell behind,
+-- are kept here instead of failing to be stored. The job moves them into their month's
+-- partition once it creates it.
+CREATE TABLE clicks_default PARTITION OF clicks DEFAULT;
@@ -0,0 +1,4 @@
+ALTER TABLE external_links
+DROP COLUMN IF EXISTS disabled_at,
+DROP COLUMN IF EXISTS threat_checked_at,
+DROP COLUMN IF EXISTS threat_type;
@@ -0,0 +1,4 @@
+ALTER TABLE external_links
+ADD COLUMN threat_type TEXT,
+ADD COLUMN threat_checked_at TIMESTAMPTZ,
+ADD COLUMN disabled_at TIMESTAMPTZ;
@@ -0,0 +1,3 @@
+ALTER TABLE review_queue
+DROP COLUMN IF EXISTS external_id,
+DROP COLUMN IF EXISTS request;
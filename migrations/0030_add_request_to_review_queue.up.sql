@@ -0,0 +1,11 @@
+-- Held creations keep the whole request, so approving one creates the link that was asked
+-- for; held external links also keep their ID
+ALTER TABLE review_queue
+ADD COLUMN request TEXT,
+ADD COLUMN external_id VARCHAR(128);
+
+UPDATE review_queue
+SET request = json_build_object('long_url', long_url)::TEXT;
+
+ALTER TABLE review_queue
+ALTER COLUMN request SET NOT NULL;
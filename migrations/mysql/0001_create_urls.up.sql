@@ -0,0 +1,29 @@
+-- Plain links of the MySQL backend, with the columns of the Postgres `urls` table.
+-- Text compares case-sensitively as in Postgres, unlike with the default collation.
+CREATE TABLE
+    urls (
+        short_code VARCHAR(32) NOT NULL PRIMARY KEY,
+        long_url TEXT NOT NULL,
+        resolved_url TEXT,
+        utm_source TEXT,
+        utm_medium TEXT,
+        utm_campaign TEXT,
+        utm_term TEXT,
+        utm_content TEXT,
+        tags JSON NOT NULL,
+        description TEXT,
+        campaign_id INT,
+        clicks BIGINT NOT NULL DEFAULT 0,
+        threat_type VARCHAR(64),
+        threat_checked_at DATETIME(6),
+        activates_at DATETIME(6),
+        single_use BOOLEAN NOT NULL DEFAULT FALSE,
+        disabled_at DATETIME(6),
+        created_at DATETIME(6) NOT NULL DEFAULT CURRENT_TIMESTAMP(6),
+        last_checked_at DATETIME(6),
+        target_status SMALLINT,
+        target_error TEXT,
+        broken_at DATETIME(6),
+        INDEX idx_urls_long_url (long_url(255)),
+        INDEX idx_urls_created_at (created_at)
+    ) CHARACTER SET utf8mb4 COLLATE utf8mb4_bin;
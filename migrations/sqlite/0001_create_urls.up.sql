@@ -0,0 +1,31 @@
+-- Plain links of the SQLite backend, with the columns of the Postgres `urls` table.
+-- Tags are a JSON array and timestamps RFC 3339 text written by the application.
+CREATE TABLE
+    urls (
+        short_code TEXT PRIMARY KEY NOT NULL,
+        long_url TEXT NOT NULL,
+        resolved_url TEXT,
+        utm_source TEXT,
+        utm_medium TEXT,
+        utm_campaign TEXT,
+        utm_term TEXT,
+        utm_content TEXT,
+        tags TEXT NOT NULL DEFAULT '[]',
+        description TEXT,
+        campaign_id INTEGER,
+        clicks INTEGER NOT NULL DEFAULT 0,
+        threat_type TEXT,
+        threat_checked_at TEXT,
+        activates_at TEXT,
+        single_use BOOLEAN NOT NULL DEFAULT FALSE,
+        disabled_at TEXT,
+        created_at TEXT NOT NULL,
+        last_checked_at TEXT,
+        target_status INTEGER,
+        target_error TEXT,
+        broken_at TEXT
+    );
+
+CREATE INDEX idx_urls_long_url ON urls (long_url);
+
+CREATE INDEX idx_urls_created_at ON urls (created_at);
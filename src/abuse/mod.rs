@@ -0,0 +1,181 @@
+use std::str::FromStr;
+
+use axum::http::{header, HeaderMap};
+
+/// What to do with creations whose risk score reaches the threshold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AbuseAction {
+    /// Risk scoring is disabled.
+    Off,
+    /// Store the creation in the review queue instead of shortening it.
+    Queue,
+    /// Pretend the creation succeeded without storing it.
+    ShadowBan,
+}
+
+impl FromStr for AbuseAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "off" => Ok(Self::Off),
+            "queue" => Ok(Self::Queue),
+            "shadow_ban" => Ok(Self::ShadowBan),
+            _ => Err(format!("unknown abuse action: {s}")),
+        }
+    }
+}
+
+/// Who is creating a link, as far as risk scoring is concerned.
+#[derive(Debug, Clone)]
+pub enum Creator {
+    /// Callers holding the admin token or a signing secret, the CLI and approved reviews,
+    /// which are never scored.
+    Trusted,
+    /// Anyone else, told apart by the client address.
+    Anonymous { client: String, headers: HeaderMap },
+}
+
+/// Information about an anonymous creation request available to risk signals.
+pub struct CreateContext<'a> {
+    pub headers: &'a HeaderMap,
+    pub long_url: &'a str,
+    pub recent_creates: u64,
+}
+
+/// A single source of risk for a creation request.
+pub trait RiskSignal: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn score(&self, ctx: &CreateContext) -> u32;
+}
+
+#[derive(Debug)]
+pub struct Assessment {
+    pub score: u32,
+    pub reasons: Vec<String>,
+}
+
+/// Combines risk signals into a single score.
+pub struct RiskScorer {
+    signals: Vec<Box<dyn RiskSignal>>,
+}
+
+impl RiskScorer {
+    pub fn new(signals: Vec<Box<dyn RiskSignal>>) -> Self {
+        Self { signals }
+    }
+
+    pub fn assess(&self, ctx: &CreateContext) -> Assessment {
+        let mut assessment = Assessment {
+            score: 0,
+            reasons: Vec::new(),
+        };
+        for signal in &self.signals {
+            let score = signal.score(ctx);
+            if score > 0 {
+                assessment.score += score;
+                assessment.reasons.push(signal.name().to_string());
+            }
+        }
+        assessment
+    }
+}
+
+impl Default for RiskScorer {
+    fn default() -> Self {
+        Self::new(vec![
+            Box::new(RequestRate),
+            Box::new(UrlReputation),
+            Box::new(HeaderAnomalies),
+        ])
+    }
+}
+
+impl std::fmt::Debug for RiskScorer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(self.signals.iter().map(|signal| signal.name()))
+            .finish()
+    }
+}
+
+/// Many creations from the same client in the last hour.
+pub struct RequestRate;
+
+impl RiskSignal for RequestRate {
+    fn name(&self) -> &'static str {
+        "request_rate"
+    }
+
+    fn score(&self, ctx: &CreateContext) -> u32 {
+        match ctx.recent_creates {
+            0..=20 => 0,
+            21..=100 => 30,
+            _ => 60,
+        }
+    }
+}
+
+// Top level domains disproportionately used for abuse
+const SUSPICIOUS_TLDS: &[&str] = &["zip", "mov", "tk", "ml", "ga", "cf", "gq", "top", "xyz"];
+
+/// Destinations that look like phishing or malware hosting.
+pub struct UrlReputation;
+
+impl RiskSignal for UrlReputation {
+    fn name(&self) -> &'static str {
+        "url_reputation"
+    }
+
+    fn score(&self, ctx: &CreateContext) -> u32 {
+        let Ok(url) = url::Url::parse(ctx.long_url) else {
+            return 0;
+        };
+
+        let mut score = 0;
+        if !matches!(url.scheme(), "http" | "https") {
+            score += 40;
+        }
+        if !url.username().is_empty() || url.password().is_some() {
+            score += 30;
+        }
+        match url.host() {
+            Some(url::Host::Ipv4(_) | url::Host::Ipv6(_)) => score += 30,
+            Some(url::Host::Domain(domain)) => {
+                if domain.split('.').any(|label| label.starts_with("xn--")) {
+                    score += 10;
+                }
+                let tld = domain.rsplit('.').next().unwrap_or_default();
+                if SUSPICIOUS_TLDS.contains(&tld) {
+                    score += 20;
+                }
+            }
+            None => {}
+        }
+        if ctx.long_url.len() > 1000 {
+            score += 10;
+        }
+        score
+    }
+}
+
+/// Requests missing headers that regular clients send.
+pub struct HeaderAnomalies;
+
+impl RiskSignal for HeaderAnomalies {
+    fn name(&self) -> &'static str {
+        "header_anomalies"
+    }
+
+    fn score(&self, ctx: &CreateContext) -> u32 {
+        let mut score = 0;
+        if !ctx.headers.contains_key(header::USER_AGENT) {
+            score += 30;
+        }
+        if !ctx.headers.contains_key(header::ACCEPT) {
+            score += 10;
+        }
+        score
+    }
+}
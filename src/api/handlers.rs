@@ -1,249 +1,3101 @@
+use std::{
+    collections::BTreeMap,
+    net::{IpAddr, SocketAddr},
+    time::Duration,
+};
+
+use async_graphql::http::GraphiQLSource;
+use async_graphql_axum::{GraphQLRequest, GraphQLResponse};
 use axum::{
-    extract::{rejection::JsonRejection, Path, State},
-    http::StatusCode,
-    response::{IntoResponse, Redirect},
-    Json,
+    extract::{
+        rejection::JsonRejection,
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        ConnectInfo, Path, Query, RawQuery, State,
+    },
+    http::{header, Extensions, HeaderMap, HeaderValue, Method, StatusCode},
+    response::{
+        sse::{Event as SseEvent, KeepAlive, Sse},
+        Html, IntoResponse, Redirect, Response,
+    },
+    Extension, Json,
 };
-use redis::Commands;
+use chrono::Utc;
+use futures_util::{stream, StreamExt};
+use maud::Markup;
+use redis::AsyncCommands;
 use serde_json::{json, Value};
-use tracing::{debug, error, info, instrument};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, instrument, warn};
 
 use crate::{
-    db::models::UrlDetail, state::AppState, types::{ShortenRequest, ShortenResponse, UrlDetailResponse}, utils::{encode_long_url, valid_short_code, valid_url}
+    abuse::{AbuseAction, Assessment, CreateContext, Creator},
+    blocklist,
+    cache::{self, stats::CacheEvent},
+    config::DuplicatePolicy,
+    db::{
+        models::{UrlDetail, UrlTarget},
+        repository::{
+            self, blocked_domains, campaigns,
+            external_links::{self, ExternalLink, NewExternalLink},
+            links,
+            review_queue::{self, NewReview},
+            routes, LinkFilter, NewLink, Page,
+        },
+    },
+    error::{AppError, FieldErrors, Problem, PROBLEM_JSON},
+    events,
+    export::{ExportFormat, ExportedLink, Exporter},
+    geo, graphql,
+    state::{AppState, RedisConn},
+    templates,
+    types::{
+        BlockedDomainRequest, BlockedDomainResponse, CacheStatsResponse, CampaignLinksRequest,
+        CampaignRequest, CampaignResponse, CampaignStatsResponse, ClickEvent, ClickStreamQuery,
+        DashboardSnapshot, DeleteQuery, DeliveryQuery, ExpandQuery, ExpandResponse,
+        ExternalLinkRequest, ExternalLinkResponse, FormatQuery, ListQuery, PageQuery, Pagination,
+        PreviewResponse, QrFormat, QrQuery, ReviewResponse, ShortenRequest, ShortenResponse,
+        TagCount, UpdateUrlRequest, UrlDetailResponse, VariantStats, VersionResponse,
+        WebhookDeliveryResponse, WebhookRequest, WebhookResponse,
+    },
+    utils::{
+        alphabet::Alphabet,
+        append_path, badge, banned_code, canonical_code, client_ip,
+        device::Device,
+        encode_long_url, merge_params, merge_query,
+        normalize::{ascii_url, normalize_url, with_default_scheme},
+        normalize_tag, preview, qr, reserved_code, resolve,
+        safe_browsing::ThreatAction,
+        short_code_from_url, ssrf, valid_deep_link, valid_short_code, valid_tag, valid_url,
+        web_url, MAX_SHORT_CODE_LENGTH,
+    },
+    webhooks::{self, Event},
+};
+
+use super::{
+    middleware::{creator, vouched_for},
+    openapi,
 };
 
+// Maximum number of attempts at generating a fresh short code
+pub(crate) const MAX_CODE_ATTEMPTS: i64 = 5;
+
+// Page size of v2 lists when none is requested, and the largest one allowed
+const DEFAULT_PAGE_SIZE: u32 = 50;
+const MAX_PAGE_SIZE: u32 = 100;
+
+// Refusal of creations outside the REST API while REQUIRE_SIGNED_CREATION is set
+pub(crate) const UNSIGNED_CREATION: &str = "Links can only be created with a signed request";
+
+// Maximum number of deliveries listed at once
+const MAX_DELIVERIES: i64 = 500;
+
+// Maximum number of tags on a single link
+const MAX_TAGS: usize = 20;
+
+// Maximum length of a link description in characters
+const MAX_DESCRIPTION_LENGTH: usize = 1000;
+
+// Maximum length of a campaign name in characters
+const MAX_CAMPAIGN_NAME_LENGTH: usize = 100;
+
+// Number of member links listed in campaign stats
+const CAMPAIGN_TOP_LINKS: i64 = 10;
+
+// Maximum number of A/B split variants on a single link
+const MAX_VARIANTS: usize = 10;
+
+// Largest relative weight of an A/B split variant
+const MAX_VARIANT_WEIGHT: i32 = 1000;
+
+// Maximum number of geo targets on a single link
+const MAX_GEO_TARGETS: usize = 50;
+
+// Maximum number of time rules on a single link
+const MAX_TIME_RULES: usize = 20;
+
+// Age after which a cached link preview is fetched again
+const PREVIEW_MAX_AGE_HOURS: i64 = 24;
+
+// How long a failed preview fetch is answered from the cache before the destination is
+// tried again
+const PREVIEW_FAILURE_TTL_SECONDS: u64 = 300;
+
+// Delay before evicting a changed link from the cache once more, covering redirects on
+// other instances that read the database just before the change and cache what they saw
+// right after
+const EVICTION_RETRY_DELAY: Duration = Duration::from_secs(2);
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/health",
+    tag = "service",
+    summary = "Liveness check",
+    responses((status = 200, description = "Service is up", body = Object)),
+)]
 #[instrument]
 pub async fn health_check() -> (StatusCode, Json<Value>) {
     let response = json!({
         "status": "ok",
-        "version": "1.0.0",
+        "version": env!("CARGO_PKG_VERSION"),
     });
     (StatusCode::OK, Json(response))
 }
 
-#[instrument(skip(state, payload))]
+#[utoipa::path(
+    get,
+    path = "/api/v1/version",
+    tag = "service",
+    summary = "Build details of the running binary",
+    responses((status = 200, body = VersionResponse)),
+)]
+#[instrument]
+pub async fn get_version() -> Json<VersionResponse> {
+    Json(VersionResponse::CURRENT)
+}
+
+// OpenAPI description of the JSON API
+#[instrument]
+pub async fn openapi_spec() -> Json<&'static utoipa::openapi::OpenApi> {
+    Json(&openapi::SPEC)
+}
+
+// Interactive API documentation generated from the OpenAPI spec
+#[instrument(skip(state))]
+pub async fn api_docs(State(state): State<AppState>) -> Markup {
+    templates::api_docs(&format!("{}/api/v1/openapi.json", state.base_url))
+}
+
+// GraphQL queries and mutations over links, tags and campaign stats
+#[instrument(skip(state, headers, extensions, request))]
+pub async fn graphql(
+    State(state): State<AppState>,
+    ConnectInfo(remote_addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    extensions: Extensions,
+    request: GraphQLRequest,
+) -> GraphQLResponse {
+    let creator = creator(&state, &headers, &extensions, Some(remote_addr));
+    graphql::SCHEMA
+        .execute(request.into_inner().data(state).data(creator))
+        .await
+        .into()
+}
+
+// GraphiQL explorer for the GraphQL endpoint
+pub async fn graphiql() -> Html<String> {
+    Html(GraphiQLSource::build().endpoint("/api/graphql").finish())
+}
+
+// HTML shorten form for browsers, a short service description for everything else
+#[instrument(skip(state, headers))]
+pub async fn landing_page(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    let wants_html = headers
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|accept| accept.contains("text/html"));
+
+    if wants_html {
+        return templates::landing().into_response();
+    }
+
+    Json(json!({
+        "name": "tlong",
+        "version": env!("CARGO_PKG_VERSION"),
+        "shorten_url": format!("{}/api/v1/shorten", state.base_url),
+    }))
+    .into_response()
+}
+
+pub async fn robots_txt(State(state): State<AppState>) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; charset=utf-8")],
+        state.robots_txt.to_string(),
+    )
+}
+
+// There is no icon; answering keeps browsers from resolving it as a short code
+pub async fn favicon() -> impl IntoResponse {
+    (
+        StatusCode::NO_CONTENT,
+        [(header::CACHE_CONTROL, "public, max-age=86400")],
+    )
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/shorten",
+    tag = "links",
+    summary = "Shorten a URL",
+    request_body = ShortenRequest,
+    params(
+        (
+            "Idempotency-Key" = Option<String>,
+            Header,
+            description = "Replays the first response to this key instead of creating again",
+        ),
+        (
+            "X-Tlong-Signature" = Option<String>,
+            Header,
+            description = "`t=<unix seconds>,v1=<hex HMAC-SHA256 of \"<t>.<body>\">`, keyed with \
+                           one of SIGNING_SECRETS",
+        ),
+    ),
+    responses(
+        (status = 201, description = "Link created", body = ShortenResponse),
+        (
+            status = 200,
+            description = "Existing link to the destination reused",
+            body = ShortenResponse,
+        ),
+        (
+            status = 409,
+            description = "Destination already has a link under DUPLICATE_POLICY=conflict, or \
+                           a request with the same Idempotency-Key is in progress",
+            body = Problem,
+            content_type = PROBLEM_JSON,
+        ),
+        (
+            status = 401,
+            description = "Missing, invalid or expired signature",
+            body = Problem,
+            content_type = PROBLEM_JSON,
+        ),
+        (status = 202, description = "Held for review under ABUSE_ACTION=queue", body = Object),
+        (status = 422, description = "Invalid fields", body = Problem, content_type = PROBLEM_JSON),
+    ),
+)]
+#[instrument(skip(state, headers, extensions, payload))]
 pub async fn create_short_url(
     State(state): State<AppState>,
+    ConnectInfo(remote_addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    extensions: Extensions,
     payload: Result<Json<ShortenRequest>, JsonRejection>,
-) -> impl IntoResponse {
-    let payload = match payload {
-        Ok(payload) => payload.0,
-        Err(rejection) => {
-            let error_message = match rejection {
-                JsonRejection::MissingJsonContentType(_) => {
-                    json!({"error": "Expected 'Content-Type: application/json' header"})
-                }
-                JsonRejection::JsonSyntaxError(_) => json!({"error": "JSON syntax error"}),
-                JsonRejection::JsonDataError(_) => json!({"error": "JSON data structure mismatch"}),
-                _ => json!({"error": "Unknown JSON parsing error"}),
-            };
-            error!(error = ?rejection, "JSON parsing error");
-            return (StatusCode::BAD_REQUEST, Json(error_message)).into_response();
+) -> Result<Response, AppError> {
+    let payload = json_payload(payload)?;
+    let creator = creator(&state, &headers, &extensions, Some(remote_addr));
+    let (status, response) = shorten(&state, payload, &creator).await?;
+    Ok((status, Json(response)).into_response())
+}
+
+// Create a link, or reuse one as DUPLICATE_POLICY allows, for the REST, GraphQL and gRPC
+// APIs; `200 OK` means an existing link was returned
+pub(crate) async fn shorten(
+    state: &AppState,
+    mut payload: ShortenRequest,
+    creator: &Creator,
+) -> Result<(StatusCode, ShortenResponse), AppError> {
+    if state.accept_schemeless_urls {
+        if let Some(long_url) = with_default_scheme(&payload.long_url) {
+            payload.long_url = long_url;
+        }
+    }
+
+    if let Err(e) = validate_shorten_request(&mut payload, state.max_url_length) {
+        error!(error = %e, "Invalid shorten request");
+        return Err(e);
+    }
+
+    if state.ssrf_dns_check {
+        if let Err(e) = check_resolved_destinations(&payload.destinations()).await {
+            error!(error = %e, "Destination resolves to a non-public address");
+            return Err(AppError::PrivateDestination);
+        }
+    }
+
+    if let Some((client, assessment)) = assess_risk(state, creator, &payload.long_url).await {
+        if state.abuse_action == AbuseAction::ShadowBan {
+            let short_code = decoy_code(state)?;
+            let short_url = format!("{}/{}", state.base_url, short_code);
+            return Ok((
+                StatusCode::CREATED,
+                ShortenResponse::new(short_code, short_url, payload),
+            ));
+        }
+        let review = NewReview {
+            long_url: &payload.long_url,
+            external_id: None,
+            request: &json!(payload).to_string(),
+            client_ip: client,
+            score: assessment.score as i32,
+            reasons: &assessment.reasons,
+        };
+        review_queue::enqueue(&state.pg_db, &review).await?;
+        return Err(AppError::HeldForReview);
+    }
+
+    let destination = merge_params(&payload.long_url, payload.utm.pairs());
+
+    // Single-use and routed links are never shared, so they always get a fresh code
+    let duplicate_policy = if payload.single_use || payload.is_routed() {
+        DuplicatePolicy::New
+    } else {
+        match payload.reuse_existing {
+            Some(true) => DuplicatePolicy::Existing,
+            Some(false) => DuplicatePolicy::New,
+            None => state.duplicate_policy,
         }
     };
 
-    if !valid_url(&payload.long_url) {
-        error!(url = %payload.long_url, "Invalid URL format");
-        return (
-            StatusCode::BAD_REQUEST,
-            Json(json!({"error": "Invalid URL format"})),
+    if duplicate_policy != DuplicatePolicy::New {
+        if let Some(short_code) = state.urls.find_existing(&payload).await? {
+            return existing_link(&state.base_url, duplicate_policy, short_code, payload);
+        }
+    }
+
+    let resolved_url = if state.resolve_redirects {
+        match resolve::final_destination(
+            &state.resolver_client,
+            &payload.long_url,
+            state.max_redirect_hops,
+            state.ssrf_dns_check,
         )
-            .into_response();
+        .await
+        {
+            Ok(resolved_url) if resolved_url.chars().count() > state.max_url_length => {
+                warn!(url = %payload.long_url, "Resolved URL too long");
+                None
+            }
+            Ok(resolved_url) if resolved_url != payload.long_url => {
+                debug!(resolved_url = %resolved_url, "Resolved redirect chain");
+                Some(resolved_url)
+            }
+            Ok(_) => None,
+            Err(e) => {
+                warn!(error = %e, url = %payload.long_url, "Failed to resolve redirect chain");
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    let blocked = payload
+        .destinations()
+        .into_iter()
+        .chain(resolved_url.as_deref())
+        .find_map(|url| state.blocklist.matching(url));
+    if let Some(domain) = blocked {
+        error!(url = %payload.long_url, domain = %domain, "Destination domain is blocked");
+        return Err(AppError::BlockedDomain { domain });
     }
 
-    let short_code = encode_long_url(&payload.long_url).await[0..8].to_string();
+    let mut destinations = payload.destinations();
+    destinations.extend(resolved_url.as_deref());
+    let threat_type = check_threats(state, &destinations).await?;
+
+    let threat_checked_at = state.safe_browsing.is_some().then(Utc::now);
+    let mut attempts = 0;
+    let mut short_code = generate_code(state, &destination, attempts).await?;
     debug!(short_code = %short_code, "Generated short code");
 
-    let query = sqlx::query(
-        "INSERT INTO urls (long_url, short_code) VALUES ($1, $2) ON CONFLICT (short_code) DO NOTHING",
-    )
-    .bind(&payload.long_url)
-    .bind(&short_code);
+    loop {
+        if reserved_code(&short_code, &state.reserved_codes)
+            || banned_code(&short_code, &state.banned_words)
+        {
+            if attempts >= MAX_CODE_ATTEMPTS {
+                return Err(unique_code_exhausted(attempts));
+            }
+            attempts += 1;
+            info!(short_code = %short_code, "Generated short code is reserved or contains a banned word");
+            short_code = generate_code(state, &destination, attempts).await?;
+            continue;
+        }
 
-    match query.execute(&state.pg_db).await {
-        Ok(_) => {
-            let short_url = format!("{}/{}", state.base_url, short_code);
-            info!(short_url = %short_url, "Created short URL");
-            let response = ShortenResponse {
-                short_code,
-                short_url,
-                long_url: payload.long_url,
-            };
-            (StatusCode::CREATED, Json(response)).into_response()
+        let link = NewLink {
+            short_code: &short_code,
+            request: &payload,
+            resolved_url: resolved_url.as_deref(),
+            threat_type: threat_type.as_deref(),
+            threat_checked_at,
+        };
+        if state.urls.insert(&link).await? {
+            break;
         }
-        Err(e) => {
-            error!(error = %e, "Database error");
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({"error": "Failed to create short URL"})),
-            )
-                .into_response()
+
+        // The code is taken: either by the same link or, since codes are
+        // truncated hashes, by a different destination that collides with it
+        let duplicate = state
+            .urls
+            .destination(&short_code)
+            .await?
+            .is_some_and(|existing| same_link(&existing, &payload));
+
+        match duplicate_policy {
+            DuplicatePolicy::Existing | DuplicatePolicy::Conflict if duplicate => {
+                return existing_link(&state.base_url, duplicate_policy, short_code, payload);
+            }
+            _ if attempts < MAX_CODE_ATTEMPTS => {
+                if !duplicate {
+                    info!(short_code = %short_code, "Short code collision");
+                }
+                attempts += 1;
+                short_code = generate_code(state, &destination, attempts).await?;
+                debug!(short_code = %short_code, "Generated new short code");
+            }
+            _ => return Err(unique_code_exhausted(attempts)),
+        }
+    }
+
+    if let Some(code_filter) = &state.code_filter {
+        code_filter.insert(&short_code);
+    }
+    forget_missing(state, &short_code).await;
+    // Links the redirect handler would cache are cached now, so that even the first
+    // visitors of a freshly shared link are served from Redis
+    if !payload.single_use
+        && !payload.is_routed()
+        && payload
+            .activates_at
+            .is_none_or(|activates_at| activates_at <= Utc::now())
+    {
+        let long_url = merge_params(
+            resolved_url.as_deref().unwrap_or(&payload.long_url),
+            payload.utm.pairs(),
+        );
+        cache::store_link(
+            &state.redis_db,
+            &short_code,
+            &long_url,
+            state.runtime.load().cache_ttl,
+        )
+        .await;
+    }
+    cache::invalidate_responses(&state.redis_db).await;
+
+    let short_url = format!("{}/{}", state.base_url, short_code);
+    info!(short_url = %short_url, "Created short URL");
+    let response = ShortenResponse::new(short_code, short_url, payload);
+    webhooks::emit(state, Event::Created, &response);
+    Ok((StatusCode::CREATED, response))
+}
+
+// Fail if the host of any destination resolves into a private network
+pub(crate) async fn check_resolved_destinations(urls: &[&str]) -> Result<(), String> {
+    for url in urls {
+        if let Ok(url) = url::Url::parse(url) {
+            ssrf::check_resolved(&url).await?;
         }
     }
+    Ok(())
 }
 
-#[instrument(skip(state))]
-pub async fn handle_short_url(
-    State(state): State<AppState>,
-    Path(short_code): Path<String>,
-) -> impl IntoResponse {
-    if !valid_short_code(&short_code) {
-        error!(short_code = %short_code, "Invalid short code");
-        return StatusCode::BAD_REQUEST.into_response();
+// Response for a request whose destination already has a link: the link itself
+// with 200 OK, or 409 Conflict under the conflict policy
+pub(crate) fn existing_link(
+    base_url: &str,
+    duplicate_policy: DuplicatePolicy,
+    short_code: String,
+    payload: ShortenRequest,
+) -> Result<(StatusCode, ShortenResponse), AppError> {
+    let short_url = format!("{base_url}/{short_code}");
+    if duplicate_policy == DuplicatePolicy::Conflict {
+        info!(short_code = %short_code, "Short code already exists");
+        return Err(AppError::LinkExists {
+            short_code,
+            short_url,
+        });
+    }
+
+    info!(short_url = %short_url, "Reusing existing short URL");
+    let response = ShortenResponse::new(short_code, short_url, payload);
+    Ok((StatusCode::OK, response))
+}
+
+// Whether an existing link is a plain link to the same destination a request asks for
+pub(crate) fn same_link(existing: &UrlTarget, payload: &ShortenRequest) -> bool {
+    existing.long_url == payload.long_url
+        && existing.utm == payload.utm
+        && existing.activates_at == payload.activates_at
+        && !existing.single_use
+        && !existing.is_disabled()
+        && !existing.is_broken()
+        && !existing.is_routed()
+}
+
+// Short code for an attempt at storing a destination: the next number of the code
+// sequence, or the destination's hash, salted on retries since the natural code is taken
+async fn generate_code(
+    state: &AppState,
+    destination: &str,
+    attempt: i64,
+) -> Result<String, AppError> {
+    if let Some(scrambler) = &state.code_scrambler {
+        let number = routes::next_code_number(&state.pg_db).await?;
+        return scrambler
+            .code(number as u64, &state.code_alphabet)
+            .ok_or_else(|| {
+                AppError::Internal(
+                    "Code sequence exhausted, increase SEQUENCE_CODE_LENGTH".to_string(),
+                )
+            });
+    }
+
+    Ok(hashed_code(destination, attempt, &state.code_alphabet).await)
+}
+
+// Code answered to a shadow-banned creation, shaped like the codes CODE_STRATEGY gives real
+// links but random, so it neither reveals nor takes the code the destination would get
+fn decoy_code(state: &AppState) -> Result<String, AppError> {
+    let length = state
+        .code_scrambler
+        .map_or(MAX_SHORT_CODE_LENGTH, |scrambler| scrambler.length());
+    state
+        .code_alphabet
+        .random(length)
+        .map_err(|e| AppError::Internal(format!("Failed to generate a code: {e}")))
+}
+
+// Short code taken from the hash of a destination, salted on retries
+pub(crate) async fn hashed_code(destination: &str, attempt: i64, alphabet: &Alphabet) -> String {
+    let input = if attempt == 0 {
+        destination.to_string()
+    } else {
+        format!(
+            "{}#{}",
+            destination,
+            Utc::now().timestamp_nanos_opt().unwrap_or_default() + attempt
+        )
+    };
+    encode_long_url(&input, alphabet).await[0..MAX_SHORT_CODE_LENGTH].to_string()
+}
+
+// Error for a creation that kept running into taken or unusable short codes
+pub(crate) fn unique_code_exhausted(attempts: i64) -> AppError {
+    AppError::Internal(format!(
+        "Failed to generate a unique short code after {attempts} attempts"
+    ))
+}
+
+// Score a creation by an anonymous caller, returning its client and assessment when the
+// score reaches ABUSE_THRESHOLD and ABUSE_ACTION has to divert it. Every transport creates
+// through here, so each creation in a batched request counts on its own
+async fn assess_risk<'a>(
+    state: &AppState,
+    creator: &'a Creator,
+    long_url: &str,
+) -> Option<(&'a str, Assessment)> {
+    let Creator::Anonymous { client, headers } = creator else {
+        return None;
+    };
+    if state.abuse_action == AbuseAction::Off {
+        return None;
+    }
+
+    let recent_creates = cache::count_recent_creates(&mut state.redis_db.clone(), client)
+        .await
+        .unwrap_or_else(|e| {
+            error!(error = %e, "Failed to count recent creations");
+            0
+        });
+    let assessment = state.risk_scorer.assess(&CreateContext {
+        headers,
+        long_url,
+        recent_creates,
+    });
+    debug!(score = assessment.score, reasons = ?assessment.reasons, "Assessed creation risk");
+    if assessment.score < state.abuse_threshold {
+        return None;
     }
 
-    let mut redis_conn = match state.redis_db.get() {
-        Ok(conn) => conn,
+    warn!(
+        client = %client,
+        url = %long_url,
+        score = assessment.score,
+        reasons = ?assessment.reasons,
+        "High-risk creation"
+    );
+    Some((client, assessment))
+}
+
+// Look destinations up with Safe Browsing, returning the threat found if
+// SAFE_BROWSING_ACTION lets unsafe destinations through and refusing them otherwise
+async fn check_threats(
+    state: &AppState,
+    destinations: &[&str],
+) -> Result<Option<String>, AppError> {
+    let Some(safe_browsing) = &state.safe_browsing else {
+        return Ok(None);
+    };
+    let threat_type = match safe_browsing.lookup(&state.http_client, destinations).await {
+        Ok(threats) => threats.into_values().next(),
+        // Creation does not depend on the lookup being available
         Err(e) => {
-            error!(error = %e, "Failed to get Redis connection");
-            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            warn!(error = %e, urls = ?destinations, "Safe Browsing lookup failed");
+            return Ok(None);
         }
     };
-
-    match redis_conn.get::<_, Option<String>>(&short_code) {
-        Ok(Some(long_url)) => {
-            info!(short_code = %short_code, "Cache hit");
-            return Redirect::permanent(&long_url).into_response();
+    if let Some(threat_type) = &threat_type {
+        warn!(urls = ?destinations, threat_type = %threat_type, "Unsafe URL");
+        if safe_browsing.action == ThreatAction::Reject {
+            return Err(AppError::UnsafeUrl {
+                threat_type: threat_type.clone(),
+            });
         }
-        Ok(None) => {
-            info!(short_code = %short_code, "Cache miss");
+    }
+    Ok(threat_type)
+}
+
+// Body of a JSON request, logging why it could not be read
+pub(crate) fn json_payload<T>(payload: Result<Json<T>, JsonRejection>) -> Result<T, AppError> {
+    payload.map(|Json(payload)| payload).map_err(|rejection| {
+        error!(error = ?rejection, "JSON parsing error");
+        AppError::from(rejection)
+    })
+}
+
+// Check a creation request and bring its fields into their stored form, reporting
+// every invalid field at once
+pub(crate) fn validate_shorten_request(
+    payload: &mut ShortenRequest,
+    max_url_length: usize,
+) -> Result<(), AppError> {
+    let mut errors = FieldErrors::default();
+
+    check_destination(
+        &mut errors,
+        "long_url",
+        &mut payload.long_url,
+        max_url_length,
+        normalize_url,
+    );
+
+    if payload.tags.len() > MAX_TAGS {
+        errors.add("tags", format!("must have at most {MAX_TAGS} tags"));
+    }
+    for (index, tag) in payload.tags.iter().enumerate() {
+        if !valid_tag(tag) {
+            errors.add(format!("tags[{index}]"), "must be 1 to 64 characters");
         }
-        Err(e) => {
-            error!(error = %e, "Redis error");
-            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+    payload.tags = payload.tags.iter().map(|tag| normalize_tag(tag)).collect();
+    payload.tags.sort();
+    payload.tags.dedup();
+
+    match normalize_description(payload.description.as_deref()) {
+        Ok(description) => payload.description = description,
+        Err(_) => errors.add(
+            "description",
+            format!("must be at most {MAX_DESCRIPTION_LENGTH} characters"),
+        ),
+    }
+
+    if payload.single_use && payload.is_routed() {
+        errors.add("single_use", "cannot be combined with routing rules");
+    }
+
+    if payload.variants.len() > MAX_VARIANTS {
+        errors.add(
+            "variants",
+            format!("must have at most {MAX_VARIANTS} variants"),
+        );
+    }
+    for (index, variant) in payload.variants.iter_mut().enumerate() {
+        check_destination(
+            &mut errors,
+            &format!("variants[{index}].long_url"),
+            &mut variant.long_url,
+            max_url_length,
+            ascii_url,
+        );
+        if !(1..=MAX_VARIANT_WEIGHT).contains(&variant.weight) {
+            errors.add(
+                format!("variants[{index}].weight"),
+                format!("must be between 1 and {MAX_VARIANT_WEIGHT}"),
+            );
         }
     }
 
-    let query = r#"
-        SELECT long_url
-        FROM urls
-        WHERE short_code = $1
-    "#;
-    let result: Result<Option<String>, sqlx::Error> = sqlx::query_scalar(query)
-        .bind(&short_code)
-        .fetch_optional(&state.pg_db)
-        .await;
+    if payload.geo_targets.len() > MAX_GEO_TARGETS {
+        errors.add(
+            "geo_targets",
+            format!("must have at most {MAX_GEO_TARGETS} regions"),
+        );
+    }
+    let mut geo_targets = BTreeMap::new();
+    for (region, mut long_url) in std::mem::take(&mut payload.geo_targets) {
+        let field = format!("geo_targets.{region}");
+        if !geo::valid_region(&region) {
+            errors.add(&field, "must be keyed by a country code or EU");
+        }
+        check_destination(
+            &mut errors,
+            &field,
+            &mut long_url,
+            max_url_length,
+            ascii_url,
+        );
+        geo_targets.insert(region.to_ascii_uppercase(), long_url);
+    }
+    payload.geo_targets = geo_targets;
 
-    match result {
-        Ok(Some(long_url)) => {
-            info!(short_code = %short_code, "Redirecting to long URL");
-            if let Err(e) = redis_conn.set_ex::<_, _, ()>(&short_code, &long_url, 3600) {
-                error!(error = %e, "Failed to cache URL in Redis");
+    let mut device_targets = BTreeMap::new();
+    for (device, mut long_url) in std::mem::take(&mut payload.device_targets) {
+        let field = format!("device_targets.{device}");
+        check_destination(
+            &mut errors,
+            &field,
+            &mut long_url,
+            max_url_length,
+            ascii_url,
+        );
+        match device.parse::<Device>() {
+            Ok(device) => {
+                device_targets.insert(device.as_str().to_string(), long_url);
             }
-            Redirect::permanent(&long_url).into_response()
+            Err(_) => errors.add(&field, "must be keyed by ios, android or desktop"),
         }
-        Ok(None) => {
-            error!(short_code = %short_code, "Short code not found");
-            StatusCode::NOT_FOUND.into_response()
+    }
+    payload.device_targets = device_targets;
+
+    if let Some(deep_link) = &mut payload.deep_link {
+        if !valid_deep_link(&deep_link.uri) {
+            errors.add("deep_link.uri", "must be an absolute URI of an app");
         }
-        Err(e) => {
-            error!(error = %e, "Database error");
-            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        if let Some(store_url) = &mut deep_link.ios_store_url {
+            check_destination(
+                &mut errors,
+                "deep_link.ios_store_url",
+                store_url,
+                max_url_length,
+                ascii_url,
+            );
+        }
+        if let Some(store_url) = &mut deep_link.android_store_url {
+            check_destination(
+                &mut errors,
+                "deep_link.android_store_url",
+                store_url,
+                max_url_length,
+                ascii_url,
+            );
         }
     }
-}
 
-#[instrument(skip(state))]
-pub async fn delete_short_url(
-    State(state): State<AppState>,
-    Path(short_code): Path<String>,
-) -> Result<Json<Value>, StatusCode> {
-    if !valid_short_code(&short_code) {
-        error!(short_code = %short_code, "Invalid short code");
-        return Err(StatusCode::BAD_REQUEST);
+    if payload.time_rules.len() > MAX_TIME_RULES {
+        errors.add(
+            "time_rules",
+            format!("must have at most {MAX_TIME_RULES} rules"),
+        );
+    }
+    for (index, rule) in payload.time_rules.iter_mut().enumerate() {
+        if rule.starts_at == rule.ends_at {
+            errors.add(
+                format!("time_rules[{index}]"),
+                "must span a non-empty window",
+            );
+        }
+        check_destination(
+            &mut errors,
+            &format!("time_rules[{index}].long_url"),
+            &mut rule.long_url,
+            max_url_length,
+            ascii_url,
+        );
+        rule.priority.get_or_insert(index as i32);
     }
 
-    let result: Option<String> = sqlx::query_scalar(
-        "
-        DELETE FROM urls
-        WHERE short_code = $1
-        RETURNING short_code
-        ",
-    )
-    .bind(&short_code)
-    .fetch_optional(&state.pg_db)
-    .await
-    .map_err(|e| {
-        error!(error = %e, "Database error");
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    errors.into_result()
+}
 
-    match result {
-        Some(_) => {
-            info!(short_code = %short_code, "Short URL deleted successfully");
-            Ok(Json(json!({"message": "short url deleted successfully"})))
-        }
-        None => {
-            error!(short_code = %short_code, "Short code not found");
-            Err(StatusCode::NOT_FOUND)
+// Check one destination of a creation request and bring it into its stored form
+fn check_destination(
+    errors: &mut FieldErrors,
+    field: &str,
+    url: &mut String,
+    max_url_length: usize,
+    normalize: fn(&str) -> String,
+) {
+    match url::Url::parse(url) {
+        Err(_) => return errors.add(field, "must be an absolute URL"),
+        Ok(parsed) if !web_url(&parsed) => return errors.add(field, "must be http/https"),
+        Ok(parsed) if !ssrf::public_host(&parsed) => {
+            return errors.add(field, "must point to a public host")
         }
+        Ok(_) => {}
+    }
+
+    *url = normalize(url);
+    if url.chars().count() > max_url_length {
+        errors.add(
+            field,
+            format!("must be at most {max_url_length} characters"),
+        );
     }
 }
 
-#[instrument(skip(state))]
-pub async fn get_all_short_url(
+#[instrument(skip(state, headers))]
+pub async fn handle_short_url(
     State(state): State<AppState>,
-) -> Result<Json<Vec<UrlDetailResponse>>, StatusCode> {
-    let results = sqlx::query_as::<_, UrlDetail>(
-        "
-        SELECT short_code, long_url, created_at
-        FROM urls
-        ORDER BY created_at DESC
-        ",
-    )
-    .fetch_all(&state.pg_db)
-    .await
-    .map_err(|e| {
-        error!(error = %e, "Database error");
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
-
-    let response: Vec<UrlDetailResponse> = results
-        .into_iter()
-        .map(|row| UrlDetailResponse {
-            short_url: format!("{}/{}", state.base_url, &row.short_code),
-            short_code: row.short_code,
-            long_url: row.long_url,
-            created_at: row.created_at.to_string(),
-        })
-        .collect();
+    Path(short_code): Path<String>,
+    RawQuery(params): RawQuery,
+    ConnectInfo(remote_addr): ConnectInfo<SocketAddr>,
+    method: Method,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    if let Some(short_code) = short_code.strip_suffix('+') {
+        return link_info_page(&state, short_code).await.into_response();
+    }
 
-    Ok(Json(response))
+    let visitor = Visitor::new(&state, &method, &headers, remote_addr);
+    redirect_short_url(&state, short_code, None, params, &visitor).await
 }
 
-#[instrument(skip(state))]
-pub async fn get_short_url_details(
+#[instrument(skip(state, headers))]
+pub async fn handle_short_url_path(
     State(state): State<AppState>,
-    Path(short_code): Path<String>,
-) -> Result<Json<UrlDetailResponse>, StatusCode> {
-    if !valid_short_code(&short_code) {
+    Path((short_code, path)): Path<(String, String)>,
+    RawQuery(params): RawQuery,
+    ConnectInfo(remote_addr): ConnectInfo<SocketAddr>,
+    method: Method,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let visitor = Visitor::new(&state, &method, &headers, remote_addr);
+    redirect_short_url(&state, short_code, Some(path), params, &visitor).await
+}
+
+// Details of the requester that routed links pick a destination by
+#[derive(Debug)]
+struct Visitor {
+    ip: Option<IpAddr>,
+    device: Option<Device>,
+    // HEAD requests, mostly from link checkers, learn where a link goes without
+    // counting as a click or using up a single-use link
+    probe: bool,
+}
+
+impl Visitor {
+    fn new(
+        state: &AppState,
+        method: &Method,
+        headers: &HeaderMap,
+        remote_addr: SocketAddr,
+    ) -> Self {
+        Self {
+            probe: method == Method::HEAD,
+            ip: client_ip(headers, Some(remote_addr), &state.trusted_proxies),
+            device: headers
+                .get(header::USER_AGENT)
+                .and_then(|value| value.to_str().ok())
+                .and_then(Device::from_user_agent),
+        }
+    }
+
+    // Country code of the visitor's address, if a GeoIP database is configured
+    fn country(&self, state: &AppState) -> Option<String> {
+        let (geoip, ip) = (state.geoip.as_ref()?, self.ip?);
+        geoip.regions(ip).into_iter().next()
+    }
+}
+
+async fn redirect_short_url(
+    state: &AppState,
+    short_code: String,
+    path: Option<String>,
+    params: Option<String>,
+    visitor: &Visitor,
+) -> Response {
+    let short_code = canonical_code(&short_code, state.case_insensitive_codes);
+    if !valid_short_code(&short_code, &state.code_alphabet) {
         error!(short_code = %short_code, "Invalid short code");
-        return Err(StatusCode::BAD_REQUEST);
+        return unknown_link(state, AppError::InvalidShortCode(short_code));
+    }
+
+    if let Some(long_url) = state
+        .local_cache
+        .as_ref()
+        .and_then(|local_cache| local_cache.get(&short_code))
+    {
+        info!(short_code = %short_code, "Local cache hit");
+        state.cache_stats.record(CacheEvent::LocalHit);
+        return cached_destination(
+            state,
+            &short_code,
+            &long_url,
+            path.as_deref(),
+            params.as_deref(),
+            visitor,
+        );
+    }
+
+    if state
+        .code_filter
+        .as_ref()
+        .is_some_and(|code_filter| !code_filter.may_contain(&short_code))
+    {
+        info!(short_code = %short_code, "Short code filtered out");
+        state.cache_stats.record(CacheEvent::Filtered);
+        return unknown_link(state, AppError::NotFound("Short URL"));
     }
 
-    match sqlx::query_as::<_, UrlDetail>(
-        "SELECT long_url, short_code, created_at FROM urls WHERE short_code = $1",
+    let mut redis_conn = state.redis_db.clone();
+    if let Some(response) = cached_redirect(
+        state,
+        &mut redis_conn,
+        &short_code,
+        path.as_deref(),
+        params.as_deref(),
+        visitor,
     )
-    .bind(&short_code)
-    .fetch_optional(&state.pg_db)
     .await
     {
-        Ok(Some(detail)) => {
-            let response = UrlDetailResponse {
-                short_url: format!("{}/{}", state.base_url, &detail.short_code),
-                short_code: detail.short_code,
-                long_url: detail.long_url,
-                created_at: detail.created_at.to_string(),
-            };
-            Ok(Json(response))
+        return response;
+    }
+
+    // When a hot link drops out of the cache, only the first request reads the database;
+    // concurrent ones wait for it and then use what it cached
+    let _lookup = match state.lookups.join(&short_code).await {
+        Some(lookup) => Some(lookup),
+        None => {
+            if let Some(response) = cached_redirect(
+                state,
+                &mut redis_conn,
+                &short_code,
+                path.as_deref(),
+                params.as_deref(),
+                visitor,
+            )
+            .await
+            {
+                return response;
+            }
+            None
         }
-        Ok(None) => {
-            error!(short_code = %short_code, "Short code not found");
-            Err(StatusCode::NOT_FOUND)
+    };
+
+    state.cache_stats.record(CacheEvent::Miss);
+
+    // Only active, reusable links are cached, so cache hits need no further checks
+    match state.urls.destination(&short_code).await {
+        Ok(Some(target)) if target.is_disabled() => {
+            info!(short_code = %short_code, "Short code disabled");
+            AppError::Gone.into_response()
         }
-        Err(e) => {
-            error!(error = %e, "Database error");
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        Ok(Some(target)) if target.is_broken() => {
+            info!(short_code = %short_code, "Short code destination broken");
+            (StatusCode::GONE, templates::broken(&short_code)).into_response()
         }
-    }
+        Ok(Some(target)) if state.blocklist.matching(&target.destination()).is_some() => {
+            info!(short_code = %short_code, "Destination domain blocked");
+            AppError::Gone.into_response()
+        }
+        Ok(Some(target)) if !target.is_active() => {
+            info!(short_code = %short_code, "Short code not active yet");
+            (
+                StatusCode::NOT_FOUND,
+                templates::not_active(&short_code, target.activates_at),
+            )
+                .into_response()
+        }
+        Ok(Some(target)) if target.single_use && visitor.probe => {
+            info!(short_code = %short_code, "Probing single-use short code");
+            (StatusCode::OK, [(header::CACHE_CONTROL, "no-store")]).into_response()
+        }
+        Ok(Some(target)) if target.single_use => match state.urls.use_once(&short_code).await {
+            Ok(true) => {
+                info!(short_code = %short_code, "Redirecting single-use short code");
+                webhooks::emit(state, Event::Expired, link_ref(state, &short_code));
+                if let Err(e) = redis_conn.del::<_, ()>(&short_code).await {
+                    error!(error = %e, "Failed to remove URL from Redis cache");
+                }
+                cache::invalidate_responses(&state.redis_db).await;
+                uncached_redirect(&redirect_target(
+                    &target.destination(),
+                    path.as_deref(),
+                    params.as_deref(),
+                ))
+            }
+            Ok(false) => {
+                info!(short_code = %short_code, "Single-use short code already used");
+                AppError::Gone.into_response()
+            }
+            Err(e) => AppError::from(e).into_response(),
+        },
+        // Routed links are never cached since each visitor may get a different destination
+        Ok(Some(target)) if target.is_routed() => {
+            if target.deep_linked {
+                match deep_link_page(state, &short_code, &target, visitor).await {
+                    Ok(Some(page)) => {
+                        info!(short_code = %short_code, "Serving deep link page");
+                        record_click(state, &short_code, visitor);
+                        return ([(header::CACHE_CONTROL, "no-store")], page).into_response();
+                    }
+                    Ok(None) => {}
+                    Err(e) => {
+                        return AppError::from(e).into_response();
+                    }
+                }
+            }
+
+            match route_destination(state, &short_code, &target, visitor).await {
+                Ok(long_url) if state.blocklist.matching(&long_url).is_some() => {
+                    info!(short_code = %short_code, long_url = %long_url, "Destination domain blocked");
+                    AppError::Gone.into_response()
+                }
+                Ok(long_url) => {
+                    info!(short_code = %short_code, long_url = %long_url, "Redirecting to routed destination");
+                    record_click(state, &short_code, visitor);
+                    uncached_redirect(&redirect_target(
+                        &long_url,
+                        path.as_deref(),
+                        params.as_deref(),
+                    ))
+                }
+                Err(e) => AppError::from(e).into_response(),
+            }
+        }
+        Ok(Some(target)) => {
+            let long_url = target.destination();
+            info!(short_code = %short_code, "Redirecting to long URL");
+            record_click(state, &short_code, visitor);
+            if let Err(e) = cache::store_destination(
+                &mut redis_conn,
+                &short_code,
+                &long_url,
+                state.runtime.load().cache_ttl,
+            )
+            .await
+            {
+                error!(error = %e, "Failed to cache URL in Redis");
+                state.cache_stats.record(CacheEvent::Error);
+            }
+            if let Some(local_cache) = &state.local_cache {
+                local_cache.insert(short_code.clone(), long_url.clone());
+            }
+            cached_redirect_response(
+                state,
+                &redirect_target(&long_url, path.as_deref(), params.as_deref()),
+            )
+        }
+        Ok(None) => {
+            error!(short_code = %short_code, "Short code not found");
+            let negative_cache_ttl = state.runtime.load().negative_cache_ttl;
+            if negative_cache_ttl > 0 {
+                if let Err(e) =
+                    cache::store_missing(&mut redis_conn, &short_code, negative_cache_ttl).await
+                {
+                    error!(error = %e, "Failed to cache unknown code in Redis");
+                    state.cache_stats.record(CacheEvent::Error);
+                }
+            }
+            unknown_link(state, AppError::NotFound("Short URL"))
+        }
+        Err(e) => AppError::from(e).into_response(),
+    }
+}
+
+// Response for a short code served from Redis: a cached destination, or a code
+// remembered not to exist; `None` on a cache miss
+async fn cached_redirect(
+    state: &AppState,
+    conn: &mut RedisConn,
+    short_code: &str,
+    path: Option<&str>,
+    params: Option<&str>,
+    visitor: &Visitor,
+) -> Option<Response> {
+    match conn.get::<_, Option<String>>(short_code).await {
+        Ok(Some(long_url)) => {
+            info!(short_code = %short_code, "Cache hit");
+            state.cache_stats.record(CacheEvent::Hit);
+            if let Some(local_cache) = &state.local_cache {
+                local_cache.insert(short_code.to_string(), long_url.clone());
+            }
+            Some(cached_destination(
+                state, short_code, &long_url, path, params, visitor,
+            ))
+        }
+        Ok(None) => {
+            info!(short_code = %short_code, "Cache miss");
+            if state.runtime.load().negative_cache_ttl > 0 {
+                match cache::is_missing(conn, short_code).await {
+                    Ok(true) => {
+                        info!(short_code = %short_code, "Short code known not to exist");
+                        state.cache_stats.record(CacheEvent::NegativeHit);
+                        return Some(unknown_link(state, AppError::NotFound("Short URL")));
+                    }
+                    Ok(false) => {}
+                    Err(e) => {
+                        error!(error = %e, "Redis error");
+                        state.cache_stats.record(CacheEvent::Error);
+                    }
+                }
+            }
+            None
+        }
+        Err(e) => {
+            state.cache_stats.record(CacheEvent::Error);
+            Some(AppError::from(e).into_response())
+        }
+    }
+}
+
+// Redirect to a destination found in one of the caches, unless its domain got blocked
+fn cached_destination(
+    state: &AppState,
+    short_code: &str,
+    long_url: &str,
+    path: Option<&str>,
+    params: Option<&str>,
+    visitor: &Visitor,
+) -> Response {
+    if state.blocklist.matching(long_url).is_some() {
+        info!(short_code = %short_code, "Destination domain blocked");
+        return AppError::Gone.into_response();
+    }
+    record_click(state, short_code, visitor);
+    cached_redirect_response(state, &redirect_target(long_url, path, params))
+}
+
+// Permanent redirect that browsers may reuse for REDIRECT_MAX_AGE_SECONDS. Clicks they
+// answer from their cache are not counted, so it is kept short and private to them
+fn cached_redirect_response(state: &AppState, target: &str) -> Response {
+    cacheable_redirect(target, state.runtime.load().redirect_max_age)
+}
+
+// Permanent redirect that browsers may reuse for `max_age` seconds
+pub(crate) fn cacheable_redirect(target: &str, max_age: u64) -> Response {
+    let cache_control = match max_age {
+        0 => "no-cache".to_string(),
+        max_age => format!("private, max-age={max_age}"),
+    };
+    let mut response = Redirect::permanent(target).into_response();
+    if let Ok(value) = HeaderValue::from_str(&cache_control) {
+        response.headers_mut().insert(header::CACHE_CONTROL, value);
+    }
+    response
+}
+
+// Temporary redirect that must not be reused, since the next request may go elsewhere
+pub(crate) fn uncached_redirect(target: &str) -> Response {
+    (
+        [(header::CACHE_CONTROL, "no-store")],
+        Redirect::temporary(target),
+    )
+        .into_response()
+}
+
+// Response for a redirect to a link that does not exist, sending visitors
+// to the configured fallback page if there is one
+fn unknown_link(state: &AppState, error: AppError) -> Response {
+    match &state.runtime.load().not_found_redirect_url {
+        Some(url) => uncached_redirect(url),
+        None => error.into_response(),
+    }
+}
+
+// Interstitial opening the app for mobile visitors of a deep-linked link
+async fn deep_link_page(
+    state: &AppState,
+    short_code: &str,
+    target: &UrlTarget,
+    visitor: &Visitor,
+) -> Result<Option<Markup>, sqlx::Error> {
+    let Some(device) = visitor.device.filter(Device::is_mobile) else {
+        return Ok(None);
+    };
+    let Some(deep_link) = routes::deep_link(&state.pg_db, short_code).await? else {
+        return Ok(None);
+    };
+
+    let store_url = match device {
+        Device::Ios => deep_link.ios_store_url,
+        _ => deep_link.android_store_url,
+    };
+    let fallback_url = match store_url {
+        Some(store_url) => store_url,
+        None => route_destination(state, short_code, target, visitor).await?,
+    };
+
+    Ok(Some(templates::deep_link(
+        short_code,
+        &deep_link.uri,
+        &fallback_url,
+    )))
+}
+
+// Destination of a routed link for one visitor: a device target, then a geo target
+// for their region, then the first matching time rule, then a weighted A/B variant,
+// then the link's own destination
+async fn route_destination(
+    state: &AppState,
+    short_code: &str,
+    target: &UrlTarget,
+    visitor: &Visitor,
+) -> Result<String, sqlx::Error> {
+    debug!(visitor = ?visitor, "Routing visitor");
+
+    if let (true, Some(device)) = (target.device_targeted, visitor.device) {
+        let device_target =
+            routes::device_target(&state.pg_db, short_code, device.as_str()).await?;
+
+        if let Some(long_url) = device_target {
+            return Ok(target.with_utm(&long_url));
+        }
+    }
+
+    if target.geo_targeted {
+        let regions = match (&state.geoip, visitor.ip) {
+            (Some(geoip), Some(ip)) => geoip.regions(ip),
+            _ => Vec::new(),
+        };
+
+        if !regions.is_empty() {
+            let geo_target =
+                routes::geo_target(&state.pg_db, short_code, &regions, geo::EUROPEAN_UNION).await?;
+
+            if let Some(long_url) = geo_target {
+                return Ok(target.with_utm(&long_url));
+            }
+        }
+    }
+
+    if target.time_routed {
+        let time_target = routes::time_target(&state.pg_db, short_code, Utc::now().time()).await?;
+
+        if let Some(long_url) = time_target {
+            return Ok(target.with_utm(&long_url));
+        }
+    }
+
+    if target.split {
+        if let Some(long_url) =
+            routes::pick_variant(&state.pg_db, short_code, visitor.probe).await?
+        {
+            return Ok(target.with_utm(&long_url));
+        }
+    }
+
+    Ok(target.destination())
+}
+
+// Count a redirect without holding up the response, in Redis when clicks are flushed to
+// the database periodically, falling back to a direct write if Redis fails
+fn record_click(state: &AppState, short_code: &str, visitor: &Visitor) {
+    if visitor.probe {
+        return;
+    }
+    crate::metrics::record_redirect();
+    webhooks::emit(state, Event::Clicked, link_ref(state, short_code));
+    if let Some(dashboard) = &state.dashboard {
+        dashboard.record(short_code);
+    }
+    let click = ClickEvent {
+        short_code: short_code.to_string(),
+        timestamp: Utc::now(),
+        country: visitor.country(state),
+    };
+    if state.click_stream.is_some() {
+        let click = click.clone();
+        let redis_db = state.redis_db.clone();
+        state.background.spawn(async move {
+            events::publish_click(&redis_db, &click).await;
+        });
+    }
+    let buffered = state.click_flush_interval.is_some();
+    let redis_db = state.redis_db.clone();
+    let pg_db = state.pg_db.clone();
+    let urls = state.urls.clone();
+    state.background.spawn(async move {
+        let short_code = &click.short_code;
+        if buffered {
+            match cache::count_click(&redis_db, &click).await {
+                Ok(()) => return,
+                Err(e) => {
+                    error!(error = %e, short_code = %short_code, "Failed to count click in Redis")
+                }
+            }
+        }
+        if let Err(e) = urls.add_clicks(short_code, 1).await {
+            error!(error = %e, short_code = %short_code, "Failed to record click");
+        }
+        if let Err(e) = repository::clicks::insert(&pg_db, &click).await {
+            error!(error = %e, short_code = %short_code, "Failed to store click event");
+        }
+    });
+}
+
+// Identifies a link in webhook payloads of events that carry nothing else
+fn link_ref(state: &AppState, short_code: &str) -> Value {
+    json!({
+        "short_code": short_code,
+        "short_url": format!("{}/{}", state.base_url, short_code),
+    })
+}
+
+// Destination for a redirect, carrying over the extra path and incoming query parameters
+pub(crate) fn redirect_target(long_url: &str, path: Option<&str>, query: Option<&str>) -> String {
+    let long_url = match path {
+        Some(path) if !path.is_empty() => append_path(long_url, path),
+        // Links stored before destinations were kept in ASCII may not be valid header values
+        _ if !long_url.is_ascii() => ascii_url(long_url),
+        _ => long_url.to_string(),
+    };
+    match query {
+        Some(query) if !query.is_empty() => merge_query(&long_url, query),
+        _ => long_url,
+    }
+}
+
+async fn link_info_page(state: &AppState, short_code: &str) -> impl IntoResponse {
+    let short_code = &canonical_code(short_code, state.case_insensitive_codes);
+    if !valid_short_code(short_code, &state.code_alphabet) {
+        error!(short_code = %short_code, "Invalid short code");
+        return (StatusCode::BAD_REQUEST, templates::not_found(short_code)).into_response();
+    }
+
+    let result = state.urls.find(short_code).await;
+
+    match result {
+        Ok(Some(detail)) => {
+            let short_url = format!("{}/{}", state.base_url, detail.short_code);
+            templates::link_info(&short_url, &detail).into_response()
+        }
+        Ok(None) => {
+            error!(short_code = %short_code, "Short code not found");
+            (StatusCode::NOT_FOUND, templates::not_found(short_code)).into_response()
+        }
+        Err(e) => AppError::from(e).into_response(),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/{short_code}",
+    tag = "links",
+    summary = "Delete a link",
+    params(
+        ("short_code" = String, Path, description = "Short code of the link"),
+        DeleteQuery,
+    ),
+    responses(
+        (status = 200, description = "Link deleted or purged", body = Object),
+        (status = 404, description = "Unknown link", body = Problem, content_type = PROBLEM_JSON),
+    ),
+)]
+#[instrument(skip(state))]
+pub async fn delete_short_url(
+    State(state): State<AppState>,
+    Path(short_code): Path<String>,
+    Query(params): Query<DeleteQuery>,
+) -> Result<Json<Value>, AppError> {
+    delete_link(&state, &short_code, params.purge).await?;
+    if params.purge {
+        Ok(Json(json!({"message": "short url purged successfully"})))
+    } else {
+        Ok(Json(json!({"message": "short url deleted successfully"})))
+    }
+}
+
+// Delete a link, or with `purge` also its click history, for the REST and gRPC APIs
+pub(crate) async fn delete_link(
+    state: &AppState,
+    short_code: &str,
+    purge: bool,
+) -> Result<(), AppError> {
+    let short_code = canonical_code(short_code, state.case_insensitive_codes);
+    if !valid_short_code(&short_code, &state.code_alphabet) {
+        error!(short_code = %short_code, "Invalid short code");
+        return Err(AppError::InvalidShortCode(short_code));
+    }
+
+    if purge {
+        return match purge_short_url(state, &short_code).await {
+            // No webhook is told, since its delivery would keep the code around
+            Ok(true) => {
+                info!(short_code = %short_code, "Short URL purged successfully");
+                Ok(())
+            }
+            Ok(false) => {
+                error!(short_code = %short_code, "Short code not found");
+                Err(AppError::NotFound("Short URL"))
+            }
+            Err(e) => Err(e.into()),
+        };
+    }
+
+    if !state.urls.delete(&short_code).await? {
+        error!(short_code = %short_code, "Short code not found");
+        return Err(AppError::NotFound("Short URL"));
+    }
+
+    evict_link(state, &short_code).await;
+    cache::invalidate_responses(&state.redis_db).await;
+    info!(short_code = %short_code, "Short URL deleted successfully");
+    webhooks::emit(state, Event::Deleted, link_ref(state, &short_code));
+    Ok(())
+}
+
+// Erase a short url together with all data derived from it
+async fn purge_short_url(state: &AppState, short_code: &str) -> Result<bool, sqlx::Error> {
+    let deleted = links::purge(&state.pg_db, short_code).await?;
+
+    evict_link(state, short_code).await;
+    cache::invalidate_responses(&state.redis_db).await;
+
+    Ok(deleted)
+}
+
+// Drop the cached destination of a deleted or changed link, now, once redirects that were
+// looking it up have cached what they read, and after EVICTION_RETRY_DELAY
+async fn evict_link(state: &AppState, short_code: &str) {
+    let short_codes = [short_code.to_string()];
+    cache::evict_links(&state.redis_db, &short_codes).await;
+    if let Some(local_cache) = &state.local_cache {
+        local_cache.invalidate(short_code);
+    }
+
+    let redis_db = state.redis_db.clone();
+    let local_cache = state.local_cache.clone();
+    let lookups = state.lookups.clone();
+    state.background.spawn(async move {
+        cache::evict_after_lookups(&lookups, &short_codes[0], EVICTION_RETRY_DELAY, || async {
+            cache::evict_links(&redis_db, &short_codes).await;
+            if let Some(local_cache) = &local_cache {
+                local_cache.invalidate(&short_codes[0]);
+            }
+        })
+        .await;
+    });
+}
+
+// Drop the negative cache entry of a new link's code, now and after EVICTION_RETRY_DELAY
+async fn forget_missing(state: &AppState, short_code: &str) {
+    cache::forget_missing(&state.redis_db, short_code).await;
+
+    let redis_db = state.redis_db.clone();
+    let short_code = short_code.to_string();
+    state.background.spawn(async move {
+        tokio::time::sleep(EVICTION_RETRY_DELAY).await;
+        cache::forget_missing(&redis_db, &short_code).await;
+    });
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/shorten",
+    tag = "links",
+    summary = "List links",
+    params(ListQuery, FormatQuery),
+    responses((
+        status = 200,
+        description = "Links as JSON, or as CSV or NDJSON export rows",
+        content(
+            (Vec<UrlDetailResponse> = "application/json"),
+            (String = "text/csv"),
+            (String = "application/x-ndjson"),
+        ),
+    )),
+)]
+#[instrument(skip(state, headers))]
+pub async fn get_all_short_url(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<ListQuery>,
+    Query(format): Query<FormatQuery>,
+) -> Result<Response, AppError> {
+    let format = negotiate_format(&headers, &format)?;
+    let results = state.urls.list(&link_filter(&params), None).await?;
+
+    if let Some(format) = format {
+        let links: Vec<ExportedLink> = results.into_iter().map(ExportedLink::from).collect();
+        return exported_links(format, &links);
+    }
+
+    let response: Vec<UrlDetailResponse> = results
+        .into_iter()
+        .map(|row| UrlDetailResponse::new(row, &state.base_url))
+        .collect();
+
+    Ok(([(header::VARY, "accept")], Json(response)).into_response())
+}
+
+// Export format asked for with `?format=` or else the Accept header, `None` for JSON
+fn negotiate_format(
+    headers: &HeaderMap,
+    query: &FormatQuery,
+) -> Result<Option<ExportFormat>, AppError> {
+    if let Some(format) = query.format.as_deref() {
+        return match format {
+            "json" => Ok(None),
+            "csv" => Ok(Some(ExportFormat::Csv)),
+            "ndjson" => Ok(Some(ExportFormat::Ndjson)),
+            _ => {
+                error!(format = %format, "Unsupported response format");
+                Err(AppError::InvalidRequest(
+                    "format must be json, csv or ndjson".to_string(),
+                ))
+            }
+        };
+    }
+
+    let accept = headers
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    if accept.contains("text/csv") {
+        Ok(Some(ExportFormat::Csv))
+    } else if accept.contains("ndjson") {
+        Ok(Some(ExportFormat::Ndjson))
+    } else {
+        Ok(None)
+    }
+}
+
+// Links serialized like `tlong export` does
+fn exported_links(format: ExportFormat, links: &[ExportedLink]) -> Result<Response, AppError> {
+    let body = Exporter::new(format)
+        .write(links)
+        .map_err(|e| AppError::Internal(format!("Failed to serialize links: {e}")))?;
+    Ok((
+        [
+            (header::CONTENT_TYPE, format.content_type()),
+            (header::VARY, "accept"),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+// Links matching the v1 list filters, one page at a time
+#[instrument(skip(state))]
+pub async fn list_short_urls(
+    State(state): State<AppState>,
+    Query(params): Query<ListQuery>,
+    Query(page): Query<PageQuery>,
+) -> Result<(Extension<Pagination>, Json<Vec<UrlDetailResponse>>), AppError> {
+    let (links, pagination) = links_page(&state, &params, &page).await?;
+    let response = links
+        .into_iter()
+        .map(|row| UrlDetailResponse::new(row, &state.base_url))
+        .collect();
+    Ok((Extension(pagination), Json(response)))
+}
+
+// One page of the link list, newest first, for the v2 and gRPC APIs
+pub(crate) async fn links_page(
+    state: &AppState,
+    params: &ListQuery,
+    page: &PageQuery,
+) -> Result<(Vec<UrlDetail>, Pagination), AppError> {
+    let (page, per_page) = page_bounds(page);
+    let filter = link_filter(params);
+    let total = state.urls.count(&filter).await?;
+    let links = state
+        .urls
+        .list(
+            &filter,
+            Some(Page {
+                limit: i64::from(per_page),
+                offset: i64::from(page - 1) * i64::from(per_page),
+            }),
+        )
+        .await?;
+
+    Ok((links, Pagination::new(page, per_page, total)))
+}
+
+// Conditions of a link list request, with the tag normalized and blank searches dropped
+pub(crate) fn link_filter(params: &ListQuery) -> LinkFilter {
+    LinkFilter {
+        tag: params.tag.as_deref().map(normalize_tag),
+        q: params
+            .q
+            .as_deref()
+            .map(str::trim)
+            .filter(|q| !q.is_empty())
+            .map(str::to_string),
+        broken: params.broken,
+    }
+}
+
+// Page number and size of a v2 list request, clamped to what can be served
+fn page_bounds(params: &PageQuery) -> (u32, u32) {
+    (
+        params.page.unwrap_or(1).max(1),
+        params
+            .per_page
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+            .clamp(1, MAX_PAGE_SIZE),
+    )
+}
+
+// One page of a list that is small enough to be loaded whole
+fn paginate<T>(items: Vec<T>, params: &PageQuery) -> (Extension<Pagination>, Json<Vec<T>>) {
+    let (page, per_page) = page_bounds(params);
+    let pagination = Pagination::new(page, per_page, items.len() as i64);
+    let items = items
+        .into_iter()
+        .skip((page as usize - 1).saturating_mul(per_page as usize))
+        .take(per_page as usize)
+        .collect();
+    (Extension(pagination), Json(items))
+}
+
+// Trim a description, treating blank ones as absent
+pub(crate) fn normalize_description(description: Option<&str>) -> Result<Option<String>, AppError> {
+    let Some(description) = description.map(str::trim).filter(|d| !d.is_empty()) else {
+        return Ok(None);
+    };
+
+    if description.chars().count() > MAX_DESCRIPTION_LENGTH {
+        error!(length = description.len(), "Description too long");
+        return Err(AppError::InvalidRequest(format!(
+            "Description must be at most {MAX_DESCRIPTION_LENGTH} characters"
+        )));
+    }
+
+    Ok(Some(description.to_string()))
+}
+
+#[utoipa::path(
+    patch,
+    path = "/api/v1/{short_code}",
+    tag = "links",
+    summary = "Update a link",
+    params(
+        ("short_code" = String, Path, description = "Short code of the link"),
+    ),
+    request_body = UpdateUrlRequest,
+    responses(
+        (status = 200, body = UrlDetailResponse),
+        (status = 404, description = "Unknown link", body = Problem, content_type = PROBLEM_JSON),
+    ),
+)]
+#[instrument(skip(state, payload))]
+pub async fn update_short_url(
+    State(state): State<AppState>,
+    Path(short_code): Path<String>,
+    payload: Result<Json<UpdateUrlRequest>, JsonRejection>,
+) -> Result<Json<UrlDetailResponse>, AppError> {
+    let short_code = canonical_code(&short_code, state.case_insensitive_codes);
+    if !valid_short_code(&short_code, &state.code_alphabet) {
+        error!(short_code = %short_code, "Invalid short code");
+        return Err(AppError::InvalidShortCode(short_code));
+    }
+
+    let payload = json_payload(payload)?;
+
+    // An empty description clears the field, an absent one leaves it as is
+    let result = match &payload.description {
+        Some(description) => {
+            let description = normalize_description(Some(description))?;
+            state
+                .urls
+                .set_description(&short_code, description.as_deref())
+                .await?
+        }
+        None => state.urls.find(&short_code).await?,
+    };
+
+    let Some(detail) = result else {
+        error!(short_code = %short_code, "Short code not found");
+        return Err(AppError::NotFound("Short URL"));
+    };
+
+    evict_link(&state, &short_code).await;
+    cache::invalidate_responses(&state.redis_db).await;
+    info!(short_code = %short_code, "Short URL updated");
+    let response = UrlDetailResponse::new(detail, &state.base_url);
+    webhooks::emit(&state, Event::Updated, &response);
+    Ok(Json(response))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/{short_code}",
+    tag = "links",
+    summary = "Link details and click count",
+    params(
+        ("short_code" = String, Path, description = "Short code of the link"),
+        FormatQuery,
+    ),
+    responses(
+        (
+            status = 200,
+            description = "The link as JSON, or as a CSV or NDJSON export row",
+            content(
+                (UrlDetailResponse = "application/json"),
+                (String = "text/csv"),
+                (String = "application/x-ndjson"),
+            ),
+        ),
+        (status = 404, description = "Unknown link", body = Problem, content_type = PROBLEM_JSON),
+    ),
+)]
+#[instrument(skip(state, headers))]
+pub async fn get_short_url_details(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(short_code): Path<String>,
+    Query(format): Query<FormatQuery>,
+) -> Result<Response, AppError> {
+    let format = negotiate_format(&headers, &format)?;
+    let detail = find_link(&state, &short_code).await?;
+    if let Some(format) = format {
+        return exported_links(format, &[ExportedLink::from(detail)]);
+    }
+    let short_code = detail.short_code.clone();
+
+    let mut response = UrlDetailResponse::new(detail, &state.base_url);
+    response.variants = link_variants(&state, &short_code).await?;
+    response.geo_targets = routes::geo_targets(&state.pg_db, &short_code)
+        .await?
+        .into_iter()
+        .collect();
+    response.device_targets = routes::device_targets(&state.pg_db, &short_code)
+        .await?
+        .into_iter()
+        .collect();
+    response.time_rules = routes::time_rules(&state.pg_db, &short_code).await?;
+    response.deep_link = routes::deep_link(&state.pg_db, &short_code).await?;
+    Ok(([(header::VARY, "accept")], Json(response)).into_response())
+}
+
+// A link by its short code, for the REST and GraphQL APIs
+pub(crate) async fn find_link(state: &AppState, short_code: &str) -> Result<UrlDetail, AppError> {
+    let short_code = canonical_code(short_code, state.case_insensitive_codes);
+    if !valid_short_code(&short_code, &state.code_alphabet) {
+        error!(short_code = %short_code, "Invalid short code");
+        return Err(AppError::InvalidShortCode(short_code));
+    }
+
+    state.urls.find(&short_code).await?.ok_or_else(|| {
+        error!(short_code = %short_code, "Short code not found");
+        AppError::NotFound("Short URL")
+    })
+}
+
+// A/B split destinations of a link with their clicks, in the order they were given
+pub(crate) async fn link_variants(
+    state: &AppState,
+    short_code: &str,
+) -> Result<Vec<VariantStats>, AppError> {
+    Ok(routes::variants(&state.pg_db, short_code).await?)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/{short_code}/badge.svg",
+    tag = "links",
+    summary = "SVG badge showing whether the link redirects",
+    params(
+        ("short_code" = String, Path, description = "Short code of the link"),
+    ),
+    responses(
+        (status = 200, description = "Badge", content_type = "image/svg+xml", body = String),
+    ),
+)]
+#[instrument(skip(state))]
+pub async fn get_short_url_badge(
+    State(state): State<AppState>,
+    Path(short_code): Path<String>,
+) -> Result<Response, AppError> {
+    let short_code = canonical_code(&short_code, state.case_insensitive_codes);
+    if !valid_short_code(&short_code, &state.code_alphabet) {
+        error!(short_code = %short_code, "Invalid short code");
+        return Err(AppError::InvalidShortCode(short_code));
+    }
+
+    // Alive when a visitor would be redirected, judged as `redirect_short_url` judges it
+    let alive = links::destination(&state.pg_read_db, &short_code)
+        .await?
+        .is_some_and(|target| {
+            !target.is_disabled()
+                && !target.is_broken()
+                && target.is_active()
+                && state.blocklist.matching(&target.destination()).is_none()
+        });
+
+    let svg = if alive {
+        badge::render(&short_code, "alive", "#4c1")
+    } else {
+        badge::render(&short_code, "dead", "#e05d44")
+    };
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "image/svg+xml"),
+            (header::CACHE_CONTROL, "public, max-age=300"),
+        ],
+        svg,
+    )
+        .into_response())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/{short_code}/qr",
+    tag = "links",
+    summary = "QR code of the short URL",
+    params(
+        ("short_code" = String, Path, description = "Short code of the link"),
+        QrQuery,
+    ),
+    responses(
+        (
+            status = 200,
+            description = "QR code as PNG or SVG",
+            content((Vec<u8> = "image/png"), (String = "image/svg+xml")),
+        ),
+    ),
+)]
+#[instrument(skip(state))]
+pub async fn get_short_url_qr(
+    State(state): State<AppState>,
+    Path(short_code): Path<String>,
+    Query(params): Query<QrQuery>,
+) -> Result<Response, AppError> {
+    let short_code = canonical_code(&short_code, state.case_insensitive_codes);
+    if !valid_short_code(&short_code, &state.code_alphabet) {
+        error!(short_code = %short_code, "Invalid short code");
+        return Err(AppError::InvalidShortCode(short_code));
+    }
+
+    let size = params.size.unwrap_or(256);
+    if !(64..=2048).contains(&size) {
+        error!(size = size, "Invalid QR code size");
+        return Err(AppError::InvalidRequest(
+            "size must be between 64 and 2048".to_string(),
+        ));
+    }
+
+    if !links::exists(&state.pg_db, &short_code).await? {
+        error!(short_code = %short_code, "Short code not found");
+        return Err(AppError::NotFound("Short URL"));
+    }
+
+    let short_url = format!("{}/{}", state.base_url, short_code);
+    let rendered = match params.format {
+        QrFormat::Png => qr::render_png(&short_url, size).map(|png| ("image/png", png)),
+        QrFormat::Svg => {
+            qr::render_svg(&short_url, size).map(|svg| ("image/svg+xml", svg.into_bytes()))
+        }
+    };
+
+    let (content_type, body) =
+        rendered.map_err(|e| AppError::Internal(format!("Failed to render QR code: {e}")))?;
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type),
+            (header::CACHE_CONTROL, "public, max-age=86400, immutable"),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/expand/{short_code}",
+    tag = "links",
+    summary = "Destination of a short code",
+    params(
+        ("short_code" = String, Path, description = "Short code of the link"),
+    ),
+    responses(
+        (status = 200, body = ExpandResponse),
+        (status = 404, description = "Unknown link", body = Problem, content_type = PROBLEM_JSON),
+    ),
+)]
+#[instrument(skip(state))]
+pub async fn expand_short_code(
+    State(state): State<AppState>,
+    Path(short_code): Path<String>,
+) -> Result<Json<ExpandResponse>, AppError> {
+    expand(&state, short_code).await.map(Json)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/expand",
+    tag = "links",
+    summary = "Destination of a short URL",
+    params(ExpandQuery),
+    responses(
+        (status = 200, body = ExpandResponse),
+        (status = 404, description = "Unknown link", body = Problem, content_type = PROBLEM_JSON),
+    ),
+)]
+#[instrument(skip(state))]
+pub async fn expand_short_url(
+    State(state): State<AppState>,
+    Query(params): Query<ExpandQuery>,
+) -> Result<Json<ExpandResponse>, AppError> {
+    let Some(short_code) = short_code_from_url(&params.url) else {
+        error!(url = %params.url, "Invalid short URL");
+        return Err(AppError::InvalidRequest(format!(
+            "Not a short URL: {}",
+            params.url
+        )));
+    };
+    expand(&state, short_code).await.map(Json)
+}
+
+pub(crate) async fn expand(
+    state: &AppState,
+    short_code: String,
+) -> Result<ExpandResponse, AppError> {
+    let short_code = canonical_code(&short_code, state.case_insensitive_codes);
+    if !valid_short_code(&short_code, &state.code_alphabet) {
+        error!(short_code = %short_code, "Invalid short code");
+        return Err(AppError::InvalidShortCode(short_code));
+    }
+
+    let cached = state
+        .redis_db
+        .clone()
+        .get::<_, Option<String>>(&short_code)
+        .await
+        .unwrap_or_else(|e| {
+            error!(error = %e, "Redis error");
+            None
+        });
+
+    let long_url = match cached {
+        Some(long_url) => long_url,
+        None => state
+            .urls
+            .destination(&short_code)
+            .await?
+            .filter(|target| {
+                target.is_active()
+                    && !target.is_disabled()
+                    && !target.is_broken()
+                    && !target.single_use
+            })
+            .ok_or_else(|| {
+                error!(short_code = %short_code, "Short code not found");
+                AppError::NotFound("Short URL")
+            })?
+            .destination(),
+    };
+
+    info!(short_code = %short_code, "Expanded short code");
+    Ok(ExpandResponse {
+        short_url: format!("{}/{}", state.base_url, short_code),
+        short_code,
+        long_url,
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/{short_code}/preview",
+    tag = "links",
+    summary = "Title, description and image of the destination",
+    params(
+        ("short_code" = String, Path, description = "Short code of the link"),
+    ),
+    responses(
+        (status = 200, body = PreviewResponse),
+        (status = 404, description = "Unknown link", body = Problem, content_type = PROBLEM_JSON),
+    ),
+)]
+#[instrument(skip(state))]
+pub async fn get_short_url_preview(
+    State(state): State<AppState>,
+    Path(short_code): Path<String>,
+) -> Result<Json<PreviewResponse>, AppError> {
+    let short_code = canonical_code(&short_code, state.case_insensitive_codes);
+    if !valid_short_code(&short_code, &state.code_alphabet) {
+        error!(short_code = %short_code, "Invalid short code");
+        return Err(AppError::InvalidShortCode(short_code));
+    }
+
+    let long_url = links::long_url(&state.pg_db, &short_code)
+        .await?
+        .ok_or_else(|| {
+            error!(short_code = %short_code, "Short code not found");
+            AppError::NotFound("Short URL")
+        })?;
+
+    let cached =
+        links::cached_preview(&state.pg_db, &short_code, PREVIEW_MAX_AGE_HOURS as i32).await?;
+
+    let link_preview = match cached {
+        Some(link_preview) => {
+            debug!(short_code = %short_code, "Preview cache hit");
+            link_preview
+        }
+        None => {
+            let mut redis_conn = state.redis_db.clone();
+            match cache::failed_preview(&mut redis_conn, &short_code).await {
+                Ok(Some(e)) => {
+                    debug!(short_code = %short_code, "Preview failure cache hit");
+                    return Err(AppError::Upstream(e));
+                }
+                Ok(None) => {}
+                Err(e) => error!(error = %e, "Failed to read preview failure from Redis"),
+            }
+
+            let page =
+                match preview::fetch(&state.resolver_client, &long_url, state.max_redirect_hops)
+                    .await
+                {
+                    Ok(page) => page,
+                    Err(e) => {
+                        error!(error = %e, url = %long_url, "Failed to fetch preview");
+                        if let Err(e) = cache::store_failed_preview(
+                            &mut redis_conn,
+                            &short_code,
+                            &e.to_string(),
+                            PREVIEW_FAILURE_TTL_SECONDS,
+                        )
+                        .await
+                        {
+                            error!(error = %e, "Failed to cache preview failure in Redis");
+                        }
+                        return Err(AppError::Upstream(e.to_string()));
+                    }
+                };
+
+            links::store_preview(&state.pg_db, &short_code, &page).await?
+        }
+    };
+
+    Ok(Json(PreviewResponse {
+        short_code,
+        long_url,
+        title: link_preview.title,
+        description: link_preview.description,
+        image_url: link_preview.image_url,
+        fetched_at: link_preview.fetched_at.to_string(),
+    }))
+}
+
+// Refusal of changes to an existing external link by callers without the admin token or a
+// request signature
+const EXTERNAL_LINK_TAKEN: &str =
+    "Changing an existing external link needs the admin token or a request signature";
+
+// Cache key for links in the external ID namespace
+pub(crate) fn external_cache_key(external_id: &str) -> String {
+    format!("x:{external_id}")
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/x/{external_id}",
+    tag = "external links",
+    summary = "Create or replace a link under a caller-chosen ID",
+    params(("external_id" = String, Path, description = "Caller-chosen ID")),
+    request_body = ExternalLinkRequest,
+    responses(
+        (status = 201, description = "Link created", body = ExternalLinkResponse),
+        (status = 200, description = "Link replaced", body = ExternalLinkResponse),
+        (status = 202, description = "Held for review under ABUSE_ACTION=queue", body = Object),
+        (status = 401, description = "Replacing a link without the admin token or a signature", body = Problem, content_type = PROBLEM_JSON),
+    ),
+)]
+#[instrument(skip(state, headers, extensions, payload))]
+pub async fn put_external_link(
+    State(state): State<AppState>,
+    Path(external_id): Path<String>,
+    ConnectInfo(remote_addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    extensions: Extensions,
+    payload: Result<Json<ExternalLinkRequest>, JsonRejection>,
+) -> Result<Response, AppError> {
+    let payload = json_payload(payload)?;
+    let creator = creator(&state, &headers, &extensions, Some(remote_addr));
+    let (status, response) = store_external_link(&state, external_id, payload, &creator).await?;
+    Ok((status, Json(response)).into_response())
+}
+
+// Point an external ID at a destination for the REST API and approved reviews; `200 OK`
+// means a taken ID was repointed
+async fn store_external_link(
+    state: &AppState,
+    external_id: String,
+    mut payload: ExternalLinkRequest,
+    creator: &Creator,
+) -> Result<(StatusCode, ExternalLinkResponse), AppError> {
+    if !state.external_id_pattern.is_match(&external_id) {
+        error!(external_id = %external_id, "Invalid external ID");
+        return Err(AppError::InvalidRequest(
+            "External ID does not match the allowed pattern".to_string(),
+        ));
+    }
+
+    if state.accept_schemeless_urls {
+        if let Some(long_url) = with_default_scheme(&payload.long_url) {
+            payload.long_url = long_url;
+        }
+    }
+
+    if !valid_url(&payload.long_url) {
+        error!(url = %payload.long_url, "Invalid URL format");
+        return Err(AppError::InvalidUrl);
+    }
+    payload.long_url = ascii_url(&payload.long_url);
+    if payload.long_url.chars().count() > state.max_url_length {
+        error!(length = payload.long_url.len(), "URL too long");
+        return Err(AppError::UrlTooLong(state.max_url_length));
+    }
+
+    if state.ssrf_dns_check {
+        if let Err(e) = check_resolved_destinations(&[&payload.long_url]).await {
+            error!(error = %e, "Destination resolves to a non-public address");
+            return Err(AppError::PrivateDestination);
+        }
+    }
+
+    if let Some(domain) = state.blocklist.matching(&payload.long_url) {
+        error!(url = %payload.long_url, domain = %domain, "Destination domain is blocked");
+        return Err(AppError::BlockedDomain { domain });
+    }
+
+    if let Some((client, assessment)) = assess_risk(state, creator, &payload.long_url).await {
+        if state.abuse_action == AbuseAction::ShadowBan {
+            let response = ExternalLinkResponse {
+                short_url: format!("{}/x/{}", state.base_url, external_id),
+                external_id,
+                long_url: payload.long_url,
+            };
+            return Ok((StatusCode::CREATED, response));
+        }
+        let review = NewReview {
+            long_url: &payload.long_url,
+            external_id: Some(&external_id),
+            request: &json!(payload).to_string(),
+            client_ip: client,
+            score: assessment.score as i32,
+            reasons: &assessment.reasons,
+        };
+        review_queue::enqueue(&state.pg_db, &review).await?;
+        return Err(AppError::HeldForReview);
+    }
+
+    let threat_type = check_threats(state, &[&payload.long_url]).await?;
+    let link = NewExternalLink {
+        external_id: &external_id,
+        long_url: &payload.long_url,
+        threat_type: threat_type.as_deref(),
+        threat_checked_at: state.safe_browsing.is_some().then(Utc::now),
+    };
+
+    // Anyone may claim a free ID, but only callers vouched for may repoint a taken one
+    let created = if matches!(creator, Creator::Trusted) {
+        external_links::upsert(&state.pg_db, &link).await?
+    } else if external_links::insert(&state.pg_db, &link).await? {
+        true
+    } else {
+        warn!(external_id = %external_id, "Anonymous attempt to replace an external link");
+        return Err(AppError::Unauthorized(EXTERNAL_LINK_TAKEN));
+    };
+
+    cache::evict_links(&state.redis_db, &[external_cache_key(&external_id)]).await;
+
+    let short_url = format!("{}/x/{}", state.base_url, external_id);
+    info!(short_url = %short_url, created = created, "Stored external link");
+    let status = if created {
+        StatusCode::CREATED
+    } else {
+        StatusCode::OK
+    };
+    let response = ExternalLinkResponse {
+        external_id,
+        short_url,
+        long_url: payload.long_url,
+    };
+    Ok((status, response))
+}
+
+#[instrument(skip(state))]
+pub async fn handle_external_link(
+    State(state): State<AppState>,
+    Path(external_id): Path<String>,
+) -> impl IntoResponse {
+    if !state.external_id_pattern.is_match(&external_id) {
+        error!(external_id = %external_id, "Invalid external ID");
+        return unknown_link(
+            &state,
+            AppError::InvalidRequest(format!("Invalid external ID: {external_id}")),
+        );
+    }
+
+    let cache_key = external_cache_key(&external_id);
+    let mut redis_conn = state.redis_db.clone();
+    match redis_conn.get::<_, Option<String>>(&cache_key).await {
+        Ok(Some(long_url)) if state.blocklist.matching(&long_url).is_some() => {
+            info!(external_id = %external_id, "Destination domain blocked");
+            return AppError::Gone.into_response();
+        }
+        Ok(Some(long_url)) => {
+            info!(external_id = %external_id, "Cache hit");
+            return cached_redirect_response(&state, &redirect_target(&long_url, None, None));
+        }
+        Ok(None) => {
+            info!(external_id = %external_id, "Cache miss");
+        }
+        Err(e) => return AppError::from(e).into_response(),
+    }
+
+    match external_links::destination(&state.pg_db, &external_id).await {
+        Ok(Some(link)) if link.disabled => {
+            info!(external_id = %external_id, "External link disabled");
+            AppError::Gone.into_response()
+        }
+        Ok(Some(link)) if state.blocklist.matching(&link.long_url).is_some() => {
+            info!(external_id = %external_id, "Destination domain blocked");
+            AppError::Gone.into_response()
+        }
+        Ok(Some(ExternalLink { long_url, .. })) => {
+            info!(external_id = %external_id, "Redirecting to long URL");
+            if let Err(e) = cache::store_destination(
+                &mut redis_conn,
+                &cache_key,
+                &long_url,
+                state.runtime.load().cache_ttl,
+            )
+            .await
+            {
+                error!(error = %e, "Failed to cache URL in Redis");
+            }
+            cached_redirect_response(&state, &redirect_target(&long_url, None, None))
+        }
+        Ok(None) => {
+            error!(external_id = %external_id, "External ID not found");
+            unknown_link(&state, AppError::NotFound("External link"))
+        }
+        Err(e) => AppError::from(e).into_response(),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/x/{external_id}",
+    tag = "external links",
+    summary = "Delete an external link",
+    params(("external_id" = String, Path, description = "Caller-chosen ID")),
+    responses(
+        (status = 200, description = "Link deleted", body = Object),
+        (status = 401, description = "Missing admin token or signature", body = Problem, content_type = PROBLEM_JSON),
+        (status = 404, description = "Unknown link", body = Problem, content_type = PROBLEM_JSON),
+    ),
+)]
+#[instrument(skip(state, headers, extensions))]
+pub async fn delete_external_link(
+    State(state): State<AppState>,
+    Path(external_id): Path<String>,
+    headers: HeaderMap,
+    extensions: Extensions,
+) -> Result<Json<Value>, AppError> {
+    // Otherwise deleting and creating the ID again would repoint it
+    if !vouched_for(&state, &headers, &extensions) {
+        warn!(external_id = %external_id, "Anonymous attempt to delete an external link");
+        return Err(AppError::Unauthorized(EXTERNAL_LINK_TAKEN));
+    }
+
+    if !external_links::delete(&state.pg_db, &external_id).await? {
+        error!(external_id = %external_id, "External ID not found");
+        return Err(AppError::NotFound("External link"));
+    }
+
+    cache::evict_links(&state.redis_db, &[external_cache_key(&external_id)]).await;
+
+    info!(external_id = %external_id, "External link deleted successfully");
+    Ok(Json(
+        json!({"message": "external link deleted successfully"}),
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/tags",
+    tag = "links",
+    summary = "Tags with the number of links using them",
+    responses((status = 200, body = Vec<TagCount>)),
+)]
+#[instrument(skip(state))]
+pub async fn get_all_tags(State(state): State<AppState>) -> Result<Json<Vec<TagCount>>, AppError> {
+    let tags = links::tag_counts(&state.pg_read_db).await?;
+
+    Ok(Json(tags))
+}
+
+#[instrument(skip(state))]
+pub async fn list_tags(
+    State(state): State<AppState>,
+    Query(page): Query<PageQuery>,
+) -> Result<(Extension<Pagination>, Json<Vec<TagCount>>), AppError> {
+    let Json(tags) = get_all_tags(State(state)).await?;
+    Ok(paginate(tags, &page))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/campaigns",
+    tag = "campaigns",
+    summary = "Create a campaign",
+    request_body = CampaignRequest,
+    responses(
+        (status = 201, body = CampaignResponse),
+        (status = 409, description = "Name is taken", body = Problem, content_type = PROBLEM_JSON),
+    ),
+)]
+#[instrument(skip(state, payload))]
+pub async fn create_campaign(
+    State(state): State<AppState>,
+    payload: Result<Json<CampaignRequest>, JsonRejection>,
+) -> Result<Response, AppError> {
+    let payload = json_payload(payload)?;
+
+    let name = payload.name.trim();
+    if name.is_empty() || name.chars().count() > MAX_CAMPAIGN_NAME_LENGTH {
+        error!(name = %name, "Invalid campaign name");
+        return Err(AppError::InvalidRequest(format!(
+            "Campaign name must be 1 to {MAX_CAMPAIGN_NAME_LENGTH} characters"
+        )));
+    }
+
+    let description = normalize_description(payload.description.as_deref())?;
+
+    let campaign = campaigns::insert(&state.pg_db, name, description.as_deref()).await?;
+
+    let Some(campaign) = campaign else {
+        info!(name = %name, "Campaign already exists");
+        return Err(AppError::Conflict(
+            "A campaign with this name already exists".to_string(),
+        ));
+    };
+
+    info!(campaign_id = campaign.id, "Created campaign");
+    Ok((StatusCode::CREATED, Json(CampaignResponse::new(campaign))).into_response())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/campaigns",
+    tag = "campaigns",
+    summary = "List campaigns",
+    responses((status = 200, body = Vec<CampaignResponse>)),
+)]
+#[instrument(skip(state))]
+pub async fn get_all_campaigns(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<CampaignResponse>>, AppError> {
+    let campaigns = campaigns::list(&state.pg_read_db).await?;
+
+    Ok(Json(
+        campaigns.into_iter().map(CampaignResponse::new).collect(),
+    ))
+}
+
+#[instrument(skip(state))]
+pub async fn list_campaigns(
+    State(state): State<AppState>,
+    Query(page): Query<PageQuery>,
+) -> Result<(Extension<Pagination>, Json<Vec<CampaignResponse>>), AppError> {
+    let Json(campaigns) = get_all_campaigns(State(state)).await?;
+    Ok(paginate(campaigns, &page))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/campaigns/{id}",
+    tag = "campaigns",
+    summary = "Delete a campaign, keeping its links",
+    params(("id" = i32, Path, description = "Campaign ID")),
+    responses(
+        (status = 200, description = "Campaign deleted", body = Object),
+        (
+            status = 404,
+            description = "Unknown campaign",
+            body = Problem,
+            content_type = PROBLEM_JSON,
+        ),
+    ),
+)]
+#[instrument(skip(state))]
+pub async fn delete_campaign(
+    State(state): State<AppState>,
+    Path(id): Path<i32>,
+) -> Result<Json<Value>, AppError> {
+    // Member links are kept and only detached from the campaign
+    if !campaigns::delete(&state.pg_db, id).await? {
+        error!(campaign_id = id, "Campaign not found");
+        return Err(AppError::NotFound("Campaign"));
+    }
+
+    cache::invalidate_responses(&state.redis_db).await;
+    info!(campaign_id = id, "Campaign deleted successfully");
+    Ok(Json(json!({"message": "campaign deleted successfully"})))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/campaigns/{id}/links",
+    tag = "campaigns",
+    summary = "Attach links to a campaign",
+    params(("id" = i32, Path, description = "Campaign ID")),
+    request_body = CampaignLinksRequest,
+    responses(
+        (status = 200, description = "Attached and unknown short codes", body = Object),
+        (
+            status = 404,
+            description = "Unknown campaign",
+            body = Problem,
+            content_type = PROBLEM_JSON,
+        ),
+    ),
+)]
+#[instrument(skip(state, payload))]
+pub async fn add_campaign_links(
+    State(state): State<AppState>,
+    Path(id): Path<i32>,
+    payload: Result<Json<CampaignLinksRequest>, JsonRejection>,
+) -> Result<Json<Value>, AppError> {
+    let mut payload = json_payload(payload)?;
+
+    for short_code in payload.short_codes.iter_mut() {
+        *short_code = canonical_code(short_code, state.case_insensitive_codes);
+    }
+    if let Some(short_code) = payload
+        .short_codes
+        .iter()
+        .find(|short_code| !valid_short_code(short_code, &state.code_alphabet))
+    {
+        error!(short_code = %short_code, "Invalid short code");
+        return Err(AppError::InvalidShortCode(short_code.clone()));
+    }
+
+    if !campaigns::exists(&state.pg_db, id).await? {
+        error!(campaign_id = id, "Campaign not found");
+        return Err(AppError::NotFound("Campaign"));
+    }
+
+    let attached = campaigns::attach(&state.pg_db, id, &payload.short_codes).await?;
+
+    let not_found: Vec<&String> = payload
+        .short_codes
+        .iter()
+        .filter(|short_code| !attached.contains(short_code))
+        .collect();
+
+    cache::invalidate_responses(&state.redis_db).await;
+    info!(
+        campaign_id = id,
+        attached = attached.len(),
+        "Attached links to campaign"
+    );
+    Ok(Json(json!({"attached": attached, "not_found": not_found})))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/campaigns/{id}/links/{short_code}",
+    tag = "campaigns",
+    summary = "Detach a link from a campaign",
+    params(
+        ("id" = i32, Path, description = "Campaign ID"),
+        ("short_code" = String, Path, description = "Short code of the link"),
+    ),
+    responses(
+        (status = 200, description = "Link detached", body = Object),
+        (
+            status = 404,
+            description = "Link is not in the campaign",
+            body = Problem,
+            content_type = PROBLEM_JSON,
+        ),
+    ),
+)]
+#[instrument(skip(state))]
+pub async fn remove_campaign_link(
+    State(state): State<AppState>,
+    Path((id, short_code)): Path<(i32, String)>,
+) -> Result<Json<Value>, AppError> {
+    let short_code = canonical_code(&short_code, state.case_insensitive_codes);
+    if !valid_short_code(&short_code, &state.code_alphabet) {
+        error!(short_code = %short_code, "Invalid short code");
+        return Err(AppError::InvalidShortCode(short_code));
+    }
+
+    if !campaigns::detach(&state.pg_db, id, &short_code).await? {
+        error!(campaign_id = id, short_code = %short_code, "Link not in campaign");
+        return Err(AppError::NotFound("Campaign link"));
+    }
+
+    cache::invalidate_responses(&state.redis_db).await;
+    info!(campaign_id = id, short_code = %short_code, "Removed link from campaign");
+    Ok(Json(json!({"message": "link removed from campaign"})))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/campaigns/{id}/stats",
+    tag = "campaigns",
+    summary = "Clicks of a campaign and its top links",
+    params(("id" = i32, Path, description = "Campaign ID")),
+    responses(
+        (status = 200, body = CampaignStatsResponse),
+        (
+            status = 404,
+            description = "Unknown campaign",
+            body = Problem,
+            content_type = PROBLEM_JSON,
+        ),
+    ),
+)]
+#[instrument(skip(state))]
+pub async fn get_campaign_stats(
+    State(state): State<AppState>,
+    Path(id): Path<i32>,
+) -> Result<Json<CampaignStatsResponse>, AppError> {
+    let Some((name, links, clicks)) = campaigns::stats(&state.pg_read_db, id).await? else {
+        error!(campaign_id = id, "Campaign not found");
+        return Err(AppError::NotFound("Campaign"));
+    };
+
+    let top_links = campaigns::top_links(&state.pg_read_db, id, CAMPAIGN_TOP_LINKS).await?;
+
+    Ok(Json(CampaignStatsResponse {
+        id,
+        name,
+        links,
+        clicks,
+        top_links,
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/blocklist",
+    tag = "admin",
+    summary = "Block a destination domain",
+    request_body = BlockedDomainRequest,
+    responses(
+        (status = 201, body = BlockedDomainResponse),
+        (
+            status = 409,
+            description = "Domain is already blocked",
+            body = Problem,
+            content_type = PROBLEM_JSON,
+        ),
+    ),
+    security(("admin_token" = [])),
+)]
+#[instrument(skip(state, payload))]
+pub async fn add_blocked_domain(
+    State(state): State<AppState>,
+    payload: Result<Json<BlockedDomainRequest>, JsonRejection>,
+) -> Result<Response, AppError> {
+    let payload = json_payload(payload)?;
+
+    let Some(domain) = blocklist::normalize_domain(&payload.domain) else {
+        error!(domain = %payload.domain, "Invalid domain");
+        return Err(AppError::InvalidRequest(format!(
+            "Invalid domain: {}",
+            payload.domain
+        )));
+    };
+
+    let reason = normalize_description(payload.reason.as_deref())?;
+
+    let blocked = blocked_domains::insert(&state.pg_db, &domain, reason.as_deref()).await?;
+
+    let Some(blocked) = blocked else {
+        info!(domain = %domain, "Domain already blocked");
+        return Err(AppError::Conflict(format!(
+            "Domain {domain} is already blocked"
+        )));
+    };
+
+    state.blocklist.insert(blocked.domain.clone());
+    info!(domain = %blocked.domain, "Blocked domain");
+    Ok((
+        StatusCode::CREATED,
+        Json(BlockedDomainResponse::new(blocked)),
+    )
+        .into_response())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/blocklist",
+    tag = "admin",
+    summary = "List blocked domains",
+    responses((status = 200, body = Vec<BlockedDomainResponse>)),
+    security(("admin_token" = [])),
+)]
+#[instrument(skip(state))]
+pub async fn get_blocked_domains(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<BlockedDomainResponse>>, AppError> {
+    let blocked = blocked_domains::list(&state.pg_db).await?;
+
+    Ok(Json(
+        blocked
+            .into_iter()
+            .map(BlockedDomainResponse::new)
+            .collect(),
+    ))
+}
+
+#[instrument(skip(state))]
+pub async fn list_blocked_domains(
+    State(state): State<AppState>,
+    Query(page): Query<PageQuery>,
+) -> Result<(Extension<Pagination>, Json<Vec<BlockedDomainResponse>>), AppError> {
+    let Json(blocked) = get_blocked_domains(State(state)).await?;
+    Ok(paginate(blocked, &page))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/cache/stats",
+    tag = "admin",
+    summary = "Redirect cache counters of this instance",
+    responses((status = 200, body = CacheStatsResponse)),
+    security(("admin_token" = [])),
+)]
+#[instrument(skip(state))]
+pub async fn get_cache_stats(State(state): State<AppState>) -> Json<CacheStatsResponse> {
+    Json(CacheStatsResponse::new(state.cache_stats.counts()))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/admin/blocklist/{domain}",
+    tag = "admin",
+    summary = "Unblock a domain",
+    params(("domain" = String, Path, description = "Blocked domain")),
+    responses(
+        (status = 200, description = "Domain unblocked", body = Object),
+        (
+            status = 404,
+            description = "Domain is not blocked",
+            body = Problem,
+            content_type = PROBLEM_JSON,
+        ),
+    ),
+    security(("admin_token" = [])),
+)]
+#[instrument(skip(state))]
+pub async fn remove_blocked_domain(
+    State(state): State<AppState>,
+    Path(domain): Path<String>,
+) -> Result<Json<Value>, AppError> {
+    let Some(domain) = blocklist::normalize_domain(&domain) else {
+        error!(domain = %domain, "Invalid domain");
+        return Err(AppError::InvalidRequest(format!(
+            "Invalid domain: {domain}"
+        )));
+    };
+
+    if !blocked_domains::delete(&state.pg_db, &domain).await? {
+        error!(domain = %domain, "Domain not blocked");
+        return Err(AppError::NotFound("Blocked domain"));
+    }
+
+    state.blocklist.remove(&domain);
+    info!(domain = %domain, "Unblocked domain");
+    Ok(Json(json!({"message": "domain unblocked successfully"})))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/reviews",
+    tag = "admin",
+    summary = "List creations held for review",
+    responses((status = 200, body = Vec<ReviewResponse>)),
+    security(("admin_token" = [])),
+)]
+#[instrument(skip(state))]
+pub async fn get_reviews(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<ReviewResponse>>, AppError> {
+    let reviews = review_queue::list(&state.pg_db).await?;
+    Ok(Json(reviews.into_iter().map(ReviewResponse::new).collect()))
+}
+
+#[instrument(skip(state))]
+pub async fn list_reviews(
+    State(state): State<AppState>,
+    Query(page): Query<PageQuery>,
+) -> Result<(Extension<Pagination>, Json<Vec<ReviewResponse>>), AppError> {
+    let Json(reviews) = get_reviews(State(state)).await?;
+    Ok(paginate(reviews, &page))
+}
+
+// Create what a held request asked for, with the checks other than abuse scoring run
+// again; the review stays queued if that fails
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/reviews/{id}/approve",
+    tag = "admin",
+    summary = "Approve a held creation",
+    params(("id" = i32, Path, description = "Review ID")),
+    responses(
+        (status = 201, description = "Link created", body = ShortenResponse),
+        (status = 200, description = "Existing link reused or external link replaced", body = ShortenResponse),
+        (status = 404, description = "Unknown review", body = Problem, content_type = PROBLEM_JSON),
+    ),
+    security(("admin_token" = [])),
+)]
+#[instrument(skip(state))]
+pub async fn approve_review(
+    State(state): State<AppState>,
+    Path(id): Path<i32>,
+) -> Result<Response, AppError> {
+    let mut tx = state.pg_db.begin().await?;
+    let Some(review) = review_queue::take(&mut tx, id).await? else {
+        error!(id, "Review not found");
+        return Err(AppError::NotFound("Review"));
+    };
+
+    let response = match review.external_id {
+        Some(external_id) => {
+            let payload = serde_json::from_str(&review.request)
+                .map_err(|e| AppError::Internal(format!("Invalid held request: {e}")))?;
+            let (status, link) =
+                store_external_link(&state, external_id, payload, &Creator::Trusted).await?;
+            (status, Json(link)).into_response()
+        }
+        None => {
+            let payload = serde_json::from_str(&review.request)
+                .map_err(|e| AppError::Internal(format!("Invalid held request: {e}")))?;
+            let (status, link) = shorten(&state, payload, &Creator::Trusted).await?;
+            (status, Json(link)).into_response()
+        }
+    };
+    tx.commit().await?;
+
+    info!(id, url = %review.long_url, "Approved held creation");
+    Ok(response)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/reviews/{id}/reject",
+    tag = "admin",
+    summary = "Reject a held creation",
+    params(("id" = i32, Path, description = "Review ID")),
+    responses(
+        (status = 200, description = "Creation rejected", body = Object),
+        (status = 404, description = "Unknown review", body = Problem, content_type = PROBLEM_JSON),
+    ),
+    security(("admin_token" = [])),
+)]
+#[instrument(skip(state))]
+pub async fn reject_review(
+    State(state): State<AppState>,
+    Path(id): Path<i32>,
+) -> Result<Json<Value>, AppError> {
+    if !review_queue::delete(&state.pg_db, id).await? {
+        error!(id, "Review not found");
+        return Err(AppError::NotFound("Review"));
+    }
+
+    info!(id, "Rejected held creation");
+    Ok(Json(json!({"message": "creation rejected"})))
+}
+
+// Live clicks on all instances, optionally of one link, as they happen; clicks sent while
+// a slow client was not reading are skipped
+#[utoipa::path(
+    get,
+    path = "/api/v1/events/clicks",
+    tag = "admin",
+    summary = "Live clicks as server-sent events",
+    params(ClickStreamQuery),
+    responses(
+        (
+            status = 200,
+            description = "One `click` event per redirect",
+            content_type = "text/event-stream",
+            body = String,
+        ),
+    ),
+    security(("admin_token" = [])),
+)]
+#[instrument(skip(state))]
+pub async fn stream_clicks(
+    State(state): State<AppState>,
+    Query(params): Query<ClickStreamQuery>,
+) -> Response {
+    let Some(click_stream) = &state.click_stream else {
+        return AppError::NotFound("Click stream").into_response();
+    };
+    let short_code = params
+        .short_code
+        .map(|short_code| canonical_code(&short_code, state.case_insensitive_codes));
+
+    let clicks = stream::unfold(click_stream.subscribe(), move |mut receiver| {
+        let short_code = short_code.clone();
+        async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(click) => {
+                        if short_code
+                            .as_ref()
+                            .is_some_and(|code| *code != click.short_code)
+                        {
+                            continue;
+                        }
+                        let event = SseEvent::default().event("click").json_data(&click);
+                        return Some((event, receiver));
+                    }
+                    Err(RecvError::Lagged(missed)) => {
+                        warn!(missed, "Click stream client fell behind");
+                        let event = SseEvent::default().comment(format!("missed {missed} clicks"));
+                        return Some((Ok(event), receiver));
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        }
+    })
+    // Open streams would otherwise hold up a graceful shutdown until its timeout
+    .take_until(state.shutdown.clone().cancelled_owned());
+
+    info!("Streaming clicks");
+    Sse::new(clicks)
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+// Traffic snapshots of this instance over a WebSocket, one text message each, until the
+// client or the server goes away
+#[utoipa::path(
+    get,
+    path = "/api/v1/ws",
+    tag = "admin",
+    summary = "Traffic snapshots over a WebSocket",
+    responses(
+        (status = 101, description = "Switching to the WebSocket protocol"),
+    ),
+    security(("admin_token" = [])),
+)]
+#[instrument(skip(state, upgrade))]
+pub async fn dashboard_feed(State(state): State<AppState>, upgrade: WebSocketUpgrade) -> Response {
+    let Some(dashboard) = &state.dashboard else {
+        return AppError::NotFound("Dashboard feed").into_response();
+    };
+    let snapshots = dashboard.snapshots.subscribe();
+    let shutdown = state.shutdown.clone();
+    upgrade.on_upgrade(move |socket| send_snapshots(socket, snapshots, shutdown))
+}
+
+async fn send_snapshots(
+    mut socket: WebSocket,
+    mut snapshots: broadcast::Receiver<DashboardSnapshot>,
+    shutdown: CancellationToken,
+) {
+    info!("Dashboard connected");
+    loop {
+        tokio::select! {
+            snapshot = snapshots.recv() => {
+                let snapshot = match snapshot {
+                    Ok(snapshot) => snapshot,
+                    Err(RecvError::Lagged(missed)) => {
+                        warn!(missed, "Dashboard fell behind");
+                        continue;
+                    }
+                    Err(RecvError::Closed) => break,
+                };
+                let Ok(text) = serde_json::to_string(&snapshot) else {
+                    continue;
+                };
+                if socket.send(Message::Text(text.into())).await.is_err() {
+                    break;
+                }
+            }
+            // Only closing matters; pings are answered by the socket itself
+            message = socket.recv() => match message {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+            _ = shutdown.cancelled() => {
+                let _ = socket.send(Message::Close(None)).await;
+                break;
+            }
+        }
+    }
+    info!("Dashboard disconnected");
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/webhooks",
+    tag = "admin",
+    summary = "Register a webhook",
+    request_body = WebhookRequest,
+    responses(
+        (
+            status = 201,
+            description = "Webhook registered, with its signing secret",
+            body = WebhookResponse,
+        ),
+    ),
+    security(("admin_token" = [])),
+)]
+#[instrument(skip(state, payload))]
+pub async fn create_webhook(
+    State(state): State<AppState>,
+    payload: Result<Json<WebhookRequest>, JsonRejection>,
+) -> Result<Response, AppError> {
+    let payload = json_payload(payload)?;
+
+    if !url::Url::parse(&payload.url).is_ok_and(|url| matches!(url.scheme(), "http" | "https")) {
+        error!(url = %payload.url, "Invalid webhook URL");
+        return Err(AppError::InvalidRequest("Invalid webhook URL".to_string()));
+    }
+    if payload.events.is_empty() {
+        return Err(AppError::InvalidRequest(
+            "At least one event is required".to_string(),
+        ));
+    }
+    if let Some(unknown) = payload
+        .events
+        .iter()
+        .find(|event| Event::from_name(event).is_none())
+    {
+        error!(event = %unknown, "Unknown webhook event");
+        return Err(AppError::InvalidRequest(format!(
+            "Unknown event: {unknown}"
+        )));
+    }
+    let mut events = payload.events;
+    events.sort();
+    events.dedup();
+
+    let secret = match payload.secret.filter(|secret| !secret.is_empty()) {
+        Some(secret) => secret,
+        None => webhooks::generate_secret()
+            .map_err(|e| AppError::Internal(format!("Failed to generate webhook secret: {e}")))?,
+    };
+
+    let webhook =
+        repository::webhooks::insert(&state.pg_db, &payload.url, &secret, &events).await?;
+
+    if let Err(e) = webhooks::refresh_click_subscribers(&state).await {
+        error!(error = %e, "Failed to look up click webhooks");
+    }
+    info!(id = webhook.id, url = %webhook.url, "Registered webhook");
+    Ok((
+        StatusCode::CREATED,
+        Json(WebhookResponse::new(webhook, true)),
+    )
+        .into_response())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/webhooks",
+    tag = "admin",
+    summary = "List webhooks",
+    responses((status = 200, body = Vec<WebhookResponse>)),
+    security(("admin_token" = [])),
+)]
+#[instrument(skip(state))]
+pub async fn get_webhooks(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<WebhookResponse>>, AppError> {
+    let webhooks = repository::webhooks::list(&state.pg_db).await?;
+
+    Ok(Json(
+        webhooks
+            .into_iter()
+            .map(|webhook| WebhookResponse::new(webhook, false))
+            .collect(),
+    ))
+}
+
+#[instrument(skip(state))]
+pub async fn list_webhooks(
+    State(state): State<AppState>,
+    Query(page): Query<PageQuery>,
+) -> Result<(Extension<Pagination>, Json<Vec<WebhookResponse>>), AppError> {
+    let Json(webhooks) = get_webhooks(State(state)).await?;
+    Ok(paginate(webhooks, &page))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/admin/webhooks/{id}",
+    tag = "admin",
+    summary = "Remove a webhook",
+    params(("id" = i32, Path, description = "Webhook ID")),
+    responses(
+        (status = 200, description = "Webhook removed", body = Object),
+        (
+            status = 404,
+            description = "Unknown webhook",
+            body = Problem,
+            content_type = PROBLEM_JSON,
+        ),
+    ),
+    security(("admin_token" = [])),
+)]
+#[instrument(skip(state))]
+pub async fn delete_webhook(
+    State(state): State<AppState>,
+    Path(id): Path<i32>,
+) -> Result<Json<Value>, AppError> {
+    if !repository::webhooks::delete(&state.pg_db, id).await? {
+        error!(id, "Webhook not found");
+        return Err(AppError::NotFound("Webhook"));
+    }
+
+    if let Err(e) = webhooks::refresh_click_subscribers(&state).await {
+        error!(error = %e, "Failed to look up click webhooks");
+    }
+    info!(id, "Removed webhook");
+    Ok(Json(json!({"message": "webhook removed successfully"})))
+}
+
+// Latest deliveries to a webhook, newest first
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/webhooks/{id}/deliveries",
+    tag = "admin",
+    summary = "Latest deliveries to a webhook",
+    params(("id" = i32, Path, description = "Webhook ID"), DeliveryQuery),
+    responses(
+        (status = 200, body = Vec<WebhookDeliveryResponse>),
+        (
+            status = 404,
+            description = "Unknown webhook",
+            body = Problem,
+            content_type = PROBLEM_JSON,
+        ),
+    ),
+    security(("admin_token" = [])),
+)]
+#[instrument(skip(state))]
+pub async fn get_webhook_deliveries(
+    State(state): State<AppState>,
+    Path(id): Path<i32>,
+    Query(params): Query<DeliveryQuery>,
+) -> Result<Json<Vec<WebhookDeliveryResponse>>, AppError> {
+    if params
+        .status
+        .as_deref()
+        .is_some_and(|status| !matches!(status, "pending" | "delivered" | "failed"))
+    {
+        error!(status = ?params.status, "Invalid delivery status");
+        return Err(AppError::InvalidRequest(
+            "status must be pending, delivered or failed".to_string(),
+        ));
+    }
+    let limit = params.limit.unwrap_or(50).clamp(1, MAX_DELIVERIES);
+
+    if !repository::webhooks::exists(&state.pg_db, id).await? {
+        error!(id, "Webhook not found");
+        return Err(AppError::NotFound("Webhook"));
+    }
+
+    let deliveries =
+        repository::webhooks::deliveries(&state.pg_db, id, params.status.as_deref(), limit).await?;
+
+    Ok(Json(
+        deliveries
+            .into_iter()
+            .map(WebhookDeliveryResponse::new)
+            .collect(),
+    ))
 }
@@ -0,0 +1,681 @@
+use std::{net::SocketAddr, time::Instant};
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::{ConnectInfo, Request, State},
+    http::{header, Extensions, HeaderMap, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::Utc;
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    abuse::Creator,
+    cache,
+    db::breaker,
+    error::{AppError, PROBLEM_JSON},
+    state::{AppState, RedisConn},
+    types::{Envelope, Meta, Pagination},
+    utils::{client_ip, signing},
+    webhooks::SIGNATURE_HEADER,
+};
+
+// Largest response body stored in the response cache
+const MAX_CACHED_BODY_SIZE: usize = 1024 * 1024;
+
+// Largest error body that gets the request id added
+const MAX_ERROR_BODY_SIZE: usize = 64 * 1024;
+
+// Largest request body checked against its signature
+const MAX_SIGNED_BODY_SIZE: usize = 64 * 1024;
+
+// Largest request or response body of a creation sent with an Idempotency-Key
+const MAX_IDEMPOTENT_BODY_SIZE: usize = 64 * 1024;
+
+// How long an idempotency key stays reserved while its first request is handled
+const IDEMPOTENCY_LOCK_SECONDS: u64 = 60;
+
+// Longest accepted Idempotency-Key
+const MAX_IDEMPOTENCY_KEY_LENGTH: usize = 255;
+
+const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+const IDEMPOTENT_REPLAYED_HEADER: &str = "idempotent-replayed";
+
+// Largest response body given an ETag
+const MAX_TAGGED_BODY_SIZE: usize = 4 * 1024 * 1024;
+
+// Largest v2 response body that is wrapped in an envelope
+const MAX_ENVELOPED_BODY_SIZE: usize = 4 * 1024 * 1024;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+// Target of access log events, for routing them apart from application logs
+pub const ACCESS_LOG_TARGET: &str = "access_log";
+
+const TIMESTAMP_HEADER: &str = "x-request-timestamp";
+const NONCE_HEADER: &str = "x-request-nonce";
+
+// Reject mutating requests that are stale or reuse a previously seen nonce
+pub async fn replay_protection(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !state.replay_protection || request.method().is_safe() {
+        return next.run(request).await;
+    }
+
+    let (Some(timestamp), Some(nonce)) = (
+        header_value(&request, TIMESTAMP_HEADER),
+        header_value(&request, NONCE_HEADER),
+    ) else {
+        error!("Missing replay protection headers");
+        return AppError::InvalidRequest(
+            "Missing X-Request-Timestamp or X-Request-Nonce header".to_string(),
+        )
+        .into_response();
+    };
+
+    let Ok(timestamp) = timestamp.parse::<i64>() else {
+        error!(timestamp = %timestamp, "Invalid request timestamp");
+        return AppError::InvalidRequest("Invalid X-Request-Timestamp header".to_string())
+            .into_response();
+    };
+
+    if nonce.is_empty() || nonce.len() > signing::MAX_NONCE_LENGTH {
+        error!("Invalid request nonce");
+        return AppError::InvalidRequest("Invalid X-Request-Nonce header".to_string())
+            .into_response();
+    }
+
+    let age = Utc::now().timestamp().abs_diff(timestamp);
+    if age > state.replay_window {
+        error!(age = age, "Request timestamp outside the replay window");
+        return AppError::Unauthorized("Request timestamp expired").into_response();
+    }
+
+    // Nonces must outlive the window on both sides of the current time
+    let claimed =
+        cache::claim_nonce(&mut state.redis_db.clone(), &nonce, state.replay_window * 2).await;
+
+    match claimed {
+        Ok(true) => next.run(request).await,
+        Ok(false) => {
+            error!(nonce = %nonce, "Replayed request nonce");
+            AppError::Unauthorized("Request nonce already used").into_response()
+        }
+        Err(e) => AppError::from(e).into_response(),
+    }
+}
+
+// Add the request id to JSON and problem details error bodies so users can quote it when
+// reporting failures
+pub async fn request_id_in_errors(request: Request, next: Next) -> Response {
+    let Some(request_id) = header_value(&request, REQUEST_ID_HEADER) else {
+        return next.run(request).await;
+    };
+
+    let response = next.run(request).await;
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|value| {
+            value.as_bytes().starts_with(b"application/json")
+                || value.as_bytes().starts_with(PROBLEM_JSON.as_bytes())
+        });
+    if !(response.status().is_client_error() || response.status().is_server_error()) || !is_json {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let body = match to_bytes(body, MAX_ERROR_BODY_SIZE).await {
+        Ok(body) => body,
+        Err(e) => {
+            return AppError::Internal(format!("Failed to buffer error response body: {e}"))
+                .into_response();
+        }
+    };
+    let body = match serde_json::from_slice::<serde_json::Value>(&body) {
+        Ok(serde_json::Value::Object(mut fields)) => {
+            fields.insert("request_id".to_string(), request_id.into());
+            parts.headers.remove(header::CONTENT_LENGTH);
+            Body::from(serde_json::Value::Object(fields).to_string())
+        }
+        _ => Body::from(body),
+    };
+    Response::from_parts(parts, body)
+}
+
+// Tag successful responses with a weak ETag of their body and answer `304 Not Modified`
+// when the client already holds it, so polling clients skip identical payloads
+pub async fn etag(request: Request, next: Next) -> Response {
+    let if_none_match = request.headers().get(header::IF_NONE_MATCH).cloned();
+    let response = next.run(request).await;
+    if response.status() != StatusCode::OK {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let body = match to_bytes(body, MAX_TAGGED_BODY_SIZE).await {
+        Ok(body) => body,
+        Err(e) => {
+            return AppError::Internal(format!("Failed to buffer response body: {e}"))
+                .into_response();
+        }
+    };
+    let tag = format!(
+        "W/\"{}\"",
+        bs58::encode(&Sha256::digest(&body)[..16]).into_string()
+    );
+
+    // Weak comparison: `W/"x"` and `"x"` name the same representation
+    let opaque = |value: &str| value.trim().trim_start_matches("W/").to_string();
+    let unchanged = if_none_match
+        .as_ref()
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| {
+            value.trim() == "*"
+                || value
+                    .split(',')
+                    .any(|candidate| opaque(candidate) == opaque(&tag))
+        });
+    if let Ok(value) = HeaderValue::from_str(&tag) {
+        parts.headers.insert(header::ETAG, value);
+    }
+    if unchanged {
+        parts.status = StatusCode::NOT_MODIFIED;
+        parts.headers.remove(header::CONTENT_LENGTH);
+        parts.headers.remove(header::CONTENT_TYPE);
+        return Response::from_parts(parts, Body::empty());
+    }
+    Response::from_parts(parts, Body::from(body))
+}
+
+// Wrap v2 JSON bodies in an envelope: successful answers become `data`, problem details
+// become `error`, and list handlers report their page in `meta`
+pub async fn envelope(request: Request, next: Next) -> Response {
+    let response = next.run(request).await;
+    let is_problem = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|value| value.as_bytes().starts_with(PROBLEM_JSON.as_bytes()));
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|value| value.as_bytes().starts_with(b"application/json"));
+    if !is_problem && !is_json {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let body = match to_bytes(body, MAX_ENVELOPED_BODY_SIZE).await {
+        Ok(body) => body,
+        Err(e) => {
+            return AppError::Internal(format!("Failed to buffer response body: {e}"))
+                .into_response();
+        }
+    };
+    let Ok(body) = serde_json::from_slice::<serde_json::Value>(&body) else {
+        return Response::from_parts(parts, Body::from(body));
+    };
+
+    let (data, error) =
+        if is_problem || parts.status.is_client_error() || parts.status.is_server_error() {
+            (None, Some(body))
+        } else {
+            (Some(body), None)
+        };
+    let envelope = Envelope {
+        data,
+        error,
+        meta: Meta {
+            pagination: parts.extensions.remove::<Pagination>(),
+        },
+    };
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts.headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/json"),
+    );
+    Response::from_parts(parts, Body::from(json!(envelope).to_string()))
+}
+
+// Hold requests over the global rate limit until the next second, refusing them once
+// REQUEST_BUFFER_SIZE requests are waiting
+pub async fn rate_limit(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let rate = state.runtime.load().rate_limit;
+    if !state.rate_limiter.acquire(rate).await {
+        warn!("Rate limit queue full, refusing request");
+        return AppError::Overloaded.into_response();
+    }
+    next.run(request).await
+}
+
+// While the database circuit is open, the errors it causes become 503s with a Retry-After,
+// so clients back off; redirects served from the cache keep working
+pub async fn database_unavailable(request: Request, next: Next) -> Response {
+    let response = next.run(request).await;
+    if response.status() != StatusCode::INTERNAL_SERVER_ERROR {
+        return response;
+    }
+    let Some(retry_after) = breaker::retry_after() else {
+        return response;
+    };
+    AppError::DatabaseUnavailable { retry_after }.into_response()
+}
+
+// Emit one access log event per request, outside of the request's span
+pub async fn access_log(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    if !state.access_log {
+        return next.run(request).await;
+    }
+
+    let method = request.method().clone();
+    let path = request.uri().path().to_string();
+    let remote_addr = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| *addr);
+    let client = client_ip(request.headers(), remote_addr, &state.trusted_proxies)
+        .map_or_else(|| "unknown".to_string(), |ip| ip.to_string());
+    let user_agent = header_value(&request, header::USER_AGENT.as_str()).unwrap_or_default();
+    let referrer = header_value(&request, header::REFERER.as_str()).unwrap_or_default();
+    let request_id = header_value(&request, REQUEST_ID_HEADER).unwrap_or_default();
+    let started = Instant::now();
+
+    let response = next.run(request).await;
+
+    info!(
+        target: ACCESS_LOG_TARGET,
+        method = %method,
+        path = %path,
+        status = response.status().as_u16(),
+        latency_ms = started.elapsed().as_secs_f64() * 1000.0,
+        client_ip = %client,
+        user_agent = %user_agent,
+        referrer = %referrer,
+        request_id = %request_id,
+        "Request served"
+    );
+    response
+}
+
+// Who sent a request, as far as cached and replayed responses are concerned
+fn principal(request: &Request) -> String {
+    request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .map(|value| bs58::encode(Sha256::digest(value.as_bytes())).into_string())
+        .unwrap_or_else(|| "anonymous".to_string())
+}
+
+fn header_value(request: &Request, name: &str) -> Option<String> {
+    request
+        .headers()
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+}
+
+// Cache successful JSON responses of read endpoints in Redis for a short time
+pub async fn cache_response(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let response_cache_ttl = state.runtime.load().response_cache_ttl;
+    if response_cache_ttl == 0 {
+        return next.run(request).await;
+    }
+
+    let mut redis_conn = state.redis_db.clone();
+    let generation = match cache::response_generation(&mut redis_conn).await {
+        Ok(generation) => generation,
+        Err(e) => {
+            error!(error = %e, "Failed to read response cache generation");
+            return next.run(request).await;
+        }
+    };
+
+    let principal = principal(&request);
+    let path_and_query = request
+        .uri()
+        .path_and_query()
+        .map(|value| value.as_str())
+        .unwrap_or_else(|| request.uri().path());
+    // JSON and the export formats of one URL are told apart by the Accept header
+    let accept = request
+        .headers()
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    let key = cache::response_key(generation, &principal, accept, path_and_query);
+
+    match redis_conn.get::<_, Option<Vec<u8>>>(&key).await {
+        Ok(Some(body)) => {
+            debug!(key = %key, "Response cache hit");
+            return (
+                [
+                    (header::CONTENT_TYPE, "application/json"),
+                    (header::VARY, "accept"),
+                    (header::HeaderName::from_static("x-cache"), "HIT"),
+                ],
+                body,
+            )
+                .into_response();
+        }
+        Ok(None) => debug!(key = %key, "Response cache miss"),
+        Err(e) => error!(error = %e, "Redis error"),
+    }
+
+    let response = next.run(request).await;
+    // Hits are served as JSON, so CSV and NDJSON exports are never stored
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|value| value.as_bytes().starts_with(b"application/json"));
+    if response.status() != StatusCode::OK || !is_json {
+        return response;
+    }
+
+    let (parts, body) = response.into_parts();
+    let body = match to_bytes(body, MAX_CACHED_BODY_SIZE).await {
+        Ok(body) => body,
+        Err(e) => {
+            return AppError::Internal(format!("Failed to buffer response body: {e}"))
+                .into_response();
+        }
+    };
+
+    if let Err(e) = redis_conn
+        .set_ex::<_, _, ()>(&key, body.as_ref(), response_cache_ttl)
+        .await
+    {
+        error!(error = %e, "Failed to cache response in Redis");
+    }
+
+    let mut response = Response::from_parts(parts, Body::from(body));
+    response
+        .headers_mut()
+        .insert("x-cache", HeaderValue::from_static("MISS"));
+    response
+}
+
+// Marks a request whose signature `verify_signature` accepted, vouching for the caller
+// like the admin token does
+#[derive(Debug, Clone, Copy)]
+struct SignedRequest;
+
+// Check the `X-Tlong-Signature` of creations signed with one of SIGNING_SECRETS, so machine
+// clients in untrusted places can create links without holding a long-lived token. Each
+// signature is accepted once. Unsigned creations are refused under REQUIRE_SIGNED_CREATION
+pub async fn verify_signature(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let signature = header_value(&request, SIGNATURE_HEADER);
+    if state.signing_secrets.is_empty() {
+        return next.run(request).await;
+    }
+    let Some(signature) = signature else {
+        // Deleting creates nothing, so only the handler's own checks apply to it
+        if state.require_signed_creation && request.method() != Method::DELETE {
+            warn!("Unsigned creation");
+            return AppError::Unauthorized("Missing request signature").into_response();
+        }
+        return next.run(request).await;
+    };
+
+    let (parts, body) = request.into_parts();
+    let body = match to_bytes(body, MAX_SIGNED_BODY_SIZE).await {
+        Ok(body) => body,
+        Err(e) => {
+            error!(error = %e, "Failed to buffer request body");
+            return AppError::PayloadTooLarge.into_response();
+        }
+    };
+
+    let (timestamp, nonce) = match signing::verify(&state.signing_secrets, &signature, &body) {
+        Ok(signed) => signed,
+        Err(reason) => {
+            warn!(reason = reason, "Rejected request signature");
+            return AppError::Unauthorized(reason).into_response();
+        }
+    };
+    let age = Utc::now().timestamp().abs_diff(timestamp);
+    if age > state.replay_window {
+        warn!(age = age, "Request signature outside the replay window");
+        return AppError::Unauthorized("Request signature expired").into_response();
+    }
+
+    // Signature nonces share the replay protection store, apart from X-Request-Nonce
+    // values so a client may use the same value for both
+    let claimed = cache::claim_nonce(
+        &mut state.redis_db.clone(),
+        &format!("signed:{nonce}"),
+        state.replay_window * 2,
+    )
+    .await;
+    match claimed {
+        Ok(true) => {
+            let mut request = Request::from_parts(parts, Body::from(body));
+            request.extensions_mut().insert(SignedRequest);
+            next.run(request).await
+        }
+        Ok(false) => {
+            warn!(nonce = %nonce, "Replayed request signature");
+            AppError::Unauthorized("Request signature already used").into_response()
+        }
+        Err(e) => AppError::from(e).into_response(),
+    }
+}
+
+// Outcome of a request sent with an Idempotency-Key, kept in Redis for retries
+#[derive(Serialize, Deserialize)]
+struct IdempotentResponse {
+    // Digest of the request body, so a key reused for another request is refused
+    fingerprint: String,
+    // Unset while the first request is still being handled
+    status: Option<u16>,
+    content_type: Option<String>,
+    body: String,
+}
+
+// Handle a creation once per Idempotency-Key and replay its response to retries, so
+// clients retrying after a network error neither create duplicates nor see other results
+pub async fn idempotency(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    if state.idempotency_ttl == 0 {
+        return next.run(request).await;
+    }
+    let Some(idempotency_key) = header_value(&request, IDEMPOTENCY_KEY_HEADER) else {
+        return next.run(request).await;
+    };
+    if idempotency_key.is_empty() || idempotency_key.len() > MAX_IDEMPOTENCY_KEY_LENGTH {
+        return AppError::InvalidRequest("Invalid Idempotency-Key header".to_string())
+            .into_response();
+    }
+
+    let key = cache::idempotency_key(&principal(&request), request.uri().path(), &idempotency_key);
+    let (parts, body) = request.into_parts();
+    let body = match to_bytes(body, MAX_IDEMPOTENT_BODY_SIZE).await {
+        Ok(body) => body,
+        Err(e) => {
+            error!(error = %e, "Failed to buffer request body");
+            return AppError::PayloadTooLarge.into_response();
+        }
+    };
+    let fingerprint = bs58::encode(Sha256::digest(&body)).into_string();
+
+    let mut redis_conn = state.redis_db.clone();
+    let pending = IdempotentResponse {
+        fingerprint: fingerprint.clone(),
+        status: None,
+        content_type: None,
+        body: String::new(),
+    };
+    let pending = match serde_json::to_string(&pending) {
+        Ok(pending) => pending,
+        Err(e) => {
+            return AppError::Internal(format!("Failed to serialize idempotency record: {e}"))
+                .into_response();
+        }
+    };
+    match cache::claim_idempotency_key(&mut redis_conn, &key, &pending, IDEMPOTENCY_LOCK_SECONDS)
+        .await
+    {
+        Ok(true) => {}
+        Ok(false) => return replay_idempotent(&mut redis_conn, &key, &fingerprint).await,
+        Err(e) => return AppError::from(e).into_response(),
+    }
+
+    let response = next.run(Request::from_parts(parts, Body::from(body))).await;
+    // Server errors may not happen again, so the key is released for the retry
+    if response.status().is_server_error() {
+        if let Err(e) = redis_conn.del::<_, ()>(&key).await {
+            error!(error = %e, "Failed to release idempotency key");
+        }
+        return response;
+    }
+
+    let (parts, body) = response.into_parts();
+    let body = match to_bytes(body, MAX_IDEMPOTENT_BODY_SIZE).await {
+        Ok(body) => body,
+        Err(e) => {
+            return AppError::Internal(format!("Failed to buffer response body: {e}"))
+                .into_response();
+        }
+    };
+    let record = IdempotentResponse {
+        fingerprint,
+        status: Some(parts.status.as_u16()),
+        content_type: parts
+            .headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string),
+        body: String::from_utf8_lossy(&body).into_owned(),
+    };
+    match serde_json::to_string(&record) {
+        Ok(record) => {
+            if let Err(e) = redis_conn
+                .set_ex::<_, _, ()>(&key, record, state.idempotency_ttl)
+                .await
+            {
+                error!(error = %e, "Failed to store idempotent response in Redis");
+            }
+        }
+        Err(e) => error!(error = %e, "Failed to serialize idempotent response"),
+    }
+
+    let mut response = Response::from_parts(parts, Body::from(body));
+    response.headers_mut().insert(
+        IDEMPOTENT_REPLAYED_HEADER,
+        HeaderValue::from_static("false"),
+    );
+    response
+}
+
+// Answer a retry with the stored response of the first request with its key
+async fn replay_idempotent(conn: &mut RedisConn, key: &str, fingerprint: &str) -> Response {
+    let record = match conn.get::<_, Option<String>>(key).await {
+        Ok(record) => record.and_then(|record| {
+            serde_json::from_str::<IdempotentResponse>(&record)
+                .inspect_err(|e| error!(error = %e, "Invalid idempotent response in Redis"))
+                .ok()
+        }),
+        Err(e) => return AppError::from(e).into_response(),
+    };
+    let Some(record) = record else {
+        // Released or expired since it was claimed; the client may simply retry
+        return AppError::Conflict("Request with this Idempotency-Key is in progress".to_string())
+            .into_response();
+    };
+    if record.fingerprint != fingerprint {
+        warn!("Idempotency-Key reused with a different request");
+        return AppError::IdempotencyKeyReused.into_response();
+    }
+    let Some(status) = record
+        .status
+        .and_then(|status| StatusCode::from_u16(status).ok())
+    else {
+        return AppError::Conflict("Request with this Idempotency-Key is in progress".to_string())
+            .into_response();
+    };
+
+    debug!(key = %key, "Replaying idempotent response");
+    let mut response = (status, record.body).into_response();
+    let headers = response.headers_mut();
+    if let Some(content_type) = record
+        .content_type
+        .and_then(|content_type| HeaderValue::from_str(&content_type).ok())
+    {
+        headers.insert(header::CONTENT_TYPE, content_type);
+    }
+    headers.insert(IDEMPOTENT_REPLAYED_HEADER, HeaderValue::from_static("true"));
+    response
+}
+
+// Whether a request carries the admin token, which vouches for it like `require_admin` does
+fn is_admin(state: &AppState, headers: &HeaderMap) -> bool {
+    state
+        .admin_token_digest
+        .as_ref()
+        .is_some_and(|expected| bearer_matches(headers, expected))
+}
+
+// Whether a request carries the admin token or a signature `verify_signature` accepted
+pub(crate) fn vouched_for(state: &AppState, headers: &HeaderMap, extensions: &Extensions) -> bool {
+    extensions.get::<SignedRequest>().is_some() || is_admin(state, headers)
+}
+
+// Who is creating through a request, for risk scoring; callers vouched for are trusted
+pub(crate) fn creator(
+    state: &AppState,
+    headers: &HeaderMap,
+    extensions: &Extensions,
+    remote_addr: Option<SocketAddr>,
+) -> Creator {
+    if vouched_for(state, headers, extensions) {
+        return Creator::Trusted;
+    }
+    let client = client_ip(headers, remote_addr, &state.trusted_proxies)
+        .map_or_else(|| "unknown".to_string(), |ip| ip.to_string());
+    Creator::Anonymous {
+        client,
+        headers: headers.clone(),
+    }
+}
+
+// Comparing digests keeps the comparison time independent of the token
+fn bearer_matches(headers: &HeaderMap, expected: &[u8]) -> bool {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|token| Sha256::digest(token).as_slice() == expected)
+}
+
+// Only let requests carrying `Authorization: Bearer <ADMIN_TOKEN>` through to admin endpoints
+pub async fn require_admin(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(expected) = &state.admin_token_digest else {
+        error!("Admin API called without ADMIN_TOKEN configured");
+        return AppError::AdminDisabled.into_response();
+    };
+
+    if !bearer_matches(request.headers(), expected) {
+        warn!("Unauthorized admin request");
+        return AppError::Unauthorized("Missing or invalid admin token").into_response();
+    }
+
+    next.run(request).await
+}
@@ -1,2 +1,4 @@
-mod handlers;
+pub(crate) mod handlers;
+pub(crate) mod middleware;
+pub(crate) mod openapi;
 pub mod routes;
@@ -0,0 +1,113 @@
+use std::sync::LazyLock;
+
+use utoipa::{
+    openapi::{
+        security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
+        Content, RefOr, Response, ResponseBuilder,
+    },
+    Modify, OpenApi, PartialSchema,
+};
+
+use crate::error::{Problem, PROBLEM_JSON};
+
+use super::handlers;
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "tlong",
+        description = "URL shortener API",
+        license(name = "MIT", identifier = "MIT")
+    ),
+    paths(
+        handlers::health_check,
+        handlers::get_version,
+        handlers::create_short_url,
+        handlers::get_all_short_url,
+        handlers::get_short_url_details,
+        handlers::update_short_url,
+        handlers::delete_short_url,
+        handlers::get_short_url_badge,
+        handlers::get_short_url_qr,
+        handlers::get_short_url_preview,
+        handlers::expand_short_url,
+        handlers::expand_short_code,
+        handlers::get_all_tags,
+        handlers::put_external_link,
+        handlers::delete_external_link,
+        handlers::create_campaign,
+        handlers::get_all_campaigns,
+        handlers::delete_campaign,
+        handlers::add_campaign_links,
+        handlers::remove_campaign_link,
+        handlers::get_campaign_stats,
+        handlers::add_blocked_domain,
+        handlers::get_blocked_domains,
+        handlers::remove_blocked_domain,
+        handlers::get_reviews,
+        handlers::approve_review,
+        handlers::reject_review,
+        handlers::get_cache_stats,
+        handlers::create_webhook,
+        handlers::get_webhooks,
+        handlers::delete_webhook,
+        handlers::get_webhook_deliveries,
+        handlers::stream_clicks,
+        handlers::dashboard_feed,
+    ),
+    components(schemas(Problem)),
+    modifiers(&ProblemResponses, &AdminToken),
+    tags(
+        (name = "links", description = "Short links and their statistics"),
+        (name = "external links", description = "Links kept under IDs chosen by the caller"),
+        (name = "campaigns", description = "Groups of links with combined statistics"),
+        (name = "admin", description = "Operator endpoints, enabled by ADMIN_TOKEN"),
+        (name = "service", description = "Health and build details"),
+    )
+)]
+pub struct ApiDoc;
+
+// Generated once, the spec only changes with the binary
+pub static SPEC: LazyLock<utoipa::openapi::OpenApi> = LazyLock::new(ApiDoc::openapi);
+
+// Every operation may fail with a problem details body (rate limiting, database outages,
+// unreadable bodies), so those are documented once instead of on each handler
+struct ProblemResponses;
+
+impl Modify for ProblemResponses {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let problem: Response = ResponseBuilder::new()
+            .description("Problem details describing why the request failed")
+            .content(PROBLEM_JSON, Content::new(Some(Problem::schema())))
+            .build();
+        for item in openapi.paths.paths.values_mut() {
+            let operations = [
+                &mut item.get,
+                &mut item.put,
+                &mut item.post,
+                &mut item.delete,
+                &mut item.patch,
+            ];
+            for operation in operations.into_iter().flatten() {
+                operation
+                    .responses
+                    .responses
+                    .entry("default".to_string())
+                    .or_insert_with(|| RefOr::T(problem.clone()));
+            }
+        }
+    }
+}
+
+// Admin endpoints expect `Authorization: Bearer <ADMIN_TOKEN>`
+struct AdminToken;
+
+impl Modify for AdminToken {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "admin_token",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+        );
+    }
+}
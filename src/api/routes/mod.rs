@@ -0,0 +1,568 @@
+use axum::{
+    http::HeaderName,
+    middleware::{from_fn, from_fn_with_state},
+    routing::get,
+    Router,
+};
+use tower_http::{
+    compression::CompressionLayer,
+    cors::CorsLayer,
+    limit::RequestBodyLimitLayer,
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
+    timeout::TimeoutLayer,
+    trace::{DefaultOnFailure, DefaultOnResponse, TraceLayer},
+    LatencyUnit,
+};
+use tracing::Level;
+
+use crate::{metrics, state::AppState, telemetry};
+
+use super::{handlers, middleware};
+
+mod v1;
+mod v2;
+
+pub fn router(state: AppState) -> Router {
+    let site = Router::new()
+        .route("/", get(handlers::landing_page))
+        .route("/robots.txt", get(handlers::robots_txt))
+        .route("/favicon.ico", get(handlers::favicon))
+        .route("/docs", get(handlers::api_docs))
+        .route(
+            "/api/graphql",
+            get(handlers::graphiql).post(handlers::graphql),
+        )
+        .route("/{short_code}", get(handlers::handle_short_url))
+        .route(
+            "/{short_code}/{*path}",
+            get(handlers::handle_short_url_path),
+        )
+        .route("/x/{external_id}", get(handlers::handle_external_link));
+
+    // v2 wraps every JSON answer in an envelope, including the errors of the request
+    // guards, so the envelope sits outside them
+    let router = guarded(site.merge(v1::routes(&state)), &state)
+        .merge(guarded(v2::routes(&state), &state).layer(from_fn(middleware::envelope)));
+
+    let router = router
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(telemetry::make_span)
+                .on_response(
+                    DefaultOnResponse::new()
+                        .latency_unit(LatencyUnit::Millis)
+                        .level(Level::DEBUG),
+                )
+                .on_failure(DefaultOnFailure::new().level(Level::ERROR)),
+        )
+        .layer(TimeoutLayer::new(state.request_timeout))
+        .layer(RequestBodyLimitLayer::new(state.request_body_limit))
+        .layer(CorsLayer::permissive())
+        .layer(CompressionLayer::new())
+        .layer(from_fn_with_state(state.clone(), middleware::access_log))
+        // Reuse the caller's X-Request-Id or assign one, and echo it in the response
+        .layer(PropagateRequestIdLayer::new(HeaderName::from_static(
+            middleware::REQUEST_ID_HEADER,
+        )))
+        .layer(SetRequestIdLayer::new(
+            HeaderName::from_static(middleware::REQUEST_ID_HEADER),
+            MakeRequestUuid,
+        ));
+
+    let router = if state.metrics.is_some() {
+        router.route_layer(from_fn(metrics::track_requests))
+    } else {
+        router
+    };
+
+    router.with_state(state)
+}
+
+// Replay protection, database outage and rate limiting checks that every route goes
+// through, with the request id added to their error bodies
+fn guarded(router: Router<AppState>, state: &AppState) -> Router<AppState> {
+    router
+        .layer(from_fn_with_state(
+            state.clone(),
+            middleware::replay_protection,
+        ))
+        .layer(from_fn(middleware::database_unavailable))
+        .layer(from_fn_with_state(state.clone(), middleware::rate_limit))
+        .layer(from_fn(middleware::request_id_in_errors))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{Method, StatusCode};
+    use chrono::{Duration, Utc};
+    use redis::AsyncCommands;
+    use serde_json::json;
+
+    use crate::{
+        abuse::AbuseAction,
+        cache::{self, stats::CacheCounts},
+        cli,
+        testkit::{unique, Link, TestApp, BASE_URL},
+    };
+
+    // These need Postgres and Redis, see `crate::testkit`, so they run with
+    // `cargo test -- --ignored`
+
+    #[tokio::test]
+    #[ignore = "needs Docker, or TEST_DATABASE_URL and TEST_REDIS_URL"]
+    async fn created_link_redirects_to_its_destination() {
+        let app = TestApp::spawn().await;
+        let builder = app.link().tags(&["docs"]);
+        let body = builder.body();
+        let link = builder.create().await;
+        assert_eq!(link.long_url, body["long_url"]);
+
+        let response = app.get(&link.path()).send().await;
+        assert!(response.status.is_redirection(), "{}", response.status);
+        assert_eq!(response.location(), Some(link.long_url.as_str()));
+
+        let details = app
+            .get(&format!("/api/v1/{}", link.short_code))
+            .send()
+            .await;
+        assert_eq!(details.status, StatusCode::OK);
+        let details = details.json();
+        assert_eq!(
+            details["short_url"],
+            format!("{BASE_URL}/{}", link.short_code)
+        );
+        assert_eq!(details["tags"], json!(["docs"]));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Docker, or TEST_DATABASE_URL and TEST_REDIS_URL"]
+    async fn creating_a_link_twice_reuses_its_code() {
+        let app = TestApp::spawn().await;
+        let first = app.link().create().await;
+        let second = app.link().long_url(&first.long_url).create().await;
+        assert_eq!(first.short_code, second.short_code);
+
+        let invalid = app
+            .post("/api/v1/shorten")
+            .json(&json!({"long_url": "not a url"}))
+            .send()
+            .await;
+        assert_eq!(invalid.status, StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Docker, or TEST_DATABASE_URL and TEST_REDIS_URL"]
+    async fn deleted_link_stops_redirecting_and_leaves_the_cache() {
+        let app = TestApp::spawn().await;
+        let link = app.link().create().await;
+        let response = app.get(&link.path()).send().await;
+        assert!(response.status.is_redirection());
+
+        let mut redis_conn = app.state.redis_db.clone();
+        let cached: Option<String> = redis_conn.get(&link.short_code).await.unwrap();
+        assert_eq!(cached.as_deref(), Some(link.long_url.as_str()));
+
+        let deleted = app
+            .delete(&format!("/api/v1/{}", link.short_code))
+            .send()
+            .await;
+        assert_eq!(deleted.status, StatusCode::OK);
+        let cached: Option<String> = redis_conn.get(&link.short_code).await.unwrap();
+        assert_eq!(cached, None);
+
+        // Gone from the local cache too, and then remembered not to exist
+        let response = app.get(&link.path()).send().await;
+        assert_eq!(response.status, StatusCode::NOT_FOUND);
+        assert!(cache::is_missing(&mut redis_conn, &link.short_code)
+            .await
+            .unwrap());
+        let before = app.state.cache_stats.counts();
+        let response = app.get(&link.path()).send().await;
+        assert_eq!(response.status, StatusCode::NOT_FOUND);
+        assert_eq!(
+            app.state.cache_stats.counts().since(&before).negative_hits,
+            1
+        );
+
+        let deleted = app
+            .delete(&format!("/api/v1/{}", link.short_code))
+            .send()
+            .await;
+        assert_eq!(deleted.status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Docker, or TEST_DATABASE_URL and TEST_REDIS_URL"]
+    async fn single_use_link_expires_after_its_first_click() {
+        let app = TestApp::spawn().await;
+        let link = app.link().single_use().create().await;
+
+        let response = app.get(&link.path()).send().await;
+        assert!(response.status.is_redirection());
+        assert_eq!(response.location(), Some(link.long_url.as_str()));
+
+        let response = app.get(&link.path()).send().await;
+        assert_eq!(response.status, StatusCode::GONE);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Docker, or TEST_DATABASE_URL and TEST_REDIS_URL"]
+    async fn link_redirects_only_once_active() {
+        let app = TestApp::spawn().await;
+        let link = app
+            .link()
+            .activates_at(Utc::now() + Duration::days(1))
+            .create()
+            .await;
+        let response = app.get(&link.path()).send().await;
+        assert_eq!(response.status, StatusCode::NOT_FOUND);
+        assert_eq!(response.location(), None);
+
+        let link = app
+            .link()
+            .activates_at(Utc::now() - Duration::minutes(1))
+            .create()
+            .await;
+        let response = app.get(&link.path()).send().await;
+        assert!(response.status.is_redirection());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Docker, or TEST_DATABASE_URL and TEST_REDIS_URL"]
+    async fn cleanup_deletes_links_disabled_long_enough_ago() {
+        let app = TestApp::spawn().await;
+        let expired = app.link().create().await;
+        let recent = app.link().create().await;
+        app.disable(&expired.short_code, Utc::now() - Duration::days(40))
+            .await;
+        app.disable(&recent.short_code, Utc::now() - Duration::days(1))
+            .await;
+
+        let deleted = cli::cleanup_expired(&app.state, 30).await.unwrap();
+        assert!(deleted >= 1);
+        let response = app.get(&expired.path()).send().await;
+        assert_eq!(response.status, StatusCode::NOT_FOUND);
+        let response = app.get(&recent.path()).send().await;
+        assert_eq!(response.status, StatusCode::GONE);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Docker, or TEST_DATABASE_URL and TEST_REDIS_URL"]
+    async fn repeated_redirects_are_served_from_the_caches() {
+        let app = TestApp::spawn().await;
+        let link = app.link().create().await;
+        let lookups = |before: &CacheCounts| app.state.cache_stats.counts().since(before);
+
+        // Creating the link already cached it in Redis, but not in this instance's memory
+        let before = app.state.cache_stats.counts();
+        let response = app.get(&link.path()).send().await;
+        assert_eq!(response.location(), Some(link.long_url.as_str()));
+        assert_eq!(lookups(&before).hits, 1);
+
+        let before = app.state.cache_stats.counts();
+        let response = app.get(&link.path()).send().await;
+        assert_eq!(response.location(), Some(link.long_url.as_str()));
+        assert_eq!(lookups(&before).local_hits, 1);
+
+        // Once evicted, one redirect reads Postgres and caches the link again
+        app.evict(&link.short_code).await;
+        let before = app.state.cache_stats.counts();
+        let response = app.get(&link.path()).send().await;
+        assert_eq!(response.location(), Some(link.long_url.as_str()));
+        assert_eq!(lookups(&before).misses, 1);
+
+        let before = app.state.cache_stats.counts();
+        app.get(&link.path()).send().await;
+        assert_eq!(lookups(&before).local_hits, 1);
+        assert_eq!(lookups(&before).misses, 0);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Docker, or TEST_DATABASE_URL and TEST_REDIS_URL"]
+    async fn admin_endpoints_need_the_admin_token() {
+        let app = TestApp::spawn().await;
+        let response = app.get("/api/v1/admin/cache/stats").send().await;
+        assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+        let response = app.get("/api/v1/admin/cache/stats").admin().send().await;
+        assert_eq!(response.status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Docker, or TEST_DATABASE_URL and TEST_REDIS_URL"]
+    async fn signed_creation_is_accepted_once() {
+        let app = TestApp::spawn().await;
+        let request = app
+            .post("/api/v1/shorten")
+            .json(&app.link().body())
+            .signed();
+        let response = request.clone().send().await;
+        assert_eq!(response.status, StatusCode::CREATED);
+        let response = request.send().await;
+        assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Docker, or TEST_DATABASE_URL and TEST_REDIS_URL"]
+    async fn taken_external_ids_change_only_for_trusted_callers() {
+        let app = TestApp::spawn().await;
+        let path = format!("/api/v1/x/{}", unique("order"));
+        let first = json!({ "long_url": "https://example.com/first" });
+        let second = json!({ "long_url": "https://example.com/second" });
+
+        let response = app.request(Method::PUT, &path).json(&first).send().await;
+        assert_eq!(response.status, StatusCode::CREATED);
+        let response = app.request(Method::PUT, &path).json(&second).send().await;
+        assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+        let response = app.delete(&path).send().await;
+        assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+
+        let response = app
+            .request(Method::PUT, &path)
+            .json(&second)
+            .admin()
+            .send()
+            .await;
+        assert_eq!(response.status, StatusCode::OK);
+        let response = app.delete(&path).signed().send().await;
+        assert_eq!(response.status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Docker, or TEST_DATABASE_URL and TEST_REDIS_URL"]
+    async fn external_links_need_a_signature_when_required() {
+        let app = TestApp::spawn_with(|state| state.require_signed_creation = true).await;
+        let path = format!("/api/v1/x/{}", unique("order"));
+        let body = json!({ "long_url": "https://example.com/signed" });
+
+        let response = app.request(Method::PUT, &path).json(&body).send().await;
+        assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+        let response = app
+            .request(Method::PUT, &path)
+            .json(&body)
+            .signed()
+            .send()
+            .await;
+        assert_eq!(response.status, StatusCode::CREATED);
+        let response = app.delete(&path).admin().send().await;
+        assert_eq!(response.status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Docker, or TEST_DATABASE_URL and TEST_REDIS_URL"]
+    async fn external_link_disabled_by_the_recheck_is_gone_until_repointed() {
+        let app = TestApp::spawn().await;
+        let external_id = unique("order");
+        let path = format!("/api/v1/x/{external_id}");
+        let response = app
+            .request(Method::PUT, &path)
+            .json(&json!({ "long_url": "https://example.com/unsafe" }))
+            .send()
+            .await;
+        assert_eq!(response.status, StatusCode::CREATED);
+
+        // What the Safe Browsing re-check does to a destination that turned unsafe
+        sqlx::query!(
+            "UPDATE external_links SET disabled_at = CURRENT_TIMESTAMP WHERE external_id = $1",
+            external_id
+        )
+        .execute(&app.state.pg_db)
+        .await
+        .expect("external link is disabled");
+        let response = app.get(&format!("/x/{external_id}")).send().await;
+        assert_eq!(response.status, StatusCode::GONE);
+
+        let response = app
+            .request(Method::PUT, &path)
+            .json(&json!({ "long_url": "https://example.com/safe" }))
+            .admin()
+            .send()
+            .await;
+        assert_eq!(response.status, StatusCode::OK);
+        let response = app.get(&format!("/x/{external_id}")).send().await;
+        assert_eq!(response.location(), Some("https://example.com/safe"));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Docker, or TEST_DATABASE_URL and TEST_REDIS_URL"]
+    async fn every_graphql_mutation_is_scored_for_abuse() {
+        // Requests without User-Agent and Accept headers score 40
+        let app = TestApp::spawn_with(|state| {
+            state.abuse_action = AbuseAction::Queue;
+            state.abuse_threshold = 40;
+        })
+        .await;
+        let query = format!(
+            r#"mutation {{
+                first: shorten(input: {{ longUrl: "https://example.com/{}" }}) {{ shortCode }}
+                second: shorten(input: {{ longUrl: "https://example.com/{}" }}) {{ shortCode }}
+            }}"#,
+            unique("first"),
+            unique("second")
+        );
+        let response = app
+            .post("/api/graphql")
+            .json(&json!({ "query": query }))
+            .send()
+            .await;
+        let errors = response.json()["errors"].clone();
+        let codes: Vec<_> = errors
+            .as_array()
+            .expect("errors")
+            .iter()
+            .map(|error| error["extensions"]["code"].clone())
+            .collect();
+        assert_eq!(codes, [json!("held_for_review"), json!("held_for_review")]);
+
+        let response = app
+            .post("/api/v1/shorten")
+            .json(&app.link().body())
+            .send()
+            .await;
+        assert_eq!(response.status, StatusCode::ACCEPTED);
+        let response = app
+            .post("/api/v1/shorten")
+            .json(&app.link().body())
+            .admin()
+            .send()
+            .await;
+        assert_eq!(response.status, StatusCode::CREATED);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Docker, or TEST_DATABASE_URL and TEST_REDIS_URL"]
+    async fn held_creations_are_created_once_approved() {
+        let app = TestApp::spawn_with(|state| {
+            state.abuse_action = AbuseAction::Queue;
+            state.abuse_threshold = 40;
+        })
+        .await;
+        let link = app.link().tags(&["held"]);
+        let response = app.post("/api/v1/shorten").json(&link.body()).send().await;
+        assert_eq!(response.status, StatusCode::ACCEPTED);
+        let external_id = unique("order");
+        let response = app
+            .request(Method::PUT, &format!("/api/v1/x/{external_id}"))
+            .json(&json!({ "long_url": "https://example.com/held" }))
+            .send()
+            .await;
+        assert_eq!(response.status, StatusCode::ACCEPTED);
+
+        let response = app.get("/api/v1/admin/reviews").send().await;
+        assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+        let reviews = app.get("/api/v1/admin/reviews").admin().send().await.json();
+        let review_id = |long_url: &str| {
+            reviews
+                .as_array()
+                .expect("reviews")
+                .iter()
+                .find(|review| review["long_url"] == long_url)
+                .map(|review| review["id"].clone())
+                .expect("creation is held")
+        };
+        let link_review = review_id(link.body()["long_url"].as_str().expect("long URL"));
+        let external_review = review_id("https://example.com/held");
+
+        let response = app
+            .post(&format!("/api/v1/admin/reviews/{link_review}/approve"))
+            .admin()
+            .send()
+            .await;
+        assert_eq!(response.status, StatusCode::CREATED);
+        let created = response.json();
+        assert_eq!(created["tags"], json!(["held"]));
+        let response = app
+            .get(&format!(
+                "/{}",
+                created["short_code"].as_str().expect("code")
+            ))
+            .send()
+            .await;
+        assert_eq!(response.location(), link.body()["long_url"].as_str());
+
+        let response = app
+            .post(&format!("/api/v1/admin/reviews/{external_review}/reject"))
+            .admin()
+            .send()
+            .await;
+        assert_eq!(response.status, StatusCode::OK);
+        let response = app
+            .post(&format!("/api/v1/admin/reviews/{external_review}/approve"))
+            .admin()
+            .send()
+            .await;
+        assert_eq!(response.status, StatusCode::NOT_FOUND);
+        let response = app.get(&format!("/x/{external_id}")).send().await;
+        assert_eq!(response.status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Docker, or TEST_DATABASE_URL and TEST_REDIS_URL"]
+    async fn shadow_banned_creations_get_random_unused_codes() {
+        let app = TestApp::spawn_with(|state| {
+            state.abuse_action = AbuseAction::ShadowBan;
+            state.abuse_threshold = 40;
+        })
+        .await;
+        let body = app.link().body();
+        let mut codes = Vec::new();
+        for _ in 0..2 {
+            let response = app.post("/api/v1/shorten").json(&body).send().await;
+            assert_eq!(response.status, StatusCode::CREATED);
+            let short_code = response.json()["short_code"]
+                .as_str()
+                .expect("short code")
+                .to_string();
+            assert_eq!(short_code.len(), 8);
+            let response = app.get(&format!("/{short_code}")).send().await;
+            assert_eq!(response.status, StatusCode::NOT_FOUND);
+            codes.push(short_code);
+        }
+        assert_ne!(codes[0], codes[1]);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Docker, or TEST_DATABASE_URL and TEST_REDIS_URL"]
+    async fn badge_is_alive_only_while_the_link_redirects() {
+        let app = TestApp::spawn().await;
+        let badge = |link: &Link| app.get(&format!("/api/v1/{}/badge.svg", link.short_code));
+
+        let link = app.link().create().await;
+        let response = badge(&link).send().await;
+        assert!(String::from_utf8_lossy(&response.body).contains(">alive<"));
+
+        app.disable(&link.short_code, Utc::now()).await;
+        let response = badge(&link).send().await;
+        assert!(String::from_utf8_lossy(&response.body).contains(">dead<"));
+
+        let scheduled = app
+            .link()
+            .activates_at(Utc::now() + Duration::hours(1))
+            .create()
+            .await;
+        let response = badge(&scheduled).send().await;
+        assert!(String::from_utf8_lossy(&response.body).contains(">dead<"));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Docker, or TEST_DATABASE_URL and TEST_REDIS_URL"]
+    async fn failed_previews_are_cached() {
+        let app = TestApp::spawn().await;
+        // The .invalid top-level domain never resolves
+        let link = app
+            .link()
+            .long_url(&format!("https://{}.invalid/", unique("preview")))
+            .create()
+            .await;
+        let path = format!("/api/v1/{}/preview", link.short_code);
+
+        let response = app.get(&path).send().await;
+        assert_eq!(response.status, StatusCode::BAD_GATEWAY);
+        let failure: Option<String> =
+            cache::failed_preview(&mut app.state.redis_db.clone(), &link.short_code)
+                .await
+                .expect("Redis is reachable");
+        assert!(failure.is_some());
+        let response = app.get(&path).send().await;
+        assert_eq!(response.status, StatusCode::BAD_GATEWAY);
+    }
+}
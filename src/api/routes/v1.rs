@@ -0,0 +1,151 @@
+use axum::{
+    middleware::{from_fn, from_fn_with_state},
+    routing::{delete, get, post, put},
+    Router,
+};
+
+use crate::{
+    api::{handlers, middleware},
+    state::AppState,
+};
+
+pub fn routes(state: &AppState) -> Router<AppState> {
+    Router::new()
+        .route("/api/v1/health", get(handlers::health_check))
+        .route("/api/v1/version", get(handlers::get_version))
+        .route("/api/v1/openapi.json", get(handlers::openapi_spec))
+        .route(
+            "/api/v1/shorten",
+            post(handlers::create_short_url)
+                .layer(from_fn_with_state(state.clone(), middleware::idempotency))
+                .layer(from_fn_with_state(
+                    state.clone(),
+                    middleware::verify_signature,
+                )),
+        )
+        .route(
+            "/api/v1/shorten",
+            get(handlers::get_all_short_url)
+                .layer(from_fn_with_state(
+                    state.clone(),
+                    middleware::cache_response,
+                ))
+                .layer(from_fn(middleware::etag)),
+        )
+        .route(
+            "/api/v1/x/{external_id}",
+            put(handlers::put_external_link)
+                .layer(from_fn_with_state(state.clone(), middleware::idempotency))
+                .layer(from_fn_with_state(
+                    state.clone(),
+                    middleware::verify_signature,
+                )),
+        )
+        .route(
+            "/api/v1/x/{external_id}",
+            delete(handlers::delete_external_link).layer(from_fn_with_state(
+                state.clone(),
+                middleware::verify_signature,
+            )),
+        )
+        .route("/api/v1/tags", get(handlers::get_all_tags))
+        .route(
+            "/api/v1/campaigns",
+            post(handlers::create_campaign).get(handlers::get_all_campaigns),
+        )
+        .route("/api/v1/campaigns/{id}", delete(handlers::delete_campaign))
+        .route(
+            "/api/v1/campaigns/{id}/links",
+            put(handlers::add_campaign_links),
+        )
+        .route(
+            "/api/v1/campaigns/{id}/links/{short_code}",
+            delete(handlers::remove_campaign_link),
+        )
+        .route(
+            "/api/v1/campaigns/{id}/stats",
+            get(handlers::get_campaign_stats),
+        )
+        .route(
+            "/api/v1/admin/blocklist",
+            post(handlers::add_blocked_domain)
+                .get(handlers::get_blocked_domains)
+                .layer(from_fn_with_state(state.clone(), middleware::require_admin)),
+        )
+        .route(
+            "/api/v1/admin/blocklist/{domain}",
+            delete(handlers::remove_blocked_domain)
+                .layer(from_fn_with_state(state.clone(), middleware::require_admin)),
+        )
+        .route(
+            "/api/v1/admin/reviews",
+            get(handlers::get_reviews)
+                .layer(from_fn_with_state(state.clone(), middleware::require_admin)),
+        )
+        .route(
+            "/api/v1/admin/reviews/{id}/approve",
+            post(handlers::approve_review)
+                .layer(from_fn_with_state(state.clone(), middleware::require_admin)),
+        )
+        .route(
+            "/api/v1/admin/reviews/{id}/reject",
+            post(handlers::reject_review)
+                .layer(from_fn_with_state(state.clone(), middleware::require_admin)),
+        )
+        .route(
+            "/api/v1/admin/cache/stats",
+            get(handlers::get_cache_stats)
+                .layer(from_fn_with_state(state.clone(), middleware::require_admin)),
+        )
+        .route(
+            "/api/v1/admin/webhooks",
+            post(handlers::create_webhook)
+                .get(handlers::get_webhooks)
+                .layer(from_fn_with_state(state.clone(), middleware::require_admin)),
+        )
+        .route(
+            "/api/v1/admin/webhooks/{id}",
+            delete(handlers::delete_webhook)
+                .layer(from_fn_with_state(state.clone(), middleware::require_admin)),
+        )
+        .route(
+            "/api/v1/admin/webhooks/{id}/deliveries",
+            get(handlers::get_webhook_deliveries)
+                .layer(from_fn_with_state(state.clone(), middleware::require_admin)),
+        )
+        .route(
+            "/api/v1/events/clicks",
+            get(handlers::stream_clicks)
+                .layer(from_fn_with_state(state.clone(), middleware::require_admin)),
+        )
+        .route(
+            "/api/v1/ws",
+            get(handlers::dashboard_feed)
+                .layer(from_fn_with_state(state.clone(), middleware::require_admin)),
+        )
+        .route("/api/v1/expand", get(handlers::expand_short_url))
+        .route(
+            "/api/v1/expand/{short_code}",
+            get(handlers::expand_short_code),
+        )
+        .route("/api/v1/{short_code}", delete(handlers::delete_short_url))
+        .route(
+            "/api/v1/{short_code}",
+            get(handlers::get_short_url_details)
+                .layer(from_fn_with_state(
+                    state.clone(),
+                    middleware::cache_response,
+                ))
+                .layer(from_fn(middleware::etag))
+                .patch(handlers::update_short_url),
+        )
+        .route(
+            "/api/v1/{short_code}/badge.svg",
+            get(handlers::get_short_url_badge),
+        )
+        .route("/api/v1/{short_code}/qr", get(handlers::get_short_url_qr))
+        .route(
+            "/api/v1/{short_code}/preview",
+            get(handlers::get_short_url_preview),
+        )
+}
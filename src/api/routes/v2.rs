@@ -0,0 +1,129 @@
+use axum::{
+    middleware::{from_fn, from_fn_with_state},
+    routing::{delete, get, post, put},
+    Router,
+};
+
+use crate::{
+    api::{handlers, middleware},
+    state::AppState,
+};
+
+// The JSON endpoints of v1 under the same paths, answered inside an envelope and with
+// paginated lists. Images and streams are only served by v1
+pub fn routes(state: &AppState) -> Router<AppState> {
+    Router::new()
+        .route("/api/v2/health", get(handlers::health_check))
+        .route("/api/v2/version", get(handlers::get_version))
+        .route(
+            "/api/v2/shorten",
+            post(handlers::create_short_url)
+                .layer(from_fn_with_state(state.clone(), middleware::idempotency))
+                .layer(from_fn_with_state(
+                    state.clone(),
+                    middleware::verify_signature,
+                ))
+                .get(handlers::list_short_urls),
+        )
+        .route(
+            "/api/v2/x/{external_id}",
+            put(handlers::put_external_link)
+                .layer(from_fn_with_state(state.clone(), middleware::idempotency))
+                .layer(from_fn_with_state(
+                    state.clone(),
+                    middleware::verify_signature,
+                )),
+        )
+        .route(
+            "/api/v2/x/{external_id}",
+            delete(handlers::delete_external_link).layer(from_fn_with_state(
+                state.clone(),
+                middleware::verify_signature,
+            )),
+        )
+        .route("/api/v2/tags", get(handlers::list_tags))
+        .route(
+            "/api/v2/campaigns",
+            post(handlers::create_campaign).get(handlers::list_campaigns),
+        )
+        .route("/api/v2/campaigns/{id}", delete(handlers::delete_campaign))
+        .route(
+            "/api/v2/campaigns/{id}/links",
+            put(handlers::add_campaign_links),
+        )
+        .route(
+            "/api/v2/campaigns/{id}/links/{short_code}",
+            delete(handlers::remove_campaign_link),
+        )
+        .route(
+            "/api/v2/campaigns/{id}/stats",
+            get(handlers::get_campaign_stats),
+        )
+        .route(
+            "/api/v2/admin/blocklist",
+            post(handlers::add_blocked_domain)
+                .get(handlers::list_blocked_domains)
+                .layer(from_fn_with_state(state.clone(), middleware::require_admin)),
+        )
+        .route(
+            "/api/v2/admin/blocklist/{domain}",
+            delete(handlers::remove_blocked_domain)
+                .layer(from_fn_with_state(state.clone(), middleware::require_admin)),
+        )
+        .route(
+            "/api/v2/admin/reviews",
+            get(handlers::list_reviews)
+                .layer(from_fn_with_state(state.clone(), middleware::require_admin)),
+        )
+        .route(
+            "/api/v2/admin/reviews/{id}/approve",
+            post(handlers::approve_review)
+                .layer(from_fn_with_state(state.clone(), middleware::require_admin)),
+        )
+        .route(
+            "/api/v2/admin/reviews/{id}/reject",
+            post(handlers::reject_review)
+                .layer(from_fn_with_state(state.clone(), middleware::require_admin)),
+        )
+        .route(
+            "/api/v2/admin/cache/stats",
+            get(handlers::get_cache_stats)
+                .layer(from_fn_with_state(state.clone(), middleware::require_admin)),
+        )
+        .route(
+            "/api/v2/admin/webhooks",
+            post(handlers::create_webhook)
+                .get(handlers::list_webhooks)
+                .layer(from_fn_with_state(state.clone(), middleware::require_admin)),
+        )
+        .route(
+            "/api/v2/admin/webhooks/{id}",
+            delete(handlers::delete_webhook)
+                .layer(from_fn_with_state(state.clone(), middleware::require_admin)),
+        )
+        .route(
+            "/api/v2/admin/webhooks/{id}/deliveries",
+            get(handlers::get_webhook_deliveries)
+                .layer(from_fn_with_state(state.clone(), middleware::require_admin)),
+        )
+        .route("/api/v2/expand", get(handlers::expand_short_url))
+        .route(
+            "/api/v2/expand/{short_code}",
+            get(handlers::expand_short_code),
+        )
+        .route(
+            "/api/v2/{short_code}",
+            get(handlers::get_short_url_details)
+                .layer(from_fn_with_state(
+                    state.clone(),
+                    middleware::cache_response,
+                ))
+                .layer(from_fn(middleware::etag))
+                .patch(handlers::update_short_url)
+                .delete(handlers::delete_short_url),
+        )
+        .route(
+            "/api/v2/{short_code}/preview",
+            get(handlers::get_short_url_preview),
+        )
+}
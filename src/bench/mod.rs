@@ -0,0 +1,333 @@
+use std::{
+    fmt,
+    net::SocketAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{ConnectInfo, Path, RawQuery, State},
+    http::{HeaderMap, Method},
+    response::IntoResponse,
+};
+use tokio::task::JoinSet;
+use tracing::{error, info};
+
+use crate::{api, api::handlers, cache, state::AppState, utils::encode_long_url};
+
+// Host of the destinations of seeded links, never resolvable
+const SEED_HOST: &str = "https://bench.tlong.invalid";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Target {
+    // Call the redirect handler directly, skipping the HTTP stack
+    Service,
+    // Send real requests to a server bound on a local port
+    Http,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Benchmark {
+    /// Resolve seeded links through the redirect handler
+    Resolve,
+}
+
+#[derive(Debug, Clone, clap::Args)]
+pub struct Options {
+    #[arg(value_enum)]
+    pub benchmark: Benchmark,
+    /// Concurrent workers
+    #[arg(long, default_value_t = 64, value_parser = parse_count)]
+    pub connections: usize,
+    /// How long each tier runs, like `500ms`, `30s` or `2m`
+    #[arg(long, default_value = "10s", value_parser = parse_duration)]
+    pub duration: Duration,
+    /// Throwaway links seeded for the run
+    #[arg(long, default_value_t = 1000, value_parser = parse_count)]
+    pub links: usize,
+    /// Send requests to a server on a local port instead of calling the handler
+    #[arg(long)]
+    pub http: bool,
+}
+
+impl Options {
+    fn target(&self) -> Target {
+        if self.http {
+            Target::Http
+        } else {
+            Target::Service
+        }
+    }
+}
+
+fn parse_count(value: &str) -> Result<usize, String> {
+    value
+        .parse()
+        .ok()
+        .filter(|count| *count > 0)
+        .ok_or_else(|| format!("expected a positive number, got {value}"))
+}
+
+// Durations like `500ms`, `30s` or `2m`; bare numbers are seconds
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    let invalid = || format!("expected a duration like 30s, got {value}");
+    let (number, unit) = match value.find(|c: char| !c.is_ascii_digit()) {
+        Some(index) => value.split_at(index),
+        None => (value, "s"),
+    };
+    let number: u64 = number.parse().map_err(|_| invalid())?;
+    let duration = match unit {
+        "ms" => Duration::from_millis(number),
+        "s" => Duration::from_secs(number),
+        "m" => Duration::from_secs(number * 60),
+        _ => return Err(invalid()),
+    };
+    (!duration.is_zero())
+        .then_some(duration)
+        .ok_or_else(invalid)
+}
+
+// Whether requests are answered from Redis or fall through to Postgres
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Tier {
+    Redis,
+    Database,
+}
+
+impl fmt::Display for Tier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Tier::Redis => f.pad("redis"),
+            Tier::Database => f.pad("database"),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Samples {
+    latencies: Vec<Duration>,
+    errors: usize,
+}
+
+enum Driver {
+    Service(Box<AppState>),
+    Http {
+        client: reqwest::Client,
+        base_url: String,
+    },
+}
+
+impl Driver {
+    // Resolve one short code, returning whether a redirect was served
+    async fn resolve(&self, short_code: &str) -> bool {
+        match self {
+            Driver::Service(state) => handlers::handle_short_url(
+                State(state.as_ref().clone()),
+                Path(short_code.to_string()),
+                RawQuery(None),
+                ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 0))),
+                Method::GET,
+                HeaderMap::new(),
+            )
+            .await
+            .into_response()
+            .status()
+            .is_redirection(),
+            Driver::Http { client, base_url } => client
+                .get(format!("{base_url}/{short_code}"))
+                .send()
+                .await
+                .is_ok_and(|response| response.status().is_redirection()),
+        }
+    }
+}
+
+// Benchmark the redirect path against seeded links and print a report
+pub async fn run(state: AppState, options: Options) -> Result<(), String> {
+    let short_codes = seed(&state, options.links).await?;
+    info!(links = short_codes.len(), "Seeded benchmark links");
+
+    let result = drive(&state, &options, short_codes.clone()).await;
+
+    if let Err(e) = sqlx::query!("DELETE FROM urls WHERE short_code = ANY($1)", &short_codes)
+        .execute(&state.pg_db)
+        .await
+    {
+        error!(error = %e, "Failed to remove benchmark links");
+    }
+    evict(&state, &short_codes).await;
+
+    result
+}
+
+async fn drive(
+    state: &AppState,
+    options: &Options,
+    short_codes: Vec<String>,
+) -> Result<(), String> {
+    let driver = match options.target() {
+        Target::Service => Driver::Service(Box::new(state.clone())),
+        Target::Http => {
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+                .await
+                .map_err(|e| format!("Failed to bind benchmark server: {e}"))?;
+            let addr = listener
+                .local_addr()
+                .map_err(|e| format!("Failed to read benchmark server address: {e}"))?;
+            let app = api::routes::router(state.clone());
+            tokio::spawn(async move {
+                axum::serve(
+                    listener,
+                    app.into_make_service_with_connect_info::<SocketAddr>(),
+                )
+                .await
+            });
+
+            let client = reqwest::Client::builder()
+                .redirect(reqwest::redirect::Policy::none())
+                .pool_max_idle_per_host(options.connections)
+                .build()
+                .map_err(|e| format!("Failed to create HTTP client: {e}"))?;
+            Driver::Http {
+                client,
+                base_url: format!("http://{addr}"),
+            }
+        }
+    };
+
+    let driver = Arc::new(driver);
+    let short_codes = Arc::new(short_codes);
+
+    // Warm the cache so the first tier is served from Redis only
+    for short_code in short_codes.iter() {
+        driver.resolve(short_code).await;
+    }
+
+    println!(
+        "{:<10} {:>10} {:>8} {:>10} {:>10} {:>10} {:>10} {:>10}",
+        "tier", "requests", "errors", "req/s", "p50", "p90", "p99", "max"
+    );
+    for tier in [Tier::Redis, Tier::Database] {
+        let samples = measure(state, &driver, &short_codes, tier, options).await;
+        report(tier, samples, options.duration);
+    }
+
+    Ok(())
+}
+
+async fn measure(
+    state: &AppState,
+    driver: &Arc<Driver>,
+    short_codes: &Arc<Vec<String>>,
+    tier: Tier,
+    options: &Options,
+) -> Samples {
+    let deadline = Instant::now() + options.duration;
+    let mut workers = JoinSet::new();
+
+    for worker in 0..options.connections {
+        let state = state.clone();
+        let driver = driver.clone();
+        let short_codes = short_codes.clone();
+        let connections = options.connections;
+
+        workers.spawn(async move {
+            let mut samples = Samples::default();
+            let mut index = worker;
+            while Instant::now() < deadline {
+                let short_code = &short_codes[index % short_codes.len()];
+                index += connections;
+
+                // Evicting outside the timed section forces a database lookup
+                if tier == Tier::Database {
+                    evict(&state, std::slice::from_ref(short_code)).await;
+                }
+
+                let start = Instant::now();
+                if driver.resolve(short_code).await {
+                    samples.latencies.push(start.elapsed());
+                } else {
+                    samples.errors += 1;
+                }
+            }
+            samples
+        });
+    }
+
+    let mut samples = Samples::default();
+    while let Some(result) = workers.join_next().await {
+        match result {
+            Ok(worker) => {
+                samples.latencies.extend(worker.latencies);
+                samples.errors += worker.errors;
+            }
+            Err(e) => error!(error = %e, "Benchmark worker failed"),
+        }
+    }
+    samples
+}
+
+fn report(tier: Tier, mut samples: Samples, duration: Duration) {
+    samples.latencies.sort_unstable();
+    let percentile = |p: usize| -> String {
+        match samples.latencies.len() {
+            0 => "-".to_string(),
+            len => format!("{:.2?}", samples.latencies[(len - 1) * p / 100]),
+        }
+    };
+
+    let requests = samples.latencies.len() + samples.errors;
+    println!(
+        "{:<10} {:>10} {:>8} {:>10.0} {:>10} {:>10} {:>10} {:>10}",
+        tier,
+        requests,
+        samples.errors,
+        samples.latencies.len() as f64 / duration.as_secs_f64(),
+        percentile(50),
+        percentile(90),
+        percentile(99),
+        percentile(100),
+    );
+}
+
+// Insert benchmark links, returning the codes of the rows that were created
+async fn seed(state: &AppState, links: usize) -> Result<Vec<String>, String> {
+    let mut short_codes = Vec::with_capacity(links);
+    for index in 0..links {
+        let long_url = format!("{SEED_HOST}/{index}");
+        let short_code = encode_long_url(&long_url, &state.code_alphabet).await[0..8].to_string();
+
+        let inserted = sqlx::query_scalar!(
+            "
+            INSERT INTO urls (long_url, short_code)
+            VALUES ($1, $2)
+            ON CONFLICT (short_code) DO NOTHING
+            RETURNING short_code
+            ",
+            long_url,
+            short_code
+        )
+        .fetch_optional(&state.pg_db)
+        .await
+        .map_err(|e| format!("Failed to seed benchmark links: {e}"))?;
+
+        if let (Some(code_filter), Some(short_code)) = (&state.code_filter, &inserted) {
+            code_filter.insert(short_code);
+        }
+        short_codes.extend(inserted);
+    }
+
+    if short_codes.is_empty() {
+        return Err("No benchmark links could be seeded".to_string());
+    }
+    Ok(short_codes)
+}
+
+async fn evict(state: &AppState, short_codes: &[String]) {
+    cache::evict_links(&state.redis_db, short_codes).await;
+    if let Some(local_cache) = &state.local_cache {
+        for short_code in short_codes {
+            local_cache.invalidate(short_code);
+        }
+    }
+}
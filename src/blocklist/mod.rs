@@ -0,0 +1,69 @@
+use std::{collections::HashSet, sync::RwLock};
+
+use sqlx::PgPool;
+use url::{Host, Url};
+
+use crate::db::Timed;
+
+// In-memory copy of the banned destination domains stored in Postgres
+#[derive(Debug, Default)]
+pub struct Blocklist {
+    domains: RwLock<HashSet<String>>,
+}
+
+impl Blocklist {
+    // Replace the in-memory domains with the stored ones, returning how many there are
+    pub async fn reload(&self, pg_db: &PgPool) -> Result<usize, sqlx::Error> {
+        let domains = sqlx::query_scalar!("SELECT domain FROM blocked_domains")
+            .fetch_all(pg_db)
+            .timed("blocked_domains")
+            .await?;
+        let count = domains.len();
+        *self.domains.write().unwrap_or_else(|e| e.into_inner()) = domains.into_iter().collect();
+        Ok(count)
+    }
+
+    pub fn insert(&self, domain: String) {
+        self.domains
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(domain);
+    }
+
+    pub fn remove(&self, domain: &str) {
+        self.domains
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(domain);
+    }
+
+    // Banned domain matching the url's host or one of its parent domains
+    pub fn matching(&self, url: &str) -> Option<String> {
+        let url = Url::parse(url).ok()?;
+        let host = url.host_str()?.trim_end_matches('.');
+        let domains = self.domains.read().unwrap_or_else(|e| e.into_inner());
+        if domains.is_empty() {
+            return None;
+        }
+
+        let mut candidate = host;
+        loop {
+            if domains.contains(candidate) {
+                return Some(candidate.to_string());
+            }
+            candidate = candidate.split_once('.')?.1;
+        }
+    }
+}
+
+// Canonical form of a domain or IP address to ban, with Unicode hosts in punycode
+pub fn normalize_domain(domain: &str) -> Option<String> {
+    let domain = domain.trim().trim_end_matches('.');
+    if domain.is_empty() {
+        return None;
+    }
+    match Host::parse(domain).ok()? {
+        Host::Domain(domain) => Some(domain.to_ascii_lowercase()),
+        host => Some(host.to_string()),
+    }
+}
@@ -0,0 +1,63 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use tokio::sync::{Mutex as AsyncMutex, OwnedMutexGuard};
+
+// Lets one request per key do an expensive lookup while concurrent requests for the
+// same key wait for it to finish, so an expired hot key costs one database query
+#[derive(Debug, Default)]
+pub struct Coalescer {
+    inflight: Mutex<HashMap<String, Arc<AsyncMutex<()>>>>,
+}
+
+// Held by the request doing the lookup for a key; followers are released when it drops
+#[derive(Debug)]
+pub struct Flight<'a> {
+    coalescer: &'a Coalescer,
+    key: String,
+    _guard: OwnedMutexGuard<()>,
+}
+
+impl Coalescer {
+    // Become the leader for a key, or wait for the current leader to finish and return `None`
+    pub async fn join(&self, key: &str) -> Option<Flight<'_>> {
+        let lock = {
+            let mut inflight = self.inflight.lock().unwrap_or_else(|e| e.into_inner());
+            match inflight.get(key) {
+                Some(lock) => Arc::clone(lock),
+                None => {
+                    let lock = Arc::new(AsyncMutex::new(()));
+                    let guard = Arc::clone(&lock)
+                        .try_lock_owned()
+                        .expect("new lock is unlocked");
+                    inflight.insert(key.to_string(), lock);
+                    return Some(Flight {
+                        coalescer: self,
+                        key: key.to_string(),
+                        _guard: guard,
+                    });
+                }
+            }
+        };
+
+        drop(lock.lock().await);
+        None
+    }
+
+    // Wait until the lookup of a key in flight, if any, has finished
+    pub async fn settled(&self, key: &str) {
+        drop(self.join(key).await);
+    }
+}
+
+impl Drop for Flight<'_> {
+    fn drop(&mut self) {
+        self.coalescer
+            .inflight
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&self.key);
+    }
+}
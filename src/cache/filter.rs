@@ -0,0 +1,122 @@
+use std::{
+    hash::{DefaultHasher, Hash, Hasher},
+    sync::RwLock,
+};
+
+use sqlx::PgPool;
+
+use crate::db::Timed;
+
+// Share of unknown codes the filter lets through to Redis and Postgres
+const FALSE_POSITIVE_RATE: f64 = 0.01;
+
+// Fewest codes the filter is sized for, leaving room for links created between rebuilds
+const MIN_CAPACITY: usize = 10_000;
+
+// In-memory bloom filter of every stored short code: a code it does not contain
+// certainly does not exist, so scans for random codes never reach Redis or Postgres
+#[derive(Debug, Default)]
+pub struct CodeFilter {
+    inner: RwLock<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    bloom: Option<Bloom>,
+    // Codes created while the filter is being rebuilt, added to the new filter
+    pending: Option<Vec<String>>,
+}
+
+impl CodeFilter {
+    // Rebuild the filter from the stored codes, returning how many there are
+    pub async fn reload(&self, pg_db: &PgPool) -> Result<usize, sqlx::Error> {
+        self.inner
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .pending = Some(Vec::new());
+
+        let result = sqlx::query_scalar!("SELECT short_code FROM urls")
+            .fetch_all(pg_db)
+            .timed("code_filter_codes")
+            .await;
+
+        let mut inner = self.inner.write().unwrap_or_else(|e| e.into_inner());
+        let pending = inner.pending.take().unwrap_or_default();
+        let short_codes = result?;
+        let count = short_codes.len();
+
+        let mut bloom = Bloom::new((count * 2).max(MIN_CAPACITY));
+        for short_code in short_codes.iter().chain(&pending) {
+            bloom.insert(short_code);
+        }
+        inner.bloom = Some(bloom);
+        Ok(count)
+    }
+
+    pub fn insert(&self, short_code: &str) {
+        let mut inner = self.inner.write().unwrap_or_else(|e| e.into_inner());
+        if let Some(pending) = &mut inner.pending {
+            pending.push(short_code.to_string());
+        }
+        if let Some(bloom) = &mut inner.bloom {
+            bloom.insert(short_code);
+        }
+    }
+
+    // Whether the code may exist; always true until the filter is first loaded
+    pub fn may_contain(&self, short_code: &str) -> bool {
+        self.inner
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .bloom
+            .as_ref()
+            .is_none_or(|bloom| bloom.contains(short_code))
+    }
+}
+
+#[derive(Debug)]
+struct Bloom {
+    bits: Vec<u64>,
+    hashes: u32,
+}
+
+impl Bloom {
+    // Filter holding `capacity` codes at FALSE_POSITIVE_RATE
+    fn new(capacity: usize) -> Self {
+        let ln2 = std::f64::consts::LN_2;
+        let bit_count =
+            (-(capacity as f64) * FALSE_POSITIVE_RATE.ln() / (ln2 * ln2)).ceil() as usize;
+        let hashes = ((bit_count as f64 / capacity as f64) * ln2)
+            .round()
+            .max(1.0) as u32;
+        Self {
+            bits: vec![0; bit_count.div_ceil(64)],
+            hashes,
+        }
+    }
+
+    fn insert(&mut self, short_code: &str) {
+        for bit in self.positions(short_code) {
+            self.bits[bit / 64] |= 1 << (bit % 64);
+        }
+    }
+
+    fn contains(&self, short_code: &str) -> bool {
+        self.positions(short_code)
+            .all(|bit| self.bits[bit / 64] & (1 << (bit % 64)) != 0)
+    }
+
+    // Bit positions of a code, derived from two hashes (Kirsch-Mitzenmacher)
+    fn positions(&self, short_code: &str) -> impl Iterator<Item = usize> {
+        let hash = |seed: u64| {
+            let mut hasher = DefaultHasher::new();
+            seed.hash(&mut hasher);
+            short_code.hash(&mut hasher);
+            hasher.finish()
+        };
+        let (first, second) = (hash(0), hash(1));
+        let bit_count = (self.bits.len() * 64) as u64;
+        (0..self.hashes as u64)
+            .map(move |i| (first.wrapping_add(i.wrapping_mul(second)) % bit_count) as usize)
+    }
+}
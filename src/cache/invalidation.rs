@@ -0,0 +1,49 @@
+use std::time::Duration;
+
+use futures_util::StreamExt;
+use moka::sync::Cache;
+use redis::{AsyncCommands, Client, RedisResult};
+use tokio::time;
+use tracing::{error, warn};
+
+use crate::state::RedisConn;
+
+// Channel carrying the codes of links whose cached destinations are stale, one per line
+const CHANNEL: &str = "links:evicted";
+
+const RESUBSCRIBE_DELAY: Duration = Duration::from_secs(1);
+
+// Tell every instance, this one included, to drop links from its in-process cache
+pub async fn publish(conn: &mut RedisConn, short_codes: &[String]) -> RedisResult<()> {
+    conn.publish(CHANNEL, short_codes.join("\n")).await
+}
+
+// Drop links evicted by any instance from this instance's in-process cache, for as long as
+// the process runs. Evictions published while unsubscribed are lost, so the whole cache is
+// dropped each time the subscription starts.
+pub async fn listen(client: Client, local_cache: Cache<String, String>) {
+    loop {
+        match client.get_async_pubsub().await {
+            Ok(mut pubsub) => match pubsub.subscribe(CHANNEL).await {
+                Ok(()) => {
+                    local_cache.invalidate_all();
+                    let mut messages = pubsub.on_message();
+                    while let Some(message) = messages.next().await {
+                        match message.get_payload::<String>() {
+                            Ok(short_codes) => {
+                                for short_code in short_codes.lines() {
+                                    local_cache.invalidate(short_code);
+                                }
+                            }
+                            Err(e) => warn!(error = %e, "Invalid cache invalidation message"),
+                        }
+                    }
+                    warn!("Cache invalidation subscription closed");
+                }
+                Err(e) => error!(error = %e, "Failed to subscribe to cache invalidations"),
+            },
+            Err(e) => error!(error = %e, "Failed to connect for cache invalidations"),
+        }
+        time::sleep(RESUBSCRIBE_DELAY).await;
+    }
+}
@@ -0,0 +1,371 @@
+pub mod coalesce;
+pub mod filter;
+pub mod invalidation;
+pub mod stats;
+
+use std::{future::Future, time::Duration};
+
+use redis::{AsyncCommands, RedisResult};
+use sha2::{Digest, Sha256};
+use tracing::error;
+
+use self::coalesce::Coalescer;
+use crate::{state::RedisConn, types::ClickEvent};
+
+// Key holding the current generation of cached API responses
+const RESPONSE_GENERATION_KEY: &str = "response_cache:generation";
+
+// Current response cache generation, bumped whenever cached responses become stale
+pub async fn response_generation(conn: &mut RedisConn) -> RedisResult<u64> {
+    conn.get::<_, Option<u64>>(RESPONSE_GENERATION_KEY)
+        .await
+        .map(Option::unwrap_or_default)
+}
+
+// Invalidate all cached API responses
+pub async fn invalidate_responses(redis_db: &RedisConn) {
+    let mut conn = redis_db.clone();
+    if let Err(e) = conn.incr::<_, _, ()>(RESPONSE_GENERATION_KEY, 1).await {
+        error!(error = %e, "Failed to invalidate response cache");
+    }
+}
+
+// Cache a redirect destination for `ttl` seconds, or until evicted if `ttl` is 0
+pub async fn store_destination(
+    conn: &mut RedisConn,
+    key: &str,
+    long_url: &str,
+    ttl: u64,
+) -> RedisResult<()> {
+    if ttl == 0 {
+        conn.set(key, long_url).await
+    } else {
+        conn.set_ex(key, long_url, ttl).await
+    }
+}
+
+// Cache the destination a short code redirects to
+pub async fn store_link(redis_db: &RedisConn, short_code: &str, long_url: &str, ttl: u64) {
+    let mut conn = redis_db.clone();
+    if let Err(e) = store_destination(&mut conn, short_code, long_url, ttl).await {
+        error!(error = %e, "Failed to cache URL in Redis");
+    }
+}
+
+// Cache the destinations of many short codes in one round trip
+pub async fn store_links(
+    redis_db: &RedisConn,
+    links: &[(String, String)],
+    ttl: u64,
+) -> RedisResult<()> {
+    let mut pipe = redis::pipe();
+    for (short_code, long_url) in links {
+        if ttl == 0 {
+            pipe.set(short_code, long_url).ignore();
+        } else {
+            pipe.set_ex(short_code, long_url, ttl).ignore();
+        }
+    }
+    let mut conn = redis_db.clone();
+    pipe.query_async(&mut conn).await
+}
+
+// Hash of redirects counted per short code and not yet written to the database
+const PENDING_CLICKS_KEY: &str = "clicks:pending";
+
+// List of redirects, as JSON click events, not yet stored in the database
+const PENDING_CLICK_EVENTS_KEY: &str = "clicks:pending_events";
+
+// Count a redirect of a short code and queue its click event
+pub async fn count_click(redis_db: &RedisConn, click: &ClickEvent) -> RedisResult<()> {
+    let event = serde_json::to_string(click).expect("click events serialize");
+    let mut conn = redis_db.clone();
+    redis::pipe()
+        .atomic()
+        .hincr(PENDING_CLICKS_KEY, &click.short_code, 1)
+        .ignore()
+        .rpush(PENDING_CLICK_EVENTS_KEY, event)
+        .ignore()
+        .query_async(&mut conn)
+        .await
+}
+
+// Take the counted redirects, leaving none behind for another instance to write again
+pub async fn take_clicks(redis_db: &RedisConn) -> RedisResult<Vec<(String, i64)>> {
+    let mut conn = redis_db.clone();
+    let (clicks,): (Vec<(String, i64)>,) = redis::pipe()
+        .atomic()
+        .hgetall(PENDING_CLICKS_KEY)
+        .del(PENDING_CLICKS_KEY)
+        .ignore()
+        .query_async(&mut conn)
+        .await?;
+    Ok(clicks)
+}
+
+// Put back redirects taken by `take_clicks` that could not be written
+pub async fn restore_clicks(redis_db: &RedisConn, clicks: &[(String, i64)]) -> RedisResult<()> {
+    let mut pipe = redis::pipe();
+    for (short_code, count) in clicks {
+        pipe.hincr(PENDING_CLICKS_KEY, short_code, *count).ignore();
+    }
+    let mut conn = redis_db.clone();
+    pipe.query_async(&mut conn).await
+}
+
+// Take the queued click events, leaving none behind for another instance to store again.
+// Events that no longer parse are dropped.
+pub async fn take_click_events(redis_db: &RedisConn) -> RedisResult<Vec<ClickEvent>> {
+    let mut conn = redis_db.clone();
+    let (events,): (Vec<String>,) = redis::pipe()
+        .atomic()
+        .lrange(PENDING_CLICK_EVENTS_KEY, 0, -1)
+        .del(PENDING_CLICK_EVENTS_KEY)
+        .ignore()
+        .query_async(&mut conn)
+        .await?;
+    Ok(events
+        .iter()
+        .filter_map(|event| serde_json::from_str(event).ok())
+        .collect())
+}
+
+// Queue again click events taken by `take_click_events` that could not be stored
+pub async fn restore_click_events(redis_db: &RedisConn, clicks: &[ClickEvent]) -> RedisResult<()> {
+    let events: Vec<String> = clicks
+        .iter()
+        .map(|click| serde_json::to_string(click).expect("click events serialize"))
+        .collect();
+    let mut conn = redis_db.clone();
+    conn.rpush(PENDING_CLICK_EVENTS_KEY, events).await
+}
+
+// Key marking a short code as not existing
+fn missing_key(short_code: &str) -> String {
+    format!("missing:{short_code}")
+}
+
+// Whether a short code was recently looked up and found not to exist
+pub async fn is_missing(conn: &mut RedisConn, short_code: &str) -> RedisResult<bool> {
+    conn.exists(missing_key(short_code)).await
+}
+
+// Remember for `ttl` seconds that a short code does not exist
+pub async fn store_missing(conn: &mut RedisConn, short_code: &str, ttl: u64) -> RedisResult<()> {
+    conn.set_ex(missing_key(short_code), 1, ttl).await
+}
+
+// Forget that a short code did not exist, once a link is created with it
+pub async fn forget_missing(redis_db: &RedisConn, short_code: &str) {
+    let mut conn = redis_db.clone();
+    if let Err(e) = conn.del::<_, ()>(missing_key(short_code)).await {
+        error!(error = %e, "Failed to remove unknown code from Redis cache");
+    }
+}
+
+// Key holding why fetching the preview of a link last failed
+fn failed_preview_key(short_code: &str) -> String {
+    format!("preview:failed:{short_code}")
+}
+
+// Why fetching the preview of a link failed, if it did within the time it is remembered
+pub async fn failed_preview(conn: &mut RedisConn, short_code: &str) -> RedisResult<Option<String>> {
+    conn.get(failed_preview_key(short_code)).await
+}
+
+// Remember for `ttl` seconds that fetching the preview of a link failed, so requests in the
+// meantime do not make the destination be fetched again
+pub async fn store_failed_preview(
+    conn: &mut RedisConn,
+    short_code: &str,
+    error: &str,
+    ttl: u64,
+) -> RedisResult<()> {
+    conn.set_ex(failed_preview_key(short_code), error, ttl)
+        .await
+}
+
+// Evict a changed link once more after the lookup of it in flight, which may have read it
+// before the change, has cached what it saw, and again after `retry_delay` for lookups on
+// other instances and requests that read the database without leading a lookup
+pub async fn evict_after_lookups<F, Fut>(
+    lookups: &Coalescer,
+    short_code: &str,
+    retry_delay: Duration,
+    evict: F,
+) where
+    F: Fn() -> Fut,
+    Fut: Future<Output = ()>,
+{
+    lookups.settled(short_code).await;
+    evict().await;
+    tokio::time::sleep(retry_delay).await;
+    evict().await;
+}
+
+// Drop the cached destinations of short codes, in Redis and in every instance's memory
+pub async fn evict_links(redis_db: &RedisConn, short_codes: &[String]) {
+    let mut conn = redis_db.clone();
+    if let Err(e) = conn.del::<_, ()>(short_codes).await {
+        error!(error = %e, "Failed to remove URL from Redis cache");
+    }
+    if let Err(e) = invalidation::publish(&mut conn, short_codes).await {
+        error!(error = %e, "Failed to publish cache invalidation");
+    }
+}
+
+// Record a request nonce, returning false if it has been seen before
+pub async fn claim_nonce(conn: &mut RedisConn, nonce: &str, ttl: u64) -> RedisResult<bool> {
+    redis::cmd("SET")
+        .arg(format!("nonce:{nonce}"))
+        .arg(1)
+        .arg("NX")
+        .arg("EX")
+        .arg(ttl)
+        .query_async::<Option<String>>(conn)
+        .await
+        .map(|reply| reply.is_some())
+}
+
+// Reserve an idempotency key for a request in progress, returning false if it is taken
+pub async fn claim_idempotency_key(
+    conn: &mut RedisConn,
+    key: &str,
+    record: &str,
+    ttl: u64,
+) -> RedisResult<bool> {
+    redis::cmd("SET")
+        .arg(key)
+        .arg(record)
+        .arg("NX")
+        .arg("EX")
+        .arg(ttl)
+        .query_async::<Option<String>>(conn)
+        .await
+        .map(|reply| reply.is_some())
+}
+
+// Key holding the outcome of a request sent with an Idempotency-Key; keys are scoped to
+// the caller and the endpoint
+pub fn idempotency_key(principal: &str, path: &str, key: &str) -> String {
+    let key = bs58::encode(Sha256::digest(key.as_bytes())).into_string();
+    format!("idempotency:{principal}:{path}:{key}")
+}
+
+// Count a creation by the client, returning its number of creations in the last hour
+pub async fn count_recent_creates(conn: &mut RedisConn, client: &str) -> RedisResult<u64> {
+    let key = format!("abuse:creates:{client}");
+    let count: u64 = conn.incr(&key, 1).await?;
+    if count == 1 {
+        conn.expire::<_, ()>(&key, 3600).await?;
+    }
+    Ok(count)
+}
+
+// Cache key for an API response
+pub fn response_key(
+    generation: u64,
+    principal: &str,
+    accept: &str,
+    path_and_query: &str,
+) -> String {
+    format!("response_cache:{generation}:{principal}:{accept}:{path_and_query}")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Mutex, time::Duration};
+
+    use moka::sync::Cache;
+    use tokio::time::Instant;
+
+    use super::{coalesce::Coalescer, evict_after_lookups};
+
+    const SHORT_CODE: &str = "abc12345";
+    const RETRY_DELAY: Duration = Duration::from_secs(2);
+
+    // Stand-ins for the stored destination of a link, the cache and the redirect lookups
+    struct Link {
+        stored: Mutex<Option<String>>,
+        cache: Cache<String, String>,
+        lookups: Coalescer,
+    }
+
+    impl Link {
+        fn new(long_url: &str) -> Self {
+            Self {
+                stored: Mutex::new(Some(long_url.to_string())),
+                cache: Cache::new(10),
+                lookups: Coalescer::default(),
+            }
+        }
+
+        // A redirect cache miss: lead the lookup, read the destination and cache it once
+        // `delay` has passed
+        async fn redirect_miss(&self, delay: Duration) {
+            let _lookup = self.lookups.join(SHORT_CODE).await;
+            let read = self.stored.lock().unwrap().clone();
+            tokio::time::sleep(delay).await;
+            if let Some(long_url) = read {
+                self.cache.insert(SHORT_CODE.to_string(), long_url);
+            }
+        }
+
+        // An update, or a delete without a destination, evicting the link like `evict_link`
+        async fn change(&self, long_url: Option<&str>) {
+            *self.stored.lock().unwrap() = long_url.map(str::to_string);
+            self.cache.invalidate(SHORT_CODE);
+            evict_after_lookups(&self.lookups, SHORT_CODE, RETRY_DELAY, || async {
+                self.cache.invalidate(SHORT_CODE);
+            })
+            .await;
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn delete_racing_a_slow_miss_leaves_no_entry() {
+        let link = Link::new("https://example.com/old");
+
+        // The miss reads before the delete and caches long after the retry delay
+        tokio::join!(link.redirect_miss(RETRY_DELAY * 5), link.change(None));
+
+        assert_eq!(link.cache.get(SHORT_CODE), None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn update_racing_a_miss_leaves_no_stale_entry() {
+        let link = Link::new("https://example.com/old");
+
+        tokio::join!(
+            link.redirect_miss(Duration::from_millis(500)),
+            link.change(Some("https://example.com/new"))
+        );
+        assert_eq!(link.cache.get(SHORT_CODE), None);
+
+        link.redirect_miss(Duration::ZERO).await;
+        assert_eq!(
+            link.cache.get(SHORT_CODE).as_deref(),
+            Some("https://example.com/new")
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn miss_during_retry_delay_caches_the_new_destination() {
+        let link = Link::new("https://example.com/old");
+        let started = Instant::now();
+
+        // Without a lookup in flight the eviction goes straight to the retry delay, and a
+        // miss in between reads what the update stored
+        tokio::join!(link.change(Some("https://example.com/new")), async {
+            tokio::time::sleep(RETRY_DELAY / 2).await;
+            link.redirect_miss(Duration::ZERO).await;
+            assert_eq!(
+                link.cache.get(SHORT_CODE).as_deref(),
+                Some("https://example.com/new")
+            );
+        });
+
+        assert_eq!(started.elapsed(), RETRY_DELAY);
+        assert_eq!(link.cache.get(SHORT_CODE), None);
+    }
+}
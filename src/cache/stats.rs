@@ -0,0 +1,86 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// Outcome of a redirect lookup in the cache tiers
+#[derive(Debug, Clone, Copy)]
+pub enum CacheEvent {
+    // Served from the in-process cache
+    LocalHit,
+    // Served from Redis
+    Hit,
+    // Answered from a remembered unknown code
+    NegativeHit,
+    // Rejected by the code filter
+    Filtered,
+    // Looked up in Postgres
+    Miss,
+    // Redis failed to answer or store
+    Error,
+}
+
+// Counters of redirect cache outcomes since startup
+#[derive(Debug, Default)]
+pub struct CacheStats {
+    local_hits: AtomicU64,
+    hits: AtomicU64,
+    negative_hits: AtomicU64,
+    filtered: AtomicU64,
+    misses: AtomicU64,
+    errors: AtomicU64,
+}
+
+// Point-in-time copy of the counters
+#[derive(Debug, Clone, Copy)]
+pub struct CacheCounts {
+    pub local_hits: u64,
+    pub hits: u64,
+    pub negative_hits: u64,
+    pub filtered: u64,
+    pub misses: u64,
+    pub errors: u64,
+}
+
+impl CacheStats {
+    pub fn record(&self, event: CacheEvent) {
+        let counter = match event {
+            CacheEvent::LocalHit => &self.local_hits,
+            CacheEvent::Hit => &self.hits,
+            CacheEvent::NegativeHit => &self.negative_hits,
+            CacheEvent::Filtered => &self.filtered,
+            CacheEvent::Miss => &self.misses,
+            CacheEvent::Error => &self.errors,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn counts(&self) -> CacheCounts {
+        CacheCounts {
+            local_hits: self.local_hits.load(Ordering::Relaxed),
+            hits: self.hits.load(Ordering::Relaxed),
+            negative_hits: self.negative_hits.load(Ordering::Relaxed),
+            filtered: self.filtered.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            errors: self.errors.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl CacheCounts {
+    // Lookups counted after an earlier copy was taken
+    pub fn since(&self, earlier: &CacheCounts) -> CacheCounts {
+        CacheCounts {
+            local_hits: self.local_hits.saturating_sub(earlier.local_hits),
+            hits: self.hits.saturating_sub(earlier.hits),
+            negative_hits: self.negative_hits.saturating_sub(earlier.negative_hits),
+            filtered: self.filtered.saturating_sub(earlier.filtered),
+            misses: self.misses.saturating_sub(earlier.misses),
+            errors: self.errors.saturating_sub(earlier.errors),
+        }
+    }
+
+    // Share of lookups answered without Postgres, if there were any
+    pub fn hit_ratio(&self) -> Option<f64> {
+        let answered = self.local_hits + self.hits + self.negative_hits + self.filtered;
+        let total = answered + self.misses;
+        (total > 0).then(|| answered as f64 / total as f64)
+    }
+}
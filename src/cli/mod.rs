@@ -0,0 +1,152 @@
+use std::io::{self, Write};
+
+use axum::{body::to_bytes, response::IntoResponse, Json};
+use clap::{Parser, Subcommand};
+use serde_json::json;
+
+use crate::{
+    abuse::Creator,
+    api::handlers,
+    bench,
+    db::Timed,
+    export::{ExportFormat, ExportedLink, Exporter},
+    jobs,
+    state::AppState,
+    types::ShortenRequest,
+};
+
+// Links read per query while exporting
+const EXPORT_BATCH_SIZE: i64 = 1000;
+
+// Largest response body printed by `create`
+const MAX_CREATE_RESPONSE_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Parser)]
+#[command(version, about = "URL shortener")]
+pub struct Cli {
+    /// Configuration file, whose values yield to environment variables
+    #[arg(long, global = true, value_name = "PATH")]
+    pub config: Option<String>,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Serve the API and redirects (the default)
+    Serve,
+    /// Apply pending database migrations and exit
+    Migrate,
+    /// Delete links that were disabled, e.g. used single-use links, a while ago
+    CleanupExpired {
+        /// Only delete links disabled at least this many days ago
+        #[arg(long, default_value_t = 30)]
+        older_than_days: u32,
+    },
+    /// Write all links to stdout as JSON lines or CSV
+    Export {
+        #[arg(long, value_enum, default_value_t)]
+        format: ExportFormat,
+    },
+    /// Shorten a URL, printing the created link as JSON
+    Create {
+        long_url: String,
+        /// Tag the link, may be repeated
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+        #[arg(long)]
+        description: Option<String>,
+        /// Disable the link after its first redirect
+        #[arg(long)]
+        single_use: bool,
+    },
+    /// Measure redirect throughput and latency
+    Bench(bench::Options),
+}
+
+impl Command {
+    // Commands other than `serve` keep per-request logging out of their output
+    pub fn is_serve(&self) -> bool {
+        matches!(self, Command::Serve)
+    }
+}
+
+// Same deletion as the purge job, for deployments that schedule it externally
+pub async fn cleanup_expired(state: &AppState, older_than_days: u32) -> Result<usize, String> {
+    jobs::purge::delete_disabled(state, older_than_days).await
+}
+
+pub async fn export(state: &AppState, format: ExportFormat) -> Result<usize, String> {
+    let mut stdout = io::stdout().lock();
+    let mut exporter = Exporter::new(format);
+    let mut exported = 0;
+    let mut after = String::new();
+
+    // Page through the links by short code so the table is never held in memory
+    loop {
+        let links = sqlx::query_as!(
+            ExportedLink,
+            "
+            SELECT short_code, long_url, tags, description, campaign_id, clicks, single_use,
+                activates_at, disabled_at, created_at
+            FROM urls
+            WHERE short_code > $1
+            ORDER BY short_code
+            LIMIT $2
+            ",
+            after,
+            EXPORT_BATCH_SIZE
+        )
+        .fetch_all(&state.pg_read_db)
+        .timed("export_links")
+        .await
+        .map_err(|e| format!("Failed to read links: {e}"))?;
+
+        let Some(last) = links.last() else {
+            break;
+        };
+        after = last.short_code.clone();
+
+        stdout
+            .write_all(&exporter.write(&links)?)
+            .map_err(|e| format!("Failed to write links: {e}"))?;
+        exported += links.len();
+    }
+
+    Ok(exported)
+}
+
+// Create a link through the API handler, so it gets the same validation and checks
+pub async fn create(
+    state: AppState,
+    long_url: String,
+    tags: Vec<String>,
+    description: Option<String>,
+    single_use: bool,
+) -> Result<(), String> {
+    let payload: ShortenRequest = serde_json::from_value(json!({
+        "long_url": long_url,
+        "tags": tags,
+        "description": description,
+        "single_use": single_use,
+    }))
+    .map_err(|e| e.to_string())?;
+
+    // The operator running the CLI is trusted like the admin token
+    let response = handlers::shorten(&state, payload, &Creator::Trusted)
+        .await
+        .map(|(status, response)| (status, Json(response)))
+        .into_response();
+    let status = response.status();
+    let body = to_bytes(response.into_body(), MAX_CREATE_RESPONSE_SIZE)
+        .await
+        .map_err(|e| format!("Failed to read response: {e}"))?;
+    let body = String::from_utf8_lossy(&body);
+
+    if status.is_success() {
+        println!("{body}");
+        Ok(())
+    } else {
+        Err(format!("{status}: {body}"))
+    }
+}
@@ -1,33 +1,618 @@
-use std::{env, process};
+use std::{
+    collections::HashMap, env, fs, net::IpAddr, path::Path, process, str::FromStr, sync::OnceLock,
+};
+
+use ipnet::IpNet;
+use regex::Regex;
+
+use crate::{
+    abuse::AbuseAction,
+    utils::{alphabet::Alphabet, safe_browsing::ThreatAction},
+};
+
+// Longest destination the database accepts, see the `long_url_length` constraints
+const MAX_URL_LENGTH_LIMIT: usize = 8192;
+
+// Served at /robots.txt unless ROBOTS_TXT_PATH is set: only the landing page may be crawled
+const DEFAULT_ROBOTS_TXT: &str = "User-agent: *\nAllow: /$\nDisallow: /\n";
+
+// Configuration file read from the working directory when no other is given
+const DEFAULT_CONFIG_FILE: &str = "tlong.toml";
+
+// Environment variables set from the configuration file at startup, which a reload may
+// replace
+static FILE_VARS: OnceLock<Vec<String>> = OnceLock::new();
+
+// Load settings from a TOML file into the environment at startup, without replacing
+// variables that are already set. Keys are the environment variable names in any case,
+// e.g. `cache_ttl_seconds = 600`; arrays become comma-separated lists.
+// Without an explicit path, TLONG_CONFIG or ./tlong.toml (if it exists) is used.
+// Returns the file that was loaded, if any.
+// This must run before anything else reads the environment; later reloads only go
+// through `RuntimeSettings::reload` and leave the environment alone.
+pub fn load_file(path: Option<&str>) -> Result<Option<String>, String> {
+    let Some((path, values)) = read_file(path)? else {
+        return Ok(None);
+    };
+
+    let mut file_vars = Vec::with_capacity(values.len());
+    for (var, value) in values {
+        if env::var_os(&var).is_none() {
+            env::set_var(&var, value);
+            file_vars.push(var);
+        }
+    }
+    let _ = FILE_VARS.set(file_vars);
+
+    Ok(Some(path))
+}
+
+// Values of the configuration file by environment variable name
+type FileSettings = HashMap<String, String>;
+
+// Path and settings of the configuration file
+fn read_file(path: Option<&str>) -> Result<Option<(String, FileSettings)>, String> {
+    let path = match path
+        .map(str::to_string)
+        .or_else(|| env::var("TLONG_CONFIG").ok())
+    {
+        Some(path) => path,
+        None if Path::new(DEFAULT_CONFIG_FILE).exists() => DEFAULT_CONFIG_FILE.to_string(),
+        None => return Ok(None),
+    };
+
+    let contents = fs::read_to_string(&path).map_err(|e| format!("{path}: {e}"))?;
+    let table: toml::Table = contents.parse().map_err(|e| format!("{path}: {e}"))?;
+
+    let mut values = HashMap::with_capacity(table.len());
+    for (key, value) in table {
+        let value = match value {
+            toml::Value::Array(values) => values
+                .into_iter()
+                .map(plain_value)
+                .collect::<Option<Vec<_>>>()
+                .map(|values| values.join(",")),
+            value => plain_value(value),
+        }
+        .ok_or_else(|| format!("{path}: {key} must be a value or a list of values"))?;
+        values.insert(key.to_uppercase(), value);
+    }
+
+    Ok(Some((path, values)))
+}
+
+// Set every variable that has a `<NAME>_FILE` counterpart to the contents of that file,
+// e.g. DATABASE_URL_FILE=/run/secrets/database_url, as mounted by Docker and Kubernetes.
+// A trailing newline is dropped. Setting both `<NAME>` and `<NAME>_FILE` is an error.
+pub fn load_secret_files() -> Result<(), String> {
+    let secret_files: Vec<(String, String)> = env::vars()
+        .filter_map(|(var, path)| {
+            let name = var.strip_suffix("_FILE")?;
+            (!name.is_empty()).then(|| (name.to_string(), path))
+        })
+        .collect();
+
+    for (var, path) in secret_files {
+        if env::var_os(&var).is_some() {
+            return Err(format!("{var}: set either {var} or {var}_FILE, not both"));
+        }
+        let secret = fs::read_to_string(&path).map_err(|e| format!("{var}_FILE {path}: {e}"))?;
+        env::set_var(&var, secret.trim_end_matches(['\r', '\n']));
+    }
+
+    Ok(())
+}
 
 pub struct Config {
+    pub runtime: RuntimeSettings,
     pub base_url: String,
     pub database_url: String,
+    // Replica for reads that tolerate replication lag, the primary serves them without it
+    pub database_read_url: Option<String>,
+    pub storage: Storage,
     pub redis_url: String,
-    pub server_addr: String,
+    // Every address serves the full router; the first one is used for defaults
+    pub server_addrs: Vec<String>,
+    pub tls: Option<TlsConfig>,
+    pub http_redirect_addr: Option<String>,
+    pub grpc_addr: Option<String>,
+    pub startup_retry_max_wait: u64,
+    pub database_max_connections: u32,
+    pub request_timeout: u64,
+    pub request_body_limit: usize,
+    pub shutdown_timeout: u64,
+    pub request_buffer_size: usize,
+    pub metrics_enabled: bool,
+    pub metrics_addr: Option<String>,
+    pub access_log: bool,
+    pub slow_query_threshold: u64,
+    pub database_breaker_failures: u32,
+    pub database_breaker_cooldown: u64,
+    pub duplicate_policy: DuplicatePolicy,
+    pub external_id_pattern: Regex,
+    pub local_cache_capacity: u64,
+    pub local_cache_ttl: u64,
+    pub cache_warm_links: u32,
+    pub cache_warm_interval: u64,
+    pub click_flush_interval: u64,
+    pub replay_protection: bool,
+    pub replay_window: u64,
+    pub idempotency_ttl: u64,
+    pub signing_secrets: Vec<String>,
+    pub require_signed_creation: bool,
+    // Reverse proxies whose X-Forwarded-For and X-Real-IP headers name the client
+    pub trusted_proxies: Vec<IpNet>,
+    pub abuse_action: AbuseAction,
+    pub abuse_threshold: u32,
+    pub resolve_redirects: bool,
+    pub max_redirect_hops: usize,
+    pub geoip_database: Option<String>,
+    pub robots_txt: String,
+    pub safe_browsing_api_key: Option<String>,
+    pub safe_browsing_url: String,
+    pub safe_browsing_action: ThreatAction,
+    pub safe_browsing_recheck_interval: u64,
+    pub admin_token: Option<String>,
+    pub blocklist_refresh_interval: u64,
+    pub code_filter_refresh_interval: u64,
+    pub link_check_interval: u64,
+    pub link_check_rate: u32,
+    pub link_check_break_after: u32,
+    pub link_check_webhook_url: Option<String>,
+    pub webhook_interval: u64,
+    pub webhook_max_attempts: u32,
+    pub click_stream: bool,
+    pub dashboard_interval: u64,
+    pub purge_interval: u64,
+    pub purge_retention_days: u32,
+    pub click_retention_months: u32,
+    pub ssrf_dns_check: bool,
+    pub accept_schemeless_urls: bool,
+    pub max_url_length: usize,
+    pub reserved_codes: Vec<String>,
+    pub banned_words: Vec<String>,
+    pub code_alphabet: Alphabet,
+    pub case_insensitive_codes: bool,
+    pub code_strategy: CodeStrategy,
+    pub sequence_code_length: usize,
+}
+
+/// Settings that take effect without a restart, reloaded on SIGHUP.
+#[derive(Debug, Clone)]
+pub struct RuntimeSettings {
+    // Requests per second across all clients
+    pub rate_limit: u64,
+    // How long redirect destinations stay in Redis, 0 to keep them until evicted
+    pub cache_ttl: u64,
+    pub response_cache_ttl: u64,
+    // How long unknown short codes are remembered, 0 to always ask the database
+    pub negative_cache_ttl: u64,
+    // How long browsers may reuse a permanent redirect, 0 to revalidate every time
+    pub redirect_max_age: u64,
+    pub not_found_redirect_url: Option<String>,
+}
+
+impl RuntimeSettings {
+    // Unlike `Config::load`, invalid values are returned so a running server can keep
+    // its current settings
+    pub fn load() -> Result<Self, String> {
+        Self::read(&|var| env::var(var).ok())
+    }
+
+    // Settings after the configuration file changed: variables set in the environment still
+    // take precedence, while those the file set at startup take their new value from the
+    // file, or their default once removed from it
+    pub fn reload(path: Option<&str>) -> Result<Self, String> {
+        let file = read_file(path)?
+            .map(|(_, values)| values)
+            .unwrap_or_default();
+        let file_vars = FILE_VARS.get().map(Vec::as_slice).unwrap_or_default();
+        Self::read(&|var| {
+            if !file_vars.iter().any(|file_var| file_var == var) {
+                if let Ok(value) = env::var(var) {
+                    return Some(value);
+                }
+            }
+            file.get(var).cloned()
+        })
+    }
+
+    fn read(lookup: &dyn Fn(&str) -> Option<String>) -> Result<Self, String> {
+        let rate_limit = try_parse_setting(lookup, "RATE_LIMIT_PER_SECOND", "200")?;
+        if rate_limit == 0 {
+            return Err("RATE_LIMIT_PER_SECOND must be greater than 0".to_string());
+        }
+        let not_found_redirect_url = lookup("NOT_FOUND_REDIRECT_URL");
+        if let Some(url) = &not_found_redirect_url {
+            if url::Url::parse(url).is_err() {
+                return Err(format!("Invalid NOT_FOUND_REDIRECT_URL: {url}"));
+            }
+        }
+        Ok(Self {
+            rate_limit,
+            cache_ttl: try_parse_setting(lookup, "CACHE_TTL_SECONDS", "3600")?,
+            response_cache_ttl: try_parse_setting(lookup, "RESPONSE_CACHE_TTL_SECONDS", "5")?,
+            negative_cache_ttl: try_parse_setting(lookup, "NEGATIVE_CACHE_TTL_SECONDS", "30")?,
+            redirect_max_age: try_parse_setting(lookup, "REDIRECT_MAX_AGE_SECONDS", "90")?,
+            not_found_redirect_url,
+        })
+    }
+}
+
+/// Certificate chain and private key for serving HTTPS, both PEM encoded.
+#[derive(Debug, Clone)]
+pub struct TlsConfig {
+    pub cert_path: String,
+    pub key_path: String,
+}
+
+/// Behavior when shortening a destination that already has a short code, the same for
+/// every caller; a request's `reuse_existing` overrides it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicatePolicy {
+    /// Return the existing short code.
+    Existing,
+    /// Always create a new short code.
+    New,
+    /// Reject with 409 Conflict, returning the existing short code.
+    Conflict,
+}
+
+/// How short codes are generated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CodeStrategy {
+    /// Truncated hash of the destination.
+    Hash,
+    /// Scrambled number from a Postgres sequence, always unique and shorter.
+    Sequence,
+}
+
+impl FromStr for CodeStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "hash" => Ok(Self::Hash),
+            "sequence" => Ok(Self::Sequence),
+            _ => Err(format!("unknown code strategy: {s}")),
+        }
+    }
+}
+
+impl FromStr for DuplicatePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "existing" => Ok(Self::Existing),
+            "new" => Ok(Self::New),
+            "conflict" => Ok(Self::Conflict),
+            _ => Err(format!("unknown duplicate policy: {s}")),
+        }
+    }
+}
+
+/// Database holding the links, picked by the scheme of DATABASE_URL.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Storage {
+    /// The full service.
+    Postgres,
+    /// Plain links in a single binary, see `crate::lite`.
+    Sqlite,
+    /// Plain links on a MySQL or MariaDB server, served like SQLite.
+    MySql,
+}
+
+impl Storage {
+    fn from_url(url: &str) -> Self {
+        if url.starts_with("sqlite:") {
+            Self::Sqlite
+        } else if url.starts_with("mysql:") || url.starts_with("mariadb:") {
+            Self::MySql
+        } else {
+            Self::Postgres
+        }
+    }
+
+    // Cargo feature a build needs to use this database
+    pub fn feature(self) -> Option<&'static str> {
+        match self {
+            Self::Postgres => None,
+            Self::Sqlite => Some("sqlite"),
+            Self::MySql => Some("mysql"),
+        }
+    }
 }
 
 impl Config {
     pub fn load() -> Self {
+        let runtime = RuntimeSettings::load().unwrap_or_else(|e| {
+            tracing::error!("{}", e);
+            process::exit(1);
+        });
         let database_url = get_env("DATABASE_URL");
-        let redis_url = get_env("REDIS_URL");
-        let server_addr = get_env_or("SERVER_ADDRESS", "0.0.0.0:8080");
+        // Links kept outside Postgres are served without Redis
+        let storage = Storage::from_url(&database_url);
+        let database_read_url = env::var("DATABASE_READ_URL").ok();
+        let redis_url = if storage == Storage::Postgres {
+            get_env("REDIS_URL")
+        } else {
+            env::var("REDIS_URL").unwrap_or_default()
+        };
+        let server_addrs: Vec<String> = get_env_or("SERVER_ADDRESS", "0.0.0.0:8080")
+            .split(',')
+            .map(|addr| addr.trim().to_string())
+            .filter(|addr| !addr.is_empty())
+            .collect();
+        let Some(server_addr) = server_addrs.first() else {
+            tracing::error!("SERVER_ADDRESS must list at least one address");
+            process::exit(1);
+        };
+        let tls = match (env::var("TLS_CERT_PATH"), env::var("TLS_KEY_PATH")) {
+            (Ok(cert_path), Ok(key_path)) => Some(TlsConfig {
+                cert_path,
+                key_path,
+            }),
+            (Err(_), Err(_)) => None,
+            _ => {
+                tracing::error!("TLS_CERT_PATH and TLS_KEY_PATH must be set together");
+                process::exit(1);
+            }
+        };
+        // Plain HTTP listener answering every request with a redirect to HTTPS
+        let http_redirect_addr = env::var("HTTP_REDIRECT_ADDRESS").ok();
+        if http_redirect_addr.is_some() && tls.is_none() {
+            tracing::error!("HTTP_REDIRECT_ADDRESS requires TLS_CERT_PATH and TLS_KEY_PATH");
+            process::exit(1);
+        }
+        // The gRPC API is only served when it has an address of its own
+        let grpc_addr = env::var("GRPC_ADDRESS").ok();
+        let startup_retry_max_wait = parse_env("STARTUP_RETRY_MAX_WAIT_SECONDS", "60");
+        let database_max_connections = parse_nonzero_env("DATABASE_MAX_CONNECTIONS", "50");
+        let request_timeout = parse_nonzero_env("REQUEST_TIMEOUT_SECONDS", "30");
+        let request_body_limit = parse_nonzero_env("REQUEST_BODY_LIMIT_BYTES", "16384");
+        let shutdown_timeout = parse_env("SHUTDOWN_TIMEOUT_SECONDS", "30");
+        let request_buffer_size = parse_nonzero_env("REQUEST_BUFFER_SIZE", "1024");
+        // Setting a separate metrics address implies exporting metrics
+        let metrics_addr = env::var("METRICS_ADDRESS").ok();
+        let metrics_enabled = metrics_addr.is_some() || parse_env("METRICS_ENABLED", "false");
+        let access_log = parse_env("ACCESS_LOG", "true");
+        let slow_query_threshold = parse_env("SLOW_QUERY_THRESHOLD_MS", "500");
+        let database_breaker_failures = parse_env("DATABASE_BREAKER_FAILURES", "5");
+        let database_breaker_cooldown = parse_env("DATABASE_BREAKER_COOLDOWN_SECONDS", "30");
         let base_url = env::var("BASE_URL").unwrap_or_else(|_| {
             tracing::warn!(
                 "BASE_URL environment variable not set, using default: {}",
-                &server_addr
+                server_addr
             );
-            format!("http://{}", server_addr)
+            let scheme = if tls.is_some() { "https" } else { "http" };
+            format!("{scheme}://{server_addr}")
         });
+        let duplicate_policy = parse_env("DUPLICATE_POLICY", "existing");
+        let external_id_pattern = get_env_or("EXTERNAL_ID_PATTERN", "[A-Za-z0-9_-]{1,64}");
+        let external_id_pattern = Regex::new(&format!("^(?:{external_id_pattern})$"))
+            .unwrap_or_else(|e| {
+                tracing::error!("Invalid EXTERNAL_ID_PATTERN: {}", e);
+                process::exit(1);
+            });
+        let local_cache_capacity = parse_env("LOCAL_CACHE_CAPACITY", "10000");
+        let local_cache_ttl = parse_env("LOCAL_CACHE_TTL_SECONDS", "5");
+        let cache_warm_links = parse_env("CACHE_WARM_LINKS", "0");
+        let cache_warm_interval = parse_env("CACHE_WARM_INTERVAL_SECONDS", "300");
+        let click_flush_interval = parse_env("CLICK_FLUSH_INTERVAL_SECONDS", "5");
+        let replay_protection = parse_env("REPLAY_PROTECTION", "false");
+        let replay_window = parse_env("REPLAY_WINDOW_SECONDS", "300");
+        let idempotency_ttl = parse_env("IDEMPOTENCY_TTL_SECONDS", "86400");
+        // Several secrets are accepted at once so they can be rotated without downtime
+        let signing_secrets: Vec<String> = env::var("SIGNING_SECRETS")
+            .unwrap_or_default()
+            .split(',')
+            .map(|secret| secret.trim().to_string())
+            .filter(|secret| !secret.is_empty())
+            .collect();
+        let require_signed_creation = parse_env("REQUIRE_SIGNED_CREATION", "false");
+        if require_signed_creation && signing_secrets.is_empty() {
+            tracing::error!("REQUIRE_SIGNED_CREATION requires SIGNING_SECRETS");
+            process::exit(1);
+        }
+        let trusted_proxies = env::var("TRUSTED_PROXIES")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|proxy| !proxy.is_empty())
+            .map(|proxy| {
+                proxy
+                    .parse()
+                    .or_else(|_| proxy.parse::<IpAddr>().map(IpNet::from))
+                    .unwrap_or_else(|_| {
+                        tracing::error!("Invalid TRUSTED_PROXIES entry: {}", proxy);
+                        process::exit(1);
+                    })
+            })
+            .collect();
+        let abuse_action = parse_env("ABUSE_ACTION", "off");
+        let abuse_threshold = parse_env("ABUSE_SCORE_THRESHOLD", "60");
+        let resolve_redirects = parse_env("RESOLVE_REDIRECTS", "false");
+        let max_redirect_hops = parse_env("MAX_REDIRECT_HOPS", "5");
+        let geoip_database = env::var("GEOIP_DATABASE").ok();
+        let robots_txt = match env::var("ROBOTS_TXT_PATH") {
+            Ok(path) => std::fs::read_to_string(&path).unwrap_or_else(|e| {
+                tracing::error!("Failed to read ROBOTS_TXT_PATH {}: {}", path, e);
+                process::exit(1);
+            }),
+            Err(_) => DEFAULT_ROBOTS_TXT.to_string(),
+        };
+        let safe_browsing_api_key = env::var("SAFE_BROWSING_API_KEY").ok();
+        let safe_browsing_url = env::var("SAFE_BROWSING_URL")
+            .unwrap_or_else(|_| crate::utils::safe_browsing::DEFAULT_ENDPOINT.to_string());
+        let safe_browsing_action = parse_env("SAFE_BROWSING_ACTION", "reject");
+        let safe_browsing_recheck_interval =
+            parse_env("SAFE_BROWSING_RECHECK_INTERVAL_SECONDS", "86400");
+        let admin_token = env::var("ADMIN_TOKEN")
+            .ok()
+            .filter(|token| !token.is_empty());
+        let blocklist_refresh_interval = parse_env("BLOCKLIST_REFRESH_SECONDS", "60");
+        let code_filter_refresh_interval = parse_env("CODE_FILTER_REFRESH_SECONDS", "0");
+        let link_check_interval = parse_env("LINK_CHECK_INTERVAL_SECONDS", "0");
+        let link_check_rate = parse_nonzero_env("LINK_CHECK_RATE_PER_SECOND", "2");
+        let link_check_break_after = parse_env("LINK_CHECK_BREAK_AFTER", "0");
+        let link_check_webhook_url = env::var("LINK_CHECK_WEBHOOK_URL")
+            .ok()
+            .filter(|url| !url.is_empty());
+        if let Some(url) = &link_check_webhook_url {
+            if url::Url::parse(url).is_err() {
+                tracing::error!("Invalid LINK_CHECK_WEBHOOK_URL: {}", url);
+                process::exit(1);
+            }
+        }
+        let webhook_interval = parse_env("WEBHOOK_INTERVAL_SECONDS", "0");
+        let webhook_max_attempts = parse_nonzero_env("WEBHOOK_MAX_ATTEMPTS", "8");
+        let click_stream = parse_env("CLICK_STREAM_ENABLED", "false");
+        let dashboard_interval = parse_env("DASHBOARD_INTERVAL_SECONDS", "0");
+        let purge_interval = parse_env("PURGE_INTERVAL_SECONDS", "0");
+        let purge_retention_days = parse_env("PURGE_RETENTION_DAYS", "30");
+        let click_retention_months = parse_env("CLICK_RETENTION_MONTHS", "12");
+        let ssrf_dns_check = parse_env("SSRF_DNS_CHECK", "false");
+        let accept_schemeless_urls = parse_env("ACCEPT_SCHEMELESS_URLS", "false");
+        let max_url_length: usize = parse_env("MAX_URL_LENGTH", "2048");
+        if max_url_length == 0 || max_url_length > MAX_URL_LENGTH_LIMIT {
+            tracing::error!(
+                "MAX_URL_LENGTH must be between 1 and {}",
+                MAX_URL_LENGTH_LIMIT
+            );
+            process::exit(1);
+        }
+        // Operators can reserve more words on top of the built-in ones
+        let mut reserved_codes: Vec<String> = crate::utils::RESERVED_CODES
+            .iter()
+            .map(|word| word.to_string())
+            .collect();
+        if let Ok(words) = env::var("RESERVED_CODES") {
+            reserved_codes.extend(
+                words
+                    .split(',')
+                    .map(|word| word.trim().to_lowercase())
+                    .filter(|word| !word.is_empty()),
+            );
+        }
+        // Generated codes also avoid the words of CODE_WORDLIST, one per line
+        let mut banned_words: Vec<String> = crate::utils::BANNED_WORDS
+            .iter()
+            .map(|word| word.to_string())
+            .collect();
+        if let Ok(path) = env::var("CODE_WORDLIST") {
+            let words = std::fs::read_to_string(&path).unwrap_or_else(|e| {
+                tracing::error!("Failed to read CODE_WORDLIST {}: {}", path, e);
+                process::exit(1);
+            });
+            banned_words.extend(
+                words
+                    .lines()
+                    .map(|word| word.trim().to_lowercase())
+                    .filter(|word| !word.is_empty() && !word.starts_with('#')),
+            );
+        }
+        let code_alphabet: Alphabet = parse_env("CODE_ALPHABET", "base58");
+        // Case-insensitive codes are generated lowercase, so only those symbols remain
+        let case_insensitive_codes = parse_env("CASE_INSENSITIVE_CODES", "false");
+        let code_alphabet = if case_insensitive_codes {
+            code_alphabet.lowercase()
+        } else {
+            code_alphabet
+        };
+        let code_strategy = parse_env("CODE_STRATEGY", "hash");
+        let sequence_code_length: usize = parse_env("SEQUENCE_CODE_LENGTH", "6");
+        if !(4..=crate::utils::MAX_SHORT_CODE_LENGTH).contains(&sequence_code_length) {
+            tracing::error!(
+                "SEQUENCE_CODE_LENGTH must be between 4 and {}",
+                crate::utils::MAX_SHORT_CODE_LENGTH
+            );
+            process::exit(1);
+        }
         Self {
+            runtime,
             base_url,
             database_url,
+            database_read_url,
+            storage,
             redis_url,
-            server_addr,
+            server_addrs,
+            tls,
+            http_redirect_addr,
+            grpc_addr,
+            startup_retry_max_wait,
+            database_max_connections,
+            request_timeout,
+            request_body_limit,
+            shutdown_timeout,
+            request_buffer_size,
+            metrics_enabled,
+            metrics_addr,
+            access_log,
+            slow_query_threshold,
+            database_breaker_failures,
+            database_breaker_cooldown,
+            duplicate_policy,
+            external_id_pattern,
+            local_cache_capacity,
+            local_cache_ttl,
+            cache_warm_links,
+            cache_warm_interval,
+            click_flush_interval,
+            replay_protection,
+            replay_window,
+            idempotency_ttl,
+            signing_secrets,
+            require_signed_creation,
+            trusted_proxies,
+            abuse_action,
+            abuse_threshold,
+            resolve_redirects,
+            max_redirect_hops,
+            geoip_database,
+            robots_txt,
+            safe_browsing_api_key,
+            safe_browsing_url,
+            safe_browsing_action,
+            safe_browsing_recheck_interval,
+            admin_token,
+            blocklist_refresh_interval,
+            code_filter_refresh_interval,
+            link_check_interval,
+            link_check_rate,
+            link_check_break_after,
+            link_check_webhook_url,
+            webhook_interval,
+            webhook_max_attempts,
+            click_stream,
+            dashboard_interval,
+            purge_interval,
+            purge_retention_days,
+            click_retention_months,
+            ssrf_dns_check,
+            accept_schemeless_urls,
+            max_url_length,
+            reserved_codes,
+            banned_words,
+            code_alphabet,
+            case_insensitive_codes,
+            code_strategy,
+            sequence_code_length,
         }
     }
 }
 
+fn plain_value(value: toml::Value) -> Option<String> {
+    match value {
+        toml::Value::String(value) => Some(value),
+        toml::Value::Integer(value) => Some(value.to_string()),
+        toml::Value::Float(value) => Some(value.to_string()),
+        toml::Value::Boolean(value) => Some(value.to_string()),
+        toml::Value::Datetime(value) => Some(value.to_string()),
+        toml::Value::Array(_) | toml::Value::Table(_) => None,
+    }
+}
+
 fn get_env(var: &str) -> String {
     env::var(var).unwrap_or_else(|_| {
         tracing::error!("{} environment variable is required but not set.", var);
@@ -45,3 +630,57 @@ fn get_env_or(var: &str, default: &str) -> String {
         default.to_string()
     })
 }
+
+fn parse_env<T>(var: &str, default: &str) -> T
+where
+    T: FromStr,
+    T::Err: std::fmt::Display,
+{
+    try_parse_env(var, default).unwrap_or_else(|e| {
+        tracing::error!("{}", e);
+        process::exit(1);
+    })
+}
+
+fn try_parse_env<T>(var: &str, default: &str) -> Result<T, String>
+where
+    T: FromStr,
+    T::Err: std::fmt::Display,
+{
+    try_parse_setting(&|var| env::var(var).ok(), var, default)
+}
+
+fn try_parse_setting<T>(
+    lookup: &dyn Fn(&str) -> Option<String>,
+    var: &str,
+    default: &str,
+) -> Result<T, String>
+where
+    T: FromStr,
+    T::Err: std::fmt::Display,
+{
+    lookup(var)
+        .unwrap_or_else(|| {
+            tracing::warn!(
+                "{} environment variable not set, using default: {}",
+                var,
+                default
+            );
+            default.to_string()
+        })
+        .parse()
+        .map_err(|e| format!("Invalid value for {var} environment variable: {e}"))
+}
+
+fn parse_nonzero_env<T>(var: &str, default: &str) -> T
+where
+    T: FromStr + Default + PartialEq,
+    T::Err: std::fmt::Display,
+{
+    let value = parse_env(var, default);
+    if value == T::default() {
+        tracing::error!("{} must be greater than 0", var);
+        process::exit(1);
+    }
+    value
+}
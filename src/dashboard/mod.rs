@@ -0,0 +1,45 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use tokio::sync::broadcast;
+
+use crate::types::DashboardSnapshot;
+
+// Snapshots kept for dashboard clients that are slow to read them
+const SNAPSHOT_BUFFER: usize = 16;
+
+// Codes listed in each snapshot
+pub const TOP_CODES: usize = 10;
+
+// Redirects of this instance since the last snapshot, and the channel snapshots are
+// broadcast on to the connected dashboards
+#[derive(Debug)]
+pub struct Dashboard {
+    redirects: Mutex<HashMap<String, u64>>,
+    pub snapshots: broadcast::Sender<DashboardSnapshot>,
+}
+
+impl Default for Dashboard {
+    fn default() -> Self {
+        Self {
+            redirects: Mutex::default(),
+            snapshots: broadcast::channel(SNAPSHOT_BUFFER).0,
+        }
+    }
+}
+
+impl Dashboard {
+    pub fn record(&self, short_code: &str) {
+        let mut redirects = self.redirects.lock().unwrap_or_else(|e| e.into_inner());
+        match redirects.get_mut(short_code) {
+            Some(count) => *count += 1,
+            None => {
+                redirects.insert(short_code.to_string(), 1);
+            }
+        }
+    }
+
+    // Redirects per code since the previous call
+    pub fn take_redirects(&self) -> HashMap<String, u64> {
+        std::mem::take(&mut *self.redirects.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+}
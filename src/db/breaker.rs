@@ -0,0 +1,100 @@
+use std::{
+    io,
+    sync::{
+        atomic::{AtomicU32, AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use tracing::{info, warn};
+
+// Consecutive connection failures that open the circuit, 0 disables the breaker
+static FAILURE_THRESHOLD: AtomicU32 = AtomicU32::new(0);
+static COOLDOWN_MS: AtomicU64 = AtomicU64::new(0);
+
+struct Circuit {
+    failures: u32,
+    open_until: Option<Instant>,
+}
+
+static CIRCUIT: Mutex<Circuit> = Mutex::new(Circuit {
+    failures: 0,
+    open_until: None,
+});
+
+pub fn configure(failure_threshold: u32, cooldown: Duration) {
+    FAILURE_THRESHOLD.store(failure_threshold, Ordering::Relaxed);
+    COOLDOWN_MS.store(cooldown.as_millis() as u64, Ordering::Relaxed);
+}
+
+// Time left before queries are attempted again, if the circuit is open
+pub fn retry_after() -> Option<Duration> {
+    if FAILURE_THRESHOLD.load(Ordering::Relaxed) == 0 {
+        return None;
+    }
+    let circuit = CIRCUIT.lock().unwrap_or_else(|e| e.into_inner());
+    circuit
+        .open_until
+        .and_then(|open_until| open_until.checked_duration_since(Instant::now()))
+}
+
+// Error returned instead of running a query while the circuit is open
+pub(super) fn open_error() -> sqlx::Error {
+    sqlx::Error::Io(io::Error::new(
+        io::ErrorKind::ConnectionRefused,
+        "database circuit breaker is open",
+    ))
+}
+
+// After the cooldown queries go through again: the first success closes the circuit,
+// the first failure opens it for another cooldown
+pub(super) fn record<T>(result: &Result<T, sqlx::Error>) {
+    let threshold = FAILURE_THRESHOLD.load(Ordering::Relaxed);
+    if threshold == 0 {
+        return;
+    }
+    let mut circuit = CIRCUIT.lock().unwrap_or_else(|e| e.into_inner());
+    match result {
+        Ok(_) => {
+            if circuit.open_until.take().is_some() {
+                info!("Database circuit closed");
+            }
+            circuit.failures = 0;
+        }
+        Err(e) if is_connection_error(e) => {
+            circuit.failures = circuit.failures.saturating_add(1);
+            let now = Instant::now();
+            let is_open = circuit
+                .open_until
+                .is_some_and(|open_until| open_until > now);
+            if circuit.failures >= threshold && !is_open {
+                let cooldown = Duration::from_millis(COOLDOWN_MS.load(Ordering::Relaxed));
+                circuit.open_until = Some(now + cooldown);
+                warn!(
+                    failures = circuit.failures,
+                    cooldown_seconds = cooldown.as_secs_f64(),
+                    error = %e,
+                    "Database circuit opened"
+                );
+            }
+        }
+        // Constraint violations, missing rows and the like say nothing about availability
+        Err(_) => {}
+    }
+}
+
+fn is_connection_error(e: &sqlx::Error) -> bool {
+    match e {
+        sqlx::Error::Io(_)
+        | sqlx::Error::Tls(_)
+        | sqlx::Error::PoolTimedOut
+        | sqlx::Error::PoolClosed
+        | sqlx::Error::WorkerCrashed => true,
+        // Connection exceptions (08xxx) and server shutdowns (57P0x)
+        sqlx::Error::Database(e) => e
+            .code()
+            .is_some_and(|code| code.starts_with("08") || code.starts_with("57P0")),
+        _ => false,
+    }
+}
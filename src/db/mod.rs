@@ -1 +1,100 @@
-pub mod models;
\ No newline at end of file
+pub mod breaker;
+pub mod models;
+pub mod repository;
+
+use std::{
+    collections::hash_map::RandomState,
+    future::Future,
+    hash::{BuildHasher, Hasher},
+    io,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+use tracing::warn;
+
+// Attempts made by `retry_transient`, including the first, and the delay before the
+// first retry, doubled for each later one
+const TRANSIENT_ATTEMPTS: u32 = 3;
+const TRANSIENT_RETRY_DELAY: Duration = Duration::from_millis(25);
+
+// Statements running longer than this are logged, 0 disables the check
+static SLOW_QUERY_THRESHOLD_MS: AtomicU64 = AtomicU64::new(0);
+
+pub fn set_slow_query_threshold(threshold: Duration) {
+    SLOW_QUERY_THRESHOLD_MS.store(threshold.as_millis() as u64, Ordering::Relaxed);
+}
+
+// Time a query under a stable name, logging it if it exceeds the slow query threshold.
+// Queries fail right away while the circuit breaker is open, and their outcome feeds it.
+pub trait Timed<T>: Future<Output = Result<T, sqlx::Error>> + Sized {
+    fn timed(self, name: &'static str) -> impl Future<Output = Self::Output> {
+        async move {
+            if breaker::retry_after().is_some() {
+                return Err(breaker::open_error());
+            }
+            let started = Instant::now();
+            let output = self.await;
+            let threshold = SLOW_QUERY_THRESHOLD_MS.load(Ordering::Relaxed);
+            let elapsed = started.elapsed();
+            if threshold > 0 && elapsed.as_millis() as u64 >= threshold {
+                warn!(
+                    query = name,
+                    duration_ms = elapsed.as_secs_f64() * 1000.0,
+                    "Slow query"
+                );
+            }
+            breaker::record(&output);
+            output
+        }
+    }
+}
+
+impl<T, F: Future<Output = Result<T, sqlx::Error>>> Timed<T> for F {}
+
+// Run a statement again after a short, jittered delay when it fails with an error that
+// a second try may not hit: a dropped connection, a serialization failure or a deadlock.
+// Only for statements that are safe to repeat, since the first try may have been applied.
+pub async fn retry_transient<T, F, Fut>(mut attempt: F) -> Result<T, sqlx::Error>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, sqlx::Error>>,
+{
+    let mut attempts = 1;
+    let mut delay = TRANSIENT_RETRY_DELAY;
+    loop {
+        match attempt().await {
+            Err(e) if attempts < TRANSIENT_ATTEMPTS && is_transient(&e) => {
+                let wait = jitter(delay);
+                warn!(error = %e, retry_in = ?wait, "Transient database error, retrying");
+                tokio::time::sleep(wait).await;
+                attempts += 1;
+                delay *= 2;
+            }
+            result => return result,
+        }
+    }
+}
+
+fn is_transient(e: &sqlx::Error) -> bool {
+    match e {
+        sqlx::Error::Io(e) => matches!(
+            e.kind(),
+            io::ErrorKind::ConnectionReset
+                | io::ErrorKind::ConnectionAborted
+                | io::ErrorKind::BrokenPipe
+                | io::ErrorKind::UnexpectedEof
+        ),
+        // Serialization failure, deadlock, and a connection closed by the server
+        sqlx::Error::Database(e) => e
+            .code()
+            .is_some_and(|code| matches!(code.as_ref(), "40001" | "40P01" | "57P01")),
+        _ => false,
+    }
+}
+
+// Somewhere between half and all of `delay`, so retries from concurrent requests spread out
+fn jitter(delay: Duration) -> Duration {
+    let random = RandomState::new().build_hasher().finish();
+    delay / 2 + delay.mul_f64((random % 1000) as f64 / 2000.0)
+}
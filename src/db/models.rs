@@ -1,8 +1,199 @@
 use chrono::{DateTime, Utc};
 
+use crate::types::UtmParams;
+
 #[derive(Debug, sqlx::FromRow)]
 pub struct UrlDetail {
     pub long_url: String,
+    pub resolved_url: Option<String>,
     pub short_code: String,
+    #[sqlx(flatten)]
+    pub utm: UtmParams,
+    pub tags: Vec<String>,
+    pub description: Option<String>,
+    pub campaign_id: Option<i32>,
+    pub clicks: i64,
+    pub threat_type: Option<String>,
+    pub activates_at: Option<DateTime<Utc>>,
+    pub single_use: bool,
+    pub disabled_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub last_checked_at: Option<DateTime<Utc>>,
+    pub target_status: Option<i16>,
+    pub target_error: Option<String>,
+    pub broken_at: Option<DateTime<Utc>>,
+}
+
+// `UrlDetail` of a record of `query!` selecting all its columns; `query_as!` cannot fill
+// the nested UTM parameters
+macro_rules! url_detail {
+    ($row:ident) => {
+        $crate::db::models::UrlDetail {
+            long_url: $row.long_url,
+            resolved_url: $row.resolved_url,
+            short_code: $row.short_code,
+            utm: $crate::types::UtmParams {
+                utm_source: $row.utm_source,
+                utm_medium: $row.utm_medium,
+                utm_campaign: $row.utm_campaign,
+                utm_term: $row.utm_term,
+                utm_content: $row.utm_content,
+            },
+            tags: $row.tags,
+            description: $row.description,
+            campaign_id: $row.campaign_id,
+            clicks: $row.clicks,
+            threat_type: $row.threat_type,
+            activates_at: $row.activates_at,
+            single_use: $row.single_use,
+            disabled_at: $row.disabled_at,
+            created_at: $row.created_at,
+            last_checked_at: $row.last_checked_at,
+            target_status: $row.target_status,
+            target_error: $row.target_error,
+            broken_at: $row.broken_at,
+        }
+    };
+}
+pub(crate) use url_detail;
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct UrlTarget {
+    pub long_url: String,
+    pub resolved_url: Option<String>,
+    #[sqlx(flatten)]
+    pub utm: UtmParams,
+    pub activates_at: Option<DateTime<Utc>>,
+    pub single_use: bool,
+    pub disabled_at: Option<DateTime<Utc>>,
+    pub broken_at: Option<DateTime<Utc>>,
+    pub split: bool,
+    pub geo_targeted: bool,
+    pub device_targeted: bool,
+    pub time_routed: bool,
+    pub deep_linked: bool,
+}
+
+// `UrlTarget` of a record of `query!` selecting all its columns, like `url_detail!`
+macro_rules! url_target {
+    ($row:ident) => {
+        $crate::db::models::UrlTarget {
+            long_url: $row.long_url,
+            resolved_url: $row.resolved_url,
+            utm: $crate::types::UtmParams {
+                utm_source: $row.utm_source,
+                utm_medium: $row.utm_medium,
+                utm_campaign: $row.utm_campaign,
+                utm_term: $row.utm_term,
+                utm_content: $row.utm_content,
+            },
+            activates_at: $row.activates_at,
+            single_use: $row.single_use,
+            disabled_at: $row.disabled_at,
+            broken_at: $row.broken_at,
+            split: $row.split,
+            geo_targeted: $row.geo_targeted,
+            device_targeted: $row.device_targeted,
+            time_routed: $row.time_routed,
+            deep_linked: $row.deep_linked,
+        }
+    };
+}
+pub(crate) use url_target;
+
+impl UrlTarget {
+    pub fn is_disabled(&self) -> bool {
+        self.disabled_at.is_some()
+    }
+
+    // Whether the dead link check stopped the link redirecting to a destination that is gone
+    pub fn is_broken(&self) -> bool {
+        self.broken_at.is_some()
+    }
+
+    // Whether the link's scheduled activation time has passed
+    pub fn is_active(&self) -> bool {
+        self.activates_at
+            .is_none_or(|activates_at| activates_at <= Utc::now())
+    }
+
+    // Whether redirects depend on the visitor rather than always going to one destination
+    pub fn is_routed(&self) -> bool {
+        self.split
+            || self.geo_targeted
+            || self.device_targeted
+            || self.time_routed
+            || self.deep_linked
+    }
+
+    // Destination url with the stored UTM parameters applied
+    pub fn destination(&self) -> String {
+        self.with_utm(self.resolved_url.as_deref().unwrap_or(&self.long_url))
+    }
+
+    // Apply the stored UTM parameters to one of the link's destinations
+    pub fn with_utm(&self, url: &str) -> String {
+        crate::utils::merge_params(url, self.utm.pairs())
+    }
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct LinkPreview {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub image_url: Option<String>,
+    pub fetched_at: DateTime<Utc>,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct Campaign {
+    pub id: i32,
+    pub name: String,
+    pub description: Option<String>,
+    pub links: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+// Creation held back by abuse scoring until a moderator approves or rejects it
+#[derive(Debug, sqlx::FromRow)]
+pub struct Review {
+    pub id: i32,
+    pub long_url: String,
+    // Set for external links, whose request is an `ExternalLinkRequest`
+    pub external_id: Option<String>,
+    // JSON of the creation request
+    pub request: String,
+    pub client_ip: Option<String>,
+    pub score: i32,
+    pub reasons: Vec<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct BlockedDomain {
+    pub domain: String,
+    pub reason: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct Webhook {
+    pub id: i32,
+    pub url: String,
+    pub secret: String,
+    pub events: Vec<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct WebhookDelivery {
+    pub id: i64,
+    pub event: String,
+    pub status: String,
+    pub attempts: i32,
+    pub response_status: Option<i16>,
+    pub last_error: Option<String>,
+    pub next_attempt_at: DateTime<Utc>,
     pub created_at: DateTime<Utc>,
+    pub delivered_at: Option<DateTime<Utc>>,
 }
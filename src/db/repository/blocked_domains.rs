@@ -0,0 +1,47 @@
+use sqlx::PgPool;
+
+use crate::db::{models::BlockedDomain, Timed};
+
+// Block a domain, returning None if it already is
+pub async fn insert(
+    pool: &PgPool,
+    domain: &str,
+    reason: Option<&str>,
+) -> Result<Option<BlockedDomain>, sqlx::Error> {
+    sqlx::query_as!(
+        BlockedDomain,
+        "
+        INSERT INTO blocked_domains (domain, reason)
+        VALUES ($1, $2)
+        ON CONFLICT (domain) DO NOTHING
+        RETURNING domain, reason, created_at
+        ",
+        domain,
+        reason
+    )
+    .fetch_optional(pool)
+    .timed("insert_blocked_domain")
+    .await
+}
+
+pub async fn list(pool: &PgPool) -> Result<Vec<BlockedDomain>, sqlx::Error> {
+    sqlx::query_as!(
+        BlockedDomain,
+        "SELECT domain, reason, created_at FROM blocked_domains ORDER BY domain"
+    )
+    .fetch_all(pool)
+    .timed("list_blocked_domains")
+    .await
+}
+
+// Unblock a domain, returning false if it was not blocked
+pub async fn delete(pool: &PgPool, domain: &str) -> Result<bool, sqlx::Error> {
+    Ok(
+        sqlx::query!("DELETE FROM blocked_domains WHERE domain = $1", domain)
+            .execute(pool)
+            .timed("delete_blocked_domain")
+            .await?
+            .rows_affected()
+            > 0,
+    )
+}
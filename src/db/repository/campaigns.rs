@@ -0,0 +1,133 @@
+use sqlx::PgPool;
+
+use crate::{
+    db::{models::Campaign, Timed},
+    types::LinkClicks,
+};
+
+// Create a campaign, returning None if the name is taken
+pub async fn insert(
+    pool: &PgPool,
+    name: &str,
+    description: Option<&str>,
+) -> Result<Option<Campaign>, sqlx::Error> {
+    sqlx::query_as!(
+        Campaign,
+        r#"
+        INSERT INTO campaigns (name, description)
+        VALUES ($1, $2)
+        ON CONFLICT (name) DO NOTHING
+        RETURNING id, name, description, created_at, 0::BIGINT AS "links!"
+        "#,
+        name,
+        description
+    )
+    .fetch_optional(pool)
+    .timed("insert_campaign")
+    .await
+}
+
+// All campaigns with their number of links, newest first
+pub async fn list(pool: &PgPool) -> Result<Vec<Campaign>, sqlx::Error> {
+    sqlx::query_as!(
+        Campaign,
+        r#"
+        SELECT id, name, description, created_at,
+            (SELECT COUNT(*) FROM urls WHERE urls.campaign_id = campaigns.id) AS "links!"
+        FROM campaigns
+        ORDER BY created_at DESC
+        "#
+    )
+    .fetch_all(pool)
+    .timed("list_campaigns")
+    .await
+}
+
+// Remove a campaign, detaching its links, returning false if there was none
+pub async fn delete(pool: &PgPool, id: i32) -> Result<bool, sqlx::Error> {
+    Ok(sqlx::query!("DELETE FROM campaigns WHERE id = $1", id)
+        .execute(pool)
+        .timed("delete_campaign")
+        .await?
+        .rows_affected()
+        > 0)
+}
+
+pub async fn exists(pool: &PgPool, id: i32) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"SELECT EXISTS (SELECT 1 FROM campaigns WHERE id = $1) AS "exists!""#,
+        id
+    )
+    .fetch_one(pool)
+    .timed("campaign_exists")
+    .await
+}
+
+// Move links into a campaign, returning the short codes that exist
+pub async fn attach(
+    pool: &PgPool,
+    id: i32,
+    short_codes: &[String],
+) -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query_scalar!(
+        "UPDATE urls SET campaign_id = $1 WHERE short_code = ANY($2) RETURNING short_code",
+        id,
+        short_codes
+    )
+    .fetch_all(pool)
+    .timed("attach_campaign_links")
+    .await
+}
+
+// Take a link out of a campaign, returning false if it was not in it
+pub async fn detach(pool: &PgPool, id: i32, short_code: &str) -> Result<bool, sqlx::Error> {
+    Ok(sqlx::query!(
+        "UPDATE urls SET campaign_id = NULL WHERE short_code = $1 AND campaign_id = $2",
+        short_code,
+        id
+    )
+    .execute(pool)
+    .timed("detach_campaign_link")
+    .await?
+    .rows_affected()
+        > 0)
+}
+
+// Name, number of links and total clicks of a campaign
+pub async fn stats(pool: &PgPool, id: i32) -> Result<Option<(String, i64, i64)>, sqlx::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT campaigns.name,
+            COUNT(urls.short_code) AS "links!",
+            COALESCE(SUM(urls.clicks), 0)::BIGINT AS "clicks!"
+        FROM campaigns
+        LEFT JOIN urls ON urls.campaign_id = campaigns.id
+        WHERE campaigns.id = $1
+        GROUP BY campaigns.id
+        "#,
+        id
+    )
+    .fetch_optional(pool)
+    .timed("campaign_stats")
+    .await?;
+    Ok(row.map(|row| (row.name, row.links, row.clicks)))
+}
+
+// Most clicked links of a campaign
+pub async fn top_links(pool: &PgPool, id: i32, limit: i64) -> Result<Vec<LinkClicks>, sqlx::Error> {
+    sqlx::query_as!(
+        LinkClicks,
+        "
+        SELECT short_code, clicks
+        FROM urls
+        WHERE campaign_id = $1
+        ORDER BY clicks DESC, short_code
+        LIMIT $2
+        ",
+        id,
+        limit
+    )
+    .fetch_all(pool)
+    .timed("campaign_top_links")
+    .await
+}
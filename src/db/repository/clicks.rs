@@ -0,0 +1,161 @@
+use chrono::{Months, NaiveDate, NaiveTime};
+use sqlx::PgPool;
+
+use crate::{db::Timed, types::ClickEvent};
+
+// Lock held while partitions are changed, so instances maintaining them at the same time
+// take turns
+const PARTITION_LOCK_KEY: i64 = 0x746c_6f6e_6763;
+
+pub async fn insert(pool: &PgPool, click: &ClickEvent) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO clicks (short_code, clicked_at, country) VALUES ($1, $2, $3)",
+        click.short_code,
+        click.timestamp,
+        click.country
+    )
+    .execute(pool)
+    .timed("insert_click")
+    .await?;
+    Ok(())
+}
+
+// Store many clicks in one statement
+pub async fn insert_many(pool: &PgPool, clicks: &[ClickEvent]) -> Result<(), sqlx::Error> {
+    let short_codes: Vec<String> = clicks.iter().map(|c| c.short_code.clone()).collect();
+    let timestamps: Vec<_> = clicks.iter().map(|c| c.timestamp).collect();
+    let countries: Vec<_> = clicks.iter().map(|c| c.country.clone()).collect();
+    sqlx::query!(
+        "
+        INSERT INTO clicks (short_code, clicked_at, country)
+        SELECT * FROM UNNEST($1::TEXT[], $2::TIMESTAMPTZ[], $3::TEXT[]) AS c (short_code, clicked_at, country)
+        ",
+        &short_codes,
+        &timestamps,
+        &countries as &[Option<String>]
+    )
+    .execute(pool)
+    .timed("insert_clicks")
+    .await?;
+    Ok(())
+}
+
+// Name of the partition holding the clicks of the month starting on `month`
+fn partition_name(month: NaiveDate) -> String {
+    month.format("clicks_y%Ym%m").to_string()
+}
+
+// Month of a partition named by `partition_name`
+fn partition_month(name: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(&format!("{name}d01"), "clicks_y%Ym%md%d").ok()
+}
+
+// What `maintain_partitions` changed
+#[derive(Debug, Default)]
+pub struct PartitionChanges {
+    // Names of the partitions dropped for being too old
+    pub dropped: Vec<String>,
+    // Clicks moved out of the default partition into the partitions just created
+    pub moved: u64,
+}
+
+// Create the partitions of the months from `from` up to and including `until`, moving
+// clicks of those months stored meanwhile out of the default partition, and drop those of
+// months before `keep_from`. Partition bounds and names cannot be bound as parameters, so
+// these statements are built here from the dates alone and are not checked at build time.
+pub async fn maintain_partitions(
+    pool: &PgPool,
+    from: NaiveDate,
+    until: NaiveDate,
+    keep_from: Option<NaiveDate>,
+) -> Result<PartitionChanges, sqlx::Error> {
+    let mut changes = PartitionChanges::default();
+    let mut tx = pool.begin().await?;
+    sqlx::query!("SELECT pg_advisory_xact_lock($1)", PARTITION_LOCK_KEY)
+        .execute(&mut *tx)
+        .timed("lock_click_partitions")
+        .await?;
+
+    let mut month = from;
+    while month <= until {
+        let Some(next) = month.checked_add_months(Months::new(1)) else {
+            break;
+        };
+        let name = partition_name(month);
+        let exists =
+            sqlx::query_scalar!(r#"SELECT to_regclass($1) IS NOT NULL AS "exists!""#, name)
+                .fetch_one(&mut *tx)
+                .timed("find_click_partition")
+                .await?;
+        if !exists {
+            // Attaching a partition fails while the default partition holds clicks of its
+            // month, so they are moved into the new table first
+            let (lower, upper) = (
+                format!("'{month} 00:00:00+00'"),
+                format!("'{next} 00:00:00+00'"),
+            );
+            sqlx::query(&format!(
+                "CREATE TABLE {name} (LIKE clicks INCLUDING DEFAULTS INCLUDING CONSTRAINTS)"
+            ))
+            .execute(&mut *tx)
+            .timed("create_click_partition")
+            .await?;
+            let moved = sqlx::query(&format!(
+                "WITH moved AS ( \
+                    DELETE FROM clicks_default \
+                    WHERE clicked_at >= {lower} AND clicked_at < {upper} \
+                    RETURNING short_code, clicked_at, country \
+                ) \
+                INSERT INTO {name} (short_code, clicked_at, country) SELECT * FROM moved"
+            ))
+            .execute(&mut *tx)
+            .timed("move_default_clicks")
+            .await?;
+            changes.moved += moved.rows_affected();
+            sqlx::query(&format!(
+                "ALTER TABLE clicks ATTACH PARTITION {name} FOR VALUES FROM ({lower}) TO ({upper})"
+            ))
+            .execute(&mut *tx)
+            .timed("attach_click_partition")
+            .await?;
+        }
+        month = next;
+    }
+
+    if let Some(keep_from) = keep_from {
+        let partitions = sqlx::query_scalar!(
+            r#"
+            SELECT child.relname::TEXT AS "name!"
+            FROM pg_inherits
+            JOIN pg_class child ON child.oid = pg_inherits.inhrelid
+            WHERE pg_inherits.inhparent = 'clicks'::REGCLASS
+            "#
+        )
+        .fetch_all(&mut *tx)
+        .timed("list_click_partitions")
+        .await?;
+
+        for name in partitions {
+            if partition_month(&name).is_some_and(|month| month < keep_from) {
+                sqlx::query(&format!("DROP TABLE {name}"))
+                    .execute(&mut *tx)
+                    .timed("drop_click_partition")
+                    .await?;
+                changes.dropped.push(name);
+            }
+        }
+
+        // Months that never got a partition only have clicks in the default one
+        let keep_from = keep_from.and_time(NaiveTime::MIN).and_utc();
+        sqlx::query!(
+            "DELETE FROM clicks_default WHERE clicked_at < $1",
+            keep_from
+        )
+        .execute(&mut *tx)
+        .timed("delete_default_clicks")
+        .await?;
+    }
+
+    tx.commit().await?;
+    Ok(changes)
+}
@@ -0,0 +1,94 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+use crate::db::Timed;
+
+// An external link about to be stored, with its destination checked
+pub struct NewExternalLink<'a> {
+    pub external_id: &'a str,
+    pub long_url: &'a str,
+    pub threat_type: Option<&'a str>,
+    // Set when the destination was looked up with Safe Browsing
+    pub threat_checked_at: Option<DateTime<Utc>>,
+}
+
+// Where an external ID points, and whether the Safe Browsing re-check disabled it
+pub struct ExternalLink {
+    pub long_url: String,
+    pub disabled: bool,
+}
+
+// Point an external ID at a destination, returning true if the ID is new. Repointing
+// enables a link disabled for its old destination
+pub async fn upsert(pool: &PgPool, link: &NewExternalLink<'_>) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"
+        INSERT INTO external_links (external_id, long_url, threat_type, threat_checked_at)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (external_id) DO UPDATE
+        SET long_url = EXCLUDED.long_url,
+            threat_type = EXCLUDED.threat_type,
+            threat_checked_at = EXCLUDED.threat_checked_at,
+            disabled_at = NULL
+        RETURNING (xmax = 0) AS "created!"
+        "#,
+        link.external_id,
+        link.long_url,
+        link.threat_type,
+        link.threat_checked_at
+    )
+    .fetch_one(pool)
+    .timed("upsert_external_link")
+    .await
+}
+
+// Point a new external ID at a destination, returning false if the ID is taken
+pub async fn insert(pool: &PgPool, link: &NewExternalLink<'_>) -> Result<bool, sqlx::Error> {
+    Ok(sqlx::query!(
+        "
+        INSERT INTO external_links (external_id, long_url, threat_type, threat_checked_at)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (external_id) DO NOTHING
+        ",
+        link.external_id,
+        link.long_url,
+        link.threat_type,
+        link.threat_checked_at
+    )
+    .execute(pool)
+    .timed("insert_external_link")
+    .await?
+    .rows_affected()
+        > 0)
+}
+
+pub async fn destination(
+    pool: &PgPool,
+    external_id: &str,
+) -> Result<Option<ExternalLink>, sqlx::Error> {
+    sqlx::query_as!(
+        ExternalLink,
+        r#"
+        SELECT long_url, disabled_at IS NOT NULL AS "disabled!"
+        FROM external_links
+        WHERE external_id = $1
+        "#,
+        external_id
+    )
+    .fetch_optional(pool)
+    .timed("external_link")
+    .await
+}
+
+// Remove an external link, returning false if there was none
+pub async fn delete(pool: &PgPool, external_id: &str) -> Result<bool, sqlx::Error> {
+    Ok(sqlx::query!(
+        "DELETE FROM external_links WHERE external_id = $1",
+        external_id
+    )
+    .execute(pool)
+    .timed("delete_external_link")
+    .await?
+    .rows_affected()
+        > 0)
+}
@@ -0,0 +1,168 @@
+use sqlx::PgPool;
+
+use crate::{
+    db::{
+        models::{url_target, LinkPreview, UrlTarget},
+        Timed,
+    },
+    types::TagCount,
+    utils::preview::PagePreview,
+};
+
+// Erase a link together with all data derived from it, returning false if there was none.
+// Webhook deliveries about the link and held-back creations of its destination go too,
+// as their payloads name the code or the destination.
+pub async fn purge(pool: &PgPool, short_code: &str) -> Result<bool, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query!(
+        "DELETE FROM link_previews WHERE short_code = $1",
+        short_code
+    )
+    .execute(&mut *tx)
+    .timed("purge_previews")
+    .await?;
+
+    sqlx::query!("DELETE FROM clicks WHERE short_code = $1", short_code)
+        .execute(&mut *tx)
+        .timed("purge_clicks")
+        .await?;
+
+    sqlx::query!(
+        "DELETE FROM webhook_deliveries WHERE payload::JSONB -> 'link' ->> 'short_code' = $1",
+        short_code
+    )
+    .execute(&mut *tx)
+    .timed("purge_webhook_deliveries")
+    .await?;
+
+    let long_url = sqlx::query_scalar!(
+        "DELETE FROM urls WHERE short_code = $1 RETURNING long_url",
+        short_code
+    )
+    .fetch_optional(&mut *tx)
+    .timed("purge_url")
+    .await?;
+
+    if let Some(long_url) = &long_url {
+        sqlx::query!("DELETE FROM review_queue WHERE long_url = $1", long_url)
+            .execute(&mut *tx)
+            .timed("purge_reviews")
+            .await?;
+    }
+
+    tx.commit().await?;
+    Ok(long_url.is_some())
+}
+
+pub async fn exists(pool: &PgPool, short_code: &str) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"SELECT EXISTS (SELECT 1 FROM urls WHERE short_code = $1) AS "exists!""#,
+        short_code
+    )
+    .fetch_one(pool)
+    .timed("link_exists")
+    .await
+}
+
+// Where a link redirects and whether it does, as `UrlRepository::destination` reads it but
+// from any pool, e.g. the read replica
+pub async fn destination(
+    pool: &PgPool,
+    short_code: &str,
+) -> Result<Option<UrlTarget>, sqlx::Error> {
+    sqlx::query!(
+        r#"
+        SELECT long_url, resolved_url, utm_source, utm_medium, utm_campaign, utm_term, utm_content,
+            activates_at, single_use, disabled_at, broken_at,
+            EXISTS (SELECT 1 FROM url_targets WHERE url_targets.short_code = urls.short_code) AS "split!",
+            EXISTS (SELECT 1 FROM url_geo_targets WHERE url_geo_targets.short_code = urls.short_code) AS "geo_targeted!",
+            EXISTS (SELECT 1 FROM url_device_targets WHERE url_device_targets.short_code = urls.short_code) AS "device_targeted!",
+            EXISTS (SELECT 1 FROM url_time_rules WHERE url_time_rules.short_code = urls.short_code) AS "time_routed!",
+            EXISTS (SELECT 1 FROM url_deep_links WHERE url_deep_links.short_code = urls.short_code) AS "deep_linked!"
+        FROM urls
+        WHERE short_code = $1
+        "#,
+        short_code
+    )
+    .map(|row| url_target!(row))
+    .fetch_optional(pool)
+    .timed("link_destination")
+    .await
+}
+
+// Stored destination of a link, without UTM parameters or routing
+pub async fn long_url(pool: &PgPool, short_code: &str) -> Result<Option<String>, sqlx::Error> {
+    sqlx::query_scalar!(
+        "SELECT long_url FROM urls WHERE short_code = $1",
+        short_code
+    )
+    .fetch_optional(pool)
+    .timed("link_long_url")
+    .await
+}
+
+// Stored preview of a link unless it is older than `max_age_hours`
+pub async fn cached_preview(
+    pool: &PgPool,
+    short_code: &str,
+    max_age_hours: i32,
+) -> Result<Option<LinkPreview>, sqlx::Error> {
+    sqlx::query_as!(
+        LinkPreview,
+        "
+        SELECT title, description, image_url, fetched_at
+        FROM link_previews
+        WHERE short_code = $1 AND fetched_at > NOW() - make_interval(hours => $2)
+        ",
+        short_code,
+        max_age_hours
+    )
+    .fetch_optional(pool)
+    .timed("cached_preview")
+    .await
+}
+
+// Store a freshly fetched preview of a link, replacing the one before
+pub async fn store_preview(
+    pool: &PgPool,
+    short_code: &str,
+    page: &PagePreview,
+) -> Result<LinkPreview, sqlx::Error> {
+    sqlx::query_as!(
+        LinkPreview,
+        "
+        INSERT INTO link_previews (short_code, title, description, image_url)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (short_code) DO UPDATE
+        SET title = EXCLUDED.title,
+            description = EXCLUDED.description,
+            image_url = EXCLUDED.image_url,
+            fetched_at = CURRENT_TIMESTAMP
+        RETURNING title, description, image_url, fetched_at
+        ",
+        short_code,
+        page.title,
+        page.description,
+        page.image_url
+    )
+    .fetch_one(pool)
+    .timed("store_preview")
+    .await
+}
+
+// Tags with the number of links using them, most used first
+pub async fn tag_counts(pool: &PgPool) -> Result<Vec<TagCount>, sqlx::Error> {
+    sqlx::query_as!(
+        TagCount,
+        r#"
+        SELECT tag AS "tag!", COUNT(*) AS "count!"
+        FROM urls, UNNEST(tags) AS tag
+        GROUP BY tag
+        ORDER BY 2 DESC, tag
+        "#
+    )
+    .fetch_all(pool)
+    .timed("list_tags")
+    .await
+}
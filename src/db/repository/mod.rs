@@ -0,0 +1,102 @@
+// Queries of everything around links. Postgres queries, here and elsewhere, are checked
+// against the schema at build time, or without a database against the query data saved
+// in `.sqlx`; only the partition statements of `clicks`, whose identifiers and bounds
+// cannot be bound, and the `sqlite` and `mysql` repositories are built at runtime.
+pub mod blocked_domains;
+pub mod campaigns;
+pub mod clicks;
+pub mod external_links;
+pub mod links;
+#[cfg(feature = "mysql")]
+mod mysql;
+mod postgres;
+pub mod review_queue;
+pub mod routes;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+pub mod webhooks;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+#[cfg(feature = "mysql")]
+pub use self::mysql::MySqlUrlRepository;
+pub use self::postgres::PgUrlRepository;
+#[cfg(feature = "sqlite")]
+pub use self::sqlite::SqliteUrlRepository;
+
+use super::models::{UrlDetail, UrlTarget};
+use crate::types::ShortenRequest;
+
+// Storage of the links themselves. Postgres backs the full service; with the `sqlite` or
+// `mysql` feature a SQLite file or a MySQL server can hold plain links for a single binary
+// without Postgres or Redis.
+// Everything built around links, like campaigns, previews and webhooks, stays on Postgres.
+#[async_trait]
+pub trait UrlRepository: Send + Sync {
+    // Details of a link
+    async fn find(&self, short_code: &str) -> Result<Option<UrlDetail>, sqlx::Error>;
+
+    // What a redirect needs to know about a link
+    async fn destination(&self, short_code: &str) -> Result<Option<UrlTarget>, sqlx::Error>;
+
+    // Code of an enabled plain link to the same destination, with the same UTM parameters
+    // and activation time, that a new request may reuse
+    async fn find_existing(&self, request: &ShortenRequest) -> Result<Option<String>, sqlx::Error>;
+
+    // Store a new link with its routing rules, returning false if the short code is taken
+    async fn insert(&self, link: &NewLink<'_>) -> Result<bool, sqlx::Error>;
+
+    // Links matching the filter, newest first, or one page of them
+    async fn list(
+        &self,
+        filter: &LinkFilter,
+        page: Option<Page>,
+    ) -> Result<Vec<UrlDetail>, sqlx::Error>;
+
+    // Number of links matching the filter
+    async fn count(&self, filter: &LinkFilter) -> Result<i64, sqlx::Error>;
+
+    // Replace or clear the description of a link, returning the updated link
+    async fn set_description(
+        &self,
+        short_code: &str,
+        description: Option<&str>,
+    ) -> Result<Option<UrlDetail>, sqlx::Error>;
+
+    // Remove a link, returning false if there was none
+    async fn delete(&self, short_code: &str) -> Result<bool, sqlx::Error>;
+
+    // Disable a single-use link and count its click, returning false if it was already used
+    async fn use_once(&self, short_code: &str) -> Result<bool, sqlx::Error>;
+
+    // Add redirects to the click count of a link
+    async fn add_clicks(&self, short_code: &str, clicks: i64) -> Result<(), sqlx::Error>;
+}
+
+// A link about to be stored, with everything checked while shortening
+pub struct NewLink<'a> {
+    pub short_code: &'a str,
+    pub request: &'a ShortenRequest,
+    pub resolved_url: Option<&'a str>,
+    pub threat_type: Option<&'a str>,
+    // Set when destinations were looked up with Safe Browsing
+    pub threat_checked_at: Option<DateTime<Utc>>,
+}
+
+// Conditions of the link list, already normalized
+#[derive(Debug, Default)]
+pub struct LinkFilter {
+    // Only links with this tag
+    pub tag: Option<String>,
+    // Only links whose description contains this text, ignoring case
+    pub q: Option<String>,
+    // Only links whose destination failed (or passed) the latest dead link check
+    pub broken: Option<bool>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Page {
+    pub limit: i64,
+    pub offset: i64,
+}
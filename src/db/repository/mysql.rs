@@ -0,0 +1,308 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{mysql::MySqlPoolOptions, MySql, MySqlPool, QueryBuilder};
+
+use super::{LinkFilter, NewLink, Page, UrlRepository};
+use crate::{
+    db::{
+        models::{UrlDetail, UrlTarget},
+        Timed,
+    },
+    types::{ShortenRequest, UtmParams},
+};
+
+// Columns selected into `LinkRow`
+const URL_DETAIL_COLUMNS: &str = "short_code, long_url, resolved_url, utm_source, utm_medium, \
+    utm_campaign, utm_term, utm_content, tags, description, campaign_id, clicks, threat_type, \
+    activates_at, single_use, disabled_at, created_at, last_checked_at, target_status, \
+    target_error, broken_at";
+
+// Links kept on a MySQL or MariaDB server. Like SQLite it only holds plain links, so a
+// routed link is refused rather than stored without its rules
+pub struct MySqlUrlRepository {
+    pool: MySqlPool,
+}
+
+impl MySqlUrlRepository {
+    // Connect to the server and bring the schema up to date
+    pub async fn connect(url: &str, max_connections: u32) -> Result<Self, sqlx::Error> {
+        let pool = MySqlPoolOptions::new()
+            .max_connections(max_connections)
+            .connect(url)
+            .await?;
+        sqlx::migrate!("migrations/mysql").run(&pool).await?;
+        Ok(Self { pool })
+    }
+}
+
+// A row of `urls`, whose tags are stored as a JSON array
+#[derive(sqlx::FromRow)]
+struct LinkRow {
+    long_url: String,
+    resolved_url: Option<String>,
+    short_code: String,
+    #[sqlx(flatten)]
+    utm: UtmParams,
+    #[sqlx(json)]
+    tags: Vec<String>,
+    description: Option<String>,
+    campaign_id: Option<i32>,
+    clicks: i64,
+    threat_type: Option<String>,
+    activates_at: Option<DateTime<Utc>>,
+    single_use: bool,
+    disabled_at: Option<DateTime<Utc>>,
+    created_at: DateTime<Utc>,
+    last_checked_at: Option<DateTime<Utc>>,
+    target_status: Option<i16>,
+    target_error: Option<String>,
+    broken_at: Option<DateTime<Utc>>,
+}
+
+impl From<LinkRow> for UrlDetail {
+    fn from(row: LinkRow) -> Self {
+        Self {
+            long_url: row.long_url,
+            resolved_url: row.resolved_url,
+            short_code: row.short_code,
+            utm: row.utm,
+            tags: row.tags,
+            description: row.description,
+            campaign_id: row.campaign_id,
+            clicks: row.clicks,
+            threat_type: row.threat_type,
+            activates_at: row.activates_at,
+            single_use: row.single_use,
+            disabled_at: row.disabled_at,
+            created_at: row.created_at,
+            last_checked_at: row.last_checked_at,
+            target_status: row.target_status,
+            target_error: row.target_error,
+            broken_at: row.broken_at,
+        }
+    }
+}
+
+// Conditions of the link list; MySQL placeholders are positional, so only the conditions
+// that apply are added
+fn push_filter(query: &mut QueryBuilder<'_, MySql>, filter: &LinkFilter) {
+    query.push(" WHERE TRUE");
+    if let Some(tag) = &filter.tag {
+        query
+            .push(" AND JSON_CONTAINS(tags, JSON_QUOTE(")
+            .push_bind(tag.clone())
+            .push("))");
+    }
+    if let Some(q) = &filter.q {
+        query
+            .push(" AND LOCATE(LOWER(")
+            .push_bind(q.clone())
+            .push("), LOWER(description)) > 0");
+    }
+    if let Some(broken) = filter.broken {
+        query
+            .push(
+                " AND (last_checked_at IS NOT NULL AND (target_status IS NULL OR target_status >= 400)) = ",
+            )
+            .push_bind(broken);
+    }
+}
+
+#[async_trait]
+impl UrlRepository for MySqlUrlRepository {
+    async fn find(&self, short_code: &str) -> Result<Option<UrlDetail>, sqlx::Error> {
+        let row = sqlx::query_as::<_, LinkRow>(&format!(
+            "SELECT {URL_DETAIL_COLUMNS} FROM urls WHERE short_code = ?"
+        ))
+        .bind(short_code)
+        .fetch_optional(&self.pool)
+        .timed("url_details")
+        .await?;
+        Ok(row.map(UrlDetail::from))
+    }
+
+    async fn destination(&self, short_code: &str) -> Result<Option<UrlTarget>, sqlx::Error> {
+        sqlx::query_as::<_, UrlTarget>(
+            "
+            SELECT long_url, resolved_url, utm_source, utm_medium, utm_campaign, utm_term, utm_content,
+                activates_at, single_use, disabled_at, broken_at,
+                FALSE AS split, FALSE AS geo_targeted, FALSE AS device_targeted,
+                FALSE AS time_routed, FALSE AS deep_linked
+            FROM urls
+            WHERE short_code = ?
+            ",
+        )
+        .bind(short_code)
+        .fetch_optional(&self.pool)
+        .timed("fetch_destination")
+        .await
+    }
+
+    async fn find_existing(&self, request: &ShortenRequest) -> Result<Option<String>, sqlx::Error> {
+        sqlx::query_scalar(
+            "
+            SELECT short_code
+            FROM urls
+            WHERE long_url = ?
+            AND utm_source <=> ?
+            AND utm_medium <=> ?
+            AND utm_campaign <=> ?
+            AND utm_term <=> ?
+            AND utm_content <=> ?
+            AND activates_at <=> ?
+            AND NOT single_use
+            AND disabled_at IS NULL
+            AND broken_at IS NULL
+            ORDER BY created_at
+            LIMIT 1
+            ",
+        )
+        .bind(&request.long_url)
+        .bind(&request.utm.utm_source)
+        .bind(&request.utm.utm_medium)
+        .bind(&request.utm.utm_campaign)
+        .bind(&request.utm.utm_term)
+        .bind(&request.utm.utm_content)
+        .bind(request.activates_at)
+        .fetch_optional(&self.pool)
+        .timed("find_existing_link")
+        .await
+    }
+
+    async fn insert(&self, link: &NewLink<'_>) -> Result<bool, sqlx::Error> {
+        let request = link.request;
+        if request.is_routed() {
+            return Err(sqlx::Error::Protocol(
+                "routing rules cannot be stored in MySQL".to_string(),
+            ));
+        }
+
+        let inserted = sqlx::query(
+            "
+            INSERT INTO urls (long_url, resolved_url, short_code, utm_source, utm_medium, utm_campaign, utm_term, utm_content, activates_at, single_use, tags, description, threat_type, threat_checked_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            ",
+        )
+        .bind(&request.long_url)
+        .bind(link.resolved_url)
+        .bind(link.short_code)
+        .bind(&request.utm.utm_source)
+        .bind(&request.utm.utm_medium)
+        .bind(&request.utm.utm_campaign)
+        .bind(&request.utm.utm_term)
+        .bind(&request.utm.utm_content)
+        .bind(request.activates_at)
+        .bind(request.single_use)
+        .bind(sqlx::types::Json(&request.tags))
+        .bind(&request.description)
+        .bind(link.threat_type)
+        .bind(link.threat_checked_at)
+        .execute(&self.pool)
+        .timed("insert_url")
+        .await;
+        // There is no ON CONFLICT DO NOTHING, and INSERT IGNORE would hide other errors too
+        match inserted {
+            Ok(_) => Ok(true),
+            Err(sqlx::Error::Database(e)) if e.is_unique_violation() => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn list(
+        &self,
+        filter: &LinkFilter,
+        page: Option<Page>,
+    ) -> Result<Vec<UrlDetail>, sqlx::Error> {
+        let mut query = QueryBuilder::new(format!("SELECT {URL_DETAIL_COLUMNS} FROM urls"));
+        push_filter(&mut query, filter);
+        let rows = match page {
+            None => {
+                query.push(" ORDER BY created_at DESC");
+                query
+                    .build_query_as::<LinkRow>()
+                    .fetch_all(&self.pool)
+                    .timed("list_urls")
+                    .await?
+            }
+            Some(page) => {
+                query
+                    .push(" ORDER BY created_at DESC, short_code LIMIT ")
+                    .push_bind(page.limit)
+                    .push(" OFFSET ")
+                    .push_bind(page.offset);
+                query
+                    .build_query_as::<LinkRow>()
+                    .fetch_all(&self.pool)
+                    .timed("list_urls_page")
+                    .await?
+            }
+        };
+        Ok(rows.into_iter().map(UrlDetail::from).collect())
+    }
+
+    async fn count(&self, filter: &LinkFilter) -> Result<i64, sqlx::Error> {
+        let mut query = QueryBuilder::new("SELECT COUNT(*) FROM urls");
+        push_filter(&mut query, filter);
+        query
+            .build_query_scalar()
+            .fetch_one(&self.pool)
+            .timed("count_urls")
+            .await
+    }
+
+    async fn set_description(
+        &self,
+        short_code: &str,
+        description: Option<&str>,
+    ) -> Result<Option<UrlDetail>, sqlx::Error> {
+        // No RETURNING for updates, so the link is read back afterwards. sqlx connects with
+        // CLIENT_FOUND_ROWS, counting matched rather than changed rows
+        let updated = sqlx::query("UPDATE urls SET description = ? WHERE short_code = ?")
+            .bind(description)
+            .bind(short_code)
+            .execute(&self.pool)
+            .timed("update_url")
+            .await?
+            .rows_affected();
+        if updated == 0 {
+            return Ok(None);
+        }
+        self.find(short_code).await
+    }
+
+    async fn delete(&self, short_code: &str) -> Result<bool, sqlx::Error> {
+        let deleted = sqlx::query("DELETE FROM urls WHERE short_code = ?")
+            .bind(short_code)
+            .execute(&self.pool)
+            .timed("delete_url")
+            .await?
+            .rows_affected();
+        Ok(deleted > 0)
+    }
+
+    async fn use_once(&self, short_code: &str) -> Result<bool, sqlx::Error> {
+        let consumed = sqlx::query(
+            "
+            UPDATE urls
+            SET disabled_at = CURRENT_TIMESTAMP(6), clicks = clicks + 1
+            WHERE short_code = ? AND single_use AND disabled_at IS NULL
+            ",
+        )
+        .bind(short_code)
+        .execute(&self.pool)
+        .timed("consume_single_use")
+        .await?
+        .rows_affected();
+        Ok(consumed > 0)
+    }
+
+    async fn add_clicks(&self, short_code: &str, clicks: i64) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE urls SET clicks = clicks + ? WHERE short_code = ?")
+            .bind(clicks)
+            .bind(short_code)
+            .execute(&self.pool)
+            .timed("record_click")
+            .await?;
+        Ok(())
+    }
+}
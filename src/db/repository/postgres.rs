@@ -0,0 +1,404 @@
+use async_trait::async_trait;
+use sqlx::{PgPool, Postgres, Transaction};
+
+use super::{LinkFilter, NewLink, Page, UrlRepository};
+use crate::{
+    db::{
+        models::{url_detail, url_target, UrlDetail, UrlTarget},
+        retry_transient, Timed,
+    },
+    types::ShortenRequest,
+};
+
+pub struct PgUrlRepository {
+    pool: PgPool,
+    // Replica serving link lists, which may lag behind the primary. Redirects always read
+    // the primary, as their answers are cached and a lagging replica would put deleted or
+    // outdated destinations back into the caches.
+    read_pool: Option<PgPool>,
+}
+
+impl PgUrlRepository {
+    pub fn new(pool: PgPool, read_pool: Option<PgPool>) -> Self {
+        Self { pool, read_pool }
+    }
+
+    fn reader(&self) -> &PgPool {
+        self.read_pool.as_ref().unwrap_or(&self.pool)
+    }
+}
+
+#[async_trait]
+impl UrlRepository for PgUrlRepository {
+    async fn find(&self, short_code: &str) -> Result<Option<UrlDetail>, sqlx::Error> {
+        sqlx::query!(
+            "
+            SELECT short_code, long_url, resolved_url, utm_source, utm_medium, utm_campaign,
+                utm_term, utm_content, tags, description, campaign_id, clicks, threat_type,
+                activates_at, single_use, disabled_at, created_at, last_checked_at, target_status,
+                target_error, broken_at
+            FROM urls
+            WHERE short_code = $1
+            ",
+            short_code
+        )
+        .map(|row| url_detail!(row))
+        .fetch_optional(&self.pool)
+        .timed("url_details")
+        .await
+    }
+
+    async fn destination(&self, short_code: &str) -> Result<Option<UrlTarget>, sqlx::Error> {
+        retry_transient(|| {
+            sqlx::query!(
+                r#"
+                SELECT long_url, resolved_url, utm_source, utm_medium, utm_campaign, utm_term, utm_content,
+                    activates_at, single_use, disabled_at, broken_at,
+                    EXISTS (SELECT 1 FROM url_targets WHERE url_targets.short_code = urls.short_code) AS "split!",
+                    EXISTS (SELECT 1 FROM url_geo_targets WHERE url_geo_targets.short_code = urls.short_code) AS "geo_targeted!",
+                    EXISTS (SELECT 1 FROM url_device_targets WHERE url_device_targets.short_code = urls.short_code) AS "device_targeted!",
+                    EXISTS (SELECT 1 FROM url_time_rules WHERE url_time_rules.short_code = urls.short_code) AS "time_routed!",
+                    EXISTS (SELECT 1 FROM url_deep_links WHERE url_deep_links.short_code = urls.short_code) AS "deep_linked!"
+                FROM urls
+                WHERE short_code = $1
+                "#,
+                short_code
+            )
+            .map(|row| url_target!(row))
+            .fetch_optional(&self.pool)
+            .timed("fetch_destination")
+        })
+        .await
+    }
+
+    async fn find_existing(&self, request: &ShortenRequest) -> Result<Option<String>, sqlx::Error> {
+        retry_transient(|| {
+            sqlx::query_scalar!(
+                "
+                SELECT short_code
+                FROM urls
+                WHERE long_url = $1
+                AND utm_source IS NOT DISTINCT FROM $2
+                AND utm_medium IS NOT DISTINCT FROM $3
+                AND utm_campaign IS NOT DISTINCT FROM $4
+                AND utm_term IS NOT DISTINCT FROM $5
+                AND utm_content IS NOT DISTINCT FROM $6
+                AND activates_at IS NOT DISTINCT FROM $7
+                AND NOT single_use
+                AND disabled_at IS NULL
+                AND broken_at IS NULL
+                AND NOT EXISTS (SELECT 1 FROM url_targets WHERE url_targets.short_code = urls.short_code)
+                AND NOT EXISTS (SELECT 1 FROM url_geo_targets WHERE url_geo_targets.short_code = urls.short_code)
+                AND NOT EXISTS (SELECT 1 FROM url_device_targets WHERE url_device_targets.short_code = urls.short_code)
+                AND NOT EXISTS (SELECT 1 FROM url_time_rules WHERE url_time_rules.short_code = urls.short_code)
+                AND NOT EXISTS (SELECT 1 FROM url_deep_links WHERE url_deep_links.short_code = urls.short_code)
+                ORDER BY created_at
+                LIMIT 1
+                ",
+                request.long_url,
+                request.utm.utm_source,
+                request.utm.utm_medium,
+                request.utm.utm_campaign,
+                request.utm.utm_term,
+                request.utm.utm_content,
+                request.activates_at
+            )
+            .fetch_optional(&self.pool)
+            .timed("find_existing_link")
+        })
+        .await
+    }
+
+    async fn insert(&self, link: &NewLink<'_>) -> Result<bool, sqlx::Error> {
+        let request = link.request;
+        let mut tx = retry_transient(|| self.pool.begin()).await?;
+
+        let inserted = sqlx::query!(
+            "
+            INSERT INTO urls (long_url, resolved_url, short_code, utm_source, utm_medium, utm_campaign, utm_term, utm_content, activates_at, single_use, tags, description, threat_type, threat_checked_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)
+            ON CONFLICT (short_code) DO NOTHING
+            ",
+            request.long_url,
+            link.resolved_url,
+            link.short_code,
+            request.utm.utm_source,
+            request.utm.utm_medium,
+            request.utm.utm_campaign,
+            request.utm.utm_term,
+            request.utm.utm_content,
+            request.activates_at,
+            request.single_use,
+            &request.tags,
+            request.description,
+            link.threat_type,
+            link.threat_checked_at
+        )
+        .execute(&mut *tx)
+        .timed("insert_url")
+        .await?
+        .rows_affected()
+            > 0;
+        if !inserted {
+            return Ok(false);
+        }
+
+        insert_routes(&mut tx, link.short_code, request).await?;
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    // Filtered by the tag, description text and dead link check outcome of `LinkFilter`
+    async fn list(
+        &self,
+        filter: &LinkFilter,
+        page: Option<Page>,
+    ) -> Result<Vec<UrlDetail>, sqlx::Error> {
+        let Some(page) = page else {
+            return sqlx::query!(
+                "
+                SELECT short_code, long_url, resolved_url, utm_source, utm_medium, utm_campaign,
+                    utm_term, utm_content, tags, description, campaign_id, clicks, threat_type,
+                    activates_at, single_use, disabled_at, created_at, last_checked_at,
+                    target_status, target_error, broken_at
+                FROM urls
+                WHERE ($1::TEXT IS NULL OR $1 = ANY(tags))
+                AND ($2::TEXT IS NULL OR POSITION(LOWER($2) IN LOWER(description)) > 0)
+                AND ($3::BOOLEAN IS NULL
+                    OR $3 = (last_checked_at IS NOT NULL AND (target_status IS NULL OR target_status >= 400)))
+                ORDER BY created_at DESC
+                ",
+                filter.tag,
+                filter.q,
+                filter.broken
+            )
+            .map(|row| url_detail!(row))
+            .fetch_all(self.reader())
+            .timed("list_urls")
+            .await;
+        };
+
+        sqlx::query!(
+            "
+            SELECT short_code, long_url, resolved_url, utm_source, utm_medium, utm_campaign,
+                utm_term, utm_content, tags, description, campaign_id, clicks, threat_type,
+                activates_at, single_use, disabled_at, created_at, last_checked_at, target_status,
+                target_error, broken_at
+            FROM urls
+            WHERE ($1::TEXT IS NULL OR $1 = ANY(tags))
+            AND ($2::TEXT IS NULL OR POSITION(LOWER($2) IN LOWER(description)) > 0)
+            AND ($3::BOOLEAN IS NULL
+                OR $3 = (last_checked_at IS NOT NULL AND (target_status IS NULL OR target_status >= 400)))
+            ORDER BY created_at DESC, short_code
+            LIMIT $4 OFFSET $5
+            ",
+            filter.tag,
+            filter.q,
+            filter.broken,
+            page.limit,
+            page.offset
+        )
+        .map(|row| url_detail!(row))
+        .fetch_all(self.reader())
+        .timed("list_urls_page")
+        .await
+    }
+
+    async fn count(&self, filter: &LinkFilter) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) AS "count!"
+            FROM urls
+            WHERE ($1::TEXT IS NULL OR $1 = ANY(tags))
+            AND ($2::TEXT IS NULL OR POSITION(LOWER($2) IN LOWER(description)) > 0)
+            AND ($3::BOOLEAN IS NULL
+                OR $3 = (last_checked_at IS NOT NULL AND (target_status IS NULL OR target_status >= 400)))
+            "#,
+            filter.tag,
+            filter.q,
+            filter.broken
+        )
+        .fetch_one(self.reader())
+        .timed("count_urls")
+        .await
+    }
+
+    async fn set_description(
+        &self,
+        short_code: &str,
+        description: Option<&str>,
+    ) -> Result<Option<UrlDetail>, sqlx::Error> {
+        sqlx::query!(
+            "
+            UPDATE urls
+            SET description = $2
+            WHERE short_code = $1
+            RETURNING short_code, long_url, resolved_url, utm_source, utm_medium, utm_campaign,
+                utm_term, utm_content, tags, description, campaign_id, clicks, threat_type,
+                activates_at, single_use, disabled_at, created_at, last_checked_at, target_status,
+                target_error, broken_at
+            ",
+            short_code,
+            description
+        )
+        .map(|row| url_detail!(row))
+        .fetch_optional(&self.pool)
+        .timed("update_url")
+        .await
+    }
+
+    async fn delete(&self, short_code: &str) -> Result<bool, sqlx::Error> {
+        let deleted = sqlx::query_scalar!(
+            "
+            DELETE FROM urls
+            WHERE short_code = $1
+            RETURNING short_code
+            ",
+            short_code
+        )
+        .fetch_optional(&self.pool)
+        .timed("delete_url")
+        .await?;
+        Ok(deleted.is_some())
+    }
+
+    async fn use_once(&self, short_code: &str) -> Result<bool, sqlx::Error> {
+        let consumed = sqlx::query_scalar!(
+            "
+            UPDATE urls
+            SET disabled_at = CURRENT_TIMESTAMP, clicks = clicks + 1
+            WHERE short_code = $1 AND single_use AND disabled_at IS NULL
+            RETURNING short_code
+            ",
+            short_code
+        )
+        .fetch_optional(&self.pool)
+        .timed("consume_single_use")
+        .await?;
+        Ok(consumed.is_some())
+    }
+
+    async fn add_clicks(&self, short_code: &str, clicks: i64) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "UPDATE urls SET clicks = clicks + $2 WHERE short_code = $1",
+            short_code,
+            clicks
+        )
+        .execute(&self.pool)
+        .timed("record_click")
+        .await?;
+        Ok(())
+    }
+}
+
+// Store the routing rules of a new link
+async fn insert_routes(
+    tx: &mut Transaction<'_, Postgres>,
+    short_code: &str,
+    payload: &ShortenRequest,
+) -> Result<(), sqlx::Error> {
+    if !payload.variants.is_empty() {
+        let (long_urls, weights): (Vec<_>, Vec<_>) = payload
+            .variants
+            .iter()
+            .map(|variant| (variant.long_url.clone(), variant.weight))
+            .unzip();
+        sqlx::query!(
+            "
+            INSERT INTO url_targets (short_code, long_url, weight)
+            SELECT $1, * FROM UNNEST($2::TEXT[], $3::INTEGER[])
+            ",
+            short_code,
+            &long_urls,
+            &weights
+        )
+        .execute(&mut **tx)
+        .timed("insert_variants")
+        .await?;
+    }
+
+    if !payload.device_targets.is_empty() {
+        let (devices, long_urls): (Vec<_>, Vec<_>) = payload
+            .device_targets
+            .iter()
+            .map(|(device, long_url)| (device.as_str().to_string(), long_url.clone()))
+            .unzip();
+        sqlx::query!(
+            "
+            INSERT INTO url_device_targets (short_code, device, long_url)
+            SELECT $1, * FROM UNNEST($2::TEXT[], $3::TEXT[])
+            ",
+            short_code,
+            &devices,
+            &long_urls
+        )
+        .execute(&mut **tx)
+        .timed("insert_device_targets")
+        .await?;
+    }
+
+    if let Some(deep_link) = &payload.deep_link {
+        sqlx::query!(
+            "
+            INSERT INTO url_deep_links (short_code, uri, ios_store_url, android_store_url)
+            VALUES ($1, $2, $3, $4)
+            ",
+            short_code,
+            deep_link.uri,
+            deep_link.ios_store_url,
+            deep_link.android_store_url
+        )
+        .execute(&mut **tx)
+        .timed("insert_deep_link")
+        .await?;
+    }
+
+    if !payload.time_rules.is_empty() {
+        let mut priorities = Vec::new();
+        let mut starts = Vec::new();
+        let mut ends = Vec::new();
+        let mut long_urls = Vec::new();
+        for rule in &payload.time_rules {
+            priorities.push(rule.priority.unwrap_or_default());
+            starts.push(rule.starts_at);
+            ends.push(rule.ends_at);
+            long_urls.push(rule.long_url.clone());
+        }
+        sqlx::query!(
+            "
+            INSERT INTO url_time_rules (short_code, priority, starts_at, ends_at, long_url)
+            SELECT $1, * FROM UNNEST($2::INTEGER[], $3::TIME[], $4::TIME[], $5::TEXT[])
+            ",
+            short_code,
+            &priorities,
+            &starts,
+            &ends,
+            &long_urls
+        )
+        .execute(&mut **tx)
+        .timed("insert_time_rules")
+        .await?;
+    }
+
+    if !payload.geo_targets.is_empty() {
+        let (regions, long_urls): (Vec<_>, Vec<_>) = payload
+            .geo_targets
+            .iter()
+            .map(|(region, long_url)| (region.clone(), long_url.clone()))
+            .unzip();
+        sqlx::query!(
+            "
+            INSERT INTO url_geo_targets (short_code, region, long_url)
+            SELECT $1, * FROM UNNEST($2::TEXT[], $3::TEXT[])
+            ",
+            short_code,
+            &regions,
+            &long_urls
+        )
+        .execute(&mut **tx)
+        .timed("insert_geo_targets")
+        .await?;
+    }
+
+    Ok(())
+}
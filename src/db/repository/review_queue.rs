@@ -0,0 +1,79 @@
+use sqlx::{PgPool, Postgres, Transaction};
+
+use crate::db::{models::Review, Timed};
+
+// A high-risk creation about to be held back
+pub struct NewReview<'a> {
+    pub long_url: &'a str,
+    pub external_id: Option<&'a str>,
+    // JSON of the creation request
+    pub request: &'a str,
+    pub client_ip: &'a str,
+    pub score: i32,
+    pub reasons: &'a [String],
+}
+
+// Hold back a high-risk creation for a moderator
+pub async fn enqueue(pool: &PgPool, review: &NewReview<'_>) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "
+        INSERT INTO review_queue (long_url, external_id, request, client_ip, score, reasons)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        ",
+        review.long_url,
+        review.external_id,
+        review.request,
+        review.client_ip,
+        review.score,
+        review.reasons
+    )
+    .execute(pool)
+    .timed("insert_abuse_review")
+    .await?;
+    Ok(())
+}
+
+// Held creations, oldest first
+pub async fn list(pool: &PgPool) -> Result<Vec<Review>, sqlx::Error> {
+    sqlx::query_as!(
+        Review,
+        "
+        SELECT id, long_url, external_id, request, client_ip, score, reasons, created_at
+        FROM review_queue
+        ORDER BY created_at, id
+        "
+    )
+    .fetch_all(pool)
+    .timed("list_abuse_reviews")
+    .await
+}
+
+// Remove a held creation to approve it, returning None if there is none. It stays queued
+// unless the transaction is committed, so a failed approval can be retried
+pub async fn take(
+    tx: &mut Transaction<'_, Postgres>,
+    id: i32,
+) -> Result<Option<Review>, sqlx::Error> {
+    sqlx::query_as!(
+        Review,
+        "
+        DELETE FROM review_queue
+        WHERE id = $1
+        RETURNING id, long_url, external_id, request, client_ip, score, reasons, created_at
+        ",
+        id
+    )
+    .fetch_optional(&mut **tx)
+    .timed("take_abuse_review")
+    .await
+}
+
+// Drop a held creation, returning false if there was none
+pub async fn delete(pool: &PgPool, id: i32) -> Result<bool, sqlx::Error> {
+    Ok(sqlx::query!("DELETE FROM review_queue WHERE id = $1", id)
+        .execute(pool)
+        .timed("delete_abuse_review")
+        .await?
+        .rows_affected()
+        > 0)
+}
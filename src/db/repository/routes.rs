@@ -0,0 +1,192 @@
+use chrono::NaiveTime;
+use sqlx::PgPool;
+
+use crate::{
+    db::{retry_transient, Timed},
+    types::{DeepLink, TimeRule, VariantStats},
+};
+
+// Next number of the short code sequence
+pub async fn next_code_number(pool: &PgPool) -> Result<i64, sqlx::Error> {
+    retry_transient(|| {
+        sqlx::query_scalar!(r#"SELECT nextval('short_code_seq') AS "number!""#)
+            .fetch_one(pool)
+            .timed("next_short_code")
+    })
+    .await
+}
+
+pub async fn deep_link(pool: &PgPool, short_code: &str) -> Result<Option<DeepLink>, sqlx::Error> {
+    retry_transient(|| {
+        sqlx::query_as!(
+            DeepLink,
+            "SELECT uri, ios_store_url, android_store_url FROM url_deep_links WHERE short_code = $1",
+            short_code
+        )
+        .fetch_optional(pool)
+        .timed("fetch_deep_link")
+    })
+    .await
+}
+
+// Destination of a link for one device type
+pub async fn device_target(
+    pool: &PgPool,
+    short_code: &str,
+    device: &str,
+) -> Result<Option<String>, sqlx::Error> {
+    retry_transient(|| {
+        sqlx::query_scalar!(
+            "SELECT long_url FROM url_device_targets WHERE short_code = $1 AND device = $2",
+            short_code,
+            device
+        )
+        .fetch_optional(pool)
+        .timed("device_target")
+    })
+    .await
+}
+
+// Destination of a link for the first of the regions that has one, where `fallback`
+// only matches after all others
+pub async fn geo_target(
+    pool: &PgPool,
+    short_code: &str,
+    regions: &[String],
+    fallback: &str,
+) -> Result<Option<String>, sqlx::Error> {
+    retry_transient(|| {
+        sqlx::query_scalar!(
+            "
+            SELECT long_url FROM url_geo_targets
+            WHERE short_code = $1 AND region = ANY($2)
+            ORDER BY region = $3
+            LIMIT 1
+            ",
+            short_code,
+            regions,
+            fallback
+        )
+        .fetch_optional(pool)
+        .timed("geo_target")
+    })
+    .await
+}
+
+// Destination of the first time rule of a link whose window contains `time`
+pub async fn time_target(
+    pool: &PgPool,
+    short_code: &str,
+    time: NaiveTime,
+) -> Result<Option<String>, sqlx::Error> {
+    retry_transient(|| {
+        sqlx::query_scalar!(
+            "
+            SELECT long_url FROM url_time_rules
+            WHERE short_code = $1
+                AND CASE
+                    WHEN starts_at < ends_at THEN $2 >= starts_at AND $2 < ends_at
+                    ELSE $2 >= starts_at OR $2 < ends_at
+                END
+            ORDER BY priority, id
+            LIMIT 1
+            ",
+            short_code,
+            time
+        )
+        .fetch_optional(pool)
+        .timed("time_rule")
+    })
+    .await
+}
+
+// Pick an A/B split variant with probability proportional to its weight, counting it as
+// served unless `probe` is set
+pub async fn pick_variant(
+    pool: &PgPool,
+    short_code: &str,
+    probe: bool,
+) -> Result<Option<String>, sqlx::Error> {
+    sqlx::query_scalar!(
+        "
+        UPDATE url_targets
+        SET clicks = clicks + CASE WHEN $2 THEN 0 ELSE 1 END
+        WHERE id = (
+            SELECT id FROM url_targets
+            WHERE short_code = $1
+            ORDER BY -LN(1.0 - RANDOM()) / weight
+            LIMIT 1
+        )
+        RETURNING long_url
+        ",
+        short_code,
+        probe
+    )
+    .fetch_optional(pool)
+    .timed("pick_variant")
+    .await
+}
+
+// A/B split destinations of a link with their clicks, in the order they were given
+pub async fn variants(pool: &PgPool, short_code: &str) -> Result<Vec<VariantStats>, sqlx::Error> {
+    sqlx::query_as!(
+        VariantStats,
+        "SELECT long_url, weight, clicks FROM url_targets WHERE short_code = $1 ORDER BY id",
+        short_code
+    )
+    .fetch_all(pool)
+    .timed("url_variants")
+    .await
+}
+
+// Regions of a link's geo targets with their destinations
+pub async fn geo_targets(
+    pool: &PgPool,
+    short_code: &str,
+) -> Result<Vec<(String, String)>, sqlx::Error> {
+    let rows = sqlx::query!(
+        "SELECT region, long_url FROM url_geo_targets WHERE short_code = $1",
+        short_code
+    )
+    .fetch_all(pool)
+    .timed("url_geo_targets")
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|row| (row.region, row.long_url))
+        .collect())
+}
+
+// Devices of a link's device targets with their destinations
+pub async fn device_targets(
+    pool: &PgPool,
+    short_code: &str,
+) -> Result<Vec<(String, String)>, sqlx::Error> {
+    let rows = sqlx::query!(
+        "SELECT device, long_url FROM url_device_targets WHERE short_code = $1",
+        short_code
+    )
+    .fetch_all(pool)
+    .timed("url_device_targets")
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|row| (row.device, row.long_url))
+        .collect())
+}
+
+// Time rules of a link in the order they are evaluated
+pub async fn time_rules(pool: &PgPool, short_code: &str) -> Result<Vec<TimeRule>, sqlx::Error> {
+    sqlx::query_as!(
+        TimeRule,
+        r#"
+        SELECT starts_at, ends_at, long_url, priority AS "priority?" FROM url_time_rules
+        WHERE short_code = $1
+        ORDER BY priority, id
+        "#,
+        short_code
+    )
+    .fetch_all(pool)
+    .timed("url_time_rules")
+    .await
+}
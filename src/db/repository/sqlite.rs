@@ -0,0 +1,312 @@
+use std::{str::FromStr, time::Duration};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{
+    sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions},
+    SqlitePool,
+};
+
+use super::{LinkFilter, NewLink, Page, UrlRepository};
+use crate::{
+    db::{
+        models::{UrlDetail, UrlTarget},
+        Timed,
+    },
+    types::{ShortenRequest, UtmParams},
+};
+
+// Columns selected into `LinkRow`
+const URL_DETAIL_COLUMNS: &str = "short_code, long_url, resolved_url, utm_source, utm_medium, \
+    utm_campaign, utm_term, utm_content, tags, description, campaign_id, clicks, threat_type, \
+    activates_at, single_use, disabled_at, created_at, last_checked_at, target_status, \
+    target_error, broken_at";
+
+// Conditions of the link list, binding the tag, search text and dead link check outcome
+// of `LinkFilter` as ?1 to ?3
+const LINK_LIST_FILTER: &str = "
+    (?1 IS NULL OR EXISTS (SELECT 1 FROM json_each(urls.tags) WHERE json_each.value = ?1))
+    AND (?2 IS NULL OR INSTR(LOWER(description), LOWER(?2)) > 0)
+    AND (?3 IS NULL
+        OR ?3 = (last_checked_at IS NOT NULL AND (target_status IS NULL OR target_status >= 400)))";
+
+// How long a write waits for another one to finish before failing
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Links kept in a SQLite file. Only plain links fit in it: it has no tables for routing
+// rules, so a routed link is refused rather than stored without its rules
+pub struct SqliteUrlRepository {
+    pool: SqlitePool,
+}
+
+impl SqliteUrlRepository {
+    // Open the database, creating the file if needed, and bring its schema up to date
+    pub async fn connect(url: &str, max_connections: u32) -> Result<Self, sqlx::Error> {
+        let options = SqliteConnectOptions::from_str(url)?
+            .create_if_missing(true)
+            .journal_mode(SqliteJournalMode::Wal)
+            .busy_timeout(BUSY_TIMEOUT);
+        let pool = SqlitePoolOptions::new()
+            .max_connections(max_connections)
+            .connect_with(options)
+            .await?;
+        sqlx::migrate!("migrations/sqlite").run(&pool).await?;
+        Ok(Self { pool })
+    }
+}
+
+// A row of `urls`, whose tags are stored as a JSON array
+#[derive(sqlx::FromRow)]
+struct LinkRow {
+    long_url: String,
+    resolved_url: Option<String>,
+    short_code: String,
+    #[sqlx(flatten)]
+    utm: UtmParams,
+    #[sqlx(json)]
+    tags: Vec<String>,
+    description: Option<String>,
+    campaign_id: Option<i32>,
+    clicks: i64,
+    threat_type: Option<String>,
+    activates_at: Option<DateTime<Utc>>,
+    single_use: bool,
+    disabled_at: Option<DateTime<Utc>>,
+    created_at: DateTime<Utc>,
+    last_checked_at: Option<DateTime<Utc>>,
+    target_status: Option<i16>,
+    target_error: Option<String>,
+    broken_at: Option<DateTime<Utc>>,
+}
+
+impl From<LinkRow> for UrlDetail {
+    fn from(row: LinkRow) -> Self {
+        Self {
+            long_url: row.long_url,
+            resolved_url: row.resolved_url,
+            short_code: row.short_code,
+            utm: row.utm,
+            tags: row.tags,
+            description: row.description,
+            campaign_id: row.campaign_id,
+            clicks: row.clicks,
+            threat_type: row.threat_type,
+            activates_at: row.activates_at,
+            single_use: row.single_use,
+            disabled_at: row.disabled_at,
+            created_at: row.created_at,
+            last_checked_at: row.last_checked_at,
+            target_status: row.target_status,
+            target_error: row.target_error,
+            broken_at: row.broken_at,
+        }
+    }
+}
+
+#[async_trait]
+impl UrlRepository for SqliteUrlRepository {
+    async fn find(&self, short_code: &str) -> Result<Option<UrlDetail>, sqlx::Error> {
+        let row = sqlx::query_as::<_, LinkRow>(&format!(
+            "SELECT {URL_DETAIL_COLUMNS} FROM urls WHERE short_code = ?1"
+        ))
+        .bind(short_code)
+        .fetch_optional(&self.pool)
+        .timed("url_details")
+        .await?;
+        Ok(row.map(UrlDetail::from))
+    }
+
+    async fn destination(&self, short_code: &str) -> Result<Option<UrlTarget>, sqlx::Error> {
+        sqlx::query_as::<_, UrlTarget>(
+            "
+            SELECT long_url, resolved_url, utm_source, utm_medium, utm_campaign, utm_term, utm_content,
+                activates_at, single_use, disabled_at, broken_at,
+                FALSE AS split, FALSE AS geo_targeted, FALSE AS device_targeted,
+                FALSE AS time_routed, FALSE AS deep_linked
+            FROM urls
+            WHERE short_code = ?1
+            ",
+        )
+        .bind(short_code)
+        .fetch_optional(&self.pool)
+        .timed("fetch_destination")
+        .await
+    }
+
+    async fn find_existing(&self, request: &ShortenRequest) -> Result<Option<String>, sqlx::Error> {
+        sqlx::query_scalar(
+            "
+            SELECT short_code
+            FROM urls
+            WHERE long_url = ?1
+            AND utm_source IS ?2
+            AND utm_medium IS ?3
+            AND utm_campaign IS ?4
+            AND utm_term IS ?5
+            AND utm_content IS ?6
+            AND activates_at IS ?7
+            AND NOT single_use
+            AND disabled_at IS NULL
+            AND broken_at IS NULL
+            ORDER BY created_at
+            LIMIT 1
+            ",
+        )
+        .bind(&request.long_url)
+        .bind(&request.utm.utm_source)
+        .bind(&request.utm.utm_medium)
+        .bind(&request.utm.utm_campaign)
+        .bind(&request.utm.utm_term)
+        .bind(&request.utm.utm_content)
+        .bind(request.activates_at)
+        .fetch_optional(&self.pool)
+        .timed("find_existing_link")
+        .await
+    }
+
+    async fn insert(&self, link: &NewLink<'_>) -> Result<bool, sqlx::Error> {
+        let request = link.request;
+        if request.is_routed() {
+            return Err(sqlx::Error::Protocol(
+                "routing rules cannot be stored in SQLite".to_string(),
+            ));
+        }
+
+        let inserted = sqlx::query(
+            "
+            INSERT INTO urls (long_url, resolved_url, short_code, utm_source, utm_medium, utm_campaign, utm_term, utm_content, activates_at, single_use, tags, description, threat_type, threat_checked_at, created_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)
+            ON CONFLICT (short_code) DO NOTHING
+            ",
+        )
+        .bind(&request.long_url)
+        .bind(link.resolved_url)
+        .bind(link.short_code)
+        .bind(&request.utm.utm_source)
+        .bind(&request.utm.utm_medium)
+        .bind(&request.utm.utm_campaign)
+        .bind(&request.utm.utm_term)
+        .bind(&request.utm.utm_content)
+        .bind(request.activates_at)
+        .bind(request.single_use)
+        .bind(sqlx::types::Json(&request.tags))
+        .bind(&request.description)
+        .bind(link.threat_type)
+        .bind(link.threat_checked_at)
+        .bind(Utc::now())
+        .execute(&self.pool)
+        .timed("insert_url")
+        .await?
+        .rows_affected()
+            > 0;
+        Ok(inserted)
+    }
+
+    async fn list(
+        &self,
+        filter: &LinkFilter,
+        page: Option<Page>,
+    ) -> Result<Vec<UrlDetail>, sqlx::Error> {
+        let rows = match page {
+            None => {
+                sqlx::query_as::<_, LinkRow>(&format!(
+                    "SELECT {URL_DETAIL_COLUMNS} FROM urls WHERE {LINK_LIST_FILTER} ORDER BY created_at DESC"
+                ))
+                .bind(&filter.tag)
+                .bind(&filter.q)
+                .bind(filter.broken)
+                .fetch_all(&self.pool)
+                .timed("list_urls")
+                .await?
+            }
+            Some(page) => {
+                sqlx::query_as::<_, LinkRow>(&format!(
+                    "
+                    SELECT {URL_DETAIL_COLUMNS} FROM urls WHERE {LINK_LIST_FILTER}
+                    ORDER BY created_at DESC, short_code
+                    LIMIT ?4 OFFSET ?5
+                    "
+                ))
+                .bind(&filter.tag)
+                .bind(&filter.q)
+                .bind(filter.broken)
+                .bind(page.limit)
+                .bind(page.offset)
+                .fetch_all(&self.pool)
+                .timed("list_urls_page")
+                .await?
+            }
+        };
+        Ok(rows.into_iter().map(UrlDetail::from).collect())
+    }
+
+    async fn count(&self, filter: &LinkFilter) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(&format!(
+            "SELECT COUNT(*) FROM urls WHERE {LINK_LIST_FILTER}"
+        ))
+        .bind(&filter.tag)
+        .bind(&filter.q)
+        .bind(filter.broken)
+        .fetch_one(&self.pool)
+        .timed("count_urls")
+        .await
+    }
+
+    async fn set_description(
+        &self,
+        short_code: &str,
+        description: Option<&str>,
+    ) -> Result<Option<UrlDetail>, sqlx::Error> {
+        let row = sqlx::query_as::<_, LinkRow>(&format!(
+            "
+            UPDATE urls
+            SET description = ?2
+            WHERE short_code = ?1
+            RETURNING {URL_DETAIL_COLUMNS}
+            "
+        ))
+        .bind(short_code)
+        .bind(description)
+        .fetch_optional(&self.pool)
+        .timed("update_url")
+        .await?;
+        Ok(row.map(UrlDetail::from))
+    }
+
+    async fn delete(&self, short_code: &str) -> Result<bool, sqlx::Error> {
+        let deleted = sqlx::query("DELETE FROM urls WHERE short_code = ?1")
+            .bind(short_code)
+            .execute(&self.pool)
+            .timed("delete_url")
+            .await?
+            .rows_affected();
+        Ok(deleted > 0)
+    }
+
+    async fn use_once(&self, short_code: &str) -> Result<bool, sqlx::Error> {
+        let consumed = sqlx::query(
+            "
+            UPDATE urls
+            SET disabled_at = ?2, clicks = clicks + 1
+            WHERE short_code = ?1 AND single_use AND disabled_at IS NULL
+            ",
+        )
+        .bind(short_code)
+        .bind(Utc::now())
+        .execute(&self.pool)
+        .timed("consume_single_use")
+        .await?
+        .rows_affected();
+        Ok(consumed > 0)
+    }
+
+    async fn add_clicks(&self, short_code: &str, clicks: i64) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE urls SET clicks = clicks + ?2 WHERE short_code = ?1")
+            .bind(short_code)
+            .bind(clicks)
+            .execute(&self.pool)
+            .timed("record_click")
+            .await?;
+        Ok(())
+    }
+}
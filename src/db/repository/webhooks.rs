@@ -0,0 +1,84 @@
+use sqlx::PgPool;
+
+use crate::db::{
+    models::{Webhook, WebhookDelivery},
+    Timed,
+};
+
+pub async fn insert(
+    pool: &PgPool,
+    url: &str,
+    secret: &str,
+    events: &[String],
+) -> Result<Webhook, sqlx::Error> {
+    sqlx::query_as!(
+        Webhook,
+        "
+        INSERT INTO webhooks (url, secret, events)
+        VALUES ($1, $2, $3)
+        RETURNING id, url, secret, events, created_at
+        ",
+        url,
+        secret,
+        events
+    )
+    .fetch_one(pool)
+    .timed("insert_webhook")
+    .await
+}
+
+pub async fn list(pool: &PgPool) -> Result<Vec<Webhook>, sqlx::Error> {
+    sqlx::query_as!(
+        Webhook,
+        "SELECT id, url, secret, events, created_at FROM webhooks ORDER BY id"
+    )
+    .fetch_all(pool)
+    .timed("list_webhooks")
+    .await
+}
+
+// Remove a webhook with its deliveries, returning false if there was none
+pub async fn delete(pool: &PgPool, id: i32) -> Result<bool, sqlx::Error> {
+    Ok(sqlx::query!("DELETE FROM webhooks WHERE id = $1", id)
+        .execute(pool)
+        .timed("delete_webhook")
+        .await?
+        .rows_affected()
+        > 0)
+}
+
+pub async fn exists(pool: &PgPool, id: i32) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"SELECT EXISTS (SELECT 1 FROM webhooks WHERE id = $1) AS "exists!""#,
+        id
+    )
+    .fetch_one(pool)
+    .timed("webhook_exists")
+    .await
+}
+
+// Latest deliveries to a webhook, newest first, optionally only those with one status
+pub async fn deliveries(
+    pool: &PgPool,
+    id: i32,
+    status: Option<&str>,
+    limit: i64,
+) -> Result<Vec<WebhookDelivery>, sqlx::Error> {
+    sqlx::query_as!(
+        WebhookDelivery,
+        "
+        SELECT id, event, status, attempts, response_status, last_error, next_attempt_at,
+            created_at, delivered_at
+        FROM webhook_deliveries
+        WHERE webhook_id = $1 AND ($2::TEXT IS NULL OR status = $2)
+        ORDER BY id DESC
+        LIMIT $3
+        ",
+        id,
+        status,
+        limit
+    )
+    .fetch_all(pool)
+    .timed("list_webhook_deliveries")
+    .await
+}
@@ -0,0 +1,263 @@
+use std::time::Duration;
+
+use axum::{
+    extract::rejection::JsonRejection,
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+use serde_json::json;
+use thiserror::Error;
+use tracing::error;
+use utoipa::ToSchema;
+
+// Media type of error bodies
+pub const PROBLEM_JSON: &str = "application/problem+json";
+
+// Everything an API request can fail with. Each error is answered with an RFC 7807
+// problem details body whose `code` stays the same when the wording of `detail` changes
+#[derive(Debug, Error)]
+pub enum AppError {
+    #[error("{}", .0.body_text())]
+    InvalidJson(#[from] JsonRejection),
+    #[error("{0}")]
+    InvalidRequest(String),
+    #[error("Invalid URL format")]
+    InvalidUrl,
+    #[error("Invalid short code: {0}")]
+    InvalidShortCode(String),
+    #[error("URL must be at most {0} characters")]
+    UrlTooLong(usize),
+    #[error("{}", FieldError::summary(.0))]
+    Validation(Vec<FieldError>),
+    #[error("Destination must resolve to a public address")]
+    PrivateDestination,
+    #[error("Destination domain is blocked")]
+    BlockedDomain { domain: String },
+    #[error("URL is flagged as unsafe")]
+    UnsafeUrl { threat_type: String },
+    #[error("{0} not found")]
+    NotFound(&'static str),
+    #[error("Short URL is no longer available")]
+    Gone,
+    #[error("Short URL already exists")]
+    LinkExists {
+        short_code: String,
+        short_url: String,
+    },
+    #[error("{0}")]
+    Conflict(String),
+    #[error("Idempotency-Key was already used with a different request")]
+    IdempotencyKeyReused,
+    #[error("Request body too large")]
+    PayloadTooLarge,
+    #[error("{0}")]
+    Unauthorized(&'static str),
+    #[error("Admin API is disabled")]
+    AdminDisabled,
+    // Not a failure for REST callers, who get the 202 answer the review queue always gave
+    #[error("Creation is held for review")]
+    HeldForReview,
+    #[error("Too many requests, please try again later")]
+    Overloaded,
+    #[error("Database unavailable, please try again later")]
+    DatabaseUnavailable { retry_after: Duration },
+    #[error("Failed to fetch the destination: {0}")]
+    Upstream(String),
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+    #[error("Redis error: {0}")]
+    Redis(#[from] redis::RedisError),
+    #[error("{0}")]
+    Internal(String),
+}
+
+impl AppError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            // Bodies over REQUEST_BODY_LIMIT_BYTES keep their 413, other unreadable
+            // payloads are 400s
+            Self::InvalidJson(rejection) if rejection.status() == StatusCode::PAYLOAD_TOO_LARGE => {
+                StatusCode::PAYLOAD_TOO_LARGE
+            }
+            Self::InvalidJson(_)
+            | Self::InvalidRequest(_)
+            | Self::InvalidUrl
+            | Self::InvalidShortCode(_)
+            | Self::PrivateDestination
+            | Self::BlockedDomain { .. }
+            | Self::UnsafeUrl { .. } => StatusCode::BAD_REQUEST,
+            Self::UrlTooLong(_) | Self::Validation(_) | Self::IdempotencyKeyReused => {
+                StatusCode::UNPROCESSABLE_ENTITY
+            }
+            Self::NotFound(_) => StatusCode::NOT_FOUND,
+            Self::Gone => StatusCode::GONE,
+            Self::LinkExists { .. } | Self::Conflict(_) => StatusCode::CONFLICT,
+            Self::PayloadTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            Self::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            Self::AdminDisabled => StatusCode::FORBIDDEN,
+            Self::HeldForReview => StatusCode::ACCEPTED,
+            Self::Overloaded | Self::DatabaseUnavailable { .. } => StatusCode::SERVICE_UNAVAILABLE,
+            Self::Upstream(_) => StatusCode::BAD_GATEWAY,
+            Self::Database(_) | Self::Redis(_) | Self::Internal(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        }
+    }
+
+    // Stable identifier of the problem for clients to match on
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::InvalidJson(_) if self.status() == StatusCode::PAYLOAD_TOO_LARGE => {
+                "payload_too_large"
+            }
+            Self::InvalidJson(_) => "invalid_json",
+            Self::InvalidRequest(_) => "invalid_request",
+            Self::InvalidUrl => "invalid_url",
+            Self::InvalidShortCode(_) => "invalid_short_code",
+            Self::UrlTooLong(_) => "url_too_long",
+            Self::Validation(_) => "validation_failed",
+            Self::PrivateDestination => "private_destination",
+            Self::BlockedDomain { .. } => "blocked_domain",
+            Self::UnsafeUrl { .. } => "unsafe_url",
+            Self::NotFound(_) => "not_found",
+            Self::Gone => "link_gone",
+            Self::LinkExists { .. } => "link_exists",
+            Self::Conflict(_) => "conflict",
+            Self::IdempotencyKeyReused => "idempotency_key_reused",
+            Self::PayloadTooLarge => "payload_too_large",
+            Self::Unauthorized(_) => "unauthorized",
+            Self::AdminDisabled => "admin_disabled",
+            Self::HeldForReview => "held_for_review",
+            Self::Overloaded => "overloaded",
+            Self::DatabaseUnavailable { .. } => "database_unavailable",
+            Self::Upstream(_) => "upstream_error",
+            Self::Database(_) | Self::Redis(_) | Self::Internal(_) => "internal_error",
+        }
+    }
+
+    // Human-readable explanation; the causes of server errors are only logged
+    pub fn detail(&self) -> String {
+        match self {
+            Self::Upstream(_) => "Failed to fetch the destination".to_string(),
+            Self::Database(_) | Self::Redis(_) | Self::Internal(_) => {
+                "The request could not be completed".to_string()
+            }
+            _ => self.to_string(),
+        }
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        if let Self::Database(_) | Self::Redis(_) | Self::Internal(_) = self {
+            error!(error = %self, "Request failed");
+        }
+
+        if let Self::HeldForReview = self {
+            return (
+                StatusCode::ACCEPTED,
+                Json(json!({"message": "short url is pending review"})),
+            )
+                .into_response();
+        }
+
+        let status = self.status();
+        let mut problem = Problem {
+            type_: "about:blank",
+            title: status.canonical_reason().unwrap_or_default(),
+            status: status.as_u16(),
+            detail: self.detail(),
+            code: self.code(),
+            ..Problem::default()
+        };
+        match &self {
+            Self::BlockedDomain { domain } => problem.domain = Some(domain.clone()),
+            Self::UnsafeUrl { threat_type } => problem.threat_type = Some(threat_type.clone()),
+            Self::Validation(errors) => problem.errors = errors.clone(),
+            Self::LinkExists {
+                short_code,
+                short_url,
+            } => {
+                problem.short_code = Some(short_code.clone());
+                problem.short_url = Some(short_url.clone());
+            }
+            _ => {}
+        }
+
+        let mut response = (
+            status,
+            [(header::CONTENT_TYPE, PROBLEM_JSON)],
+            Json(problem),
+        )
+            .into_response();
+        if let Self::DatabaseUnavailable { retry_after } = self {
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, retry_after.as_secs().max(1).into());
+        }
+        response
+    }
+}
+
+// Body of every error response; the members after `code` are only present for the
+// problems that carry them
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct Problem {
+    #[serde(rename = "type")]
+    pub type_: &'static str,
+    pub title: &'static str,
+    pub status: u16,
+    pub detail: String,
+    pub code: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub domain: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub threat_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub short_code: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub short_url: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<FieldError>,
+}
+
+// Why one field of a request body was refused, with the field as a JSON path like
+// `variants[1].weight`
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+impl FieldError {
+    fn summary(errors: &[FieldError]) -> String {
+        errors
+            .iter()
+            .map(|error| format!("{}: {}", error.field, error.message))
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+}
+
+// Field errors collected while checking a request, so all of them are reported at once
+#[derive(Debug, Default)]
+pub struct FieldErrors(Vec<FieldError>);
+
+impl FieldErrors {
+    pub fn add(&mut self, field: impl Into<String>, message: impl Into<String>) {
+        self.0.push(FieldError {
+            field: field.into(),
+            message: message.into(),
+        });
+    }
+
+    pub fn into_result(self) -> Result<(), AppError> {
+        if self.0.is_empty() {
+            Ok(())
+        } else {
+            Err(AppError::Validation(self.0))
+        }
+    }
+}
@@ -0,0 +1,61 @@
+use std::time::Duration;
+
+use futures_util::StreamExt;
+use redis::{AsyncCommands, Client};
+use tokio::{sync::broadcast, time};
+use tracing::{error, warn};
+
+use crate::{state::RedisConn, types::ClickEvent};
+
+// Channel every instance publishes its redirects on for the live click stream
+const CLICKS_CHANNEL: &str = "clicks:live";
+
+const RESUBSCRIBE_DELAY: Duration = Duration::from_secs(1);
+
+// Clicks buffered per stream client before it starts missing them
+pub const CLICK_BUFFER: usize = 1024;
+
+pub async fn publish_click(redis_db: &RedisConn, click: &ClickEvent) {
+    let payload = match serde_json::to_string(click) {
+        Ok(payload) => payload,
+        Err(e) => {
+            error!(error = %e, "Failed to encode click event");
+            return;
+        }
+    };
+    let mut conn = redis_db.clone();
+    if let Err(e) = conn.publish::<_, _, ()>(CLICKS_CHANNEL, payload).await {
+        error!(error = %e, "Failed to publish click event");
+    }
+}
+
+// Pass the clicks published by all instances on to this instance's stream clients, for as
+// long as the process runs
+pub async fn listen(client: Client, clicks: broadcast::Sender<ClickEvent>) {
+    loop {
+        match client.get_async_pubsub().await {
+            Ok(mut pubsub) => match pubsub.subscribe(CLICKS_CHANNEL).await {
+                Ok(()) => {
+                    let mut messages = pubsub.on_message();
+                    while let Some(message) = messages.next().await {
+                        let click = message
+                            .get_payload::<String>()
+                            .map_err(|e| e.to_string())
+                            .and_then(|payload| {
+                                serde_json::from_str(&payload).map_err(|e| e.to_string())
+                            });
+                        match click {
+                            // Nobody may be listening, which is fine
+                            Ok(click) => drop(clicks.send(click)),
+                            Err(e) => warn!(error = %e, "Invalid click event"),
+                        }
+                    }
+                    warn!("Click event subscription closed");
+                }
+                Err(e) => error!(error = %e, "Failed to subscribe to click events"),
+            },
+            Err(e) => error!(error = %e, "Failed to connect for click events"),
+        }
+        time::sleep(RESUBSCRIBE_DELAY).await;
+    }
+}
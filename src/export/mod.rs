@@ -0,0 +1,131 @@
+use chrono::{DateTime, SecondsFormat, Utc};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::db::models::UrlDetail;
+
+// Columns of CSV exports, in the order of `ExportedLink`'s fields
+const CSV_HEADER: [&str; 10] = [
+    "short_code",
+    "long_url",
+    "tags",
+    "description",
+    "campaign_id",
+    "clicks",
+    "single_use",
+    "activates_at",
+    "disabled_at",
+    "created_at",
+];
+
+// A link as written by `tlong export` and the CSV and NDJSON API responses
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct ExportedLink {
+    pub short_code: String,
+    pub long_url: String,
+    pub tags: Vec<String>,
+    pub description: Option<String>,
+    pub campaign_id: Option<i32>,
+    pub clicks: i64,
+    pub single_use: bool,
+    pub activates_at: Option<DateTime<Utc>>,
+    pub disabled_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<UrlDetail> for ExportedLink {
+    fn from(detail: UrlDetail) -> Self {
+        Self {
+            short_code: detail.short_code,
+            long_url: detail.long_url,
+            tags: detail.tags,
+            description: detail.description,
+            campaign_id: detail.campaign_id,
+            clicks: detail.clicks,
+            single_use: detail.single_use,
+            activates_at: detail.activates_at,
+            disabled_at: detail.disabled_at,
+            created_at: detail.created_at,
+        }
+    }
+}
+
+impl ExportedLink {
+    // Tags share one column, separated by semicolons
+    fn csv_record(&self) -> [String; 10] {
+        // Same RFC 3339 form as the JSON lines
+        let timestamp = |value: DateTime<Utc>| value.to_rfc3339_opts(SecondsFormat::AutoSi, true);
+        [
+            self.short_code.clone(),
+            self.long_url.clone(),
+            self.tags.join(";"),
+            self.description.clone().unwrap_or_default(),
+            self.campaign_id
+                .map(|id| id.to_string())
+                .unwrap_or_default(),
+            self.clicks.to_string(),
+            self.single_use.to_string(),
+            self.activates_at.map(timestamp).unwrap_or_default(),
+            self.disabled_at.map(timestamp).unwrap_or_default(),
+            timestamp(self.created_at),
+        ]
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Ndjson,
+    Csv,
+}
+
+impl ExportFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Ndjson => "application/x-ndjson",
+            Self::Csv => "text/csv; charset=utf-8",
+        }
+    }
+}
+
+// Serializes links in one format, writing the CSV header before the first one
+pub struct Exporter {
+    format: ExportFormat,
+    started: bool,
+}
+
+impl Exporter {
+    pub fn new(format: ExportFormat) -> Self {
+        Self {
+            format,
+            started: false,
+        }
+    }
+
+    pub fn write(&mut self, links: &[ExportedLink]) -> Result<Vec<u8>, String> {
+        let mut out = Vec::new();
+        match self.format {
+            ExportFormat::Ndjson => {
+                for link in links {
+                    serde_json::to_writer(&mut out, link).map_err(|e| e.to_string())?;
+                    out.push(b'\n');
+                }
+            }
+            ExportFormat::Csv => {
+                let mut writer = csv::Writer::from_writer(&mut out);
+                if !self.started {
+                    writer.write_record(CSV_HEADER).map_err(|e| e.to_string())?;
+                }
+                for link in links {
+                    writer
+                        .write_record(link.csv_record())
+                        .map_err(|e| e.to_string())?;
+                }
+                writer.flush().map_err(|e| e.to_string())?;
+            }
+        }
+        self.started = true;
+        Ok(out)
+    }
+}
@@ -0,0 +1,48 @@
+use std::{fmt, net::IpAddr, path::Path};
+
+use maxminddb::{geoip2, MaxMindDBError, Reader};
+
+// Pseudo-region matching every member state of the European Union
+pub const EUROPEAN_UNION: &str = "EU";
+
+// Country lookups against a MaxMind GeoLite2/GeoIP2 country database
+pub struct GeoIp {
+    reader: Reader<Vec<u8>>,
+}
+
+impl GeoIp {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, MaxMindDBError> {
+        Ok(Self {
+            reader: Reader::open_readfile(path)?,
+        })
+    }
+
+    // Regions an address belongs to, most specific first: its country code, then `EU` if applicable
+    pub fn regions(&self, ip: IpAddr) -> Vec<String> {
+        let Ok(record) = self.reader.lookup::<geoip2::Country>(ip) else {
+            return Vec::new();
+        };
+        let Some(country) = record.country else {
+            return Vec::new();
+        };
+
+        let mut regions: Vec<String> = country.iso_code.map(str::to_string).into_iter().collect();
+        if country.is_in_european_union == Some(true) {
+            regions.push(EUROPEAN_UNION.to_string());
+        }
+        regions
+    }
+}
+
+impl fmt::Debug for GeoIp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GeoIp")
+            .field("database_type", &self.reader.metadata.database_type)
+            .finish()
+    }
+}
+
+// Region keys are ISO 3166-1 alpha-2 country codes or `EU`
+pub fn valid_region(region: &str) -> bool {
+    region.len() == 2 && region.chars().all(|c| c.is_ascii_alphabetic())
+}
@@ -0,0 +1,260 @@
+use std::sync::LazyLock;
+
+use async_graphql::{
+    Context, EmptySubscription, ErrorExtensions, InputObject, Object, ResultExt, Schema,
+    SimpleObject,
+};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde_json::json;
+use tracing::error;
+
+use crate::{
+    abuse::Creator,
+    api::handlers,
+    db::models::UrlDetail,
+    error::AppError,
+    state::AppState,
+    types::{
+        CampaignStatsResponse, ListQuery, PageQuery, Pagination, ShortenRequest, TagCount,
+        UtmParams, VariantStats,
+    },
+};
+
+// Deepest selection and most fields a single query may ask for
+const MAX_DEPTH: usize = 8;
+const MAX_COMPLEXITY: usize = 500;
+
+pub type TlongSchema = Schema<Query, Mutation, EmptySubscription>;
+
+// Built once; every request brings the application state and its creator along as
+// context data
+pub static SCHEMA: LazyLock<TlongSchema> = LazyLock::new(|| {
+    Schema::build(Query, Mutation, EmptySubscription)
+        .limit_depth(MAX_DEPTH)
+        .limit_complexity(MAX_COMPLEXITY)
+        .finish()
+});
+
+pub struct Query;
+
+#[Object]
+impl Query {
+    // A link by its short code
+    async fn link(&self, ctx: &Context<'_>, short_code: String) -> async_graphql::Result<Link> {
+        let state = ctx.data::<AppState>()?;
+        let detail = handlers::find_link(state, &short_code).await.extend()?;
+        Ok(Link(detail))
+    }
+
+    // Links matching the filters, newest first, one page at a time
+    async fn links(
+        &self,
+        ctx: &Context<'_>,
+        tag: Option<String>,
+        q: Option<String>,
+        broken: Option<bool>,
+        page: Option<u32>,
+        per_page: Option<u32>,
+    ) -> async_graphql::Result<LinkPage> {
+        let state = ctx.data::<AppState>()?;
+        let params = ListQuery { tag, q, broken };
+        let (links, pagination) =
+            handlers::links_page(state, &params, &PageQuery { page, per_page })
+                .await
+                .extend()?;
+        Ok(LinkPage {
+            items: links.into_iter().map(Link).collect(),
+            pagination,
+        })
+    }
+
+    // Tags with the number of links using them, most used first
+    async fn tags(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<TagCount>> {
+        let state = ctx.data::<AppState>()?.clone();
+        let Json(tags) = handlers::get_all_tags(State(state)).await.extend()?;
+        Ok(tags)
+    }
+
+    // Clicks of a campaign and its most clicked links
+    async fn campaign_stats(
+        &self,
+        ctx: &Context<'_>,
+        id: i32,
+    ) -> async_graphql::Result<CampaignStatsResponse> {
+        let state = ctx.data::<AppState>()?.clone();
+        let Json(stats) = handlers::get_campaign_stats(State(state), Path(id))
+            .await
+            .extend()?;
+        Ok(stats)
+    }
+}
+
+pub struct Mutation;
+
+#[Object]
+impl Mutation {
+    // Create a short link, or return the existing one as DUPLICATE_POLICY allows
+    async fn shorten(
+        &self,
+        ctx: &Context<'_>,
+        input: ShortenInput,
+    ) -> async_graphql::Result<ShortenResult> {
+        let state = ctx.data::<AppState>()?;
+        // Only REST creations carry a signature
+        if state.require_signed_creation {
+            return Err(AppError::Unauthorized(handlers::UNSIGNED_CREATION).extend());
+        }
+        let payload = ShortenRequest {
+            long_url: input.long_url,
+            utm: UtmParams::default(),
+            activates_at: input.activates_at,
+            single_use: input.single_use,
+            tags: input.tags,
+            description: input.description,
+            variants: Vec::new(),
+            geo_targets: Default::default(),
+            device_targets: Default::default(),
+            time_rules: Vec::new(),
+            deep_link: None,
+            reuse_existing: input.reuse_existing,
+        };
+
+        // Each aliased mutation in a request is scored on its own
+        let creator = ctx.data::<Creator>()?;
+        let (status, link) = handlers::shorten(state, payload, creator).await.extend()?;
+        Ok(ShortenResult {
+            short_code: link.short_code,
+            short_url: link.short_url,
+            long_url: link.long_url,
+            created: status == StatusCode::CREATED,
+        })
+    }
+
+    // Delete a link, or with `purge` also its click history
+    async fn delete(
+        &self,
+        ctx: &Context<'_>,
+        short_code: String,
+        #[graphql(default)] purge: bool,
+    ) -> async_graphql::Result<bool> {
+        let state = ctx.data::<AppState>()?;
+        handlers::delete_link(state, &short_code, purge)
+            .await
+            .extend()?;
+        Ok(true)
+    }
+}
+
+pub struct Link(UrlDetail);
+
+#[Object]
+impl Link {
+    async fn short_code(&self) -> &str {
+        &self.0.short_code
+    }
+
+    async fn short_url(&self, ctx: &Context<'_>) -> async_graphql::Result<String> {
+        let state = ctx.data::<AppState>()?;
+        Ok(format!("{}/{}", state.base_url, self.0.short_code))
+    }
+
+    async fn long_url(&self) -> &str {
+        &self.0.long_url
+    }
+
+    async fn tags(&self) -> &[String] {
+        &self.0.tags
+    }
+
+    async fn description(&self) -> Option<&str> {
+        self.0.description.as_deref()
+    }
+
+    async fn campaign_id(&self) -> Option<i32> {
+        self.0.campaign_id
+    }
+
+    async fn clicks(&self) -> i64 {
+        self.0.clicks
+    }
+
+    async fn single_use(&self) -> bool {
+        self.0.single_use
+    }
+
+    async fn activates_at(&self) -> Option<DateTime<Utc>> {
+        self.0.activates_at
+    }
+
+    async fn disabled_at(&self) -> Option<DateTime<Utc>> {
+        self.0.disabled_at
+    }
+
+    async fn created_at(&self) -> DateTime<Utc> {
+        self.0.created_at
+    }
+
+    // Set while the link serves an error page instead of its unreachable destination
+    async fn broken_at(&self) -> Option<DateTime<Utc>> {
+        self.0.broken_at
+    }
+
+    // A/B split destinations with their clicks, only loaded when selected
+    async fn variants(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<VariantStats>> {
+        let state = ctx.data::<AppState>()?;
+        handlers::link_variants(state, &self.0.short_code)
+            .await
+            .extend()
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct LinkPage {
+    items: Vec<Link>,
+    pagination: Pagination,
+}
+
+#[derive(InputObject)]
+pub struct ShortenInput {
+    long_url: String,
+    #[graphql(default)]
+    tags: Vec<String>,
+    description: Option<String>,
+    #[graphql(default)]
+    single_use: bool,
+    activates_at: Option<DateTime<Utc>>,
+    // Overrides DUPLICATE_POLICY like `reuse_existing` in the REST API
+    reuse_existing: Option<bool>,
+}
+
+#[derive(SimpleObject)]
+pub struct ShortenResult {
+    short_code: String,
+    short_url: String,
+    long_url: String,
+    // False when an existing link to the destination was returned
+    created: bool,
+}
+
+// Errors keep the problem `detail` as their message and its `code` as an extension
+impl ErrorExtensions for AppError {
+    fn extend(&self) -> async_graphql::Error {
+        if let Self::Database(_) | Self::Redis(_) | Self::Internal(_) = self {
+            error!(error = %self, "GraphQL request failed");
+        }
+        async_graphql::Error::new(self.detail()).extend_with(|_, extensions| {
+            extensions.set("code", self.code());
+            extensions.set("status", self.status().as_u16());
+            if let Self::Validation(errors) = self {
+                if let Ok(errors) = async_graphql::Value::from_json(json!(errors)) {
+                    extensions.set("errors", errors);
+                }
+            }
+        })
+    }
+}
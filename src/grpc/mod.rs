@@ -0,0 +1,168 @@
+use axum::http::Extensions;
+use tonic::{Code, Request, Response, Status};
+use tracing::{error, instrument};
+
+use crate::{
+    api::{handlers, middleware},
+    db::models::UrlDetail,
+    error::AppError,
+    state::AppState,
+    types::{ListQuery, PageQuery, ShortenRequest, UtmParams},
+};
+
+pub mod proto {
+    tonic::include_proto!("tlong.v1");
+}
+
+use proto::links_server::{Links, LinksServer};
+
+// gRPC counterpart of the link endpoints, for internal services that prefer protobuf
+pub struct LinkService {
+    state: AppState,
+}
+
+pub fn service(state: AppState) -> LinksServer<LinkService> {
+    LinksServer::new(LinkService { state })
+}
+
+#[tonic::async_trait]
+impl Links for LinkService {
+    #[instrument(skip(self, request))]
+    async fn shorten(
+        &self,
+        request: Request<proto::ShortenRequest>,
+    ) -> Result<Response<proto::ShortenResponse>, Status> {
+        // Only REST creations carry a signature
+        if self.state.require_signed_creation {
+            return Err(AppError::Unauthorized(handlers::UNSIGNED_CREATION).into());
+        }
+        // Metadata carries the admin token and forwarding headers as REST headers would
+        let creator = middleware::creator(
+            &self.state,
+            &request.metadata().clone().into_headers(),
+            &Extensions::new(),
+            request.remote_addr(),
+        );
+        let request = request.into_inner();
+        let payload = ShortenRequest {
+            long_url: request.long_url,
+            utm: UtmParams::default(),
+            activates_at: None,
+            single_use: request.single_use,
+            tags: request.tags,
+            description: request.description,
+            variants: Vec::new(),
+            geo_targets: Default::default(),
+            device_targets: Default::default(),
+            time_rules: Vec::new(),
+            deep_link: None,
+            reuse_existing: request.reuse_existing,
+        };
+
+        let (status, link) = handlers::shorten(&self.state, payload, &creator).await?;
+        Ok(Response::new(proto::ShortenResponse {
+            short_code: link.short_code,
+            short_url: link.short_url,
+            long_url: link.long_url,
+            created: status == axum::http::StatusCode::CREATED,
+        }))
+    }
+
+    #[instrument(skip(self, request))]
+    async fn resolve(
+        &self,
+        request: Request<proto::ResolveRequest>,
+    ) -> Result<Response<proto::ResolveResponse>, Status> {
+        let expanded = handlers::expand(&self.state, request.into_inner().short_code).await?;
+        Ok(Response::new(proto::ResolveResponse {
+            short_code: expanded.short_code,
+            short_url: expanded.short_url,
+            long_url: expanded.long_url,
+        }))
+    }
+
+    #[instrument(skip(self, request))]
+    async fn delete(
+        &self,
+        request: Request<proto::DeleteRequest>,
+    ) -> Result<Response<proto::DeleteResponse>, Status> {
+        let request = request.into_inner();
+        handlers::delete_link(&self.state, &request.short_code, request.purge).await?;
+        Ok(Response::new(proto::DeleteResponse {}))
+    }
+
+    #[instrument(skip(self, request))]
+    async fn list_links(
+        &self,
+        request: Request<proto::ListLinksRequest>,
+    ) -> Result<Response<proto::ListLinksResponse>, Status> {
+        let request = request.into_inner();
+        let params = ListQuery {
+            tag: request.tag,
+            q: request.q,
+            broken: request.broken,
+        };
+        // Unset numbers arrive as 0 and fall back to the defaults
+        let page = PageQuery {
+            page: Some(request.page).filter(|page| *page > 0),
+            per_page: Some(request.per_page).filter(|per_page| *per_page > 0),
+        };
+
+        let (links, pagination) = handlers::links_page(&self.state, &params, &page).await?;
+        Ok(Response::new(proto::ListLinksResponse {
+            links: links.into_iter().map(|link| self.link(link)).collect(),
+            total: pagination.total,
+            page: pagination.page,
+            per_page: pagination.per_page,
+        }))
+    }
+}
+
+impl LinkService {
+    fn link(&self, detail: UrlDetail) -> proto::Link {
+        proto::Link {
+            short_url: format!("{}/{}", self.state.base_url, detail.short_code),
+            short_code: detail.short_code,
+            long_url: detail.long_url,
+            tags: detail.tags,
+            description: detail.description,
+            clicks: detail.clicks,
+            single_use: detail.single_use,
+            created_at: detail.created_at.to_rfc3339(),
+            disabled_at: detail
+                .disabled_at
+                .map(|disabled_at| disabled_at.to_rfc3339()),
+        }
+    }
+}
+
+// The REST problem codes mapped onto gRPC status codes, with the same details
+impl From<AppError> for Status {
+    fn from(error: AppError) -> Self {
+        let code = match &error {
+            AppError::InvalidJson(_)
+            | AppError::InvalidRequest(_)
+            | AppError::InvalidUrl
+            | AppError::InvalidShortCode(_)
+            | AppError::UrlTooLong(_)
+            | AppError::Validation(_)
+            | AppError::PrivateDestination
+            | AppError::BlockedDomain { .. }
+            | AppError::UnsafeUrl { .. }
+            | AppError::PayloadTooLarge
+            | AppError::IdempotencyKeyReused => Code::InvalidArgument,
+            AppError::NotFound(_) => Code::NotFound,
+            AppError::Gone | AppError::HeldForReview => Code::FailedPrecondition,
+            AppError::LinkExists { .. } | AppError::Conflict(_) => Code::AlreadyExists,
+            AppError::Unauthorized(_) => Code::Unauthenticated,
+            AppError::AdminDisabled => Code::PermissionDenied,
+            AppError::Overloaded => Code::ResourceExhausted,
+            AppError::DatabaseUnavailable { .. } | AppError::Upstream(_) => Code::Unavailable,
+            AppError::Database(_) | AppError::Redis(_) | AppError::Internal(_) => {
+                error!(error = %error, "gRPC request failed");
+                Code::Internal
+            }
+        };
+        Status::new(code, error.detail())
+    }
+}
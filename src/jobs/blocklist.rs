@@ -0,0 +1,21 @@
+use std::time::Duration;
+
+use tokio::time::{self, MissedTickBehavior};
+use tracing::{debug, error};
+
+use crate::state::AppState;
+
+// Periodically reload the blocklist so bans made through other instances take effect
+pub async fn refresh(state: AppState, interval: Duration) {
+    let mut ticker = time::interval(interval);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    // The blocklist was loaded at startup
+    ticker.tick().await;
+    loop {
+        ticker.tick().await;
+        match state.blocklist.reload(&state.pg_db).await {
+            Ok(domains) => debug!(domains, "Reloaded blocklist"),
+            Err(e) => error!(error = %e, "Failed to reload blocklist"),
+        }
+    }
+}
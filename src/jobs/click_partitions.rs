@@ -0,0 +1,57 @@
+use std::time::Duration;
+
+use chrono::{Datelike, Months, NaiveDate, Utc};
+use tokio::time::{self, MissedTickBehavior};
+use tracing::{error, info, warn};
+
+use crate::{db::repository, state::AppState};
+
+// How often the partitions of the clicks table are checked
+const INTERVAL: Duration = Duration::from_secs(3600);
+
+// Months after the current one that already have a partition, so clicks can be stored
+// across a month change while the job is not running
+const MONTHS_AHEAD: u32 = 2;
+
+// Periodically create the partitions of the coming months and drop those of months older
+// than `retention_months`
+pub async fn run(state: AppState, retention_months: Option<u32>) {
+    let mut ticker = time::interval(INTERVAL);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        ticker.tick().await;
+        if let Err(e) = maintain(&state, retention_months).await {
+            error!(error = %e, "Failed to maintain click partitions");
+        }
+    }
+}
+
+async fn maintain(state: &AppState, retention_months: Option<u32>) -> Result<(), String> {
+    let today = Utc::now().date_naive();
+    let this_month =
+        NaiveDate::from_ymd_opt(today.year(), today.month(), 1).ok_or("Invalid current month")?;
+    let until = this_month
+        .checked_add_months(Months::new(MONTHS_AHEAD))
+        .ok_or("Invalid partition month")?;
+    // The current month and the `retention_months - 1` before it are kept whole
+    let keep_from = retention_months
+        .map(|months| this_month.checked_sub_months(Months::new(months - 1)))
+        .map(|month| month.ok_or("Invalid retention month"))
+        .transpose()?;
+
+    let changes =
+        repository::clicks::maintain_partitions(&state.pg_db, this_month, until, keep_from)
+            .await
+            .map_err(|e| e.to_string())?;
+    // Clicks only end up in the default partition while this job is behind
+    if changes.moved > 0 {
+        warn!(
+            clicks = changes.moved,
+            "Moved clicks stored before their partition existed out of the default partition"
+        );
+    }
+    for partition in changes.dropped {
+        info!(partition = %partition, "Dropped expired click partition");
+    }
+    Ok(())
+}
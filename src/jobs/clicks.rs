@@ -0,0 +1,91 @@
+use std::time::Duration;
+
+use tokio::time::{self, Instant, MissedTickBehavior};
+use tracing::{debug, error};
+
+use crate::{
+    cache,
+    db::{repository, Timed},
+    state::AppState,
+};
+
+// Periodically write the redirects counted in Redis to the links' click counts and
+// the click events table
+pub async fn run(state: AppState, interval: Duration) {
+    let mut ticker = time::interval_at(Instant::now() + interval, interval);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        ticker.tick().await;
+        match flush(&state).await {
+            Ok(0) => {}
+            Ok(links) => debug!(links, "Flushed click counts"),
+            Err(e) => error!(error = %e, "Failed to flush click counts"),
+        }
+    }
+}
+
+// Write the counted redirects and then their click events, returning the number of
+// links updated
+pub async fn flush(state: &AppState) -> Result<usize, String> {
+    let links = flush_counts(state).await?;
+    flush_events(state).await?;
+    Ok(links)
+}
+
+// Add the counted redirects to the database in one statement, returning the number of
+// links updated; counts are put back for the next flush if the write fails
+async fn flush_counts(state: &AppState) -> Result<usize, String> {
+    let clicks = cache::take_clicks(&state.redis_db)
+        .await
+        .map_err(|e| e.to_string())?;
+    if clicks.is_empty() {
+        return Ok(0);
+    }
+
+    let (short_codes, counts): (Vec<&str>, Vec<i64>) = clicks
+        .iter()
+        .map(|(short_code, count)| (short_code.as_str(), *count))
+        .unzip();
+    let result = sqlx::query!(
+        "
+        UPDATE urls
+        SET clicks = urls.clicks + counted.clicks
+        FROM UNNEST($1::TEXT[], $2::BIGINT[]) AS counted (short_code, clicks)
+        WHERE urls.short_code = counted.short_code
+        ",
+        &short_codes as &[&str],
+        &counts
+    )
+    .execute(&state.pg_db)
+    .timed("flush_clicks")
+    .await;
+
+    match result {
+        Ok(_) => Ok(clicks.len()),
+        Err(e) => {
+            if let Err(restore_error) = cache::restore_clicks(&state.redis_db, &clicks).await {
+                error!(error = %restore_error, links = clicks.len(), "Lost click counts");
+            }
+            Err(e.to_string())
+        }
+    }
+}
+
+// Store the queued click events in one statement; they are queued again for the next
+// flush if the write fails
+async fn flush_events(state: &AppState) -> Result<(), String> {
+    let events = cache::take_click_events(&state.redis_db)
+        .await
+        .map_err(|e| e.to_string())?;
+    if events.is_empty() {
+        return Ok(());
+    }
+
+    if let Err(e) = repository::clicks::insert_many(&state.pg_db, &events).await {
+        if let Err(restore_error) = cache::restore_click_events(&state.redis_db, &events).await {
+            error!(error = %restore_error, clicks = events.len(), "Lost click events");
+        }
+        return Err(e.to_string());
+    }
+    Ok(())
+}
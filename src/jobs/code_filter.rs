@@ -0,0 +1,22 @@
+use std::{sync::Arc, time::Duration};
+
+use tokio::time::{self, MissedTickBehavior};
+use tracing::{debug, error};
+
+use crate::{cache::filter::CodeFilter, state::AppState};
+
+// Periodically rebuild the code filter, picking up links created through other
+// instances and resizing it as the number of links grows
+pub async fn refresh(state: AppState, code_filter: Arc<CodeFilter>, interval: Duration) {
+    let mut ticker = time::interval(interval);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    // The filter was loaded at startup
+    ticker.tick().await;
+    loop {
+        ticker.tick().await;
+        match code_filter.reload(&state.pg_db).await {
+            Ok(codes) => debug!(codes, "Rebuilt code filter"),
+            Err(e) => error!(error = %e, "Failed to rebuild code filter"),
+        }
+    }
+}
@@ -0,0 +1,54 @@
+use std::{sync::Arc, time::Duration};
+
+use chrono::Utc;
+use tokio::time::{self, MissedTickBehavior};
+
+use crate::{
+    dashboard::{Dashboard, TOP_CODES},
+    state::AppState,
+    types::{CodeRedirects, DashboardSnapshot},
+};
+
+// Every `interval`, sum up this instance's traffic since the previous snapshot and
+// broadcast it to the connected dashboards
+pub async fn run(state: AppState, dashboard: Arc<Dashboard>, interval: Duration) {
+    let mut ticker = time::interval(interval);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    ticker.tick().await;
+    let mut cache_counts = state.cache_stats.counts();
+    loop {
+        ticker.tick().await;
+        let redirects = dashboard.take_redirects();
+        let counts = state.cache_stats.counts();
+        let window = counts.since(&cache_counts);
+        cache_counts = counts;
+        if dashboard.snapshots.receiver_count() == 0 {
+            continue;
+        }
+
+        let total: u64 = redirects.values().sum();
+        let mut top_codes: Vec<CodeRedirects> = redirects
+            .into_iter()
+            .map(|(short_code, redirects)| CodeRedirects {
+                short_code,
+                redirects,
+            })
+            .collect();
+        top_codes.sort_unstable_by(|a, b| {
+            b.redirects
+                .cmp(&a.redirects)
+                .then_with(|| a.short_code.cmp(&b.short_code))
+        });
+        top_codes.truncate(TOP_CODES);
+
+        // Nobody may be connected anymore, which is fine
+        drop(dashboard.snapshots.send(DashboardSnapshot {
+            timestamp: Utc::now(),
+            interval_seconds: interval.as_secs(),
+            redirects: total,
+            redirects_per_second: total as f64 / interval.as_secs_f64(),
+            top_codes,
+            cache_hit_ratio: window.hit_ratio(),
+        }));
+    }
+}
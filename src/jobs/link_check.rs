@@ -0,0 +1,227 @@
+use std::time::Duration;
+
+use reqwest::header::CONTENT_TYPE;
+use serde_json::json;
+use tokio::time::{self, MissedTickBehavior};
+use tracing::{debug, error, info, warn};
+
+use crate::{cache, db::Timed, state::AppState, utils::resolve};
+
+// Links read per query; their results are stored together
+const BATCH_SIZE: i64 = 100;
+
+#[derive(sqlx::FromRow)]
+struct Link {
+    short_code: String,
+    long_url: String,
+    target_failures: i32,
+    broken: bool,
+}
+
+// Link that stopped redirecting during a check
+struct BrokenLink {
+    short_code: String,
+    long_url: String,
+    status: Option<i16>,
+    error: Option<String>,
+    failures: i32,
+}
+
+// Whether a check shows the destination is gone for good: the page does not exist or
+// its host is unknown. Timeouts, server errors and the like may pass and count for nothing.
+fn is_dead(status: Option<i16>, error: Option<&str>) -> bool {
+    match (status, error) {
+        (Some(status), _) => status == 404 || status == 410,
+        (None, Some(error)) => {
+            error.contains("Name or service not known")
+                || error.contains("No address associated with hostname")
+                || error.contains("nodename nor servname provided")
+        }
+        (None, None) => false,
+    }
+}
+
+// Periodically request the destinations of enabled links that were not checked recently
+pub async fn run(state: AppState, interval: Duration, rate: u32) {
+    let mut ticker = time::interval(interval);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        ticker.tick().await;
+        match check_stale(&state, interval, rate).await {
+            Ok(0) => {}
+            Ok(broken) => info!(broken, "Dead link check found broken links"),
+            Err(e) => error!(error = %e, "Dead link check failed"),
+        }
+    }
+}
+
+// Check links in batches until none are stale, at most `rate` per second, returning the
+// number found broken
+async fn check_stale(state: &AppState, interval: Duration, rate: u32) -> Result<usize, String> {
+    let stale_before =
+        chrono::Utc::now() - chrono::Duration::from_std(interval).map_err(|e| e.to_string())?;
+    let mut pacing = time::interval(Duration::from_secs(1) / rate);
+    pacing.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    let mut broken = 0;
+    loop {
+        let links = sqlx::query_as!(
+            Link,
+            r#"
+            SELECT short_code, long_url, target_failures, broken_at IS NOT NULL AS "broken!"
+            FROM urls
+            WHERE disabled_at IS NULL
+            AND (last_checked_at IS NULL OR last_checked_at < $1)
+            ORDER BY last_checked_at NULLS FIRST, short_code
+            LIMIT $2
+            "#,
+            stale_before,
+            BATCH_SIZE
+        )
+        .fetch_all(&state.pg_db)
+        .timed("unchecked_links")
+        .await
+        .map_err(|e| e.to_string())?;
+        if links.is_empty() {
+            return Ok(broken);
+        }
+
+        let mut short_codes = Vec::with_capacity(links.len());
+        let mut statuses = Vec::with_capacity(links.len());
+        let mut errors = Vec::with_capacity(links.len());
+        let mut failures = Vec::with_capacity(links.len());
+        let mut broken_flags = Vec::with_capacity(links.len());
+        let mut recovered = 0;
+        let mut newly_broken = Vec::new();
+        for link in links {
+            pacing.tick().await;
+            let (status, error) = match resolve::final_status(
+                &state.resolver_client,
+                &link.long_url,
+                state.max_redirect_hops,
+                state.ssrf_dns_check,
+            )
+            .await
+            {
+                Ok(status) => (Some(status.as_u16() as i16), None),
+                Err(e) => (None, Some(e)),
+            };
+            if status.is_none_or(|status| status >= 400) {
+                debug!(short_code = %link.short_code, status, error, "Broken link");
+                broken += 1;
+            }
+
+            // Links stop redirecting after enough dead results in a row and start again
+            // once their destination answers
+            let threshold = state.link_check_break_after;
+            let (link_failures, link_broken) = if status.is_some_and(|status| status < 400) {
+                (0, false)
+            } else if is_dead(status, error.as_deref()) {
+                let link_failures = link.target_failures.saturating_add(1);
+                let reached = threshold > 0 && link_failures as u32 >= threshold;
+                (link_failures, link.broken || reached)
+            } else {
+                (link.target_failures, link.broken)
+            };
+            if link_broken && !link.broken {
+                newly_broken.push(BrokenLink {
+                    short_code: link.short_code.clone(),
+                    long_url: link.long_url,
+                    status,
+                    error: error.clone(),
+                    failures: link_failures,
+                });
+            } else if link.broken && !link_broken {
+                info!(short_code = %link.short_code, "Broken link destination is back");
+                recovered += 1;
+            }
+
+            short_codes.push(link.short_code);
+            statuses.push(status);
+            errors.push(error);
+            failures.push(link_failures);
+            broken_flags.push(link_broken);
+        }
+
+        sqlx::query!(
+            "
+            UPDATE urls
+            SET last_checked_at = CURRENT_TIMESTAMP,
+                target_status = checked.target_status,
+                target_error = checked.target_error,
+                target_failures = checked.target_failures,
+                broken_at = CASE
+                    WHEN checked.broken THEN COALESCE(urls.broken_at, CURRENT_TIMESTAMP)
+                END
+            FROM UNNEST($1::TEXT[], $2::SMALLINT[], $3::TEXT[], $4::INTEGER[], $5::BOOLEAN[])
+                AS checked (short_code, target_status, target_error, target_failures, broken)
+            WHERE urls.short_code = checked.short_code
+            ",
+            &short_codes,
+            &statuses as &[Option<i16>],
+            &errors as &[Option<String>],
+            &failures,
+            &broken_flags
+        )
+        .execute(&state.pg_db)
+        .timed("record_link_checks")
+        .await
+        .map_err(|e| e.to_string())?;
+
+        // Links that broke were cached as plain redirects; ones that recovered are cached
+        // again on their next redirect
+        if !newly_broken.is_empty() {
+            let short_codes: Vec<String> = newly_broken
+                .iter()
+                .map(|link| link.short_code.clone())
+                .collect();
+            cache::evict_links(&state.redis_db, &short_codes).await;
+            if let Some(local_cache) = &state.local_cache {
+                for short_code in &short_codes {
+                    local_cache.invalidate(short_code);
+                }
+            }
+        }
+        if !newly_broken.is_empty() || recovered > 0 {
+            cache::invalidate_responses(&state.redis_db).await;
+        }
+
+        for link in newly_broken {
+            warn!(
+                short_code = %link.short_code,
+                status = link.status,
+                error = link.error,
+                failures = link.failures,
+                "Link stopped redirecting to a dead destination"
+            );
+            if let Some(webhook_url) = &state.link_check_webhook_url {
+                notify(state, webhook_url, &link).await;
+            }
+        }
+    }
+}
+
+// Tell the operator a link stopped redirecting; failures are only logged, the link stays
+// broken either way
+async fn notify(state: &AppState, webhook_url: &str, link: &BrokenLink) {
+    let body = json!({
+        "event": "link.broken",
+        "short_code": link.short_code,
+        "short_url": format!("{}/{}", state.base_url, link.short_code),
+        "long_url": link.long_url,
+        "target_status": link.status,
+        "target_error": link.error,
+        "consecutive_failures": link.failures,
+    });
+    let result = state
+        .http_client
+        .post(webhook_url)
+        .header(CONTENT_TYPE, "application/json")
+        .body(body.to_string())
+        .send()
+        .await
+        .and_then(|response| response.error_for_status());
+    if let Err(e) = result {
+        error!(error = %e, short_code = %link.short_code, "Broken link webhook failed");
+    }
+}
@@ -0,0 +1,92 @@
+use tracing::info;
+
+use crate::state::AppState;
+
+mod blocklist;
+mod click_partitions;
+pub mod clicks;
+mod code_filter;
+mod dashboard;
+mod link_check;
+pub mod purge;
+mod safe_browsing;
+mod warm;
+mod webhooks;
+
+// Start the enabled background jobs; they run until the process exits
+pub fn spawn(state: &AppState) {
+    if let Some(interval) = state.blocklist_refresh_interval {
+        tokio::spawn(blocklist::refresh(state.clone(), interval));
+    }
+
+    if let (Some(code_filter), Some(interval)) =
+        (&state.code_filter, state.code_filter_refresh_interval)
+    {
+        tokio::spawn(code_filter::refresh(
+            state.clone(),
+            code_filter.clone(),
+            interval,
+        ));
+    }
+
+    if let Some(interval) = state
+        .safe_browsing
+        .as_ref()
+        .and_then(|safe_browsing| safe_browsing.recheck_interval)
+    {
+        info!(interval = ?interval, "Starting Safe Browsing re-check job");
+        tokio::spawn(safe_browsing::recheck(state.clone(), interval));
+    }
+
+    if let Some(interval) = state.link_check_interval {
+        info!(interval = ?interval, "Starting dead link check job");
+        tokio::spawn(link_check::run(
+            state.clone(),
+            interval,
+            state.link_check_rate,
+        ));
+    }
+
+    // Clicks are always stored, so their table always needs partitions for the coming months
+    info!(retention_months = ?state.click_retention_months, "Starting click partition job");
+    tokio::spawn(click_partitions::run(
+        state.clone(),
+        state.click_retention_months,
+    ));
+
+    if let Some(interval) = state.click_flush_interval {
+        info!(interval = ?interval, "Starting click flush job");
+        tokio::spawn(clicks::run(state.clone(), interval));
+    }
+
+    if state.cache_warm_links > 0 {
+        info!(links = state.cache_warm_links, interval = ?state.cache_warm_interval, "Starting cache warming job");
+        tokio::spawn(warm::run(
+            state.clone(),
+            state.cache_warm_interval,
+            state.cache_warm_links,
+        ));
+    }
+
+    if let Some(interval) = state.webhook_interval {
+        info!(interval = ?interval, "Starting webhook delivery job");
+        tokio::spawn(webhooks::run(
+            state.clone(),
+            interval,
+            state.webhook_max_attempts,
+        ));
+    }
+
+    if let (Some(dashboard), Some(interval)) = (&state.dashboard, state.dashboard_interval) {
+        tokio::spawn(dashboard::run(state.clone(), dashboard.clone(), interval));
+    }
+
+    if let Some(interval) = state.purge_interval {
+        info!(interval = ?interval, "Starting disabled link purge job");
+        tokio::spawn(purge::run(
+            state.clone(),
+            interval,
+            state.purge_retention_days,
+        ));
+    }
+}
@@ -0,0 +1,62 @@
+use std::time::Duration;
+
+use tokio::time::{self, MissedTickBehavior};
+use tracing::{error, info};
+
+use crate::{cache, db::Timed, state::AppState};
+
+// Links deleted per statement, so a large purge never holds long locks
+const PURGE_BATCH_SIZE: i64 = 1000;
+
+// Periodically delete links disabled more than `retention_days` ago
+pub async fn run(state: AppState, interval: Duration, retention_days: u32) {
+    let mut ticker = time::interval(interval);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        ticker.tick().await;
+        match delete_disabled(&state, retention_days).await {
+            Ok(0) => {}
+            Ok(deleted) => info!(deleted, "Purged disabled links"),
+            Err(e) => error!(error = %e, "Failed to purge disabled links"),
+        }
+    }
+}
+
+// Delete links whose `disabled_at`, e.g. the first visit of a single-use link, is at least
+// `older_than_days` old, in batches, evicting each batch from the cache
+pub async fn delete_disabled(state: &AppState, older_than_days: u32) -> Result<usize, String> {
+    let mut deleted = 0;
+    loop {
+        let short_codes = sqlx::query_scalar!(
+            "
+            DELETE FROM urls
+            WHERE short_code IN (
+                SELECT short_code FROM urls
+                WHERE disabled_at < CURRENT_TIMESTAMP - make_interval(days => $1)
+                LIMIT $2
+            )
+            RETURNING short_code
+            ",
+            older_than_days as i32,
+            PURGE_BATCH_SIZE
+        )
+        .fetch_all(&state.pg_db)
+        .timed("delete_disabled")
+        .await
+        .map_err(|e| format!("Failed to delete expired links: {e}"))?;
+
+        if short_codes.is_empty() {
+            break;
+        }
+        cache::evict_links(&state.redis_db, &short_codes).await;
+        deleted += short_codes.len();
+        if (short_codes.len() as i64) < PURGE_BATCH_SIZE {
+            break;
+        }
+    }
+
+    if deleted > 0 {
+        cache::invalidate_responses(&state.redis_db).await;
+    }
+    Ok(deleted)
+}
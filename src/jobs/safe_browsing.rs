@@ -0,0 +1,226 @@
+use std::time::Duration;
+
+use tokio::time::{self, MissedTickBehavior};
+use tracing::{error, info, warn};
+
+use crate::{
+    api::handlers::external_cache_key,
+    cache,
+    db::Timed,
+    state::AppState,
+    utils::safe_browsing::{ThreatAction, MAX_URLS_PER_LOOKUP},
+};
+
+// Links checked per lookup; each contributes up to two urls
+const BATCH_SIZE: i64 = (MAX_URLS_PER_LOOKUP / 2) as i64;
+
+// External links checked per lookup, with one url each
+const EXTERNAL_BATCH_SIZE: i64 = MAX_URLS_PER_LOOKUP as i64;
+
+#[derive(sqlx::FromRow)]
+struct Link {
+    short_code: String,
+    long_url: String,
+    resolved_url: Option<String>,
+}
+
+#[derive(sqlx::FromRow)]
+struct ExternalLink {
+    external_id: String,
+    long_url: String,
+}
+
+// Periodically look up the destinations of enabled links that were not checked recently
+pub async fn recheck(state: AppState, interval: Duration) {
+    let mut ticker = time::interval(interval);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        ticker.tick().await;
+        match recheck_stale(&state, interval).await {
+            Ok(0) => {}
+            Ok(unsafe_links) => info!(unsafe_links, "Safe Browsing re-check found unsafe links"),
+            Err(e) => error!(error = %e, "Safe Browsing re-check failed"),
+        }
+        match recheck_stale_external(&state, interval).await {
+            Ok(0) => {}
+            Ok(unsafe_links) => {
+                info!(
+                    unsafe_links,
+                    "Safe Browsing re-check found unsafe external links"
+                )
+            }
+            Err(e) => error!(error = %e, "Safe Browsing re-check of external links failed"),
+        }
+    }
+}
+
+// Re-check links in batches until none are stale, returning the number found unsafe
+async fn recheck_stale(state: &AppState, interval: Duration) -> Result<usize, String> {
+    let Some(safe_browsing) = &state.safe_browsing else {
+        return Ok(0);
+    };
+    let stale_before =
+        chrono::Utc::now() - chrono::Duration::from_std(interval).map_err(|e| e.to_string())?;
+
+    let mut unsafe_links = 0;
+    loop {
+        let links = sqlx::query_as!(
+            Link,
+            "
+            SELECT short_code, long_url, resolved_url
+            FROM urls
+            WHERE disabled_at IS NULL
+            AND (threat_checked_at IS NULL OR threat_checked_at < $1)
+            ORDER BY threat_checked_at NULLS FIRST, short_code
+            LIMIT $2
+            ",
+            stale_before,
+            BATCH_SIZE
+        )
+        .fetch_all(&state.pg_db)
+        .timed("stale_links")
+        .await
+        .map_err(|e| e.to_string())?;
+        if links.is_empty() {
+            return Ok(unsafe_links);
+        }
+
+        let urls: Vec<&str> = links
+            .iter()
+            .flat_map(|link| [Some(link.long_url.as_str()), link.resolved_url.as_deref()])
+            .flatten()
+            .collect();
+        let threats = safe_browsing.lookup(&state.http_client, &urls).await?;
+
+        let mut short_codes = Vec::with_capacity(links.len());
+        let mut threat_types = Vec::with_capacity(links.len());
+        let mut disabled = Vec::new();
+        for link in &links {
+            let threat_type = threats
+                .get(&link.long_url)
+                .or_else(|| link.resolved_url.as_ref().and_then(|url| threats.get(url)))
+                .cloned();
+            if let Some(threat_type) = &threat_type {
+                warn!(short_code = %link.short_code, threat_type = %threat_type, "Unsafe link");
+                unsafe_links += 1;
+                if safe_browsing.action == ThreatAction::Reject {
+                    disabled.push(link.short_code.clone());
+                }
+            }
+            short_codes.push(link.short_code.clone());
+            threat_types.push(threat_type);
+        }
+
+        sqlx::query!(
+            "
+            UPDATE urls
+            SET threat_type = checked.threat_type,
+                threat_checked_at = CURRENT_TIMESTAMP,
+                disabled_at = CASE
+                    WHEN checked.short_code = ANY($3) THEN CURRENT_TIMESTAMP
+                    ELSE urls.disabled_at
+                END
+            FROM UNNEST($1::TEXT[], $2::TEXT[]) AS checked (short_code, threat_type)
+            WHERE urls.short_code = checked.short_code
+            ",
+            &short_codes,
+            &threat_types as &[Option<String>],
+            &disabled
+        )
+        .execute(&state.pg_db)
+        .timed("flag_threats")
+        .await
+        .map_err(|e| e.to_string())?;
+
+        if !disabled.is_empty() {
+            cache::evict_links(&state.redis_db, &disabled).await;
+            if let Some(local_cache) = &state.local_cache {
+                for short_code in &disabled {
+                    local_cache.invalidate(short_code);
+                }
+            }
+            cache::invalidate_responses(&state.redis_db).await;
+        }
+    }
+}
+
+// Re-check external links the same way, returning the number found unsafe
+async fn recheck_stale_external(state: &AppState, interval: Duration) -> Result<usize, String> {
+    let Some(safe_browsing) = &state.safe_browsing else {
+        return Ok(0);
+    };
+    let stale_before =
+        chrono::Utc::now() - chrono::Duration::from_std(interval).map_err(|e| e.to_string())?;
+
+    let mut unsafe_links = 0;
+    loop {
+        let links = sqlx::query_as!(
+            ExternalLink,
+            "
+            SELECT external_id, long_url
+            FROM external_links
+            WHERE disabled_at IS NULL
+            AND (threat_checked_at IS NULL OR threat_checked_at < $1)
+            ORDER BY threat_checked_at NULLS FIRST, external_id
+            LIMIT $2
+            ",
+            stale_before,
+            EXTERNAL_BATCH_SIZE
+        )
+        .fetch_all(&state.pg_db)
+        .timed("stale_external_links")
+        .await
+        .map_err(|e| e.to_string())?;
+        if links.is_empty() {
+            return Ok(unsafe_links);
+        }
+
+        let urls: Vec<&str> = links.iter().map(|link| link.long_url.as_str()).collect();
+        let threats = safe_browsing.lookup(&state.http_client, &urls).await?;
+
+        let mut external_ids = Vec::with_capacity(links.len());
+        let mut threat_types = Vec::with_capacity(links.len());
+        let mut disabled = Vec::new();
+        for link in &links {
+            let threat_type = threats.get(&link.long_url).cloned();
+            if let Some(threat_type) = &threat_type {
+                warn!(external_id = %link.external_id, threat_type = %threat_type, "Unsafe external link");
+                unsafe_links += 1;
+                if safe_browsing.action == ThreatAction::Reject {
+                    disabled.push(link.external_id.clone());
+                }
+            }
+            external_ids.push(link.external_id.clone());
+            threat_types.push(threat_type);
+        }
+
+        sqlx::query!(
+            "
+            UPDATE external_links
+            SET threat_type = checked.threat_type,
+                threat_checked_at = CURRENT_TIMESTAMP,
+                disabled_at = CASE
+                    WHEN checked.external_id = ANY($3) THEN CURRENT_TIMESTAMP
+                    ELSE external_links.disabled_at
+                END
+            FROM UNNEST($1::TEXT[], $2::TEXT[]) AS checked (external_id, threat_type)
+            WHERE external_links.external_id = checked.external_id
+            ",
+            &external_ids,
+            &threat_types as &[Option<String>],
+            &disabled
+        )
+        .execute(&state.pg_db)
+        .timed("flag_external_threats")
+        .await
+        .map_err(|e| e.to_string())?;
+
+        if !disabled.is_empty() {
+            let cache_keys: Vec<String> = disabled
+                .iter()
+                .map(|external_id| external_cache_key(external_id))
+                .collect();
+            cache::evict_links(&state.redis_db, &cache_keys).await;
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use std::time::Duration;
+
+use tokio::time::{self, Instant, MissedTickBehavior};
+use tracing::{error, info};
+
+use crate::{
+    cache,
+    db::{models::url_target, Timed},
+    state::AppState,
+};
+
+// Load the most clicked links into the caches right away, then again every `interval`
+// if set, so a cold start does not send all their redirects to the database at once
+pub async fn run(state: AppState, interval: Option<Duration>, links: u32) {
+    warm_logged(&state, links).await;
+    let Some(interval) = interval else {
+        return;
+    };
+    let mut ticker = time::interval_at(Instant::now() + interval, interval);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        ticker.tick().await;
+        warm_logged(&state, links).await;
+    }
+}
+
+async fn warm_logged(state: &AppState, links: u32) {
+    match warm(state, links).await {
+        Ok(warmed) => info!(warmed, "Warmed link cache"),
+        Err(e) => error!(error = %e, "Failed to warm link cache"),
+    }
+}
+
+// Cache the destinations of the `links` most clicked links the redirect path would cache
+// itself: active plain links that are neither disabled, broken, single-use nor routed
+async fn warm(state: &AppState, links: u32) -> Result<usize, String> {
+    let hot_links = sqlx::query!(
+        r#"
+        SELECT short_code, long_url, resolved_url, utm_source, utm_medium, utm_campaign, utm_term, utm_content,
+            activates_at, single_use, disabled_at, broken_at,
+            FALSE AS "split!", FALSE AS "geo_targeted!", FALSE AS "device_targeted!",
+            FALSE AS "time_routed!", FALSE AS "deep_linked!"
+        FROM urls
+        WHERE disabled_at IS NULL
+        AND broken_at IS NULL
+        AND NOT single_use
+        AND (activates_at IS NULL OR activates_at <= CURRENT_TIMESTAMP)
+        AND NOT EXISTS (SELECT 1 FROM url_targets WHERE url_targets.short_code = urls.short_code)
+        AND NOT EXISTS (SELECT 1 FROM url_geo_targets WHERE url_geo_targets.short_code = urls.short_code)
+        AND NOT EXISTS (SELECT 1 FROM url_device_targets WHERE url_device_targets.short_code = urls.short_code)
+        AND NOT EXISTS (SELECT 1 FROM url_time_rules WHERE url_time_rules.short_code = urls.short_code)
+        AND NOT EXISTS (SELECT 1 FROM url_deep_links WHERE url_deep_links.short_code = urls.short_code)
+        ORDER BY clicks DESC
+        LIMIT $1
+        "#,
+        i64::from(links)
+    )
+    .map(|row| (row.short_code.clone(), url_target!(row)))
+    // Read from the primary, so the cache never gets what a lagging replica still has
+    .fetch_all(&state.pg_db)
+    .timed("hot_links")
+    .await
+    .map_err(|e| e.to_string())?;
+
+    // Blocked destinations are refused on every cache hit anyway
+    let destinations: Vec<(String, String)> = hot_links
+        .into_iter()
+        .map(|(short_code, target)| (short_code, target.destination()))
+        .filter(|(_, long_url)| state.blocklist.matching(long_url).is_none())
+        .collect();
+    if destinations.is_empty() {
+        return Ok(0);
+    }
+
+    cache::store_links(
+        &state.redis_db,
+        &destinations,
+        state.runtime.load().cache_ttl,
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+    if let Some(local_cache) = &state.local_cache {
+        for (short_code, long_url) in &destinations {
+            local_cache.insert(short_code.clone(), long_url.clone());
+        }
+    }
+    Ok(destinations.len())
+}
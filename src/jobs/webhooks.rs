@@ -0,0 +1,168 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use reqwest::header::CONTENT_TYPE;
+use tokio::{
+    task::JoinSet,
+    time::{self, MissedTickBehavior},
+};
+use tracing::{debug, error, warn};
+
+use crate::{
+    db::Timed,
+    state::AppState,
+    utils::resolve,
+    webhooks::{self, DELIVERY_HEADER, EVENT_HEADER, SIGNATURE_HEADER},
+};
+
+// Deliveries sent per round, concurrently
+const BATCH_SIZE: i64 = 50;
+
+// Delay before the first retry, doubled for each further one up to a day
+const RETRY_BASE_SECONDS: f64 = 30.0;
+
+#[derive(sqlx::FromRow)]
+struct Delivery {
+    id: i64,
+    event: String,
+    payload: String,
+    attempts: i32,
+    url: String,
+    secret: String,
+}
+
+// Periodically send due webhook deliveries, giving up on one after `max_attempts`
+pub async fn run(state: AppState, interval: Duration, max_attempts: u32) {
+    let mut ticker = time::interval(interval);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        ticker.tick().await;
+        // Webhooks may have been registered or removed through another instance
+        if let Err(e) = webhooks::refresh_click_subscribers(&state).await {
+            error!(error = %e, "Failed to look up click webhooks");
+        }
+        match deliver_due(&state, max_attempts).await {
+            Ok(0) => {}
+            Ok(sent) => debug!(sent, "Sent webhook deliveries"),
+            Err(e) => error!(error = %e, "Failed to send webhook deliveries"),
+        }
+    }
+}
+
+// Send due deliveries in rounds until none are left, returning the number attempted
+async fn deliver_due(state: &AppState, max_attempts: u32) -> Result<usize, String> {
+    let mut sent = 0;
+    loop {
+        // Claimed deliveries are leased for a while, so other instances skip them and
+        // a crash mid-send only delays them
+        let deliveries = sqlx::query_as!(
+            Delivery,
+            "
+            WITH due AS (
+                SELECT id FROM webhook_deliveries
+                WHERE status = 'pending' AND next_attempt_at <= CURRENT_TIMESTAMP
+                ORDER BY next_attempt_at
+                LIMIT $1
+                FOR UPDATE SKIP LOCKED
+            )
+            UPDATE webhook_deliveries
+            SET attempts = webhook_deliveries.attempts + 1,
+                next_attempt_at = CURRENT_TIMESTAMP + INTERVAL '5 minutes'
+            FROM due, webhooks
+            WHERE webhook_deliveries.id = due.id AND webhooks.id = webhook_deliveries.webhook_id
+            RETURNING webhook_deliveries.id, webhook_deliveries.event, webhook_deliveries.payload,
+                webhook_deliveries.attempts, webhooks.url, webhooks.secret
+            ",
+            BATCH_SIZE
+        )
+        .fetch_all(&state.pg_db)
+        .timed("claim_webhook_deliveries")
+        .await
+        .map_err(|e| e.to_string())?;
+        if deliveries.is_empty() {
+            return Ok(sent);
+        }
+        sent += deliveries.len();
+
+        let mut sends = JoinSet::new();
+        for delivery in deliveries {
+            let state = state.clone();
+            sends.spawn(async move {
+                let outcome = send(&state.http_client, &delivery).await;
+                record(&state, &delivery, outcome, max_attempts).await;
+            });
+        }
+        sends.join_all().await;
+    }
+}
+
+// Result of one attempt: the receiver's status, if it answered, and why it failed, if it did
+type Outcome = (Option<i16>, Option<String>);
+
+async fn send(client: &reqwest::Client, delivery: &Delivery) -> Outcome {
+    let timestamp = Utc::now().timestamp();
+    let result = client
+        .post(&delivery.url)
+        .header(CONTENT_TYPE, "application/json")
+        .header(EVENT_HEADER, &delivery.event)
+        .header(DELIVERY_HEADER, delivery.id.to_string())
+        .header(
+            SIGNATURE_HEADER,
+            webhooks::signature(&delivery.secret, timestamp, &delivery.payload),
+        )
+        .body(delivery.payload.clone())
+        .send()
+        .await;
+    match result {
+        Ok(response) if response.status().is_success() => {
+            (Some(response.status().as_u16() as i16), None)
+        }
+        Ok(response) => (
+            Some(response.status().as_u16() as i16),
+            Some(format!("receiver answered {}", response.status())),
+        ),
+        Err(e) => (None, Some(resolve::request_error(&e))),
+    }
+}
+
+// Mark a delivery done, or schedule its retry with exponential backoff, or give up on it
+async fn record(state: &AppState, delivery: &Delivery, outcome: Outcome, max_attempts: u32) {
+    let (response_status, error) = outcome;
+    let gave_up = error.is_some() && delivery.attempts as u32 >= max_attempts;
+    if gave_up {
+        warn!(
+            delivery = delivery.id,
+            attempts = delivery.attempts,
+            error,
+            "Giving up on webhook delivery"
+        );
+    }
+
+    let result = sqlx::query!(
+        "
+        UPDATE webhook_deliveries
+        SET status = CASE
+                WHEN $3::TEXT IS NULL THEN 'delivered'
+                WHEN $4 THEN 'failed'
+                ELSE 'pending'
+            END,
+            response_status = $2,
+            last_error = $3,
+            delivered_at = CASE WHEN $3::TEXT IS NULL THEN CURRENT_TIMESTAMP END,
+            next_attempt_at = CURRENT_TIMESTAMP
+                + LEAST(make_interval(secs => $5 * POWER(2, LEAST(attempts - 1, 12))), INTERVAL '1 day')
+        WHERE id = $1
+        ",
+        delivery.id,
+        response_status,
+        error,
+        gave_up,
+        RETRY_BASE_SECONDS
+    )
+    .execute(&state.pg_db)
+    .timed("record_webhook_delivery")
+    .await;
+    if let Err(e) = result {
+        error!(error = %e, delivery = delivery.id, "Failed to record webhook delivery");
+    }
+}
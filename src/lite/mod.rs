@@ -0,0 +1,461 @@
+use std::{net::SocketAddr, process, sync::Arc, time::Duration};
+
+use axum::{
+    extract::{rejection::JsonRejection, Path, Query, RawQuery, State},
+    http::{header, HeaderName, Method, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use serde_json::{json, Value};
+use tokio::task::JoinSet;
+use tokio_util::sync::CancellationToken;
+use tower_http::{
+    cors::CorsLayer,
+    limit::RequestBodyLimitLayer,
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
+    timeout::TimeoutLayer,
+    trace::{DefaultOnFailure, DefaultOnResponse, TraceLayer},
+    LatencyUnit,
+};
+use tracing::{debug, error, info, instrument, warn, Level};
+
+#[cfg(feature = "mysql")]
+use crate::db::repository::MySqlUrlRepository;
+#[cfg(feature = "sqlite")]
+use crate::db::repository::SqliteUrlRepository;
+use crate::{
+    api::{handlers, middleware},
+    cli,
+    config::{CodeStrategy, Config, DuplicatePolicy, Storage},
+    db::repository::{NewLink, UrlRepository},
+    error::AppError,
+    telemetry, templates, tls,
+    types::{ListQuery, ShortenRequest, ShortenResponse, UpdateUrlRequest, UrlDetailResponse},
+    utils::{
+        alphabet::Alphabet, banned_code, canonical_code, merge_params,
+        normalize::with_default_scheme, reserved_code, valid_short_code,
+    },
+};
+
+// Single binary mode, used when DATABASE_URL points at a SQLite file or a MySQL server:
+// plain links are created, listed, updated, deleted and redirected without Postgres or
+// Redis, through the `UrlRepository` of that database. Routing
+// rules, campaigns, previews, webhooks, click history and the response cache all need
+// the full service and are not available here.
+
+#[derive(Clone)]
+struct LiteState {
+    urls: Arc<dyn UrlRepository>,
+    base_url: String,
+    code_alphabet: Arc<Alphabet>,
+    case_insensitive_codes: bool,
+    accept_schemeless_urls: bool,
+    ssrf_dns_check: bool,
+    max_url_length: usize,
+    duplicate_policy: DuplicatePolicy,
+    reserved_codes: Arc<[String]>,
+    banned_words: Arc<[String]>,
+    // Read once at startup, there is no reload on SIGHUP in this mode
+    redirect_max_age: u64,
+}
+
+// Open the database and run `serve` or `migrate` against it
+pub async fn run(config: &Config, command: cli::Command) {
+    let urls = open(config).await.unwrap_or_else(|e| {
+        error!("Failed to open database: {e}");
+        process::exit(1);
+    });
+    info!("Database migrations applied successfully.");
+
+    match command {
+        cli::Command::Migrate => return,
+        cli::Command::Serve => {}
+        _ => {
+            error!("Only `serve` and `migrate` are available with a SQLite database");
+            process::exit(1);
+        }
+    }
+    if config.code_strategy == CodeStrategy::Sequence {
+        warn!("CODE_STRATEGY=sequence needs Postgres, hashing destinations instead.");
+    }
+
+    let state = LiteState {
+        urls,
+        base_url: config.base_url.clone(),
+        code_alphabet: Arc::new(config.code_alphabet.clone()),
+        case_insensitive_codes: config.case_insensitive_codes,
+        accept_schemeless_urls: config.accept_schemeless_urls,
+        ssrf_dns_check: config.ssrf_dns_check,
+        max_url_length: config.max_url_length,
+        duplicate_policy: config.duplicate_policy,
+        reserved_codes: config.reserved_codes.as_slice().into(),
+        banned_words: config.banned_words.as_slice().into(),
+        redirect_max_age: config.runtime.redirect_max_age,
+    };
+
+    let rustls = match &config.tls {
+        Some(tls) => Some(tls::load(tls).await.unwrap_or_else(|e| {
+            error!("Failed to load TLS certificate: {e}");
+            process::exit(1);
+        })),
+        None => None,
+    };
+
+    let shutdown = CancellationToken::new();
+    tokio::spawn({
+        let shutdown = shutdown.clone();
+        async move {
+            crate::shutdown_signal().await;
+            info!("Shutting down.");
+            shutdown.cancel();
+        }
+    });
+
+    info!(
+        "Starting server on {} with {:?} storage",
+        config.server_addrs.join(", "),
+        config.storage
+    );
+    let mut listeners = Vec::with_capacity(config.server_addrs.len());
+    for server_addr in &config.server_addrs {
+        let listener = tokio::net::TcpListener::bind(server_addr)
+            .await
+            .unwrap_or_else(|e| {
+                error!("Failed to bind to address {server_addr}: {e}");
+                process::exit(1);
+            });
+        listeners.push(listener);
+    }
+
+    let app = router(state, config).into_make_service_with_connect_info::<SocketAddr>();
+    let mut servers = JoinSet::new();
+    for listener in listeners {
+        servers.spawn(crate::serve(
+            listener,
+            app.clone(),
+            rustls.clone(),
+            shutdown.clone(),
+        ));
+    }
+    while let Some(served) = servers.join_next().await {
+        if let Err(e) = served.unwrap_or_else(|e| Err(std::io::Error::other(e))) {
+            error!("Server error: {e}");
+            process::exit(1);
+        }
+    }
+    info!("Server stopped.");
+}
+
+// Repository for the database in DATABASE_URL, with its schema brought up to date
+async fn open(config: &Config) -> Result<Arc<dyn UrlRepository>, sqlx::Error> {
+    let max_connections = config.database_max_connections;
+    match config.storage {
+        #[cfg(feature = "sqlite")]
+        Storage::Sqlite => Ok(Arc::new(
+            SqliteUrlRepository::connect(&config.database_url, max_connections).await?,
+        )),
+        #[cfg(feature = "mysql")]
+        Storage::MySql => Ok(Arc::new(
+            MySqlUrlRepository::connect(&config.database_url, max_connections).await?,
+        )),
+        storage => Err(sqlx::Error::Configuration(
+            format!(
+                "DATABASE_URL needs a build with the `{}` feature",
+                storage.feature().unwrap_or_default()
+            )
+            .into(),
+        )),
+    }
+}
+
+fn router(state: LiteState, config: &Config) -> Router {
+    Router::new()
+        .route("/api/v1/health", get(handlers::health_check))
+        .route("/api/v1/version", get(handlers::get_version))
+        .route(
+            "/api/v1/shorten",
+            get(get_all_short_url).post(create_short_url),
+        )
+        .route(
+            "/api/v1/{short_code}",
+            get(get_short_url_details)
+                .patch(update_short_url)
+                .delete(delete_short_url),
+        )
+        .route("/{short_code}", get(handle_short_url))
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(telemetry::make_span)
+                .on_response(
+                    DefaultOnResponse::new()
+                        .latency_unit(LatencyUnit::Millis)
+                        .level(Level::DEBUG),
+                )
+                .on_failure(DefaultOnFailure::new().level(Level::ERROR)),
+        )
+        .layer(TimeoutLayer::new(Duration::from_secs(
+            config.request_timeout,
+        )))
+        .layer(RequestBodyLimitLayer::new(config.request_body_limit))
+        .layer(CorsLayer::permissive())
+        .layer(PropagateRequestIdLayer::new(HeaderName::from_static(
+            middleware::REQUEST_ID_HEADER,
+        )))
+        .layer(SetRequestIdLayer::new(
+            HeaderName::from_static(middleware::REQUEST_ID_HEADER),
+            MakeRequestUuid,
+        ))
+        .with_state(state)
+}
+
+#[instrument(skip(state, payload))]
+async fn create_short_url(
+    State(state): State<LiteState>,
+    payload: Result<Json<ShortenRequest>, JsonRejection>,
+) -> Result<Response, AppError> {
+    let payload = handlers::json_payload(payload)?;
+    let (status, response) = shorten(&state, payload).await?;
+    Ok((status, Json(response)).into_response())
+}
+
+// Create a plain link like the full service does, without the checks that need Postgres
+// or outside services: the domain blocklist, Safe Browsing and redirect chain resolution
+async fn shorten(
+    state: &LiteState,
+    mut payload: ShortenRequest,
+) -> Result<(StatusCode, ShortenResponse), AppError> {
+    if state.accept_schemeless_urls {
+        if let Some(long_url) = with_default_scheme(&payload.long_url) {
+            payload.long_url = long_url;
+        }
+    }
+
+    if let Err(e) = handlers::validate_shorten_request(&mut payload, state.max_url_length) {
+        error!(error = %e, "Invalid shorten request");
+        return Err(e);
+    }
+    if payload.is_routed() {
+        error!("Routing rules requested without Postgres storage");
+        return Err(AppError::InvalidRequest(
+            "Routing rules need Postgres storage".to_string(),
+        ));
+    }
+
+    if state.ssrf_dns_check {
+        if let Err(e) = handlers::check_resolved_destinations(&payload.destinations()).await {
+            error!(error = %e, "Destination resolves to a non-public address");
+            return Err(AppError::PrivateDestination);
+        }
+    }
+
+    let duplicate_policy = if payload.single_use {
+        DuplicatePolicy::New
+    } else {
+        match payload.reuse_existing {
+            Some(true) => DuplicatePolicy::Existing,
+            Some(false) => DuplicatePolicy::New,
+            None => state.duplicate_policy,
+        }
+    };
+    if duplicate_policy != DuplicatePolicy::New {
+        if let Some(short_code) = state.urls.find_existing(&payload).await? {
+            return handlers::existing_link(&state.base_url, duplicate_policy, short_code, payload);
+        }
+    }
+
+    let destination = merge_params(&payload.long_url, payload.utm.pairs());
+    let mut attempts = 0;
+    let short_code = loop {
+        let short_code = handlers::hashed_code(&destination, attempts, &state.code_alphabet).await;
+        debug!(short_code = %short_code, "Generated short code");
+
+        if reserved_code(&short_code, &state.reserved_codes)
+            || banned_code(&short_code, &state.banned_words)
+        {
+            info!(short_code = %short_code, "Generated short code is reserved or contains a banned word");
+        } else {
+            let link = NewLink {
+                short_code: &short_code,
+                request: &payload,
+                resolved_url: None,
+                threat_type: None,
+                threat_checked_at: None,
+            };
+            if state.urls.insert(&link).await? {
+                break short_code;
+            }
+
+            let duplicate = state
+                .urls
+                .destination(&short_code)
+                .await?
+                .is_some_and(|existing| handlers::same_link(&existing, &payload));
+            if duplicate && duplicate_policy != DuplicatePolicy::New {
+                return handlers::existing_link(
+                    &state.base_url,
+                    duplicate_policy,
+                    short_code,
+                    payload,
+                );
+            }
+            if !duplicate {
+                info!(short_code = %short_code, "Short code collision");
+            }
+        }
+
+        if attempts >= handlers::MAX_CODE_ATTEMPTS {
+            return Err(handlers::unique_code_exhausted(attempts));
+        }
+        attempts += 1;
+    };
+
+    let short_url = format!("{}/{}", state.base_url, short_code);
+    info!(short_url = %short_url, "Created short URL");
+    Ok((
+        StatusCode::CREATED,
+        ShortenResponse::new(short_code, short_url, payload),
+    ))
+}
+
+#[instrument(skip(state))]
+async fn get_all_short_url(
+    State(state): State<LiteState>,
+    Query(params): Query<ListQuery>,
+) -> Result<Json<Vec<UrlDetailResponse>>, AppError> {
+    let links = state
+        .urls
+        .list(&handlers::link_filter(&params), None)
+        .await?;
+    Ok(Json(
+        links
+            .into_iter()
+            .map(|link| UrlDetailResponse::new(link, &state.base_url))
+            .collect(),
+    ))
+}
+
+#[instrument(skip(state))]
+async fn get_short_url_details(
+    State(state): State<LiteState>,
+    Path(short_code): Path<String>,
+) -> Result<Json<UrlDetailResponse>, AppError> {
+    let short_code = checked_code(&state, &short_code)?;
+    let Some(detail) = state.urls.find(&short_code).await? else {
+        error!(short_code = %short_code, "Short code not found");
+        return Err(AppError::NotFound("Short URL"));
+    };
+    Ok(Json(UrlDetailResponse::new(detail, &state.base_url)))
+}
+
+#[instrument(skip(state, payload))]
+async fn update_short_url(
+    State(state): State<LiteState>,
+    Path(short_code): Path<String>,
+    payload: Result<Json<UpdateUrlRequest>, JsonRejection>,
+) -> Result<Json<UrlDetailResponse>, AppError> {
+    let short_code = checked_code(&state, &short_code)?;
+    let payload = handlers::json_payload(payload)?;
+
+    // An empty description clears the field, an absent one leaves it as is
+    let result = match &payload.description {
+        Some(description) => {
+            let description = handlers::normalize_description(Some(description))?;
+            state
+                .urls
+                .set_description(&short_code, description.as_deref())
+                .await?
+        }
+        None => state.urls.find(&short_code).await?,
+    };
+    let Some(detail) = result else {
+        error!(short_code = %short_code, "Short code not found");
+        return Err(AppError::NotFound("Short URL"));
+    };
+
+    info!(short_code = %short_code, "Short URL updated");
+    Ok(Json(UrlDetailResponse::new(detail, &state.base_url)))
+}
+
+#[instrument(skip(state))]
+async fn delete_short_url(
+    State(state): State<LiteState>,
+    Path(short_code): Path<String>,
+) -> Result<Json<Value>, AppError> {
+    let short_code = checked_code(&state, &short_code)?;
+    if !state.urls.delete(&short_code).await? {
+        error!(short_code = %short_code, "Short code not found");
+        return Err(AppError::NotFound("Short URL"));
+    }
+
+    info!(short_code = %short_code, "Short URL deleted successfully");
+    Ok(Json(json!({"message": "short url deleted successfully"})))
+}
+
+// Every redirect reads the database, there being no cache in front of it
+#[instrument(skip(state))]
+async fn handle_short_url(
+    State(state): State<LiteState>,
+    Path(short_code): Path<String>,
+    RawQuery(params): RawQuery,
+    method: Method,
+) -> Result<Response, AppError> {
+    let short_code = checked_code(&state, &short_code)?;
+    // HEAD requests neither count as clicks nor use up single-use links
+    let probe = method == Method::HEAD;
+
+    let Some(target) = state.urls.destination(&short_code).await? else {
+        error!(short_code = %short_code, "Short code not found");
+        return Err(AppError::NotFound("Short URL"));
+    };
+    if target.is_disabled() {
+        info!(short_code = %short_code, "Short code disabled");
+        return Err(AppError::Gone);
+    }
+    if !target.is_active() {
+        info!(short_code = %short_code, "Short code not active yet");
+        return Ok((
+            StatusCode::NOT_FOUND,
+            templates::not_active(&short_code, target.activates_at),
+        )
+            .into_response());
+    }
+
+    let destination = handlers::redirect_target(&target.destination(), None, params.as_deref());
+    if target.single_use {
+        if probe {
+            info!(short_code = %short_code, "Probing single-use short code");
+            return Ok((StatusCode::OK, [(header::CACHE_CONTROL, "no-store")]).into_response());
+        }
+        if !state.urls.use_once(&short_code).await? {
+            info!(short_code = %short_code, "Single-use short code already used");
+            return Err(AppError::Gone);
+        }
+        info!(short_code = %short_code, "Redirecting single-use short code");
+        return Ok(handlers::uncached_redirect(&destination));
+    }
+
+    info!(short_code = %short_code, "Redirecting to long URL");
+    if !probe {
+        let urls = state.urls.clone();
+        tokio::spawn(async move {
+            if let Err(e) = urls.add_clicks(&short_code, 1).await {
+                error!(error = %e, "Failed to record click");
+            }
+        });
+    }
+    Ok(handlers::cacheable_redirect(
+        &destination,
+        state.redirect_max_age,
+    ))
+}
+
+// Short code in its stored form, refusing codes the alphabet cannot produce
+fn checked_code(state: &LiteState, short_code: &str) -> Result<String, AppError> {
+    let short_code = canonical_code(short_code, state.case_insensitive_codes);
+    if !valid_short_code(&short_code, &state.code_alphabet) {
+        error!(short_code = %short_code, "Invalid short code");
+        return Err(AppError::InvalidShortCode(short_code));
+    }
+    Ok(short_code)
+}
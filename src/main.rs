@@ -1,54 +1,150 @@
-use std::{env, process};
+use std::{env, io, net::SocketAddr, pin::pin, process, sync::Arc, time::Duration};
 
+use axum::{extract::connect_info::IntoMakeServiceWithConnectInfo, Router};
+use clap::Parser;
 use dotenvy::dotenv;
-use redis::Client;
-use sqlx::postgres::PgPoolOptions;
+use redis::{aio::ConnectionManager, Client};
+use sqlx::{
+    postgres::{PgConnection, PgPoolOptions},
+    Connection,
+};
 use state::AppState;
-use tokio::signal;
-use tracing::{error, info, level_filters::LevelFilter};
-use tracing_appender::rolling::{RollingFileAppender, Rotation};
-use tracing_subscriber::{fmt, prelude::*, EnvFilter};
+use tokio::{
+    signal,
+    time::{self, Instant},
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, level_filters::LevelFilter, warn};
+use utils::retry;
 
+mod abuse;
 mod api;
+mod bench;
+mod blocklist;
+mod cache;
+mod cli;
 mod config;
+mod dashboard;
 mod db;
+mod error;
+mod events;
+mod export;
+mod geo;
+mod graphql;
+mod grpc;
+mod jobs;
+#[cfg(any(feature = "sqlite", feature = "mysql"))]
+mod lite;
+mod metrics;
 mod state;
+mod telemetry;
+mod templates;
+#[cfg(test)]
+mod testkit;
+mod tls;
 mod types;
 mod utils;
+mod webhooks;
 
 #[tokio::main]
 async fn main() {
     dotenv().ok();
 
-    // Configure logging
-    let filter = EnvFilter::builder()
-        .with_env_var("APP_LOG")
-        .with_default_directive(LevelFilter::INFO.into())
-        .from_env_lossy();
+    let cli = cli::Cli::parse();
+    let command = cli.command.unwrap_or(cli::Command::Serve);
 
-    let log_dir = env::var("LOG_DIR").unwrap_or_else(|_| "log/".to_string());
+    // Values from the configuration file yield to the environment
+    let config_file = config::load_file(cli.config.as_deref()).unwrap_or_else(|e| {
+        eprintln!("Failed to load configuration file {e}");
+        process::exit(1);
+    });
+    config::load_secret_files().unwrap_or_else(|e| {
+        eprintln!("Failed to load secret {e}");
+        process::exit(1);
+    });
+
+    // Configure logging, keeping command output free of per-request logs
+    let default_level = if command.is_serve() {
+        LevelFilter::INFO
+    } else {
+        LevelFilter::WARN
+    };
 
-    let file_appender = RollingFileAppender::new(Rotation::DAILY, log_dir, "tlong.log");
-    let (non_blocking_writer, _guard) = tracing_appender::non_blocking(file_appender);
+    // Spans are also exported over OTLP when an endpoint is configured
+    let tracer_provider = telemetry::init_tracer().unwrap_or_else(|e| {
+        eprintln!("Failed to set up trace export: {e}");
+        process::exit(1);
+    });
 
-    tracing_subscriber::registry()
-        .with(fmt::layer().json())
-        .with(fmt::layer().json().with_writer(non_blocking_writer))
-        .with(filter)
-        .init();
+    let _guard =
+        telemetry::logging::init(default_level, tracer_provider.as_ref(), !command.is_serve());
 
     // App configuration
+    if let Some(path) = &config_file {
+        info!("Loaded configuration file {path}");
+    }
     let config = config::Config::load();
 
-    // Postgres
-    let pg_db = PgPoolOptions::new()
-        .max_connections(50)
-        .connect(&config.database_url)
-        .await
-        .unwrap_or_else(|e| {
-            error!("Failed to connect to database: {e}");
+    // Other databases than Postgres are served by the single binary mode, without Redis
+    if config.storage != config::Storage::Postgres {
+        #[cfg(any(feature = "sqlite", feature = "mysql"))]
+        return lite::run(&config, command).await;
+        #[cfg(not(any(feature = "sqlite", feature = "mysql")))]
+        {
+            let feature = config.storage.feature().unwrap_or_default();
+            error!("DATABASE_URL needs a build with the `{feature}` feature");
             process::exit(1);
-        });
+        }
+    }
+
+    // Dependencies that are still starting up get this long to become reachable
+    let startup_retry_max_wait = Duration::from_secs(config.startup_retry_max_wait);
+
+    // Postgres
+    let pg_db = retry::with_backoff("database", startup_retry_max_wait, || async {
+        // A single connection fails fast, unlike the pool which waits for its acquire timeout
+        PgConnection::connect(&config.database_url)
+            .await?
+            .close()
+            .await?;
+        PgPoolOptions::new()
+            .max_connections(config.database_max_connections)
+            .connect(&config.database_url)
+            .await
+    })
+    .await
+    .unwrap_or_else(|e| {
+        error!("Failed to connect to database: {e}");
+        process::exit(1);
+    });
+
+    // Read replica, with the same retries as the primary
+    let pg_read_db = match &config.database_read_url {
+        Some(database_read_url) => Some(
+            retry::with_backoff("read replica", startup_retry_max_wait, || async {
+                PgConnection::connect(database_read_url)
+                    .await?
+                    .close()
+                    .await?;
+                PgPoolOptions::new()
+                    .max_connections(config.database_max_connections)
+                    .connect(database_read_url)
+                    .await
+            })
+            .await
+            .unwrap_or_else(|e| {
+                error!("Failed to connect to read replica: {e}");
+                process::exit(1);
+            }),
+        ),
+        None => None,
+    };
+
+    db::set_slow_query_threshold(Duration::from_millis(config.slow_query_threshold));
+    db::breaker::configure(
+        config.database_breaker_failures,
+        Duration::from_secs(config.database_breaker_cooldown),
+    );
 
     // Run database migrations
     if let Err(e) = sqlx::migrate!().run(&pg_db).await {
@@ -56,46 +152,393 @@ async fn main() {
         process::exit(1);
     }
     info!("Database migrations applied successfully.");
+    if let cli::Command::Migrate = command {
+        return;
+    }
 
     // Redis
-    let client = Client::open(config.redis_url).unwrap_or_else(|e| {
+    let client = Client::open(config.redis_url.as_str()).unwrap_or_else(|e| {
         error!("Failed to create redis database connection: {e}");
         process::exit(1);
     });
-    let redis_db = r2d2::Pool::builder()
-        .max_size(25)
-        .build(client)
+    let redis_db = retry::with_backoff("redis database", startup_retry_max_wait, || async {
+        // Likewise for a plain connection, the manager retries on its own with long delays
+        drop(client.get_multiplexed_async_connection().await?);
+        ConnectionManager::new(client.clone()).await
+    })
+    .await
+    .unwrap_or_else(|e| {
+        error!("Failed to connect to redis database: {e}");
+        process::exit(1);
+    });
+
+    // HTTP client for outbound requests
+    let http_client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(5))
+        .user_agent(concat!("tlong/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .unwrap_or_else(|e| {
+            error!("Failed to create HTTP client: {e}");
+            process::exit(1);
+        });
+
+    // HTTP client for resolving redirect chains hop by hop
+    let resolver_client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(5))
+        .redirect(reqwest::redirect::Policy::none())
+        .user_agent(concat!("tlong/", env!("CARGO_PKG_VERSION")))
+        .build()
         .unwrap_or_else(|e| {
-            error!("Failed to connect to redis database: {e}");
+            error!("Failed to create HTTP client: {e}");
             process::exit(1);
         });
 
+    // GeoIP database for geo-targeted redirects
+    let geoip = config.geoip_database.as_ref().map(|path| {
+        geo::GeoIp::open(path).unwrap_or_else(|e| {
+            error!("Failed to open GeoIP database {path}: {e}");
+            process::exit(1);
+        })
+    });
+    if geoip.is_none() {
+        info!("GEOIP_DATABASE not set, geo-targeted redirects use their default destination.");
+    }
+    if config.safe_browsing_api_key.is_none() {
+        info!("SAFE_BROWSING_API_KEY not set, destinations are not checked for threats.");
+    }
+
+    // Prometheus metrics
+    let metrics = config.metrics_enabled.then(|| {
+        metrics::install().unwrap_or_else(|e| {
+            error!("Failed to install metrics recorder: {e}");
+            process::exit(1);
+        })
+    });
+
     // Application state
-    let state = AppState::new(pg_db, redis_db, config.base_url);
+    let mut state = AppState::new(
+        pg_db,
+        redis_db,
+        http_client,
+        resolver_client,
+        geoip,
+        metrics,
+        &config,
+    );
+    if let Some(pg_read_db) = pg_read_db {
+        info!("Reading listings and statistics from the replica at DATABASE_READ_URL.");
+        state = state.with_read_replica(pg_read_db);
+    }
 
-    // Build the application router
-    let app = api::routes::router(state);
+    // Banned destination domains
+    match state.blocklist.reload(&state.pg_db).await {
+        Ok(domains) => info!("Loaded {domains} blocked domains."),
+        Err(e) => {
+            error!("Failed to load blocklist: {e}");
+            process::exit(1);
+        }
+    }
+    if let Some(code_filter) = &state.code_filter {
+        match code_filter.reload(&state.pg_db).await {
+            Ok(codes) => info!("Loaded {codes} short codes into the code filter."),
+            Err(e) => {
+                error!("Failed to load code filter: {e}");
+                process::exit(1);
+            }
+        }
+    }
 
-    info!("Starting server on {}", &config.server_addr);
+    // Commands other than `serve` run once and exit
+    if !command.is_serve() {
+        let result = match command {
+            cli::Command::Serve | cli::Command::Migrate => Ok(()),
+            cli::Command::CleanupExpired { older_than_days } => {
+                cli::cleanup_expired(&state, older_than_days)
+                    .await
+                    .map(|deleted| println!("Deleted {deleted} expired links."))
+            }
+            cli::Command::Export { format } => cli::export(&state, format)
+                .await
+                .map(|exported| eprintln!("Exported {exported} links.")),
+            cli::Command::Create {
+                long_url,
+                tags,
+                description,
+                single_use,
+            } => cli::create(state.clone(), long_url, tags, description, single_use).await,
+            cli::Command::Bench(options) => bench::run(state.clone(), options)
+                .await
+                .map_err(|e| format!("Benchmark failed: {e}")),
+        };
+        finish_background_work(
+            &state,
+            Instant::now() + Duration::from_secs(config.shutdown_timeout),
+        )
+        .await;
+        if let Err(e) = result {
+            error!("{e}");
+            process::exit(1);
+        }
+        return;
+    }
 
-    // Server configuration
-    let listener = tokio::net::TcpListener::bind(&config.server_addr)
-        .await
-        .unwrap_or_else(|e| {
-            error!("Failed to bind to address: {e}");
+    jobs::spawn(&state);
+    // Links changed through other instances must not linger in this one's memory
+    if let Some(local_cache) = &state.local_cache {
+        tokio::spawn(cache::invalidation::listen(
+            client.clone(),
+            local_cache.clone(),
+        ));
+    }
+    if let Some(click_stream) = &state.click_stream {
+        tokio::spawn(events::listen(client, click_stream.clone()));
+    }
+
+    // Load the certificate up front, so a bad one fails startup rather than each handshake
+    let rustls = match &config.tls {
+        Some(tls) => Some(tls::load(tls).await.unwrap_or_else(|e| {
+            error!("Failed to load TLS certificate: {e}");
             process::exit(1);
+        })),
+        None => None,
+    };
+
+    // SIGHUP reloads the settings that can change without a restart
+    #[cfg(unix)]
+    tokio::spawn(reload_on_hangup(
+        state.clone(),
+        cli.config.clone(),
+        config.tls.clone().zip(rustls.clone()),
+    ));
+
+    // The first SIGINT or SIGTERM stops every listener from accepting connections
+    let shutdown = state.shutdown.clone();
+    tokio::spawn({
+        let shutdown = shutdown.clone();
+        async move {
+            shutdown_signal().await;
+            info!("Shutting down.");
+            shutdown.cancel();
+        }
+    });
+
+    // Build the application router, serving metrics alongside unless they have their own address
+    let mut app = api::routes::router(state.clone());
+    if state.metrics.is_some() {
+        match &config.metrics_addr {
+            Some(metrics_addr) => {
+                let listener = tokio::net::TcpListener::bind(metrics_addr)
+                    .await
+                    .unwrap_or_else(|e| {
+                        error!("Failed to bind metrics address: {e}");
+                        process::exit(1);
+                    });
+                info!("Serving metrics on {}", metrics_addr);
+                let metrics_app = metrics::router(state.clone());
+                let shutdown = shutdown.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = axum::serve(listener, metrics_app)
+                        .with_graceful_shutdown(shutdown.cancelled_owned())
+                        .await
+                    {
+                        error!("Metrics server error: {e}");
+                    }
+                });
+            }
+            None => app = app.merge(metrics::router(state.clone())),
+        }
+    }
+
+    info!("Starting server on {}", config.server_addrs.join(", "));
+
+    // Server configuration, binding every address before serving any of them
+    let mut listeners = Vec::with_capacity(config.server_addrs.len());
+    for server_addr in &config.server_addrs {
+        let listener = tokio::net::TcpListener::bind(server_addr)
+            .await
+            .unwrap_or_else(|e| {
+                error!("Failed to bind to address {server_addr}: {e}");
+                process::exit(1);
+            });
+        listeners.push(listener);
+    }
+
+    // Plain HTTP requests are redirected to HTTPS when a separate address is given for them
+    if let Some(http_redirect_addr) = &config.http_redirect_addr {
+        let listener = tokio::net::TcpListener::bind(http_redirect_addr)
+            .await
+            .unwrap_or_else(|e| {
+                error!("Failed to bind HTTP redirect address: {e}");
+                process::exit(1);
+            });
+        info!("Redirecting HTTP on {} to HTTPS", http_redirect_addr);
+        let redirect_app = tls::redirect_router(&config.server_addrs[0]);
+        let shutdown = shutdown.clone();
+        tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, redirect_app)
+                .with_graceful_shutdown(shutdown.cancelled_owned())
+                .await
+            {
+                error!("HTTP redirect server error: {e}");
+            }
         });
+    }
 
-    // Start the server
-    axum::serve(listener, app)
-        .with_graceful_shutdown(shutdown_signal())
-        .await
-        .unwrap_or_else(|e| {
-            error!("Server error: {e}");
-            process::exit(1);
+    // gRPC API for internal services, on its own address
+    if let Some(grpc_addr) = &config.grpc_addr {
+        let listener = tokio::net::TcpListener::bind(grpc_addr)
+            .await
+            .unwrap_or_else(|e| {
+                error!("Failed to bind gRPC address: {e}");
+                process::exit(1);
+            });
+        info!("Serving gRPC on {}", grpc_addr);
+        let grpc_service = grpc::service(state.clone());
+        let shutdown = shutdown.clone();
+        tokio::spawn(async move {
+            if let Err(e) = tonic::transport::Server::builder()
+                .add_service(grpc_service)
+                .serve_with_incoming_shutdown(
+                    tonic::transport::server::TcpIncoming::from(listener),
+                    shutdown.cancelled_owned(),
+                )
+                .await
+            {
+                error!("gRPC server error: {e}");
+            }
         });
+    }
 
+    // Start the servers, stopping all of them if one fails
+    let app = app.into_make_service_with_connect_info::<SocketAddr>();
+    let mut servers = tokio::task::JoinSet::new();
+    for listener in listeners {
+        servers.spawn(serve(
+            listener,
+            app.clone(),
+            rustls.clone(),
+            shutdown.clone(),
+        ));
+    }
+    let mut serving = pin!(async move {
+        while let Some(served) = servers.join_next().await {
+            if let Err(e) = served.unwrap_or_else(|e| Err(io::Error::other(e))) {
+                error!("Server error: {e}");
+                process::exit(1);
+            }
+        }
+    });
+    let stopped = tokio::select! {
+        _ = &mut serving => true,
+        _ = shutdown.cancelled() => false,
+    };
+
+    // In-flight requests and then background writes share SHUTDOWN_TIMEOUT_SECONDS;
+    // the servers may already have finished, and must not be waited on again then
+    let deadline = Instant::now() + Duration::from_secs(config.shutdown_timeout);
+    if !stopped && time::timeout_at(deadline, serving).await.is_err() {
+        warn!("Requests still running after the shutdown timeout, dropping them");
+    }
     info!("Server stopped.");
+    finish_background_work(&state, deadline).await;
+
+    // Flush the spans still waiting to be exported
+    if let Some(tracer_provider) = tracer_provider {
+        if let Err(e) = tracer_provider.shutdown() {
+            error!("Failed to flush traces: {e}");
+        }
+    }
+}
+
+// Wait for writes that outlive their requests, then close the database connections.
+// Redis needs no closing: its connection goes away with the last handle.
+async fn finish_background_work(state: &AppState, deadline: Instant) {
+    state.background.close();
+    if time::timeout_at(deadline, state.background.wait())
+        .await
+        .is_err()
+    {
+        warn!(
+            tasks = state.background.len(),
+            "Background writes still running after the shutdown timeout, dropping them"
+        );
+    }
+    // Counted clicks would otherwise wait in Redis for the next instance to start
+    if state.click_flush_interval.is_some() {
+        match time::timeout_at(deadline, jobs::clicks::flush(state)).await {
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => error!(error = %e, "Failed to flush click counts"),
+            Err(_) => warn!("Click counts still flushing after the shutdown timeout"),
+        }
+    }
+    state.pg_db.close().await;
+    state.pg_read_db.close().await;
+}
+
+// Serve the app on one listener until shutdown, finishing the requests in flight, over TLS
+// if configured
+async fn serve(
+    listener: tokio::net::TcpListener,
+    app: IntoMakeServiceWithConnectInfo<Router, SocketAddr>,
+    rustls: Option<axum_server::tls_rustls::RustlsConfig>,
+    shutdown: CancellationToken,
+) -> io::Result<()> {
+    let Some(rustls) = rustls else {
+        return axum::serve(listener, app)
+            .with_graceful_shutdown(shutdown.cancelled_owned())
+            .await;
+    };
+
+    let handle = axum_server::Handle::new();
+    tokio::spawn({
+        let handle = handle.clone();
+        async move {
+            shutdown.cancelled().await;
+            handle.graceful_shutdown(None);
+        }
+    });
+    axum_server::from_tcp_rustls(listener.into_std()?, rustls)?
+        .handle(handle)
+        .serve(app)
+        .await
+}
+
+// Re-read the configuration file and the blocklist on every SIGHUP; invalid settings
+// are logged and the current ones kept
+#[cfg(unix)]
+async fn reload_on_hangup(
+    state: AppState,
+    config_path: Option<String>,
+    tls: Option<(config::TlsConfig, axum_server::tls_rustls::RustlsConfig)>,
+) {
+    let mut hangup = match signal::unix::signal(signal::unix::SignalKind::hangup()) {
+        Ok(hangup) => hangup,
+        Err(e) => {
+            error!("Failed to install SIGHUP handler: {e}");
+            return;
+        }
+    };
+
+    while hangup.recv().await.is_some() {
+        info!("Reloading configuration.");
+        match config::RuntimeSettings::reload(config_path.as_deref()) {
+            Ok(settings) => {
+                info!(settings = ?settings, "Runtime settings reloaded.");
+                state.runtime.store(Arc::new(settings));
+            }
+            Err(e) => error!("Keeping current settings: {e}"),
+        }
+        match state.blocklist.reload(&state.pg_db).await {
+            Ok(domains) => info!("Loaded {domains} blocked domains."),
+            Err(e) => error!("Failed to reload blocklist: {e}"),
+        }
+        if let Some((tls, rustls)) = &tls {
+            match tls::reload(rustls, tls).await {
+                Ok(()) => info!("Reloaded TLS certificate."),
+                Err(e) => error!("Keeping current TLS certificate: {e}"),
+            }
+        }
+    }
 }
 
 async fn shutdown_signal() {
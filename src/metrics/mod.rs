@@ -0,0 +1,95 @@
+use std::time::Instant;
+
+use axum::{
+    extract::{MatchedPath, Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+
+use crate::{db, state::AppState};
+
+// Request latency buckets in seconds, from cache hits to slow upstream calls
+const LATENCY_BUCKETS: &[f64] = &[
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+// Install the global Prometheus recorder; everything recorded before this is dropped
+pub fn install() -> Result<PrometheusHandle, String> {
+    PrometheusBuilder::new()
+        .set_buckets_for_metric(
+            Matcher::Full("http_request_duration_seconds".to_string()),
+            LATENCY_BUCKETS,
+        )
+        .map_err(|e| e.to_string())?
+        .install_recorder()
+        .map_err(|e| e.to_string())
+}
+
+// Router serving the scrape endpoint, merged into the app or bound to its own address
+pub fn router(state: AppState) -> Router {
+    Router::new()
+        .route("/metrics", get(render))
+        .with_state(state)
+}
+
+// Count and time every request by its route template, so short codes don't become labels
+pub async fn track_requests(request: Request, next: Next) -> Response {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+    let method = request.method().to_string();
+    let started = Instant::now();
+
+    let response = next.run(request).await;
+
+    let labels = [
+        ("method", method),
+        ("route", route),
+        ("status", response.status().as_u16().to_string()),
+    ];
+    ::metrics::counter!("http_requests_total", &labels).increment(1);
+    ::metrics::histogram!("http_request_duration_seconds", &labels)
+        .record(started.elapsed().as_secs_f64());
+
+    response
+}
+
+pub fn record_redirect() {
+    ::metrics::counter!("redirects_total").increment(1);
+}
+
+// Point-in-time values are sampled on scrape instead of on every change
+async fn render(State(state): State<AppState>) -> Response {
+    let Some(handle) = &state.metrics else {
+        return axum::http::StatusCode::NOT_FOUND.into_response();
+    };
+
+    let counts = state.cache_stats.counts();
+    for (result, count) in [
+        ("local_hit", counts.local_hits),
+        ("hit", counts.hits),
+        ("negative_hit", counts.negative_hits),
+        ("filtered", counts.filtered),
+        ("miss", counts.misses),
+        ("error", counts.errors),
+    ] {
+        ::metrics::counter!("cache_lookups_total", "result" => result).absolute(count);
+    }
+    if let Some(ratio) = counts.hit_ratio() {
+        ::metrics::gauge!("cache_hit_ratio").set(ratio);
+    }
+
+    ::metrics::gauge!("db_pool_connections").set(state.pg_db.size() as f64);
+    ::metrics::gauge!("db_pool_idle_connections").set(state.pg_db.num_idle() as f64);
+    ::metrics::gauge!("db_pool_max_connections")
+        .set(state.pg_db.options().get_max_connections() as f64);
+    let circuit_open = db::breaker::retry_after().is_some();
+    ::metrics::gauge!("db_circuit_open").set(if circuit_open { 1.0 } else { 0.0 });
+
+    handle.render().into_response()
+}
@@ -1,22 +1,255 @@
-use r2d2::Pool;
-use redis::Client;
+use std::{
+    sync::{atomic::AtomicBool, Arc},
+    time::Duration,
+};
+
+use arc_swap::ArcSwap;
+
+use ipnet::IpNet;
+use metrics_exporter_prometheus::PrometheusHandle;
+use moka::sync::Cache;
+use redis::aio::ConnectionManager;
+use regex::Regex;
+use sha2::{Digest, Sha256};
 use sqlx::PgPool;
+use tokio::sync::broadcast;
+use tokio_util::{sync::CancellationToken, task::TaskTracker};
+
+use crate::{
+    abuse::{AbuseAction, RiskScorer},
+    blocklist::Blocklist,
+    cache::{coalesce::Coalescer, filter::CodeFilter, stats::CacheStats},
+    config::{CodeStrategy, Config, DuplicatePolicy, RuntimeSettings},
+    dashboard::Dashboard,
+    db::repository::{PgUrlRepository, UrlRepository},
+    events,
+    geo::GeoIp,
+    types::ClickEvent,
+    utils::{
+        alphabet::Alphabet, rate_limit::RateLimiter, safe_browsing::SafeBrowsing,
+        sequence::Scrambler,
+    },
+};
 
-pub type RedisPool = Pool<Client>;
+// Multiplexed connection shared by all requests; clones are cheap and reconnect on failure
+pub type RedisConn = ConnectionManager;
 
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct AppState {
     pub pg_db: PgPool,
-    pub redis_db: RedisPool,
+    // Replica from DATABASE_READ_URL for reads that may lag behind writes, like listings
+    // and statistics; the same pool as `pg_db` without one. Redirect caches are never filled
+    // from it, since they would keep what the replica had not caught up on.
+    pub pg_read_db: PgPool,
+    // The links themselves, written to and looked up for redirects in `pg_db`, and listed
+    // from `pg_read_db`
+    pub urls: Arc<dyn UrlRepository>,
+    pub redis_db: RedisConn,
+    pub http_client: reqwest::Client,
+    pub resolver_client: reqwest::Client,
     pub base_url: String,
+    pub duplicate_policy: DuplicatePolicy,
+    pub external_id_pattern: Regex,
+    pub access_log: bool,
+    // Settings replaced on SIGHUP, read with `state.runtime.load()`
+    pub runtime: Arc<ArcSwap<RuntimeSettings>>,
+    pub request_timeout: Duration,
+    pub request_body_limit: usize,
+    pub rate_limiter: Arc<RateLimiter>,
+    // In-process copy of the hottest redirect destinations, consulted before Redis
+    pub local_cache: Option<Cache<String, String>>,
+    // Number of most clicked links cached at startup, and again every interval if set
+    pub cache_warm_links: u32,
+    pub cache_warm_interval: Option<Duration>,
+    // Set when redirects are counted in Redis and added to the database this often
+    pub click_flush_interval: Option<Duration>,
+    // Redirect cache misses being looked up in the database
+    pub lookups: Arc<Coalescer>,
+    pub cache_stats: Arc<CacheStats>,
+    pub replay_protection: bool,
+    pub replay_window: u64,
+    // How long responses to creations sent with an Idempotency-Key are replayed, 0 disables
+    pub idempotency_ttl: u64,
+    // Shared secrets that creations may be signed with, see `middleware::verify_signature`
+    pub signing_secrets: Arc<[String]>,
+    pub require_signed_creation: bool,
+    // Peers whose forwarding headers are believed, see `utils::client_ip`
+    pub trusted_proxies: Arc<[IpNet]>,
+    pub abuse_action: AbuseAction,
+    pub abuse_threshold: u32,
+    pub risk_scorer: Arc<RiskScorer>,
+    pub resolve_redirects: bool,
+    pub max_redirect_hops: usize,
+    pub ssrf_dns_check: bool,
+    pub accept_schemeless_urls: bool,
+    pub max_url_length: usize,
+    pub reserved_codes: Arc<[String]>,
+    pub banned_words: Arc<[String]>,
+    pub code_alphabet: Arc<Alphabet>,
+    pub case_insensitive_codes: bool,
+    // Set when codes are generated from the code sequence instead of hashed
+    pub code_scrambler: Option<Scrambler>,
+    pub geoip: Option<Arc<GeoIp>>,
+    pub robots_txt: Arc<str>,
+    pub safe_browsing: Option<Arc<SafeBrowsing>>,
+    // Only the digest of ADMIN_TOKEN is kept so the token never shows up in debug output
+    pub admin_token_digest: Option<Vec<u8>>,
+    pub blocklist: Arc<Blocklist>,
+    pub blocklist_refresh_interval: Option<Duration>,
+    // Set when unknown codes are screened out in memory, together with its rebuild interval
+    pub code_filter: Option<Arc<CodeFilter>>,
+    pub code_filter_refresh_interval: Option<Duration>,
+    // Set when destinations are checked periodically, at most `link_check_rate` per second
+    pub link_check_interval: Option<Duration>,
+    pub link_check_rate: u32,
+    // Consecutive 404/410/unknown host results that stop a link redirecting, 0 never does;
+    // each broken link is POSTed to the webhook when set
+    pub link_check_break_after: u32,
+    pub link_check_webhook_url: Option<String>,
+    // Set when link events are sent to registered webhooks, with due deliveries sent this
+    // often and retried up to `webhook_max_attempts` times; `webhook_clicks` tells whether
+    // any webhook wants every click
+    pub webhook_interval: Option<Duration>,
+    pub webhook_max_attempts: u32,
+    pub webhook_clicks: Arc<AtomicBool>,
+    // Set when redirects are published for the live click stream, fanning out the clicks
+    // of all instances to this one's stream clients
+    pub click_stream: Option<broadcast::Sender<ClickEvent>>,
+    // Set when traffic snapshots are broadcast to dashboards this often
+    pub dashboard: Option<Arc<Dashboard>>,
+    pub dashboard_interval: Option<Duration>,
+    // Set when disabled links are deleted periodically, after `purge_retention_days`
+    pub purge_interval: Option<Duration>,
+    pub purge_retention_days: u32,
+    // Set when click events are dropped a month at a time once older than this many months
+    pub click_retention_months: Option<u32>,
+    // Set when metrics are exported, rendering the scrape output
+    pub metrics: Option<PrometheusHandle>,
+    // Writes that finish after their response, such as click counts, awaited on shutdown
+    pub background: TaskTracker,
+    // Cancelled once the process starts shutting down, ending long-lived responses
+    pub shutdown: CancellationToken,
 }
 
 impl AppState {
-    pub fn new(pg_db: PgPool, redis_db: RedisPool, base_url: String) -> Self {
+    pub fn new(
+        pg_db: PgPool,
+        redis_db: RedisConn,
+        http_client: reqwest::Client,
+        resolver_client: reqwest::Client,
+        geoip: Option<GeoIp>,
+        metrics: Option<PrometheusHandle>,
+        config: &Config,
+    ) -> Self {
         Self {
+            urls: Arc::new(PgUrlRepository::new(pg_db.clone(), None)),
+            pg_read_db: pg_db.clone(),
             pg_db,
             redis_db,
-            base_url,
+            http_client,
+            resolver_client,
+            base_url: config.base_url.clone(),
+            duplicate_policy: config.duplicate_policy,
+            external_id_pattern: config.external_id_pattern.clone(),
+            access_log: config.access_log,
+            runtime: Arc::new(ArcSwap::from_pointee(config.runtime.clone())),
+            request_timeout: Duration::from_secs(config.request_timeout),
+            request_body_limit: config.request_body_limit,
+            rate_limiter: Arc::new(RateLimiter::new(config.request_buffer_size)),
+            local_cache: (config.local_cache_capacity > 0 && config.local_cache_ttl > 0).then(
+                || {
+                    Cache::builder()
+                        .max_capacity(config.local_cache_capacity)
+                        .time_to_live(Duration::from_secs(config.local_cache_ttl))
+                        .build()
+                },
+            ),
+            cache_warm_links: config.cache_warm_links,
+            cache_warm_interval: (config.cache_warm_interval > 0)
+                .then(|| Duration::from_secs(config.cache_warm_interval)),
+            click_flush_interval: (config.click_flush_interval > 0)
+                .then(|| Duration::from_secs(config.click_flush_interval)),
+            lookups: Arc::default(),
+            cache_stats: Arc::default(),
+            replay_protection: config.replay_protection,
+            replay_window: config.replay_window,
+            idempotency_ttl: config.idempotency_ttl,
+            signing_secrets: config.signing_secrets.as_slice().into(),
+            require_signed_creation: config.require_signed_creation,
+            trusted_proxies: config.trusted_proxies.as_slice().into(),
+            abuse_action: config.abuse_action,
+            abuse_threshold: config.abuse_threshold,
+            risk_scorer: Arc::new(RiskScorer::default()),
+            resolve_redirects: config.resolve_redirects,
+            max_redirect_hops: config.max_redirect_hops,
+            ssrf_dns_check: config.ssrf_dns_check,
+            accept_schemeless_urls: config.accept_schemeless_urls,
+            max_url_length: config.max_url_length,
+            reserved_codes: config.reserved_codes.as_slice().into(),
+            banned_words: config.banned_words.as_slice().into(),
+            code_alphabet: Arc::new(config.code_alphabet.clone()),
+            case_insensitive_codes: config.case_insensitive_codes,
+            code_scrambler: match config.code_strategy {
+                CodeStrategy::Hash => None,
+                CodeStrategy::Sequence => {
+                    Scrambler::new(config.code_alphabet.base(), config.sequence_code_length)
+                }
+            },
+            geoip: geoip.map(Arc::new),
+            robots_txt: config.robots_txt.as_str().into(),
+            safe_browsing: config.safe_browsing_api_key.as_ref().map(|api_key| {
+                Arc::new(SafeBrowsing::new(
+                    config.safe_browsing_url.clone(),
+                    api_key.clone(),
+                    config.safe_browsing_action,
+                    (config.safe_browsing_recheck_interval > 0)
+                        .then(|| Duration::from_secs(config.safe_browsing_recheck_interval)),
+                ))
+            }),
+            admin_token_digest: config
+                .admin_token
+                .as_ref()
+                .map(|token| Sha256::digest(token).to_vec()),
+            blocklist: Arc::new(Blocklist::default()),
+            blocklist_refresh_interval: (config.blocklist_refresh_interval > 0)
+                .then(|| Duration::from_secs(config.blocklist_refresh_interval)),
+            code_filter: (config.code_filter_refresh_interval > 0)
+                .then(|| Arc::new(CodeFilter::default())),
+            code_filter_refresh_interval: (config.code_filter_refresh_interval > 0)
+                .then(|| Duration::from_secs(config.code_filter_refresh_interval)),
+            link_check_interval: (config.link_check_interval > 0)
+                .then(|| Duration::from_secs(config.link_check_interval)),
+            link_check_rate: config.link_check_rate,
+            link_check_break_after: config.link_check_break_after,
+            link_check_webhook_url: config.link_check_webhook_url.clone(),
+            webhook_interval: (config.webhook_interval > 0)
+                .then(|| Duration::from_secs(config.webhook_interval)),
+            webhook_max_attempts: config.webhook_max_attempts,
+            webhook_clicks: Arc::default(),
+            click_stream: config
+                .click_stream
+                .then(|| broadcast::channel(events::CLICK_BUFFER).0),
+            dashboard: (config.dashboard_interval > 0).then(Arc::default),
+            dashboard_interval: (config.dashboard_interval > 0)
+                .then(|| Duration::from_secs(config.dashboard_interval)),
+            purge_interval: (config.purge_interval > 0)
+                .then(|| Duration::from_secs(config.purge_interval)),
+            purge_retention_days: config.purge_retention_days,
+            click_retention_months: (config.click_retention_months > 0)
+                .then_some(config.click_retention_months),
+            metrics,
+            background: TaskTracker::new(),
+            shutdown: CancellationToken::new(),
         }
     }
+
+    // Send the reads that tolerate replication lag to a replica
+    pub fn with_read_replica(mut self, pg_read_db: PgPool) -> Self {
+        self.urls = Arc::new(PgUrlRepository::new(
+            self.pg_db.clone(),
+            Some(pg_read_db.clone()),
+        ));
+        self.pg_read_db = pg_read_db;
+        self
+    }
 }
@@ -0,0 +1,153 @@
+use std::{env, process, str::FromStr};
+
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use tracing::level_filters::LevelFilter;
+use tracing_appender::{
+    non_blocking::WorkerGuard,
+    rolling::{RollingFileAppender, Rotation},
+};
+use tracing_subscriber::{
+    fmt::{self, MakeWriter},
+    prelude::*,
+    EnvFilter, Layer, Registry,
+};
+
+type BoxedLayer = Box<dyn Layer<Registry> + Send + Sync>;
+
+/// How log lines are rendered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    /// One JSON object per line.
+    Json,
+    /// Multi-line, human-readable output.
+    Pretty,
+    /// Single-line, human-readable output.
+    Compact,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "json" => Ok(Self::Json),
+            "pretty" => Ok(Self::Pretty),
+            "compact" => Ok(Self::Compact),
+            _ => Err(format!("unknown log format: {s}")),
+        }
+    }
+}
+
+/// Where log lines are written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogOutput {
+    Stdout,
+    /// Daily rotated files in LOG_DIR.
+    File,
+    Both,
+}
+
+impl FromStr for LogOutput {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "stdout" => Ok(Self::Stdout),
+            "file" => Ok(Self::File),
+            "both" => Ok(Self::Both),
+            _ => Err(format!("unknown log output: {s}")),
+        }
+    }
+}
+
+// Install the global subscriber. Each sink has its own filter (LOG_STDOUT_FILTER,
+// LOG_FILE_FILTER), falling back to APP_LOG and then `default_level`.
+// The stdout sink writes to stderr instead for commands whose output goes to stdout.
+// The returned guard flushes the log file when dropped.
+pub fn init(
+    default_level: LevelFilter,
+    tracer_provider: Option<&SdkTracerProvider>,
+    console_to_stderr: bool,
+) -> Option<WorkerGuard> {
+    // The subscriber isn't installed yet, so configuration errors go to stderr
+    let format: LogFormat = parse_env("LOG_FORMAT", "json");
+    let output: LogOutput = parse_env("LOG_OUTPUT", "both");
+
+    let mut layers: Vec<BoxedLayer> = Vec::new();
+    let mut guard = None;
+
+    if matches!(output, LogOutput::Stdout | LogOutput::Both) {
+        let console = if console_to_stderr {
+            fmt_layer(format, std::io::stderr, true)
+        } else {
+            fmt_layer(format, std::io::stdout, true)
+        };
+        layers.push(
+            console
+                .with_filter(filter("LOG_STDOUT_FILTER", default_level))
+                .boxed(),
+        );
+    }
+
+    if matches!(output, LogOutput::File | LogOutput::Both) {
+        let log_dir = env::var("LOG_DIR").unwrap_or_else(|_| "log/".to_string());
+        let file_appender = RollingFileAppender::new(Rotation::DAILY, log_dir, "tlong.log");
+        let (non_blocking_writer, file_guard) = tracing_appender::non_blocking(file_appender);
+        guard = Some(file_guard);
+        layers.push(
+            fmt_layer(format, non_blocking_writer, false)
+                .with_filter(filter("LOG_FILE_FILTER", default_level))
+                .boxed(),
+        );
+    }
+
+    if let Some(tracer_provider) = tracer_provider {
+        layers.push(
+            super::layer(tracer_provider)
+                .with_filter(filter("APP_LOG", default_level))
+                .boxed(),
+        );
+    }
+
+    tracing_subscriber::registry().with(layers).init();
+
+    guard
+}
+
+fn fmt_layer<W>(format: LogFormat, writer: W, ansi: bool) -> BoxedLayer
+where
+    W: for<'writer> MakeWriter<'writer> + Send + Sync + 'static,
+{
+    let layer = fmt::layer().with_writer(writer).with_ansi(ansi);
+    match format {
+        LogFormat::Json => layer.json().boxed(),
+        LogFormat::Pretty => layer.pretty().boxed(),
+        LogFormat::Compact => layer.compact().boxed(),
+    }
+}
+
+fn filter(var: &str, default_level: LevelFilter) -> EnvFilter {
+    let var = if env::var(var).is_ok() {
+        var
+    } else {
+        "APP_LOG"
+    };
+    EnvFilter::builder()
+        .with_env_var(var)
+        .with_default_directive(default_level.into())
+        .from_env_lossy()
+}
+
+fn parse_env<T>(var: &str, default: &str) -> T
+where
+    T: FromStr,
+    T::Err: std::fmt::Display,
+{
+    env::var(var)
+        .unwrap_or_else(|_| default.to_string())
+        .parse()
+        .unwrap_or_else(|e| {
+            eprintln!("Invalid value for {var} environment variable: {e}");
+            process::exit(1);
+        })
+}
@@ -0,0 +1,68 @@
+pub mod logging;
+
+use std::env;
+
+use axum::http::Request;
+use opentelemetry::{global, trace::TracerProvider as _};
+use opentelemetry_http::HeaderExtractor;
+use opentelemetry_otlp::SpanExporter;
+use opentelemetry_sdk::{propagation::TraceContextPropagator, trace::SdkTracerProvider, Resource};
+use tracing::{Span, Subscriber};
+use tracing_opentelemetry::{OpenTelemetryLayer, OpenTelemetrySpanExt};
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::api::middleware::REQUEST_ID_HEADER;
+
+// Exporter for spans over OTLP/HTTP, enabled by setting OTEL_EXPORTER_OTLP_ENDPOINT.
+// The remaining standard OTEL_* variables (headers, timeout, resource attributes) are
+// read by the exporter and SDK themselves.
+pub fn init_tracer() -> Result<Option<SdkTracerProvider>, String> {
+    if env::var("OTEL_EXPORTER_OTLP_ENDPOINT").is_err() {
+        return Ok(None);
+    }
+
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .build()
+        .map_err(|e| e.to_string())?;
+    let service_name = env::var("OTEL_SERVICE_NAME").unwrap_or_else(|_| "tlong".to_string());
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(Resource::builder().with_service_name(service_name).build())
+        .build();
+
+    // Incoming `traceparent` headers make our spans children of the caller's trace
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
+    Ok(Some(provider))
+}
+
+pub fn layer<S>(
+    provider: &SdkTracerProvider,
+) -> OpenTelemetryLayer<S, opentelemetry_sdk::trace::Tracer>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    tracing_opentelemetry::layer().with_tracer(provider.tracer("tlong"))
+}
+
+// Root span of a request, continuing the trace context sent by the caller if any
+pub fn make_span<B>(request: &Request<B>) -> Span {
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    let span = tracing::info_span!(
+        "request",
+        method = %request.method(),
+        uri = %request.uri(),
+        version = ?request.version(),
+        request_id = %request_id,
+    );
+    let parent = global::get_text_map_propagator(|propagator| {
+        propagator.extract(&HeaderExtractor(request.headers()))
+    });
+    span.set_parent(parent);
+    span
+}
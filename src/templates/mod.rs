@@ -0,0 +1,207 @@
+use chrono::{DateTime, Utc};
+use maud::{html, Markup, PreEscaped, DOCTYPE};
+
+use crate::{db::models::UrlDetail, utils::web_url};
+
+fn layout(title: &str, body: Markup) -> Markup {
+    html! {
+        (DOCTYPE)
+        html lang="en" {
+            head {
+                meta charset="utf-8";
+                meta name="viewport" content="width=device-width, initial-scale=1";
+                meta name="robots" content="noindex";
+                title { (title) " - tlong" }
+                style {
+                    "body{font-family:system-ui,sans-serif;max-width:40rem;margin:4rem auto;padding:0 1rem;color:#222}"
+                    "dt{font-weight:600;margin-top:1rem}dd{margin:0;word-break:break-all}"
+                    "a.button{display:inline-block;margin-top:2rem;padding:.5rem 1rem;background:#222;color:#fff;text-decoration:none;border-radius:4px}"
+                    "form{display:flex;gap:.5rem}input{flex:1;padding:.5rem;font:inherit}button{padding:.5rem 1rem;font:inherit;background:#222;color:#fff;border:0;border-radius:4px;cursor:pointer}"
+                }
+            }
+            body { (body) }
+        }
+    }
+}
+
+// Submits the landing page form to the shorten API and shows the outcome
+const LANDING_SCRIPT: &str = r#"
+document.getElementById("shorten").addEventListener("submit", async function (event) {
+    event.preventDefault();
+    var result = document.getElementById("result");
+    result.textContent = "Shortening…";
+    try {
+        var response = await fetch(this.action, {
+            method: "POST",
+            headers: {
+                "Content-Type": "application/json",
+                "X-Request-Timestamp": String(Math.floor(Date.now() / 1000)),
+                "X-Request-Nonce": self.crypto && crypto.randomUUID ? crypto.randomUUID() : Math.random().toString(36).slice(2) + Date.now()
+            },
+            body: JSON.stringify({ long_url: document.getElementById("long_url").value })
+        });
+        var body = await response.json().catch(function () { return {}; });
+        if (response.ok && body.short_url) {
+            var link = document.createElement("a");
+            link.href = body.short_url;
+            link.textContent = body.short_url;
+            result.replaceChildren(link);
+        } else {
+            result.textContent = body.message || body.error || "Request failed with status " + response.status;
+        }
+    } catch (error) {
+        result.textContent = "Request failed";
+    }
+});
+"#;
+
+// Landing page with a form for shortening URLs from the browser
+pub fn landing() -> Markup {
+    layout(
+        "Shorten a URL",
+        html! {
+            h1 { "Shorten a URL" }
+            form #shorten action="/api/v1/shorten" method="post" {
+                input #long_url type="url" name="long_url" required placeholder="https://example.com/very-long-path" aria-label="Long URL" autofocus;
+                button type="submit" { "Shorten" }
+            }
+            p #result role="status" aria-live="polite" {}
+            script { (PreEscaped(LANDING_SCRIPT)) }
+        },
+    )
+}
+
+// Info page describing a short url without redirecting
+pub fn link_info(short_url: &str, detail: &UrlDetail) -> Markup {
+    layout(
+        &detail.short_code,
+        html! {
+            h1 { "Link info" }
+            dl {
+                dt { "Short URL" }
+                dd { (short_url) }
+                dt { "Destination" }
+                @if detail.single_use {
+                    dd { "Hidden (single-use link)" }
+                } @else {
+                    dd { (detail.long_url) }
+                }
+                @if let Some(description) = &detail.description {
+                    dt { "Description" }
+                    dd { (description) }
+                }
+                dt { "Created" }
+                dd { (detail.created_at.format("%Y-%m-%d %H:%M UTC")) }
+                dt { "Clicks" }
+                dd { (detail.clicks) }
+            }
+            @if detail.disabled_at.is_none() {
+                @if detail.single_use {
+                    a.button href=(short_url) rel="noopener noreferrer nofollow" { "Open link (can only be used once)" }
+                } @else if followable(&detail.long_url) {
+                    a.button href=(detail.long_url) rel="noopener noreferrer nofollow" { "Continue to destination" }
+                }
+            }
+        },
+    )
+}
+
+// Whether a destination may be linked from our own pages; links stored before other
+// schemes were refused could hold `javascript:` or `data:` urls
+fn followable(long_url: &str) -> bool {
+    url::Url::parse(long_url).is_ok_and(|url| web_url(&url))
+}
+
+// Page shown when a short code does not exist
+pub fn not_found(short_code: &str) -> Markup {
+    layout(
+        "Not found",
+        html! {
+            h1 { "Link not found" }
+            p { "The short code " code { (short_code) } " does not exist." }
+        },
+    )
+}
+
+// Holding page shown before a link's scheduled activation time
+pub fn not_active(short_code: &str, activates_at: Option<DateTime<Utc>>) -> Markup {
+    layout(
+        short_code,
+        html! {
+            h1 { "Coming soon" }
+            p {
+                "This link is not active yet."
+                @if let Some(activates_at) = activates_at {
+                    " It will be available from " (activates_at.format("%Y-%m-%d %H:%M UTC")) "."
+                }
+            }
+        },
+    )
+}
+
+// Page for a link whose destination kept failing the dead link check
+pub fn broken(short_code: &str) -> Markup {
+    layout(
+        short_code,
+        html! {
+            h1 { "Link unavailable" }
+            p { "The page this link points to no longer exists." }
+        },
+    )
+}
+
+// Interstitial that tries to open an app deep link and falls back to a web destination
+pub fn deep_link(short_code: &str, app_url: &str, fallback_url: &str) -> Markup {
+    // Stay on the page if the app opened and hid it, otherwise move on to the fallback
+    let script = format!(
+        "window.location.href={};setTimeout(function(){{if(!document.hidden)window.location.replace({})}},1500);",
+        js_string(app_url),
+        js_string(fallback_url),
+    );
+
+    layout(
+        short_code,
+        html! {
+            h1 { "Opening the app…" }
+            p { "If nothing happens, " a href=(fallback_url) rel="noopener noreferrer nofollow" { "continue here" } "." }
+            a.button href=(app_url) { "Open in app" }
+            script { (PreEscaped(script)) }
+        },
+    )
+}
+
+// Swagger UI assets, loaded from a CDN so the binary does not have to bundle them
+const SWAGGER_UI_CDN: &str = "https://unpkg.com/swagger-ui-dist@5";
+
+// Swagger UI rendering the OpenAPI spec served at `spec_url`
+pub fn api_docs(spec_url: &str) -> Markup {
+    let script = format!(
+        "window.ui=SwaggerUIBundle({{url:{},dom_id:\"#swagger-ui\"}});",
+        js_string(spec_url),
+    );
+
+    html! {
+        (DOCTYPE)
+        html lang="en" {
+            head {
+                meta charset="utf-8";
+                meta name="viewport" content="width=device-width, initial-scale=1";
+                meta name="robots" content="noindex";
+                title { "API docs - tlong" }
+                link rel="stylesheet" href={ (SWAGGER_UI_CDN) "/swagger-ui.css" };
+            }
+            body {
+                div #swagger-ui {}
+                script src={ (SWAGGER_UI_CDN) "/swagger-ui-bundle.js" } crossorigin="anonymous" {}
+                script { (PreEscaped(script)) }
+            }
+        }
+    }
+}
+
+// JavaScript string literal that is safe to embed in a script element
+fn js_string(value: &str) -> String {
+    serde_json::to_string(value)
+        .unwrap_or_default()
+        .replace("</", "<\\/")
+}
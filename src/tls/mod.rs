@@ -0,0 +1,59 @@
+use axum::{
+    extract::{Request, State},
+    http::{uri::Authority, StatusCode},
+    response::{IntoResponse, Redirect, Response},
+    Router,
+};
+use axum_server::tls_rustls::RustlsConfig;
+
+use crate::config::TlsConfig;
+
+// Certificates and keys are read as PEM; the certificate file may hold the full chain
+pub async fn load(tls: &TlsConfig) -> Result<RustlsConfig, String> {
+    RustlsConfig::from_pem_file(&tls.cert_path, &tls.key_path)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// Pick up a renewed certificate; connections already open keep the old one
+pub async fn reload(rustls: &RustlsConfig, tls: &TlsConfig) -> Result<(), String> {
+    rustls
+        .reload_from_pem_file(&tls.cert_path, &tls.key_path)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// Router for the plain HTTP listener, sending every request to the same path over HTTPS
+// on `https_addr`'s port
+pub fn redirect_router(https_addr: &str) -> Router {
+    let https_port = https_addr
+        .rsplit_once(':')
+        .and_then(|(_, port)| port.parse::<u16>().ok())
+        .unwrap_or(443);
+    Router::new()
+        .fallback(redirect_to_https)
+        .with_state(https_port)
+}
+
+async fn redirect_to_https(State(https_port): State<u16>, request: Request) -> Response {
+    let Some(authority) = request
+        .headers()
+        .get("host")
+        .and_then(|host| host.to_str().ok())
+        .and_then(|host| host.parse::<Authority>().ok())
+    else {
+        return (StatusCode::BAD_REQUEST, "Missing Host header").into_response();
+    };
+
+    let path = request
+        .uri()
+        .path_and_query()
+        .map(|path| path.as_str())
+        .unwrap_or("/");
+    let location = if https_port == 443 {
+        format!("https://{}{path}", authority.host())
+    } else {
+        format!("https://{}:{https_port}{path}", authority.host())
+    };
+    Redirect::permanent(&location).into_response()
+}
@@ -1,21 +1,633 @@
+use std::collections::BTreeMap;
+
+use async_graphql::SimpleObject;
+use chrono::{DateTime, NaiveTime, Utc};
 use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    cache::stats::CacheCounts,
+    db::models::{BlockedDomain, Campaign, Review, UrlDetail, Webhook, WebhookDelivery},
+    utils::normalize::display_url,
+};
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
 pub struct ShortenRequest {
     pub long_url: String,
+    #[serde(flatten)]
+    pub utm: UtmParams,
+    pub activates_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub single_use: bool,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub variants: Vec<Variant>,
+    #[serde(default)]
+    pub geo_targets: BTreeMap<String, String>,
+    #[serde(default)]
+    pub device_targets: BTreeMap<String, String>,
+    #[serde(default)]
+    pub time_rules: Vec<TimeRule>,
+    pub deep_link: Option<DeepLink>,
+    // Overrides DUPLICATE_POLICY: reuse an existing link to the destination or always create one
+    pub reuse_existing: Option<bool>,
 }
 
-#[derive(Debug, Serialize)]
+// Destination used between two times of day (UTC); windows may wrap past midnight
+#[derive(Debug, Clone, Deserialize, Serialize, sqlx::FromRow, ToSchema)]
+pub struct TimeRule {
+    #[serde(rename = "from")]
+    pub starts_at: NaiveTime,
+    #[serde(rename = "to")]
+    pub ends_at: NaiveTime,
+    pub long_url: String,
+    // Lower values are evaluated first; defaults to the rule's position
+    pub priority: Option<i32>,
+}
+
+// App deep link opened on mobile devices, falling back to the platform's app store
+#[derive(Debug, Clone, Deserialize, Serialize, sqlx::FromRow, ToSchema)]
+pub struct DeepLink {
+    pub uri: String,
+    pub ios_store_url: Option<String>,
+    pub android_store_url: Option<String>,
+}
+
+// Alternative destination of an A/B split link
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+pub struct Variant {
+    pub long_url: String,
+    pub weight: i32,
+}
+
+impl ShortenRequest {
+    // Whether redirects depend on the visitor rather than always going to one destination
+    pub fn is_routed(&self) -> bool {
+        !self.variants.is_empty()
+            || !self.geo_targets.is_empty()
+            || !self.device_targets.is_empty()
+            || !self.time_rules.is_empty()
+            || self.deep_link.is_some()
+    }
+
+    // Every web destination a visitor may be sent to
+    pub fn destinations(&self) -> Vec<&str> {
+        let mut destinations = vec![self.long_url.as_str()];
+        destinations.extend(
+            self.variants
+                .iter()
+                .map(|variant| variant.long_url.as_str()),
+        );
+        destinations.extend(self.geo_targets.values().map(String::as_str));
+        destinations.extend(self.device_targets.values().map(String::as_str));
+        destinations.extend(self.time_rules.iter().map(|rule| rule.long_url.as_str()));
+        if let Some(deep_link) = &self.deep_link {
+            destinations.extend(deep_link.ios_store_url.as_deref());
+            destinations.extend(deep_link.android_store_url.as_deref());
+        }
+        destinations
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize, sqlx::FromRow, ToSchema)]
+pub struct UtmParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub utm_source: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub utm_medium: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub utm_campaign: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub utm_term: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub utm_content: Option<String>,
+}
+
+impl UtmParams {
+    pub fn pairs(&self) -> Vec<(String, String)> {
+        [
+            ("utm_source", &self.utm_source),
+            ("utm_medium", &self.utm_medium),
+            ("utm_campaign", &self.utm_campaign),
+            ("utm_term", &self.utm_term),
+            ("utm_content", &self.utm_content),
+        ]
+        .into_iter()
+        .filter_map(|(key, value)| Some((key.to_string(), value.clone()?)))
+        .collect()
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct ExternalLinkRequest {
+    pub long_url: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ExternalLinkResponse {
+    pub external_id: String,
+    pub short_url: String,
+    pub long_url: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
 pub struct ShortenResponse {
     pub short_code: String,
     pub short_url: String,
     pub long_url: String,
+    #[serde(flatten)]
+    pub utm: UtmParams,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub activates_at: Option<String>,
+    pub single_use: bool,
+    pub tags: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub variants: Vec<Variant>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub geo_targets: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub device_targets: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub time_rules: Vec<TimeRule>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deep_link: Option<DeepLink>,
+}
+
+impl ShortenResponse {
+    pub fn new(short_code: String, short_url: String, request: ShortenRequest) -> Self {
+        Self {
+            short_code,
+            short_url,
+            long_url: request.long_url,
+            utm: request.utm,
+            activates_at: request
+                .activates_at
+                .map(|activates_at| activates_at.to_string()),
+            single_use: request.single_use,
+            tags: request.tags,
+            description: request.description,
+            variants: request.variants,
+            geo_targets: request.geo_targets,
+            device_targets: request.device_targets,
+            time_rules: request.time_rules,
+            deep_link: request.deep_link,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListQuery {
+    pub tag: Option<String>,
+    pub q: Option<String>,
+    // Only links whose destination failed (or passed) the latest dead link check
+    pub broken: Option<bool>,
+}
+
+// Body format of link lists and details: `json`, `csv` or `ndjson`, overriding Accept
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct FormatQuery {
+    pub format: Option<String>,
+}
+
+// Page of a v2 list, counted from 1
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PageQuery {
+    pub page: Option<u32>,
+    pub per_page: Option<u32>,
+}
+
+// Position of a v2 list page, reported in the envelope's `meta`
+#[derive(Debug, Clone, Serialize, SimpleObject)]
+pub struct Pagination {
+    pub page: u32,
+    pub per_page: u32,
+    pub total: i64,
+    pub total_pages: i64,
+}
+
+impl Pagination {
+    pub fn new(page: u32, per_page: u32, total: i64) -> Self {
+        Self {
+            page,
+            per_page,
+            total,
+            total_pages: (total + i64::from(per_page) - 1) / i64::from(per_page),
+        }
+    }
+}
+
+// Body of every v2 response: `data` on success, the problem details in `error` otherwise
+#[derive(Debug, Serialize)]
+pub struct Envelope {
+    pub data: Option<serde_json::Value>,
+    pub error: Option<serde_json::Value>,
+    pub meta: Meta,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct Meta {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pagination: Option<Pagination>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateUrlRequest {
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow, ToSchema, SimpleObject)]
+pub struct TagCount {
+    pub tag: String,
+    pub count: i64,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DeleteQuery {
+    #[serde(default)]
+    pub purge: bool,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ExpandQuery {
+    pub url: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ExpandResponse {
+    pub short_code: String,
+    pub short_url: String,
+    pub long_url: String,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct UrlDetailResponse {
     pub short_code: String,
     pub short_url: String,
     pub long_url: String,
+    // Destination with internationalized domain names in Unicode, if it has any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub display_url: Option<String>,
+    pub resolved_url: Option<String>,
+    #[serde(flatten)]
+    pub utm: UtmParams,
+    pub tags: Vec<String>,
+    pub description: Option<String>,
+    pub campaign_id: Option<i32>,
+    pub clicks: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub threat_type: Option<String>,
+    pub activates_at: Option<String>,
+    pub single_use: bool,
+    pub disabled_at: Option<String>,
     pub created_at: String,
+    // Latest dead link check, absent until the destination was checked
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_checked_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_status: Option<i16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_error: Option<String>,
+    // Set while the link serves an error page instead of its unreachable destination
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub broken_at: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub variants: Vec<VariantStats>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub geo_targets: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub device_targets: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub time_rules: Vec<TimeRule>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deep_link: Option<DeepLink>,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow, ToSchema, SimpleObject)]
+pub struct VariantStats {
+    pub long_url: String,
+    pub weight: i32,
+    pub clicks: i64,
+}
+
+impl UrlDetailResponse {
+    pub fn new(detail: UrlDetail, base_url: &str) -> Self {
+        Self {
+            short_url: format!("{}/{}", base_url, detail.short_code),
+            short_code: detail.short_code,
+            display_url: display_url(&detail.long_url),
+            long_url: detail.long_url,
+            resolved_url: detail.resolved_url,
+            utm: detail.utm,
+            tags: detail.tags,
+            description: detail.description,
+            campaign_id: detail.campaign_id,
+            clicks: detail.clicks,
+            threat_type: detail.threat_type,
+            activates_at: detail
+                .activates_at
+                .map(|activates_at| activates_at.to_string()),
+            single_use: detail.single_use,
+            disabled_at: detail
+                .disabled_at
+                .map(|disabled_at| disabled_at.to_string()),
+            created_at: detail.created_at.to_string(),
+            last_checked_at: detail
+                .last_checked_at
+                .map(|last_checked_at| last_checked_at.to_string()),
+            target_status: detail.target_status,
+            target_error: detail.target_error,
+            broken_at: detail.broken_at.map(|broken_at| broken_at.to_string()),
+            variants: Vec::new(),
+            geo_targets: BTreeMap::new(),
+            device_targets: BTreeMap::new(),
+            time_rules: Vec::new(),
+            deep_link: None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum QrFormat {
+    #[default]
+    Png,
+    Svg,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct QrQuery {
+    #[serde(default)]
+    #[param(inline)]
+    pub format: QrFormat,
+    pub size: Option<u32>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PreviewResponse {
+    pub short_code: String,
+    pub long_url: String,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub image_url: Option<String>,
+    pub fetched_at: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CampaignRequest {
+    pub name: String,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CampaignResponse {
+    pub id: i32,
+    pub name: String,
+    pub description: Option<String>,
+    pub links: i64,
+    pub created_at: String,
+}
+
+impl CampaignResponse {
+    pub fn new(campaign: Campaign) -> Self {
+        Self {
+            id: campaign.id,
+            name: campaign.name,
+            description: campaign.description,
+            links: campaign.links,
+            created_at: campaign.created_at.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CampaignLinksRequest {
+    pub short_codes: Vec<String>,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow, ToSchema, SimpleObject)]
+pub struct LinkClicks {
+    pub short_code: String,
+    pub clicks: i64,
+}
+
+#[derive(Debug, Serialize, ToSchema, SimpleObject)]
+#[graphql(name = "CampaignStats")]
+pub struct CampaignStatsResponse {
+    pub id: i32,
+    pub name: String,
+    pub links: i64,
+    pub clicks: i64,
+    pub top_links: Vec<LinkClicks>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct BlockedDomainRequest {
+    pub domain: String,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ReviewResponse {
+    pub id: i32,
+    pub long_url: String,
+    // Set when the held creation is an external link
+    pub external_id: Option<String>,
+    pub client_ip: Option<String>,
+    pub score: i32,
+    pub reasons: Vec<String>,
+    pub created_at: String,
+}
+
+impl ReviewResponse {
+    pub fn new(review: Review) -> Self {
+        Self {
+            id: review.id,
+            long_url: review.long_url,
+            external_id: review.external_id,
+            client_ip: review.client_ip,
+            score: review.score,
+            reasons: review.reasons,
+            created_at: review.created_at.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BlockedDomainResponse {
+    pub domain: String,
+    pub reason: Option<String>,
+    pub created_at: String,
+}
+
+impl BlockedDomainResponse {
+    pub fn new(blocked: BlockedDomain) -> Self {
+        Self {
+            domain: blocked.domain,
+            reason: blocked.reason,
+            created_at: blocked.created_at.to_string(),
+        }
+    }
+}
+
+// A redirect as shown on the live click stream
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClickEvent {
+    pub short_code: String,
+    pub timestamp: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub country: Option<String>,
+}
+
+// Traffic of one instance over the last interval, as sent to dashboards
+#[derive(Debug, Clone, Serialize)]
+pub struct DashboardSnapshot {
+    pub timestamp: DateTime<Utc>,
+    pub interval_seconds: u64,
+    pub redirects: u64,
+    pub redirects_per_second: f64,
+    pub top_codes: Vec<CodeRedirects>,
+    // Absent when no redirect was looked up
+    pub cache_hit_ratio: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CodeRedirects {
+    pub short_code: String,
+    pub redirects: u64,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ClickStreamQuery {
+    pub short_code: Option<String>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct WebhookRequest {
+    pub url: String,
+    pub events: Vec<String>,
+    // Generated when absent
+    pub secret: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct WebhookResponse {
+    pub id: i32,
+    pub url: String,
+    pub events: Vec<String>,
+    // Only shown when the webhook is registered
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+    pub created_at: String,
+}
+
+impl WebhookResponse {
+    pub fn new(webhook: Webhook, show_secret: bool) -> Self {
+        Self {
+            id: webhook.id,
+            url: webhook.url,
+            events: webhook.events,
+            secret: show_secret.then_some(webhook.secret),
+            created_at: webhook.created_at.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DeliveryQuery {
+    // `pending`, `delivered` or `failed`
+    pub status: Option<String>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct WebhookDeliveryResponse {
+    pub id: i64,
+    pub event: String,
+    pub status: String,
+    pub attempts: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_status: Option<i16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    // When a pending delivery is attempted next
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_attempt_at: Option<String>,
+    pub created_at: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delivered_at: Option<String>,
+}
+
+impl WebhookDeliveryResponse {
+    pub fn new(delivery: WebhookDelivery) -> Self {
+        Self {
+            id: delivery.id,
+            next_attempt_at: (delivery.status == "pending")
+                .then(|| delivery.next_attempt_at.to_string()),
+            event: delivery.event,
+            status: delivery.status,
+            attempts: delivery.attempts,
+            response_status: delivery.response_status,
+            last_error: delivery.last_error,
+            created_at: delivery.created_at.to_string(),
+            delivered_at: delivery
+                .delivered_at
+                .map(|delivered_at| delivered_at.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CacheStatsResponse {
+    pub local_hits: u64,
+    pub hits: u64,
+    pub negative_hits: u64,
+    pub filtered: u64,
+    pub misses: u64,
+    pub errors: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hit_ratio: Option<f64>,
+}
+
+impl CacheStatsResponse {
+    pub fn new(counts: CacheCounts) -> Self {
+        Self {
+            local_hits: counts.local_hits,
+            hits: counts.hits,
+            negative_hits: counts.negative_hits,
+            filtered: counts.filtered,
+            misses: counts.misses,
+            errors: counts.errors,
+            hit_ratio: counts.hit_ratio(),
+        }
+    }
+}
+
+// Build details captured at compile time by build.rs
+#[derive(Debug, Serialize, ToSchema)]
+pub struct VersionResponse {
+    pub version: &'static str,
+    pub git_sha: &'static str,
+    pub build_timestamp: &'static str,
+    pub rustc_version: &'static str,
+}
+
+impl VersionResponse {
+    pub const CURRENT: Self = Self {
+        version: env!("CARGO_PKG_VERSION"),
+        git_sha: env!("VERGEN_GIT_SHA"),
+        build_timestamp: env!("VERGEN_BUILD_TIMESTAMP"),
+        rustc_version: env!("VERGEN_RUSTC_SEMVER"),
+    };
 }
@@ -0,0 +1,136 @@
+use std::str::FromStr;
+
+// Bitcoin base58, without the look-alikes 0, O, I and l
+pub const BASE58: &str = "123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+pub const BASE62: &str = "0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+// Smallest custom alphabet, keeping 8 character codes at over four billion combinations
+const MIN_SYMBOLS: usize = 16;
+
+/// Characters short codes are written with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Alphabet {
+    symbols: Vec<u8>,
+}
+
+impl Default for Alphabet {
+    fn default() -> Self {
+        Self {
+            symbols: BASE58.as_bytes().to_vec(),
+        }
+    }
+}
+
+impl FromStr for Alphabet {
+    type Err = String;
+
+    // `base58`, `base62` or the symbols of a custom alphabet in order
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let symbols = match s.to_ascii_lowercase().as_str() {
+            "base58" => BASE58,
+            "base62" => BASE62,
+            _ => s,
+        };
+
+        if let Some(c) = symbols
+            .chars()
+            .find(|c| !c.is_ascii_alphanumeric() && !matches!(c, '-' | '_'))
+        {
+            return Err(format!("unsupported character in code alphabet: {c:?}"));
+        }
+        let mut unique = symbols.as_bytes().to_vec();
+        unique.sort_unstable();
+        unique.dedup();
+        if unique.len() != symbols.len() {
+            return Err("code alphabet contains repeated characters".to_string());
+        }
+        if symbols.len() < MIN_SYMBOLS {
+            return Err(format!(
+                "code alphabet needs at least {MIN_SYMBOLS} characters"
+            ));
+        }
+
+        Ok(Self {
+            symbols: symbols.as_bytes().to_vec(),
+        })
+    }
+}
+
+impl Alphabet {
+    // Encode bytes as a big-endian number in this alphabet, one leading zero symbol
+    // per leading zero byte; with the base58 alphabet this matches `bs58::encode`
+    pub fn encode(&self, bytes: &[u8]) -> String {
+        let base = self.symbols.len() as u32;
+        let mut digits: Vec<u32> = Vec::with_capacity(bytes.len() * 2);
+        for &byte in bytes {
+            let mut carry = byte as u32;
+            for digit in digits.iter_mut() {
+                carry += *digit << 8;
+                *digit = carry % base;
+                carry /= base;
+            }
+            while carry > 0 {
+                digits.push(carry % base);
+                carry /= base;
+            }
+        }
+
+        let zeros = bytes.iter().take_while(|&&byte| byte == 0).count();
+        std::iter::repeat_n(self.symbols[0], zeros)
+            .chain(
+                digits
+                    .iter()
+                    .rev()
+                    .map(|&digit| self.symbols[digit as usize]),
+            )
+            .map(char::from)
+            .collect()
+    }
+
+    // The lowercase symbols of this alphabet, without the duplicates left by lowercasing,
+    // for codes that resolve regardless of case
+    pub fn lowercase(&self) -> Self {
+        let mut symbols: Vec<u8> = Vec::with_capacity(self.symbols.len());
+        for symbol in self.symbols.iter().map(u8::to_ascii_lowercase) {
+            if !symbols.contains(&symbol) {
+                symbols.push(symbol);
+            }
+        }
+        Self { symbols }
+    }
+
+    // Number of symbols
+    pub fn base(&self) -> usize {
+        self.symbols.len()
+    }
+
+    // Encode a number with exactly `length` symbols, padding with the zero symbol
+    pub fn encode_number(&self, mut number: u64, length: usize) -> String {
+        let base = self.symbols.len() as u64;
+        let mut code = vec![self.symbols[0]; length];
+        for symbol in code.iter_mut().rev() {
+            *symbol = self.symbols[(number % base) as usize];
+            number /= base;
+        }
+        code.into_iter().map(char::from).collect()
+    }
+
+    // Code of exactly `length` symbols picked at random
+    pub fn random(&self, length: usize) -> Result<String, getrandom::Error> {
+        let mut bytes = vec![0u8; length * 4];
+        getrandom::fill(&mut bytes)?;
+        Ok(bytes
+            .chunks_exact(4)
+            .map(|chunk| {
+                let number = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+                char::from(self.symbols[number as usize % self.symbols.len()])
+            })
+            .collect())
+    }
+
+    // Whether the text only uses symbols of this alphabet
+    pub fn contains(&self, text: &str) -> bool {
+        text.bytes().all(|byte| self.symbols.contains(&byte))
+    }
+}
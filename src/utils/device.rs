@@ -0,0 +1,49 @@
+use std::str::FromStr;
+
+use woothee::parser::Parser;
+
+// Device class a link can send visitors to a dedicated destination for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Device {
+    Ios,
+    Android,
+    Desktop,
+}
+
+impl Device {
+    // Classify a User-Agent header; crawlers and unrecognized agents have no device
+    pub fn from_user_agent(user_agent: &str) -> Option<Self> {
+        let result = Parser::new().parse(user_agent)?;
+        match (result.os, result.category) {
+            ("iPhone" | "iPad" | "iPod", _) => Some(Self::Ios),
+            ("Android", _) => Some(Self::Android),
+            (_, "pc") => Some(Self::Desktop),
+            _ => None,
+        }
+    }
+
+    pub fn is_mobile(&self) -> bool {
+        matches!(self, Self::Ios | Self::Android)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Ios => "ios",
+            Self::Android => "android",
+            Self::Desktop => "desktop",
+        }
+    }
+}
+
+impl FromStr for Device {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "ios" => Ok(Self::Ios),
+            "android" => Ok(Self::Android),
+            "desktop" => Ok(Self::Desktop),
+            _ => Err(format!("unknown device: {s}")),
+        }
+    }
+}
@@ -1,22 +1,221 @@
+pub mod alphabet;
+pub mod badge;
+pub mod device;
+pub mod normalize;
+pub mod preview;
+pub mod qr;
+pub mod rate_limit;
+pub mod resolve;
+pub mod retry;
+pub mod safe_browsing;
+pub mod sequence;
+pub mod signing;
+pub mod ssrf;
 // pub mod logging;
 
+use std::net::{IpAddr, SocketAddr};
+
+use axum::http::HeaderMap;
+use ipnet::IpNet;
 use sha2::{Digest, Sha256};
 
+use self::alphabet::Alphabet;
+
 // Encoding the long url
-pub async fn encode_long_url(url: &String) -> String {
+pub async fn encode_long_url(url: &str, alphabet: &Alphabet) -> String {
     let hash = Sha256::digest(url.as_bytes());
-    bs58::encode(hash).into_string()
+    alphabet.encode(&hash)
 }
 
-// Validation for long url
+// Validation for long url, which must be a web page outside private networks
 pub fn valid_url(url: &str) -> bool {
-    url::Url::parse(url).is_ok()
+    url::Url::parse(url).is_ok_and(|url| web_url(&url) && ssrf::public_host(&url))
+}
+
+// Whether a url opens a web page, rather than running script or reading local data
+pub fn web_url(url: &url::Url) -> bool {
+    matches!(url.scheme(), "http" | "https")
+}
+
+// Longest short code the database columns hold; hashed codes always use all of it
+pub const MAX_SHORT_CODE_LENGTH: usize = 8;
+
+// Words kept free for routes and future top-level paths, matched case-insensitively
+pub const RESERVED_CODES: [&str; 21] = [
+    "admin",
+    "api",
+    "app",
+    "assets",
+    "auth",
+    "dashboard",
+    "docs",
+    "favicon.ico",
+    "graphql",
+    "health",
+    "login",
+    "logout",
+    "metrics",
+    "robots.txt",
+    "settings",
+    "signup",
+    "sitemap.xml",
+    "static",
+    "status",
+    "version",
+    "x",
+];
+
+// Offensive words generated codes must not contain, matched case-insensitively and
+// through digit look-alikes like `5h1t`
+pub const BANNED_WORDS: [&str; 27] = [
+    "anal", "anus", "arse", "ass", "bitch", "boob", "cock", "crap", "cum", "cunt", "dick", "fag",
+    "fuck", "jizz", "nazi", "nigg", "penis", "piss", "porn", "rape", "sex", "shit", "slut", "tit",
+    "twat", "wank", "whore",
+];
+
+// Whether a short code contains one of the banned words, which must be lowercase
+pub fn banned_code(short_code: &str, banned: &[String]) -> bool {
+    let plain: String = short_code
+        .chars()
+        .map(|c| match c.to_ascii_lowercase() {
+            '0' => 'o',
+            '1' => 'i',
+            '3' => 'e',
+            '4' => 'a',
+            '5' => 's',
+            '7' => 't',
+            c => c,
+        })
+        .collect();
+    let lowercase = short_code.to_lowercase();
+    banned
+        .iter()
+        .any(|word| plain.contains(word.as_str()) || lowercase.contains(word.as_str()))
+}
+
+// Short code as stored, which is lowercase when codes are case-insensitive
+pub fn canonical_code(short_code: &str, case_insensitive: bool) -> String {
+    if case_insensitive {
+        short_code.to_ascii_lowercase()
+    } else {
+        short_code.to_string()
+    }
+}
+
+// Whether a short code is one of the reserved words, which must be lowercase
+pub fn reserved_code(short_code: &str, reserved: &[String]) -> bool {
+    reserved.contains(&short_code.to_lowercase())
 }
 
 // Short code validation
-pub fn valid_short_code(short_code: &str) -> bool {
-    if short_code.len() != 8 {
-        return false;
+pub fn valid_short_code(short_code: &str, alphabet: &Alphabet) -> bool {
+    (1..=MAX_SHORT_CODE_LENGTH).contains(&short_code.len()) && alphabet.contains(short_code)
+}
+
+// Extract the short code from a full short url
+pub fn short_code_from_url(short_url: &str) -> Option<String> {
+    let url = url::Url::parse(short_url).ok()?;
+    let short_code = url.path_segments()?.rfind(|segment| !segment.is_empty())?;
+    Some(short_code.to_string())
+}
+
+// Merge query parameters into the url, overriding existing keys
+pub fn merge_query(long_url: &str, query: &str) -> String {
+    let incoming = url::form_urlencoded::parse(query.as_bytes())
+        .into_owned()
+        .collect();
+    merge_params(long_url, incoming)
+}
+
+// Merge parameters into the url's query string, overriding existing keys
+pub fn merge_params(long_url: &str, params: Vec<(String, String)>) -> String {
+    if params.is_empty() {
+        return long_url.to_string();
+    }
+    let Ok(mut url) = url::Url::parse(long_url) else {
+        return long_url.to_string();
+    };
+
+    let existing: Vec<(String, String)> = url
+        .query_pairs()
+        .into_owned()
+        .filter(|(key, _)| !params.iter().any(|(param_key, _)| param_key == key))
+        .collect();
+
+    url.query_pairs_mut()
+        .clear()
+        .extend_pairs(existing)
+        .extend_pairs(params);
+    url.into()
+}
+
+// Append extra path segments to the url's path
+pub fn append_path(long_url: &str, path: &str) -> String {
+    let Ok(mut url) = url::Url::parse(long_url) else {
+        return long_url.to_string();
+    };
+    let joined = format!(
+        "{}/{}",
+        url.path().trim_end_matches('/'),
+        path.trim_start_matches('/')
+    );
+    url.set_path(&joined);
+    url.into()
+}
+
+// Client IP address. Forwarding headers are only believed when the peer is one of the
+// trusted proxies, since anyone else can send them; X-Forwarded-For is read from the
+// nearest hop back, so the client is the first address that is not a trusted proxy.
+pub fn client_ip(
+    headers: &HeaderMap,
+    remote_addr: Option<SocketAddr>,
+    trusted_proxies: &[IpNet],
+) -> Option<IpAddr> {
+    let peer = remote_addr?.ip();
+    let trusted = |ip: &IpAddr| trusted_proxies.iter().any(|proxy| proxy.contains(ip));
+    if !trusted(&peer) {
+        return Some(peer);
     }
-    bs58::decode(short_code).into_vec().is_ok()
+
+    if let Some(forwarded) = headers
+        .get("x-forwarded-for")
+        .and_then(|value| value.to_str().ok())
+    {
+        let mut client = peer;
+        for hop in forwarded.rsplit(',') {
+            let Ok(ip) = hop.trim().parse() else {
+                break;
+            };
+            client = ip;
+            if !trusted(&ip) {
+                break;
+            }
+        }
+        return Some(client);
+    }
+
+    headers
+        .get("x-real-ip")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse().ok())
+        .or(Some(peer))
+}
+
+// Tag validation
+pub fn valid_tag(tag: &str) -> bool {
+    let tag = tag.trim();
+    !tag.is_empty() && tag.chars().count() <= 64
+}
+
+// Canonical form of a tag
+pub fn normalize_tag(tag: &str) -> String {
+    tag.trim().to_lowercase()
+}
+
+// App deep link validation: any absolute URI except schemes that run code in the browser
+pub fn valid_deep_link(uri: &str) -> bool {
+    url::Url::parse(uri).is_ok_and(|uri| {
+        !matches!(uri.scheme(), "javascript" | "data" | "vbscript" | "file")
+            && ssrf::public_host(&uri)
+    })
 }
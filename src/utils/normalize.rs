@@ -0,0 +1,218 @@
+use url::{Host, Url};
+
+// Per-click identifiers added by ad platforms, which never change the destination page
+const TRACKING_PARAMS: [&str; 13] = [
+    "fbclid",
+    "gclid",
+    "gclsrc",
+    "dclid",
+    "gbraid",
+    "wbraid",
+    "msclkid",
+    "yclid",
+    "twclid",
+    "ttclid",
+    "igshid",
+    "li_fat_id",
+    "mc_eid",
+];
+
+// Canonical spelling of a url, so equivalent urls hash to the same short code:
+// lowercase scheme and host, no default port, no percent-encoded unreserved characters,
+// no click identifiers, query parameters sorted by name and no empty query or fragment.
+// Urls that cannot be parsed are returned unchanged.
+pub fn normalize_url(url: &str) -> String {
+    // Parsing lowercases the scheme and host and drops default ports
+    let Ok(mut parsed) = Url::parse(url) else {
+        return url.to_string();
+    };
+
+    if !parsed.cannot_be_a_base() {
+        let path = normalize_percent_encoding(parsed.path());
+        parsed.set_path(&path);
+    }
+
+    // Work on the raw pairs so their encoding and value-less keys are kept as submitted
+    let query = parsed.query().map(|query| {
+        let mut pairs: Vec<String> = query
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(normalize_percent_encoding)
+            .filter(|pair| !TRACKING_PARAMS.contains(&param_key(pair)))
+            .collect();
+        // Stable so repeated keys keep their relative order
+        pairs.sort_by(|a, b| param_key(a).cmp(param_key(b)));
+        pairs.join("&")
+    });
+    parsed.set_query(query.as_deref().filter(|query| !query.is_empty()));
+
+    if parsed.fragment() == Some("") {
+        parsed.set_fragment(None);
+    }
+
+    parsed.into()
+}
+
+// Complete a url submitted without a scheme, e.g. `example.com/page`, with `https://`.
+// Input that already has a scheme is returned as is; `None` means the text does not
+// start with something that looks like a public host name or an IP address.
+pub fn with_default_scheme(input: &str) -> Option<String> {
+    let input = input.trim();
+    if input.contains("://") {
+        return Some(input.to_string());
+    }
+
+    let candidate = match input.strip_prefix("//") {
+        Some(rest) => format!("https://{rest}"),
+        None => format!("https://{input}"),
+    };
+    let Ok(url) = Url::parse(&candidate) else {
+        // Urls with another scheme, like `mailto:` or `tel:`, are kept
+        return Url::parse(input).is_ok().then(|| input.to_string());
+    };
+    // `user:pass@host` without a scheme is more likely `mailto:user@host` than a login
+    if !url.username().is_empty() || url.password().is_some() {
+        return Some(input.to_string());
+    }
+
+    let plausible_host =
+        match url.host() {
+            Some(Host::Domain(domain)) => domain
+                .trim_end_matches('.')
+                .rsplit_once('.')
+                .is_some_and(|(_, tld)| {
+                    tld.len() >= 2
+                        && (tld.chars().all(|c| c.is_ascii_alphabetic()) || tld.starts_with("xn--"))
+                }),
+            Some(Host::Ipv4(_) | Host::Ipv6(_)) => true,
+            None => false,
+        };
+    plausible_host.then_some(candidate)
+}
+
+// Url with an ASCII host (punycode for internationalized domain names) and a
+// percent-encoded path and query, as stored and sent in `Location` headers
+pub fn ascii_url(url: &str) -> String {
+    Url::parse(url).map_or_else(|_| url.to_string(), String::from)
+}
+
+// Url with internationalized domain names shown in Unicode, if its host has any
+pub fn display_url(url: &str) -> Option<String> {
+    let parsed = Url::parse(url).ok()?;
+    let Some(Host::Domain(host)) = parsed.host() else {
+        return None;
+    };
+    if !host.split('.').any(|label| label.starts_with("xn--")) {
+        return None;
+    }
+
+    let (unicode_host, result) = idna::domain_to_unicode(host);
+    result.ok()?;
+    let serialized = parsed.as_str();
+    let host_start = serialized.find(host)?;
+    Some(format!(
+        "{}{}{}",
+        &serialized[..host_start],
+        unicode_host,
+        &serialized[host_start + host.len()..]
+    ))
+}
+
+fn param_key(pair: &str) -> &str {
+    pair.split('=').next().unwrap_or_default()
+}
+
+// Decode percent-encoded unreserved characters and uppercase the hex digits of the rest
+fn normalize_percent_encoding(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut output = String::with_capacity(input.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let decoded = (bytes[index] == b'%')
+            .then(|| input.get(index + 1..index + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match decoded {
+            Some(byte) if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) => {
+                output.push(byte as char);
+                index += 3;
+            }
+            Some(byte) => {
+                output.push_str(&format!("%{byte:02X}"));
+                index += 3;
+            }
+            None => {
+                let len = input[index..].chars().next().map_or(1, char::len_utf8);
+                output.push_str(&input[index..index + len]);
+                index += len;
+            }
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::with_default_scheme;
+
+    #[test]
+    fn bare_host_gets_https() {
+        assert_eq!(
+            with_default_scheme("example.com/page?q=1").as_deref(),
+            Some("https://example.com/page?q=1")
+        );
+        assert_eq!(
+            with_default_scheme("  www.example.co.uk  ").as_deref(),
+            Some("https://www.example.co.uk")
+        );
+        assert_eq!(
+            with_default_scheme("93.184.216.34/index.html").as_deref(),
+            Some("https://93.184.216.34/index.html")
+        );
+    }
+
+    #[test]
+    fn host_with_port_keeps_port() {
+        assert_eq!(
+            with_default_scheme("example.com:8080/path").as_deref(),
+            Some("https://example.com:8080/path")
+        );
+        assert_eq!(
+            with_default_scheme("[2606:2800:220:1::248]:8443").as_deref(),
+            Some("https://[2606:2800:220:1::248]:8443")
+        );
+    }
+
+    #[test]
+    fn existing_scheme_is_kept() {
+        assert_eq!(
+            with_default_scheme("http://example.com/page").as_deref(),
+            Some("http://example.com/page")
+        );
+        assert_eq!(
+            with_default_scheme("ftp://example.com/file").as_deref(),
+            Some("ftp://example.com/file")
+        );
+        assert_eq!(
+            with_default_scheme("mailto:someone@example.com").as_deref(),
+            Some("mailto:someone@example.com")
+        );
+    }
+
+    #[test]
+    fn scheme_relative_url_gets_https() {
+        assert_eq!(
+            with_default_scheme("//example.com/page").as_deref(),
+            Some("https://example.com/page")
+        );
+    }
+
+    #[test]
+    fn implausible_input_is_rejected() {
+        assert_eq!(with_default_scheme("hello"), None);
+        assert_eq!(with_default_scheme("not a url"), None);
+        assert_eq!(with_default_scheme("example.c0m"), None);
+        assert_eq!(with_default_scheme("//"), None);
+        assert_eq!(with_default_scheme(""), None);
+    }
+}
@@ -0,0 +1,165 @@
+use reqwest::header::LOCATION;
+use url::Url;
+
+use super::{resolve::request_error, ssrf, web_url};
+
+// Maximum number of bytes read from the target page
+const MAX_BODY_SIZE: usize = 512 * 1024;
+
+#[derive(Debug, Default)]
+pub struct PagePreview {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub image_url: Option<String>,
+}
+
+// Fetch the target page and extract its preview metadata.
+// The client must not follow redirects on its own: each hop is followed here, and refused
+// unless it is a web page whose host, and every address it resolves to, is public
+pub async fn fetch(
+    client: &reqwest::Client,
+    url: &str,
+    max_hops: usize,
+) -> Result<PagePreview, String> {
+    let mut current = Url::parse(url).map_err(|e| e.to_string())?;
+    let mut hops = 0;
+    let mut response = loop {
+        if !web_url(&current) {
+            return Err(format!("unsupported scheme: {}", current.scheme()));
+        }
+        if !ssrf::public_host(&current) {
+            return Err(format!("non-public host: {current}"));
+        }
+        ssrf::check_resolved(&current).await?;
+
+        let response = client
+            .get(current.clone())
+            .send()
+            .await
+            .map_err(|e| request_error(&e))?;
+        let location = response
+            .status()
+            .is_redirection()
+            .then(|| response.headers().get(LOCATION))
+            .flatten()
+            .and_then(|value| value.to_str().ok());
+        let Some(location) = location else {
+            break response.error_for_status().map_err(|e| e.to_string())?;
+        };
+
+        if hops == max_hops {
+            return Err(format!("more than {max_hops} redirects"));
+        }
+        hops += 1;
+        current = current.join(location).map_err(|e| e.to_string())?;
+    };
+
+    let is_html = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.contains("text/html"));
+    if !is_html {
+        return Ok(PagePreview::default());
+    }
+
+    let base = response.url().clone();
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await.map_err(|e| e.to_string())? {
+        body.extend_from_slice(&chunk);
+        if body.len() >= MAX_BODY_SIZE {
+            body.truncate(MAX_BODY_SIZE);
+            break;
+        }
+    }
+
+    Ok(parse(&String::from_utf8_lossy(&body), &base))
+}
+
+// Extract the title, description and OpenGraph image from an HTML document
+pub fn parse(html: &str, base: &Url) -> PagePreview {
+    let lower = html.to_ascii_lowercase();
+    let mut preview = PagePreview::default();
+    let mut og_title = None;
+    let mut og_description = None;
+
+    let mut offset = 0;
+    while let Some(start) = lower[offset..].find("<meta") {
+        let start = offset + start;
+        let Some(end) = lower[start..].find('>') else {
+            break;
+        };
+        let end = start + end;
+        let tag = &html[start..end];
+        offset = end;
+
+        let key = attribute(tag, "property").or_else(|| attribute(tag, "name"));
+        let Some(content) = attribute(tag, "content") else {
+            continue;
+        };
+        match key.map(|key| key.to_ascii_lowercase()).as_deref() {
+            Some("og:title") => og_title = Some(content),
+            Some("og:description") => og_description = Some(content),
+            Some("description") => preview.description = Some(content),
+            Some("og:image") => {
+                preview.image_url = base.join(&content).ok().map(String::from);
+            }
+            _ => {}
+        }
+    }
+
+    if let Some(start) = lower.find("<title") {
+        if let Some(open) = lower[start..].find('>') {
+            let open = start + open + 1;
+            if let Some(close) = lower[open..].find("</title>") {
+                let title = decode_entities(html[open..open + close].trim());
+                if !title.is_empty() {
+                    preview.title = Some(title);
+                }
+            }
+        }
+    }
+
+    preview.title = og_title.or(preview.title);
+    preview.description = og_description.or(preview.description);
+    preview
+}
+
+// Read an attribute value from a single HTML tag
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    let lower = tag.to_ascii_lowercase();
+    let mut offset = 0;
+    while let Some(position) = lower[offset..].find(name) {
+        let position = offset + position;
+        offset = position + name.len();
+
+        let preceded_by_space = lower[..position]
+            .chars()
+            .last()
+            .is_some_and(char::is_whitespace);
+        let rest = lower[offset..].trim_start();
+        if !preceded_by_space || !rest.starts_with('=') {
+            continue;
+        }
+
+        let value_start = tag.len() - rest.len() + 1;
+        let value = tag[value_start..].trim_start();
+        let value = match value.chars().next() {
+            Some(quote @ ('"' | '\'')) => value[1..].split(quote).next()?,
+            _ => value
+                .split(|c: char| c.is_whitespace() || c == '/')
+                .next()?,
+        };
+        return Some(decode_entities(value.trim()));
+    }
+    None
+}
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&amp;", "&")
+}
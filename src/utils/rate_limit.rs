@@ -0,0 +1,78 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+use tokio::time::{self, Instant};
+
+const WINDOW: Duration = Duration::from_secs(1);
+
+// Global limit of requests per second. Requests over the limit wait for the next window,
+// up to `max_waiting` of them at a time. The rate is passed per request so it can be
+// changed while the server runs.
+#[derive(Debug)]
+pub struct RateLimiter {
+    window: Mutex<Window>,
+    waiting: AtomicUsize,
+    max_waiting: usize,
+}
+
+#[derive(Debug)]
+struct Window {
+    start: Instant,
+    admitted: u64,
+}
+
+// Releases a waiting spot, also when the request is dropped while waiting
+struct Waiting<'a>(&'a AtomicUsize);
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl RateLimiter {
+    pub fn new(max_waiting: usize) -> Self {
+        Self {
+            window: Mutex::new(Window {
+                start: Instant::now(),
+                admitted: 0,
+            }),
+            waiting: AtomicUsize::new(0),
+            max_waiting,
+        }
+    }
+
+    // Wait until the request may proceed; false if too many requests are waiting already
+    pub async fn acquire(&self, rate: u64) -> bool {
+        let mut waiting = None;
+        loop {
+            let next_window = {
+                let mut window = self.window.lock().unwrap_or_else(|e| e.into_inner());
+                let now = Instant::now();
+                if now >= window.start + WINDOW {
+                    window.start = now;
+                    window.admitted = 0;
+                }
+                if window.admitted < rate {
+                    window.admitted += 1;
+                    return true;
+                }
+                window.start + WINDOW
+            };
+
+            if waiting.is_none() {
+                if self.waiting.fetch_add(1, Ordering::Relaxed) >= self.max_waiting {
+                    self.waiting.fetch_sub(1, Ordering::Relaxed);
+                    return false;
+                }
+                waiting = Some(Waiting(&self.waiting));
+            }
+            time::sleep_until(next_window).await;
+        }
+    }
+}
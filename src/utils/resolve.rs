@@ -0,0 +1,106 @@
+use std::error::Error;
+
+use reqwest::{header::LOCATION, Method, StatusCode};
+use url::Url;
+
+use super::ssrf;
+
+// Follow the redirect chain of a url, returning the final destination
+// The client must not follow redirects on its own; hops into private networks
+// are refused, checking the addresses hosts resolve to if `check_dns` is set
+pub async fn final_destination(
+    client: &reqwest::Client,
+    long_url: &str,
+    max_hops: usize,
+    check_dns: bool,
+) -> Result<String, String> {
+    follow(client, long_url, max_hops, check_dns)
+        .await
+        .map(|(destination, _)| destination.into())
+}
+
+// Status of the page at the end of a url's redirect chain, under the same rules as
+// `final_destination`
+pub async fn final_status(
+    client: &reqwest::Client,
+    long_url: &str,
+    max_hops: usize,
+    check_dns: bool,
+) -> Result<StatusCode, String> {
+    follow(client, long_url, max_hops, check_dns)
+        .await
+        .map(|(_, status)| status)
+}
+
+async fn follow(
+    client: &reqwest::Client,
+    long_url: &str,
+    max_hops: usize,
+    check_dns: bool,
+) -> Result<(Url, StatusCode), String> {
+    let mut current = Url::parse(long_url).map_err(|e| e.to_string())?;
+
+    for _ in 0..max_hops {
+        if !ssrf::public_host(&current) {
+            return Err(format!("redirect to non-public host: {current}"));
+        }
+        if check_dns {
+            ssrf::check_resolved(&current).await?;
+        }
+
+        let mut response = client
+            .request(Method::HEAD, current.clone())
+            .send()
+            .await
+            .map_err(|e| request_error(&e))?;
+        if matches!(
+            response.status(),
+            StatusCode::METHOD_NOT_ALLOWED | StatusCode::NOT_IMPLEMENTED
+        ) {
+            response = client
+                .get(current.clone())
+                .send()
+                .await
+                .map_err(|e| request_error(&e))?;
+        }
+
+        if !response.status().is_redirection() {
+            return Ok((current, response.status()));
+        }
+
+        let Some(location) = response
+            .headers()
+            .get(LOCATION)
+            .and_then(|value| value.to_str().ok())
+        else {
+            return Ok((current, response.status()));
+        };
+
+        let next = current.join(location).map_err(|e| e.to_string())?;
+        if !matches!(next.scheme(), "http" | "https") {
+            return Err(format!("redirect to unsupported scheme: {}", next.scheme()));
+        }
+        current = next;
+    }
+
+    Err(format!("more than {max_hops} redirects"))
+}
+
+// Failed request described by its causes, e.g. "client error (Connect): dns error: ...",
+// since reqwest's own message only names the url
+pub fn request_error(e: &reqwest::Error) -> String {
+    if e.is_timeout() {
+        return "timed out".to_string();
+    }
+    let mut causes = Vec::new();
+    let mut source = e.source();
+    while let Some(cause) = source {
+        causes.push(cause.to_string());
+        source = cause.source();
+    }
+    if causes.is_empty() {
+        e.to_string()
+    } else {
+        causes.join(": ")
+    }
+}
@@ -0,0 +1,39 @@
+use std::{fmt::Display, future::Future, time::Duration};
+
+use tokio::time::{self, Instant};
+use tracing::warn;
+
+// First delay between attempts, doubled after every failure up to MAX_DELAY
+const INITIAL_DELAY: Duration = Duration::from_millis(500);
+const MAX_DELAY: Duration = Duration::from_secs(10);
+
+// Run `attempt` until it succeeds or `max_wait` has passed, backing off exponentially.
+// With a zero `max_wait` the first error is returned right away.
+pub async fn with_backoff<T, E, F, Fut>(
+    what: &str,
+    max_wait: Duration,
+    mut attempt: F,
+) -> Result<T, E>
+where
+    E: Display,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let deadline = Instant::now() + max_wait;
+    let mut delay = INITIAL_DELAY;
+    loop {
+        match attempt().await {
+            Ok(value) => return Ok(value),
+            Err(e) => {
+                let now = Instant::now();
+                if now >= deadline {
+                    return Err(e);
+                }
+                let wait = delay.min(deadline - now);
+                warn!(error = %e, retry_in = ?wait, "Failed to connect to {what}, retrying");
+                time::sleep(wait).await;
+                delay = (delay * 2).min(MAX_DELAY);
+            }
+        }
+    }
+}
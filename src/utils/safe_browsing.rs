@@ -0,0 +1,139 @@
+use std::{collections::HashMap, fmt, str::FromStr, time::Duration};
+
+use reqwest::header::CONTENT_TYPE;
+use serde::Deserialize;
+use serde_json::json;
+
+// Lookup API endpoint used unless SAFE_BROWSING_URL overrides it
+pub const DEFAULT_ENDPOINT: &str = "https://safebrowsing.googleapis.com/v4/threatMatches:find";
+
+// Maximum number of urls the Lookup API accepts in one request
+pub const MAX_URLS_PER_LOOKUP: usize = 500;
+
+const THREAT_TYPES: [&str; 4] = [
+    "MALWARE",
+    "SOCIAL_ENGINEERING",
+    "UNWANTED_SOFTWARE",
+    "POTENTIALLY_HARMFUL_APPLICATION",
+];
+
+/// What happens to a link whose destination is reported as unsafe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThreatAction {
+    /// Refuse to create the link; links found later are disabled.
+    Reject,
+    /// Keep the link working but record the threat type on it.
+    Flag,
+}
+
+impl FromStr for ThreatAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "reject" => Ok(Self::Reject),
+            "flag" => Ok(Self::Flag),
+            _ => Err(format!("unknown threat action: {s}")),
+        }
+    }
+}
+
+// Client for the Google Safe Browsing Lookup API (v4)
+pub struct SafeBrowsing {
+    endpoint: String,
+    api_key: String,
+    pub action: ThreatAction,
+    // How often existing links are checked again, if at all
+    pub recheck_interval: Option<Duration>,
+}
+
+#[derive(Deserialize)]
+struct LookupResponse {
+    #[serde(default)]
+    matches: Vec<ThreatMatch>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ThreatMatch {
+    threat_type: String,
+    threat: ThreatEntry,
+}
+
+#[derive(Deserialize)]
+struct ThreatEntry {
+    url: String,
+}
+
+impl SafeBrowsing {
+    pub fn new(
+        endpoint: String,
+        api_key: String,
+        action: ThreatAction,
+        recheck_interval: Option<Duration>,
+    ) -> Self {
+        Self {
+            endpoint,
+            api_key,
+            action,
+            recheck_interval,
+        }
+    }
+
+    // Look up urls in batches, returning the threat type of every url reported as unsafe
+    pub async fn lookup(
+        &self,
+        client: &reqwest::Client,
+        urls: &[&str],
+    ) -> Result<HashMap<String, String>, String> {
+        let mut threats = HashMap::new();
+        for batch in urls.chunks(MAX_URLS_PER_LOOKUP) {
+            let body = json!({
+                "client": {
+                    "clientId": "tlong",
+                    "clientVersion": env!("CARGO_PKG_VERSION"),
+                },
+                "threatInfo": {
+                    "threatTypes": THREAT_TYPES,
+                    "platformTypes": ["ANY_PLATFORM"],
+                    "threatEntryTypes": ["URL"],
+                    "threatEntries": batch.iter().map(|url| json!({"url": url})).collect::<Vec<_>>(),
+                },
+            });
+
+            // Errors are stripped of their url so the API key never ends up in logs
+            let response = client
+                .post(&self.endpoint)
+                .query(&[("key", &self.api_key)])
+                .header(CONTENT_TYPE, "application/json")
+                .body(body.to_string())
+                .send()
+                .await
+                .and_then(|response| response.error_for_status())
+                .map_err(|e| e.without_url().to_string())?;
+            let bytes = response
+                .bytes()
+                .await
+                .map_err(|e| e.without_url().to_string())?;
+            let response: LookupResponse =
+                serde_json::from_slice(&bytes).map_err(|e| e.to_string())?;
+
+            for threat_match in response.matches {
+                threats
+                    .entry(threat_match.threat.url)
+                    .or_insert(threat_match.threat_type);
+            }
+        }
+        Ok(threats)
+    }
+}
+
+impl fmt::Debug for SafeBrowsing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SafeBrowsing")
+            .field("endpoint", &self.endpoint)
+            .field("action", &self.action)
+            .field("recheck_interval", &self.recheck_interval)
+            .finish_non_exhaustive()
+    }
+}
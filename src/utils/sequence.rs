@@ -0,0 +1,48 @@
+use super::alphabet::Alphabet;
+
+// Bijective mapping of sequence numbers onto fixed-length codes, so consecutive
+// links do not get consecutive codes: n -> n * multiplier mod base^length
+#[derive(Debug, Clone, Copy)]
+pub struct Scrambler {
+    modulus: u64,
+    multiplier: u64,
+    length: usize,
+}
+
+impl Scrambler {
+    pub fn new(base: usize, length: usize) -> Option<Self> {
+        let modulus = (base as u64).checked_pow(length.try_into().ok()?)?;
+        // Near the golden ratio of the code space and coprime with its size, which keeps
+        // the mapping a permutation while spreading neighbouring numbers far apart
+        let mut multiplier = (modulus as f64 * 0.618_033_988_75) as u64 | 1;
+        while gcd(multiplier, modulus) != 1 {
+            multiplier += 2;
+        }
+        Some(Self {
+            modulus,
+            multiplier,
+            length,
+        })
+    }
+
+    // Number of symbols in every code
+    pub fn length(&self) -> usize {
+        self.length
+    }
+
+    // Code for a sequence number, or `None` once the code space is exhausted
+    pub fn code(&self, number: u64, alphabet: &Alphabet) -> Option<String> {
+        if number >= self.modulus {
+            return None;
+        }
+        let scrambled = (number as u128 * self.multiplier as u128 % self.modulus as u128) as u64;
+        Some(alphabet.encode_number(scrambled, self.length))
+    }
+}
+
+fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
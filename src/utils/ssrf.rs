@@ -0,0 +1,97 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use url::{Host, Url};
+
+// Host names that only ever point inside the local machine or network
+const PRIVATE_DOMAINS: [&str; 3] = ["localhost", "local", "internal"];
+
+// Whether an address is reachable on the public internet, excluding loopback,
+// private (RFC 1918), shared, link-local (including cloud metadata services) and reserved ranges
+pub fn public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => public_ipv4(ip),
+        IpAddr::V6(ip) => public_ipv6(ip),
+    }
+}
+
+fn public_ipv4(ip: Ipv4Addr) -> bool {
+    let [a, b, c, _] = ip.octets();
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        || a == 0
+        // Shared address space (RFC 6598)
+        || (a == 100 && (64..128).contains(&b))
+        // IETF protocol assignments
+        || (a == 192 && b == 0 && c == 0)
+        // Benchmarking (RFC 2544)
+        || (a == 198 && (18..20).contains(&b))
+        // Reserved for future use
+        || a >= 240)
+}
+
+fn public_ipv6(ip: Ipv6Addr) -> bool {
+    if let Some(ipv4) = ip.to_ipv4_mapped() {
+        return public_ipv4(ipv4);
+    }
+
+    let segments = ip.segments();
+    // NAT64 (RFC 6052) addresses embed the IPv4 destination
+    if segments[..6] == [0x64, 0xff9b, 0, 0, 0, 0] {
+        let [a, b] = segments[6].to_be_bytes();
+        let [c, d] = segments[7].to_be_bytes();
+        return public_ipv4(Ipv4Addr::new(a, b, c, d));
+    }
+
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        // Unique local (fc00::/7)
+        || (segments[0] & 0xfe00) == 0xfc00
+        // Link-local (fe80::/10) and deprecated site-local (fec0::/10)
+        || (segments[0] & 0xffc0) == 0xfe80
+        || (segments[0] & 0xffc0) == 0xfec0)
+}
+
+// Whether the url's host can be on the public internet; urls without a host pass
+pub fn public_host(url: &Url) -> bool {
+    match url.host() {
+        None => true,
+        Some(Host::Ipv4(ip)) => public_ipv4(ip),
+        Some(Host::Ipv6(ip)) => public_ipv6(ip),
+        Some(Host::Domain(domain)) => {
+            let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+            !PRIVATE_DOMAINS.iter().any(|private| {
+                domain == *private
+                    || domain
+                        .strip_suffix(private)
+                        .is_some_and(|rest| rest.ends_with('.'))
+            })
+        }
+    }
+}
+
+// Resolve the url's host and fail if any of its addresses is not public.
+// Hosts that cannot be resolved pass, as nothing can be reached through them.
+pub async fn check_resolved(url: &Url) -> Result<(), String> {
+    let Some(Host::Domain(domain)) = url.host() else {
+        return Ok(());
+    };
+    let port = url.port_or_known_default().unwrap_or(80);
+
+    let Ok(addrs) = tokio::net::lookup_host((domain, port)).await else {
+        return Ok(());
+    };
+    for addr in addrs {
+        if !public_ip(addr.ip()) {
+            return Err(format!(
+                "{domain} resolves to non-public address {}",
+                addr.ip()
+            ));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,108 @@
+use std::sync::atomic::Ordering;
+
+use chrono::Utc;
+use serde::Serialize;
+use serde_json::json;
+use tracing::error;
+
+use crate::{db::Timed, state::AppState, utils::signing};
+
+// Header carrying `t=<unix seconds>,v1=<hex HMAC-SHA256 of "<t>.<body>">`
+pub const SIGNATURE_HEADER: &str = "x-tlong-signature";
+pub const EVENT_HEADER: &str = "x-tlong-event";
+pub const DELIVERY_HEADER: &str = "x-tlong-delivery";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    Created,
+    Updated,
+    Deleted,
+    Expired,
+    Clicked,
+}
+
+impl Event {
+    pub const ALL: [Event; 5] = [
+        Event::Created,
+        Event::Updated,
+        Event::Deleted,
+        Event::Expired,
+        Event::Clicked,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Event::Created => "link.created",
+            Event::Updated => "link.updated",
+            Event::Deleted => "link.deleted",
+            Event::Expired => "link.expired",
+            Event::Clicked => "link.clicked",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|event| event.name() == name)
+    }
+}
+
+// Queue a notification of a link event for every webhook subscribed to it, without
+// holding up the request; the delivery job sends it
+pub fn emit(state: &AppState, event: Event, link: impl Serialize) {
+    if state.webhook_interval.is_none() {
+        return;
+    }
+    // Clicks are too frequent to ask the database about subscribers each time
+    if event == Event::Clicked && !state.webhook_clicks.load(Ordering::Relaxed) {
+        return;
+    }
+
+    let payload = json!({
+        "event": event.name(),
+        "occurred_at": Utc::now(),
+        "link": link,
+    })
+    .to_string();
+    let pg_db = state.pg_db.clone();
+    state.background.spawn(async move {
+        let result = sqlx::query!(
+            "
+            INSERT INTO webhook_deliveries (webhook_id, event, payload)
+            SELECT id, $1, $2 FROM webhooks WHERE $1 = ANY(events)
+            ",
+            event.name(),
+            payload
+        )
+        .execute(&pg_db)
+        .timed("queue_webhook_deliveries")
+        .await;
+        if let Err(e) = result {
+            error!(error = %e, event = event.name(), "Failed to queue webhook deliveries");
+        }
+    });
+}
+
+// Look up again whether any webhook wants clicks, e.g. after webhooks changed
+pub async fn refresh_click_subscribers(state: &AppState) -> Result<(), sqlx::Error> {
+    let subscribed = sqlx::query_scalar!(
+        r#"SELECT EXISTS (SELECT 1 FROM webhooks WHERE $1 = ANY(events)) AS "exists!""#,
+        Event::Clicked.name()
+    )
+    .fetch_one(&state.pg_db)
+    .timed("webhook_click_subscribers")
+    .await?;
+    state.webhook_clicks.store(subscribed, Ordering::Relaxed);
+    Ok(())
+}
+
+// Value of the signature header for a payload sent at `timestamp`; receivers recompute it
+// with the webhook's secret and should reject old timestamps
+pub fn signature(secret: &str, timestamp: i64, body: &str) -> String {
+    signing::sign(secret, timestamp, body.as_bytes())
+}
+
+// Random signing secret for a webhook registered without one
+pub fn generate_secret() -> Result<String, String> {
+    let mut bytes = [0u8; 32];
+    getrandom::fill(&mut bytes).map_err(|e| e.to_string())?;
+    Ok(hex::encode(bytes))
+}